@@ -0,0 +1,34 @@
+use ablet::AText;
+use criterion::{criterion_group, criterion_main, Criterion};
+use crossterm::style::{Color, Stylize};
+
+/// a line with a differently colored fragment per word, like highlighted code
+fn styled_line(seed: u8) -> AText {
+    let mut res = AText::default();
+    for i in 0..40u8 {
+        let color = Color::Rgb {
+            r: seed,
+            g: i,
+            b: seed.wrapping_mul(i),
+        };
+        res += format!("word{i} ").with(color);
+    }
+    res + "\n"
+}
+
+fn append(c: &mut Criterion) {
+    let lines = (0..=255).map(styled_line).collect::<Vec<_>>();
+
+    c.bench_function("append 256 lines with 40 styles each", |b| {
+        b.iter(|| {
+            let mut doc = AText::default();
+            for line in &lines {
+                doc.append_text(line.clone());
+            }
+            doc
+        })
+    });
+}
+
+criterion_group!(benches, append);
+criterion_main!(benches);
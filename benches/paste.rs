@@ -0,0 +1,39 @@
+use ablet::{AText, Buffer};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use crossterm::style::Stylize;
+
+/// a document with a few styles, so pastes have to merge style maps
+fn styled_doc(lines: usize) -> AText {
+    let mut res = AText::default();
+    for i in 0..lines {
+        res += AText::from("line ") + format!("{i}").green() + " of the document\n";
+    }
+    res
+}
+
+fn paste(c: &mut Criterion) {
+    let text = "pasted text, which is long enough to matter\n".repeat(1000);
+
+    c.bench_function("paste 44KB into an empty buffer", |b| {
+        b.iter_batched(
+            || Buffer::new().into_ref(),
+            |buf| buf.insert_text_at_cursor(text.as_str()),
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("paste 44KB into a styled buffer", |b| {
+        b.iter_batched(
+            || {
+                let buf = Buffer::from_text(styled_doc(1000)).into_ref();
+                buf.move_cursor_by(10_000);
+                buf
+            },
+            |buf| buf.insert_text_at_cursor(text.as_str()),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, paste);
+criterion_main!(benches);
@@ -31,7 +31,7 @@ fn run() -> io::Result<()> {
     let (tx_kill, rx_kill) = mpsc::sync_channel::<()>(1);
     start_background_thread(tree.clone(), def_buffer.clone(), rx_kill);
 
-    let mut handler = SimpleLineHandler;
+    let mut handler = SimpleLineHandler::new();
     loop {
         use SimpleLineHandlerResult::*;
         match ablet::edit_buffer(&prompt_buffer, &tree, &mut handler)? {
@@ -58,7 +58,9 @@ fn start_background_thread(splits: SplitTree, buf: BufferRef, rx_kill: Receiver<
             let now = Instant::now();
             if now.duration_since(last_msg_ts) > Duration::from_secs(2) {
                 buf.add_line(AText::from("< ".green()) + "Hello at " + format!("{now:?}").yellow());
-                splits.render().unwrap();
+                splits
+                    .render_at_most_every(Duration::from_millis(30))
+                    .unwrap();
                 last_msg_ts = now;
             }
         }
@@ -0,0 +1,39 @@
+use std::io;
+
+use ablet::prelude::*;
+
+fn main() -> Result<(), ablet::SetupError<io::Error>> {
+    with_setup_terminal(run)
+}
+
+fn run() -> io::Result<()> {
+    let output = Buffer::from_text("Enter lisp expressions, Ctrl+C quits\n").into_ref();
+    let ablet = Ablet::new(split_tree!(Vertical: { 1: output }));
+    let prompt_doc = ablet.prompt().buffer().get_doc();
+    ablet.prompt().set_prefix("> ".green());
+
+    // an expression is only submitted once all parentheses are closed, until then
+    // Enter starts a new line in the prompt
+    // Up and Down go through the expressions that were entered in earlier runs too
+    let history_file = std::env::temp_dir().join("ablet-repl-history");
+    let history = ablet.prompt().history();
+    history.set_dedup(true);
+    history.set_max_len(Some(1000));
+    _ = history.load(&history_file);
+    let mut handler = ablet.prompt().line_editor();
+    handler.set_input_complete(|input| input.matches('(').count() <= input.matches(')').count());
+
+    loop {
+        use SimpleLineHandlerResult::*;
+        match ablet.edit_prompt(&mut handler)? {
+            LineDone => {
+                let input = prompt_doc.take();
+                for line in input.as_str().lines() {
+                    output.add_line(AText::from("> ".grey()) + line);
+                }
+                output.add_line("nil".yellow());
+            }
+            Abort => return history.save(&history_file),
+        }
+    }
+}
@@ -0,0 +1,1354 @@
+//! Contains the Ablet type, which combines a split tree with a prompt
+
+use std::{
+    io,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{self, Event, MouseButton, MouseEventKind},
+    style::{ContentStyle, Stylize},
+};
+use log::warn;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    app_event::{LoopMessage, UserEvent},
+    copy_to_clipboard,
+    dialog::{Dialog, DialogHandler},
+    edit_with,
+    palette::{Palette, PaletteHandler},
+    quickfix,
+    renderer::present,
+    shared,
+    splittree::{prerender_maps, render_screen_too_small_info, SplitMap},
+    AText, AbletCtx, AppEvent, AppEventHandler, BufferPosition, BufferRef, BufferRegistry,
+    Completion, CrosstermRenderer, EventHandler, EventSender, Frame, HandlerResult, Prompt,
+    Quickfix, QuickfixEntry, Range, Rect, Renderer, SearchPattern, Shared, Size, SplitTree,
+    TabCompletion, TextOffset, Theme,
+};
+
+/// An application: a split tree, with a prompt below it, separated by a line.
+/// The prompt can also be put above the tree, or left out, see [Ablet::with_prompt_at]
+/// and [Ablet::without_prompt].
+/// Clones share the same state, so an Ablet can be handed to background threads
+/// that update the ui.
+#[derive(Clone)]
+pub struct Ablet {
+    split_tree: Shared<SplitTree>,
+    prompt: Prompt,
+    /// None if the prompt isn't shown
+    prompt_position: Shared<Option<PromptPosition>>,
+    focus: Shared<FocusState>,
+    /// shown in the separator line above the prompt
+    status: Shared<AText>,
+    /// see [Ablet::set_separator_progress]
+    progress: Shared<Option<f32>>,
+    /// see [Ablet::set_popup]
+    popup: Shared<Option<AText>>,
+    /// the buffers of the tree, and the inputs that are attached to them, see
+    /// [Ablet::set_split_input]
+    split_inputs: Shared<Vec<(BufferRef, BufferRef)>>,
+    /// see [Ablet::dialog]
+    dialog: Shared<Option<Dialog>>,
+    /// see [Ablet::open_palette]
+    palette: Shared<Option<Palette>>,
+    /// the channel behind [Ablet::event_sender], which the event loop waits on
+    user_events: (Sender<LoopMessage>, Shared<Receiver<LoopMessage>>),
+    /// whether a thread waits for the terminal to have an event, see
+    /// [Ablet::wait_for_terminal]
+    terminal_waiter: Shared<bool>,
+    /// see [Ablet::set_tick]
+    tick: Shared<Option<Ticker>>,
+    /// see [Ablet::set_theme]
+    theme: Shared<Arc<Theme>>,
+}
+
+/// Where [Ablet] shows the prompt, see [Ablet::with_prompt_at]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PromptPosition {
+    #[default]
+    Bottom,
+    Top,
+}
+
+/// where the parts of an [Ablet] are drawn
+struct Areas {
+    tree: Rect,
+    /// the row of the line between the tree and the prompt
+    separator: Option<u16>,
+    prompt: Option<Rect>,
+}
+
+struct Ticker {
+    interval: Duration,
+    next: Instant,
+}
+
+/// How the mouse changes the focus. Mouse events must be enabled via
+/// [set_mouse_capture](crate::set_mouse_capture) for this to have any effect
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MouseFocus {
+    /// clicking a split focuses it
+    pub click_to_focus: bool,
+    /// resting the pointer over a split for the given time focuses it
+    pub follows_mouse: Option<Duration>,
+    /// Dragging selects text, and releasing the button copies it to the clipboard,
+    /// like terminals do when they don't capture the mouse
+    pub select_and_copy: bool,
+}
+
+struct FocusState {
+    focused: BufferRef,
+    mouse: MouseFocus,
+    /// the buffer under the pointer, and since when it's there
+    hovered: Option<(BufferRef, Instant)>,
+    /// the size of the last rendered frame, to map mouse positions to buffers
+    last_size: Option<Size>,
+    /// the selection that is currently being dragged
+    drag: Option<Drag>,
+    /// see [Ablet::set_focus_bias]
+    focus_bias: Option<u16>,
+}
+
+#[derive(Clone)]
+struct Drag {
+    buf: BufferRef,
+    rect: Rect,
+    /// the text offset where the drag started
+    anchor: TextOffset,
+}
+
+/// the position relative to the rect, clamped into it
+fn relative_to(rect: Rect, pos: BufferPosition) -> BufferPosition {
+    let clamp = |v: u16, start: u16, len: u16| v.clamp(start, start + len.max(1) - 1) - start;
+    BufferPosition::new(
+        clamp(pos.row, rect.pos.row, rect.size.h),
+        clamp(pos.col, rect.pos.col, rect.size.w),
+    )
+}
+
+impl Ablet {
+    pub fn new(split_tree: SplitTree) -> Self {
+        let prompt = Prompt::new();
+        let (tx, rx) = mpsc::channel();
+        Self {
+            split_tree: shared(split_tree),
+            focus: shared(FocusState {
+                focused: prompt.buffer().clone(),
+                mouse: MouseFocus::default(),
+                hovered: None,
+                last_size: None,
+                drag: None,
+                focus_bias: None,
+            }),
+            prompt,
+            prompt_position: shared(Some(PromptPosition::Bottom)),
+            status: shared(AText::default()),
+            progress: shared(None),
+            popup: shared(None),
+            split_inputs: shared(vec![]),
+            dialog: shared(None),
+            palette: shared(None),
+            user_events: (tx, shared(rx)),
+            terminal_waiter: shared(false),
+            tick: shared(None),
+            theme: shared(Arc::new(Theme::default())),
+        }
+    }
+
+    /// Shows the prompt, and the separator line, at the given position
+    pub fn with_prompt_at(self, position: PromptPosition) -> Self {
+        *self.prompt_position.lock().unwrap() = Some(position);
+        self
+    }
+
+    /// Leaves out the prompt and the separator line, e.g. for dashboards that don't
+    /// take line input. The split tree gets the whole screen, and its first buffer is
+    /// focused. Since the status is shown in the separator, it isn't shown either, and
+    /// [Ablet::edit_prompt] fails
+    pub fn without_prompt(self) -> Self {
+        *self.prompt_position.lock().unwrap() = None;
+        let first = self.split_tree.lock().unwrap().first_buffer();
+        self.focus.lock().unwrap().focused = first;
+        self
+    }
+
+    /// Sets the styles that the styles from [named_style](crate::named_style) are drawn
+    /// with, from the next render on. Named styles that the theme doesn't have are drawn
+    /// without them
+    pub fn set_theme(&self, theme: Theme) {
+        *self.theme.lock().unwrap() = Arc::new(theme);
+    }
+
+    /// Shows the text in the separator line between the prompt and the splits. It
+    /// should be a single line, and is cut off if it's too long. Handlers set it via
+    /// [AbletCtx::set_status], e.g. the [LineEditor](crate::LineEditor) for the errors
+    /// of its validator, and to show OVERWRITE in overwrite mode. Without a prompt,
+    /// there is no separator, so the status isn't shown, see [Ablet::without_prompt]
+    pub fn set_status(&self, text: impl Into<AText>) {
+        *self.status.lock().unwrap() = text.into();
+    }
+
+    /// the text from [Ablet::set_status]
+    pub fn status(&self) -> AText {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Fills the separator line from the left, up to the given fraction between 0 and
+    /// 1, so it doubles as a progress bar for long running operations. None hides it
+    pub fn set_separator_progress(&self, progress: Option<f32>) {
+        *self.progress.lock().unwrap() = progress;
+    }
+
+    /// Shows the text in a box above the prompt, on top of the splits, e.g. for
+    /// completion candidates. Lines that don't fit are left out. None hides it
+    pub fn set_popup(&self, text: Option<AText>) {
+        *self.popup.lock().unwrap() = text;
+    }
+
+    /// Shows the input in the last row of the split of the buffer, e.g. for a command
+    /// line per pane. It's focused and edited like any other buffer, e.g. with
+    /// [Ablet::run_until]. None removes the input of the split
+    pub fn set_split_input(&self, split: &BufferRef, input: Option<BufferRef>) {
+        let mut inputs = self.split_inputs.lock().unwrap();
+        inputs.retain(|(buf, _)| buf != split);
+        if let Some(input) = input {
+            inputs.push((split.clone(), input));
+        }
+    }
+
+    /// the focused buffer, initially the prompt
+    pub fn focused(&self) -> BufferRef {
+        self.focus.lock().unwrap().focused.clone()
+    }
+
+    /// focuses the buffer, and expands the stacked splits that contain it
+    pub fn focus(&self, buf: &BufferRef) {
+        self.split_tree.lock().unwrap().activate(buf);
+        self.focus.lock().unwrap().focused = buf.clone();
+    }
+
+    /// Expands the next (or previous) child of the stacked split that contains the
+    /// focused buffer, and focuses it, see [SplitTree::cycle_stack]
+    pub fn cycle_stack(&self, forward: bool) {
+        let focused = self.focused();
+        let next = self
+            .split_tree
+            .lock()
+            .unwrap()
+            .cycle_stack(&focused, forward);
+        if let Some(next) = next {
+            self.focus(&next);
+        }
+    }
+
+    /// Removes the focused buffer from the split tree, and focuses the buffer that was
+    /// closest to it, or the prompt if there is none. If its document is modified,
+    /// `confirm` is asked first, see [Ablet::close_focused_confirmed] for asking the user
+    /// with a dialog. Returns whether it was closed.
+    /// The prompt and the last buffer of the tree can't be closed
+    pub fn close_focused(&self, confirm: impl FnOnce(&BufferRef) -> bool) -> bool {
+        let focused = self.focused();
+        let rects = self.rects();
+        let Some(focused_rect) = rects
+            .iter()
+            .find(|(_, buf)| *buf == focused)
+            .map(|(rect, _)| *rect)
+        else {
+            return false;
+        };
+        let can_close = focused != *self.prompt.buffer()
+            && self.split_tree.lock().unwrap().clone().remove(&focused);
+        if !can_close || (focused.get_doc().is_modified() && !confirm(&focused)) {
+            return false;
+        }
+
+        self.split_tree.lock().unwrap().remove(&focused);
+        let center = |rect: Rect| {
+            (
+                2 * rect.pos.row as i32 + rect.size.h as i32,
+                2 * rect.pos.col as i32 + rect.size.w as i32,
+            )
+        };
+        let (row, col) = center(focused_rect);
+        let neighbor = rects
+            .into_iter()
+            .filter(|(_, buf)| *buf != focused && buf != self.prompt.buffer())
+            .min_by_key(|(rect, _)| {
+                let (r, c) = center(*rect);
+                (r - row).abs() + (c - col).abs()
+            })
+            .map_or_else(|| self.prompt.buffer().clone(), |(_, buf)| buf);
+        self.focus(&neighbor);
+        true
+    }
+
+    /// Like [Ablet::close_focused], but a modified document is only closed if the user
+    /// picks "Close" in a [dialog](Ablet::dialog)
+    pub fn close_focused_confirmed(&self) -> io::Result<bool> {
+        let mut error = None;
+        let closed = self.close_focused(|_| {
+            let choices = ["Close", "Cancel"];
+            match self.dialog("Close", "The buffer has unsaved changes", &choices) {
+                Ok(choice) => choice == Some(0),
+                Err(e) => {
+                    error = Some(e);
+                    false
+                }
+            }
+        });
+        error.map_or(Ok(closed), Err)
+    }
+
+    /// Shows the new buffer instead of the old one, see [SplitTree::replace_buffer],
+    /// and moves the focus along, if the old one had it
+    pub fn replace_buffer(&self, old: &BufferRef, new: &BufferRef) -> bool {
+        let replaced = self.split_tree.lock().unwrap().replace_buffer(old, new);
+        if replaced && self.focused() == *old {
+            self.focus(new);
+        }
+        replaced
+    }
+
+    pub fn set_mouse_focus(&self, mouse: MouseFocus) {
+        let mut focus = self.focus.lock().unwrap();
+        focus.mouse = mouse;
+        focus.hovered = None;
+    }
+
+    /// If set, the split that contains the focused buffer gets its proportion multiplied
+    /// by the factor, on every level of the tree, so the focused buffer gets more space,
+    /// like the main pane of a tiling window manager. Fixed sizes are kept
+    pub fn set_focus_bias(&self, factor: Option<u16>) {
+        self.focus.lock().unwrap().focus_bias = factor;
+    }
+
+    /// the split tree, with the focus bias applied
+    fn layout_tree(&self) -> SplitTree {
+        let tree = self.split_tree.lock().unwrap().clone();
+        let focus = self.focus.lock().unwrap();
+        match focus.focus_bias {
+            Some(factor) => tree.biased_towards(&focus.focused, factor),
+            None => tree,
+        }
+    }
+
+    /// Returns the buffer that was rendered at the given position, if any. This is
+    /// based on the last render, since it's what the user sees
+    pub fn buffer_at(&self, pos: BufferPosition) -> Option<BufferRef> {
+        self.rects()
+            .into_iter()
+            .find(|(rect, _)| rect.contains(pos))
+            .map(|(_, buf)| buf)
+    }
+
+    /// the rects of the buffers, including the prompt, as they were rendered last
+    fn rects(&self) -> Vec<(Rect, BufferRef)> {
+        let Some(size) = self.focus.lock().unwrap().last_size else {
+            return vec![];
+        };
+        let Some(areas) = self.areas(size) else {
+            return vec![];
+        };
+
+        let mut res = self
+            .split_map(areas.tree)
+            .map(|split_map| split_map.rects.into_iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        if let Some(prompt_rect) = areas.prompt {
+            let input_rect = self.prompt.input_rect(prompt_rect);
+            res.push((input_rect, self.prompt.buffer().clone()));
+        }
+        res
+    }
+
+    /// the buffer that is expanded by clicking the title bar at the given position
+    fn title_bar_target_at(&self, pos: BufferPosition) -> Option<BufferRef> {
+        let size = self.focus.lock().unwrap().last_size?;
+        let areas = self.areas(size)?;
+        self.split_map(areas.tree)?
+            .title_bars
+            .into_iter()
+            .find(|bar| bar.rect.contains(pos))
+            .map(|bar| bar.target)
+    }
+
+    /// Adapts the views of all shown buffers to the new layout on a resize event,
+    /// so their offsets and cursors fit it before anything else happens. Called by
+    /// [Ablet::edit_prompt] for each event, but must be called by custom event loops,
+    /// which should render afterwards
+    pub fn handle_resize(&self, ev: &Event) {
+        let Event::Resize(w, h) = *ev else {
+            return;
+        };
+        self.focus.lock().unwrap().last_size = Some(Size { w, h });
+        for (rect, buf) in self.rects() {
+            buf.fit_to(rect.size);
+        }
+    }
+
+    /// Updates the focus and selections according to the mouse settings. Clicking the
+    /// title bar of a stacked split always expands and focuses its buffer. Called by
+    /// [Ablet::edit_prompt] for each event, but must be called by custom event loops.
+    /// While a dialog or the palette is shown, the mouse is ignored, so what's behind
+    /// them stays as it was
+    pub fn handle_mouse(&self, ev: &Event) -> io::Result<()> {
+        let Event::Mouse(me) = ev else {
+            return Ok(());
+        };
+        if self.is_modal() {
+            return Ok(());
+        }
+        let pos = BufferPosition::new(me.row, me.column);
+        self.handle_mouse_selection(me.kind, pos)?;
+
+        if let MouseEventKind::Down(MouseButton::Left) = me.kind {
+            if let Some(target) = self.title_bar_target_at(pos) {
+                self.focus(&target);
+                return Ok(());
+            }
+        }
+
+        let mouse = self.focus.lock().unwrap().mouse;
+        let target = self.buffer_at(pos);
+        let mut focus = self.focus.lock().unwrap();
+        match me.kind {
+            MouseEventKind::Down(_) if mouse.click_to_focus => {
+                if let Some(target) = target {
+                    focus.focused = target;
+                }
+                focus.hovered = None;
+            }
+            MouseEventKind::Moved if mouse.follows_mouse.is_some() => match target {
+                Some(target) if target == focus.focused => focus.hovered = None,
+                Some(target) => {
+                    if !matches!(&focus.hovered, Some((hovered, _)) if *hovered == target) {
+                        focus.hovered = Some((target, Instant::now()));
+                    }
+                }
+                None => focus.hovered = None,
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Dragging with the left button selects text in the buffer where the drag
+    /// started, and releasing the button copies it to the clipboard
+    fn handle_mouse_selection(&self, kind: MouseEventKind, pos: BufferPosition) -> io::Result<()> {
+        if !self.focus.lock().unwrap().mouse.select_and_copy {
+            return Ok(());
+        }
+
+        let drag = self.focus.lock().unwrap().drag.clone();
+        match (kind, drag) {
+            (MouseEventKind::Down(MouseButton::Left), _) => {
+                let Some((rect, buf)) = self.rects().into_iter().find(|(r, _)| r.contains(pos))
+                else {
+                    return Ok(());
+                };
+                buf.clear_selections();
+                let anchor = buf.text_offset_at(relative_to(rect, pos));
+                self.focus.lock().unwrap().drag = Some(Drag { buf, rect, anchor });
+            }
+            (MouseEventKind::Drag(MouseButton::Left), Some(drag)) => {
+                let head = drag.buf.text_offset_at(relative_to(drag.rect, pos));
+                drag.buf
+                    .set_selection(drag.anchor.min(head)..drag.anchor.max(head));
+            }
+            (MouseEventKind::Up(MouseButton::Left), Some(drag)) => {
+                self.focus.lock().unwrap().drag = None;
+                if let Some(text) = drag.buf.selected_text().filter(|t| !t.is_empty()) {
+                    copy_to_clipboard(&text)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Focuses the hovered buffer, if the pointer rested on it long enough. Otherwise,
+    /// returns when that will be the case, if there is a hovered buffer.
+    pub fn update_hover_focus(&self) -> Option<Instant> {
+        if self.is_modal() {
+            return None;
+        }
+        let mut focus = self.focus.lock().unwrap();
+        let delay = focus.mouse.follows_mouse?;
+        let (_, since) = focus.hovered.as_ref()?;
+        let deadline = *since + delay;
+        if Instant::now() < deadline {
+            return Some(deadline);
+        }
+        let (hovered, _) = focus.hovered.take()?;
+        focus.focused = hovered;
+        None
+    }
+
+    pub fn prompt(&self) -> &Prompt {
+        &self.prompt
+    }
+
+    /// gives access to the split tree, e.g. to change the layout
+    pub fn update_split_tree<T>(&self, f: impl FnOnce(&mut SplitTree) -> T) -> T {
+        f(&mut self.split_tree.lock().unwrap())
+    }
+
+    /// Like [SplitTree::prerender], for a tree that is about to replace the shown one,
+    /// like a hidden workspace, or for the shown one, to prerender the collapsed
+    /// children of its stacked splits. It's laid out like the shown tree was at the
+    /// last render, and buffers that are shown right now are left out, since they are
+    /// rendered anyway
+    pub fn prerender(&self, tree: &SplitTree) -> thread::JoinHandle<io::Result<()>> {
+        let shown: Vec<_> = self.rects().into_iter().map(|(_, buf)| buf).collect();
+        let size = self.focus.lock().unwrap().last_size;
+        let maps = size
+            .and_then(|size| self.areas(size))
+            .map(|areas| {
+                tree.expansions()
+                    .iter()
+                    .filter_map(|tree| self.split_map_of(tree, areas.tree))
+                    .map(|mut split_map| {
+                        split_map.rects.retain(|_, buf| !shown.contains(buf));
+                        split_map
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        prerender_maps(maps, size.unwrap_or(Size { w: 0, h: 0 }))
+    }
+
+    /// renders everything to the terminal
+    pub fn render(&self) -> io::Result<()> {
+        self.render_with(&mut CrosstermRenderer::stdout())
+    }
+
+    /// Suspends the app, like C-z does in other terminal programs, which has to be
+    /// bound to this, since raw mode turns it into a key event: the terminal is
+    /// restored, and the process is stopped, so the shell takes over. When it's
+    /// continued, e.g. with `fg`, the terminal is set up again, and everything is
+    /// rendered anew. Expects the terminal to be set up like
+    /// [with_setup_terminal](crate::with_setup_terminal) does it
+    #[cfg(unix)]
+    pub fn suspend(&self) -> io::Result<()> {
+        crate::termutils::suspend_process()?;
+        self.render()
+    }
+
+    /// renders everything through the given renderer, using its size
+    pub fn render_with(&self, renderer: &mut dyn Renderer) -> io::Result<()> {
+        crate::timer::update_shown_timers();
+        let frame = self.render_to_frame(renderer.size()?)?;
+        present(frame, renderer)
+    }
+
+    /// Draws the splits, the separator, and the prompt into a new frame of the given
+    /// size, with the named styles resolved by the theme. The prompt grows with its
+    /// content, up to half of the height
+    pub fn render_to_frame(&self, size: Size) -> io::Result<Frame> {
+        self.focus.lock().unwrap().last_size = Some(size);
+        let theme = self.theme.lock().unwrap().clone();
+        let mut frame = Frame::new(size).with_theme(theme);
+        let Some(areas) = self.areas(size) else {
+            render_screen_too_small_info(&mut frame);
+            return Ok(frame);
+        };
+
+        match self.split_map(areas.tree) {
+            Some(split_map) => split_map.draw_into(&mut frame, areas.tree)?,
+            None => render_screen_too_small_info(&mut frame),
+        }
+
+        if let Some(popup) = &*self.popup.lock().unwrap() {
+            let at_top = *self.prompt_position.lock().unwrap() == Some(PromptPosition::Top);
+            draw_popup(&mut frame, popup, areas.tree, at_top);
+        }
+
+        if let Some(separator_row) = areas.separator {
+            self.draw_separator(&mut frame, separator_row);
+        }
+        if let Some(prompt_rect) = areas.prompt {
+            self.prompt.render_at(&mut frame, prompt_rect)?;
+        }
+        if let Some(palette) = &*self.palette.lock().unwrap() {
+            palette.draw(&mut frame);
+        }
+        if let Some(dialog) = &*self.dialog.lock().unwrap() {
+            dialog.draw(&mut frame);
+        }
+        Ok(frame)
+    }
+
+    /// draws the separator line, with the progress and the status in it
+    fn draw_separator(&self, frame: &mut Frame, row: u16) {
+        let w = frame.size().w;
+        let separator = "\u{2500}".repeat(w as usize);
+        let separator_pos = BufferPosition::new(row, 0);
+        frame.print(separator_pos, &separator, ContentStyle::default(), w);
+        if let Some(progress) = *self.progress.lock().unwrap() {
+            let filled = (progress.clamp(0.0, 1.0) * w as f32).round() as usize;
+            let bar = "\u{2501}".repeat(filled);
+            frame.print(separator_pos, &bar, ContentStyle::new().green(), w);
+        }
+        let status = self.status.lock().unwrap();
+        let mut col = 2;
+        for run in status.get_range_style_pairs(Range::new(0, status.len())) {
+            let pos = BufferPosition::new(row, col);
+            col = frame.print(pos, &status.text[run.range.into_native()], *run.style, w);
+        }
+    }
+
+    /// the rects of the split tree in the area, where splits with an input give up
+    /// their last row to it, if they have more than one
+    fn split_map(&self, area: Rect) -> Option<SplitMap> {
+        self.split_map_of(&self.layout_tree(), area)
+    }
+
+    /// like [Ablet::split_map], for another tree
+    fn split_map_of(&self, tree: &SplitTree, area: Rect) -> Option<SplitMap> {
+        let mut split_map = tree.compute_rects_in(area)?;
+        for (split, input) in &*self.split_inputs.lock().unwrap() {
+            let Some(rect) = split_map
+                .rects
+                .iter()
+                .find(|(_, buf)| *buf == split)
+                .map(|(rect, _)| *rect)
+            else {
+                continue;
+            };
+            if rect.size.h < 2 {
+                continue;
+            }
+            split_map.rects.remove(&rect);
+            let bottom = rect.pos.row + rect.size.h - 1;
+            split_map.rects.insert(
+                Rect::new(rect.pos.row, rect.pos.col, rect.size.w, rect.size.h - 1),
+                split.clone(),
+            );
+            split_map.rects.insert(
+                Rect::new(bottom, rect.pos.col, rect.size.w, 1),
+                input.clone(),
+            );
+        }
+        Some(split_map)
+    }
+
+    /// Splits the screen between the tree, the separator and the prompt, or returns
+    /// None if there is not enough space
+    fn areas(&self, size: Size) -> Option<Areas> {
+        let Some(position) = *self.prompt_position.lock().unwrap() else {
+            return Some(Areas {
+                tree: Rect::new(0, 0, size.w, size.h),
+                separator: None,
+                prompt: None,
+            });
+        };
+        if size.h < 3 {
+            return None;
+        }
+        let prompt_h = self.prompt.height().clamp(1, size.h / 2);
+        let tree_h = size.h - prompt_h - 1;
+        Some(match position {
+            PromptPosition::Bottom => Areas {
+                tree: Rect::new(0, 0, size.w, tree_h),
+                separator: Some(tree_h),
+                prompt: Some(Rect::new(tree_h + 1, 0, size.w, prompt_h)),
+            },
+            PromptPosition::Top => Areas {
+                tree: Rect::new(prompt_h + 1, 0, size.w, tree_h),
+                separator: Some(prompt_h),
+                prompt: Some(Rect::new(0, 0, size.w, prompt_h)),
+            },
+        })
+    }
+
+    /// Like [edit_buffer](crate::edit_buffer), but for the prompt. Mouse events
+    /// change the focus as configured via [Ablet::set_mouse_focus]. Fails with
+    /// `ErrorKind::Unsupported` if the prompt isn't shown, see [Ablet::without_prompt]
+    pub fn edit_prompt<H: EventHandler<T>, T>(&self, event_handler: &mut H) -> io::Result<T> {
+        if self.prompt_position.lock().unwrap().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the Ablet has no prompt to edit",
+            ));
+        }
+        self.edit(self.prompt.buffer(), event_handler)
+    }
+
+    /// Like [Ablet::edit_prompt], but Tab completes the input with the candidates that
+    /// `complete` returns for the input and the cursor position, which are shown in a
+    /// popup meanwhile. See [TabCompletion] for more control
+    pub fn edit_prompt_with_completion<H: EventHandler<T>, T>(
+        &self,
+        event_handler: &mut H,
+        complete: impl FnMut(&str, usize) -> Vec<Completion>,
+    ) -> io::Result<T> {
+        let mut handler = TabCompletion::new(event_handler, complete).with_popup(true);
+        let res = self.edit_prompt(&mut handler);
+        self.set_popup(None);
+        res
+    }
+
+    /// Searches the documents of all buffers in the registry, in parallel, and returns
+    /// the matches, together with a buffer that lists them, see [Quickfix]
+    pub fn search_all(
+        &self,
+        registry: &BufferRegistry,
+        pattern: &(impl SearchPattern + Sync),
+    ) -> Quickfix {
+        quickfix::search_all(registry, pattern)
+    }
+
+    /// Focuses the buffer of the match, which expands the stacked splits that contain
+    /// it, and selects the match, with the cursor at its start
+    pub fn jump_to(&self, entry: &QuickfixEntry) {
+        self.focus(&entry.buffer);
+        entry.buffer.set_selection(entry.range.clone());
+        entry.buffer.set_cursor(entry.range.start);
+    }
+
+    /// whether a dialog or the palette is shown, which take all input
+    fn is_modal(&self) -> bool {
+        self.dialog.lock().unwrap().is_some() || self.palette.lock().unwrap().is_some()
+    }
+
+    /// Shows a box with the title, message and choices in the middle of the screen, and
+    /// waits until one is picked with Left/Right and Enter. Returns its index, or None
+    /// if the dialog was closed with Esc. Afterwards, the screen is as it was before
+    pub fn dialog(
+        &self,
+        title: &str,
+        message: impl Into<AText>,
+        choices: &[&str],
+    ) -> io::Result<Option<usize>> {
+        *self.dialog.lock().unwrap() = Some(Dialog::new(title, message.into(), choices));
+        let res = self.edit(&self.focused(), &mut DialogHandler(self.dialog.clone()));
+        *self.dialog.lock().unwrap() = None;
+        res
+    }
+
+    /// Shows a command palette at the top of the screen: typing filters the commands
+    /// with fuzzy matching, Up and Down select one, and Enter returns its index. Esc
+    /// returns None
+    pub fn open_palette(&self, commands: &[&str]) -> io::Result<Option<usize>> {
+        *self.palette.lock().unwrap() = Some(Palette::new(commands));
+        let res = self.edit(&self.focused(), &mut PaletteHandler(self.palette.clone()));
+        *self.palette.lock().unwrap() = None;
+        res
+    }
+
+    /// Runs an event loop that edits the given buffer, which is focused meanwhile,
+    /// until the handler returns a result. Errors of the handler are propagated, as
+    /// well as io errors, which are converted. Handlers can call this again, e.g.
+    /// to show a dialog and return to the prompt afterwards, via [AbletCtx::ablet](crate::AbletCtx::ablet).
+    /// Afterwards, the focus goes back to where it was.
+    pub fn run_until<H, T, E>(&self, buf: &BufferRef, event_handler: &mut H) -> Result<T, E>
+    where
+        H: EventHandler<Result<T, E>>,
+        E: From<io::Error>,
+    {
+        let previous_focus = self.focused();
+        self.focus(buf);
+        let res = self.edit(buf, event_handler);
+        self.focus(&previous_focus);
+        res?
+    }
+
+    /// Like [Ablet::edit_prompt], but each event goes to the handler that is attached
+    /// to the focused buffer, see [BufferRef::set_handler], and to the given one if
+    /// there is none, or if the attached one ignores it. So an app with a handler per
+    /// pane doesn't have to pick the one for the focused buffer itself
+    pub fn edit_focused<H: EventHandler<T>, T: 'static>(
+        &self,
+        event_handler: &mut H,
+    ) -> io::Result<T> {
+        edit_with(
+            Some(self),
+            || self.render(),
+            || self.read_terminal_event(),
+            &self.focused(),
+            |ev, _buf, ctx| self.handle_focused(&ev, event_handler, ctx).done(),
+        )
+    }
+
+    /// see [Ablet::edit_focused]
+    fn handle_focused<H: EventHandler<T>, T: 'static>(
+        &self,
+        ev: &Event,
+        event_handler: &mut H,
+        ctx: &mut AbletCtx,
+    ) -> HandlerResult<T> {
+        let focused = self.focused();
+        match focused.handle_attached(ev, ctx) {
+            Some(HandlerResult::Ignored) | None => event_handler.handle(ev, &focused, ctx),
+            Some(res) => res,
+        }
+    }
+
+    fn edit<H: EventHandler<T>, T>(&self, buf: &BufferRef, event_handler: &mut H) -> io::Result<T> {
+        edit_with(
+            Some(self),
+            || self.render(),
+            || self.read_terminal_event(),
+            buf,
+            |ev, buf, ctx| event_handler.handle(&ev, buf, ctx).done(),
+        )
+    }
+
+    fn read_terminal_event(&self) -> io::Result<Event> {
+        match self.read_event(false)? {
+            AppEvent::Terminal(ev) => Ok(ev),
+            AppEvent::User(_) | AppEvent::Tick => {
+                unreachable!("user events weren't requested")
+            }
+        }
+    }
+
+    /// Returns a sender, through which background threads can send events to
+    /// [Ablet::edit_with_events], instead of rendering themselves
+    pub fn event_sender<E: Send + 'static>(&self) -> EventSender<E> {
+        EventSender::new(self.user_events.0.clone())
+    }
+
+    /// Like [Ablet::edit_prompt], but for the given buffer, and the handler also gets
+    /// the events that are sent through the senders from [Ablet::event_sender].
+    /// Each one wakes the loop up, so it renders again. Events of other types than E
+    /// are dropped, with a warning in the log
+    pub fn edit_with_events<E: 'static, H: AppEventHandler<E, T>, T>(
+        &self,
+        buf: &BufferRef,
+        event_handler: &mut H,
+    ) -> io::Result<T> {
+        let read_event = || loop {
+            match self.read_event(true)? {
+                AppEvent::Terminal(ev) => return Ok(AppEvent::Terminal(ev)),
+                AppEvent::Tick => return Ok(AppEvent::Tick),
+                AppEvent::User(ev) => match ev.downcast::<E>() {
+                    Ok(ev) => return Ok(AppEvent::User(*ev)),
+                    Err(_) => warn!(
+                        "dropped a user event, since it isn't a {}",
+                        std::any::type_name::<E>()
+                    ),
+                },
+            }
+        };
+        edit_with(
+            Some(self),
+            || self.render(),
+            read_event,
+            buf,
+            |ev, buf, ctx| event_handler.handle(ev, buf, ctx),
+        )
+    }
+
+    /// Makes [Ablet::edit_with_events] hand an [AppEvent::Tick] to its handler once
+    /// per interval, even if there is no input, e.g. for clocks or timeouts. None
+    /// turns it off
+    pub fn set_tick(&self, interval: Option<Duration>) {
+        *self.tick.lock().unwrap() = interval.map(|interval| Ticker {
+            interval,
+            next: Instant::now() + interval,
+        });
+    }
+
+    /// Waits for the next terminal event, or user event and tick if requested, and
+    /// focuses the hovered buffer meanwhile, when it's time. Resize and mouse events
+    /// are handled before they're returned
+    pub(crate) fn read_event(&self, user_events: bool) -> io::Result<AppEvent<UserEvent>> {
+        loop {
+            let mut tick_timeout = None;
+            if user_events {
+                if let Some(ticker) = &mut *self.tick.lock().unwrap() {
+                    let now = Instant::now();
+                    if now >= ticker.next {
+                        // ticks that were missed are skipped
+                        ticker.next = (ticker.next + ticker.interval).max(now);
+                        return Ok(AppEvent::Tick);
+                    }
+                    tick_timeout = Some(ticker.next - now);
+                }
+            }
+            let hover_timeout = self
+                .update_hover_focus()
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let timeout = [hover_timeout, tick_timeout].into_iter().flatten().min();
+            let terminal_ready = if user_events {
+                match self.wait_for_message(timeout) {
+                    Some(LoopMessage::User(ev)) => return Ok(AppEvent::User(ev)),
+                    // the event may have been read elsewhere meanwhile
+                    Some(LoopMessage::TerminalReady) => event::poll(Duration::ZERO)?,
+                    None => false,
+                }
+            } else {
+                timeout.map_or(Ok(true), event::poll)?
+            };
+            if terminal_ready {
+                let ev = event::read()?;
+                self.handle_resize(&ev);
+                self.handle_mouse(&ev)?;
+                return Ok(AppEvent::Terminal(ev));
+            }
+        }
+    }
+
+    /// Waits for the next message of the channel, with the terminal being watched
+    /// meanwhile, see [Ablet::wait_for_terminal]. None if the timeout is over first
+    fn wait_for_message(&self, timeout: Option<Duration>) -> Option<LoopMessage> {
+        self.wait_for_terminal();
+        let rx = self.user_events.1.lock().unwrap();
+        match timeout {
+            Some(timeout) => rx.recv_timeout(timeout).ok(),
+            // the ablet holds a sender, so the channel stays open
+            None => rx.recv().ok(),
+        }
+    }
+
+    /// Unless it's done already, starts a thread that waits for the terminal to have
+    /// an event, and then sends [LoopMessage::TerminalReady] through the channel, so
+    /// the event loop wakes up for both, terminal and user events. It only polls, so
+    /// the event is still read by whoever reads the terminal next. If an event loop
+    /// ends meanwhile, the thread keeps waiting for the next terminal event
+    fn wait_for_terminal(&self) {
+        let mut waiting = self.terminal_waiter.lock().unwrap();
+        if *waiting {
+            return;
+        }
+        *waiting = true;
+        let waiter = self.terminal_waiter.clone();
+        let tx = self.user_events.0.clone();
+        thread::spawn(move || {
+            // errors come up again when the event is read
+            while let Ok(false) = event::poll(Duration::from_secs(60)) {}
+            *waiter.lock().unwrap() = false;
+            _ = tx.send(LoopMessage::TerminalReady);
+        });
+    }
+}
+
+/// Draws the lines of the popup into the bottom left of the split tree, or into the
+/// top left if `at_top`, so it's next to the prompt
+fn draw_popup(frame: &mut Frame, popup: &AText, tree: Rect, at_top: bool) {
+    let w = tree.pos.col + tree.size.w;
+    let mut lines = vec![];
+    let mut start = 0;
+    for line in popup.text.split('\n') {
+        lines.push(Range::new(start, start + line.len()));
+        start += line.len() + 1;
+    }
+    let n_shown = lines.len().min(tree.size.h as usize);
+    let popup_w = lines
+        .iter()
+        .map(|r| popup.text[r.into_native()].width())
+        .max()
+        .unwrap_or(0)
+        .saturating_add(2)
+        .min(tree.size.w as usize) as u16;
+
+    let first_row = if at_top {
+        tree.pos.row
+    } else {
+        tree.pos.row + tree.size.h - n_shown as u16
+    };
+    for (row, line) in (first_row..).zip(&lines[..n_shown]) {
+        let blank = " ".repeat(popup_w as usize);
+        frame.print(
+            BufferPosition::new(row, 0),
+            &blank,
+            ContentStyle::default(),
+            w,
+        );
+        let mut col = 1;
+        for run in popup.get_range_style_pairs(*line) {
+            let text = &popup.text[run.range.into_native()];
+            col = frame.print(BufferPosition::new(row, col), text, *run.style, popup_w - 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_tree, AbletCtx, Buffer, LineEditor};
+
+    #[test]
+    fn test_prompt_grows_with_input() {
+        let buf = Buffer::from_text("output").into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf }));
+        ablet.prompt().buffer().insert_text_at_cursor("(+ 1\n   2)");
+
+        let frame = ablet.render_to_frame(Size { w: 8, h: 5 }).unwrap();
+        let rows = frame.to_lines();
+        insta::assert_debug_snapshot!(rows);
+    }
+
+    #[test]
+    fn test_status_in_separator() {
+        let buf = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf }));
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(Some(&ablet), &render);
+        ctx.set_status("3 matches");
+
+        let frame = ablet.render_to_frame(Size { w: 14, h: 4 }).unwrap();
+        assert_eq!(
+            frame.to_lines()[2],
+            "\u{2500}\u{2500}3 matches\u{2500}\u{2500}\u{2500}"
+        );
+    }
+
+    #[test]
+    fn test_separator_progress() {
+        let buf = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf }));
+        ablet.set_status("ab");
+        ablet.set_separator_progress(Some(0.5));
+
+        let frame = ablet.render_to_frame(Size { w: 8, h: 4 }).unwrap();
+        let separator = frame.rows().nth(2).unwrap();
+        assert_eq!(
+            frame.to_lines()[2],
+            "\u{2501}\u{2501}ab\u{2500}\u{2500}\u{2500}\u{2500}"
+        );
+        assert_eq!(separator[1].style, ContentStyle::new().green());
+        assert_eq!(separator[4].style, ContentStyle::default());
+    }
+
+    #[test]
+    fn test_popup_above_prompt() {
+        let buf = Buffer::from_text("aaaaaaaa\nbbbbbbbb\ncccccccc").into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf }));
+        ablet.set_popup(Some("one\ntwo".into()));
+
+        let frame = ablet.render_to_frame(Size { w: 8, h: 5 }).unwrap();
+        let rows = frame.to_lines();
+        assert_eq!(rows[..3], ["aaaaaaaa", " one bbb", " two ccc"]);
+    }
+
+    #[test]
+    fn test_prompt_position() {
+        let buf = Buffer::from_text("a\nb\nc").into_ref();
+        let rows = |ablet: &Ablet| {
+            let frame = ablet.render_to_frame(Size { w: 3, h: 4 }).unwrap();
+            frame.to_lines()
+        };
+
+        let tree = split_tree!(Vertical: { 1: buf });
+        let ablet = Ablet::new(tree.clone()).with_prompt_at(PromptPosition::Top);
+        ablet.prompt().buffer().insert_text_at_cursor("p");
+        assert_eq!(
+            rows(&ablet),
+            ["p  ", "\u{2500}\u{2500}\u{2500}", "a  ", "b  "]
+        );
+        assert!(ablet.buffer_at(BufferPosition::new(2, 0)) == Some(buf.clone()));
+
+        let ablet = Ablet::new(tree).without_prompt();
+        assert_eq!(rows(&ablet), ["a  ", "b  ", "c  ", "   "]);
+        assert!(ablet.focused() == buf);
+        let res = ablet.edit_prompt(&mut LineEditor::new());
+        assert!(matches!(res, Err(e) if e.kind() == io::ErrorKind::Unsupported));
+    }
+
+    #[test]
+    fn test_prerender_collapsed_children() {
+        let side = Buffer::new().into_ref();
+        let a = Buffer::new().into_ref();
+        let b = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: {
+            1: side,
+            1: Stacked: {
+                1: a,
+                1: b,
+            },
+        }));
+        ablet.render_to_frame(Size { w: 21, h: 12 }).unwrap();
+        assert_eq!(b.rendered_size(), None);
+
+        let tree = ablet.update_split_tree(|tree| tree.clone());
+        ablet.prerender(&tree).join().unwrap().unwrap();
+        // the tree gets 10 rows, minus one for each title bar
+        assert_eq!(b.rendered_size(), Some(Size { w: 10, h: 8 }));
+    }
+
+    #[test]
+    fn test_resize_fits_the_views() {
+        let text = (0..20)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let buf = Buffer::from_text(text).into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf })).without_prompt();
+        ablet.render_to_frame(Size { w: 5, h: 10 }).unwrap();
+        buf.set_cursor_visible(true);
+        buf.move_cursor_down(8);
+        assert_eq!(
+            buf.text_offset_at(BufferPosition::new(0, 0)),
+            TextOffset::ZERO
+        );
+
+        ablet.handle_resize(&Event::Resize(4, 5));
+        assert_eq!(buf.rendered_size(), Some(Size { w: 4, h: 5 }));
+        // the cursor in line 8 is scrolled back into view
+        assert_eq!(buf.text_offset_at(BufferPosition::new(4, 0)), buf.cursor());
+    }
+
+    #[test]
+    fn test_attached_handlers_get_the_events_of_their_buffer() {
+        let a = Buffer::new().into_ref();
+        let b = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: a, 1: b }));
+        a.set_handler(crate::handlers::from_fn(|_ev, _buf| {
+            HandlerResult::Done("a")
+        }));
+        // a handler with another result type is ignored
+        b.set_handler(crate::handlers::from_fn(|_ev, _buf| HandlerResult::Done(1)));
+        let mut fallback = crate::handlers::from_fn(|_ev, _buf| HandlerResult::Done("fallback"));
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(Some(&ablet), &render);
+        let ev = Event::FocusGained;
+
+        ablet.focus(&a);
+        assert_eq!(
+            ablet.handle_focused(&ev, &mut fallback, &mut ctx),
+            HandlerResult::Done("a")
+        );
+        ablet.focus(&b);
+        assert_eq!(
+            ablet.handle_focused(&ev, &mut fallback, &mut ctx),
+            HandlerResult::Done("fallback")
+        );
+        // events that the attached handler ignores go to the given one
+        b.set_handler(crate::handlers::from_fn(|_ev, _buf| {
+            HandlerResult::<&str>::Ignored
+        }));
+        assert_eq!(
+            ablet.handle_focused(&ev, &mut fallback, &mut ctx),
+            HandlerResult::Done("fallback")
+        );
+        a.clear_handler();
+        ablet.focus(&a);
+        assert_eq!(
+            ablet.handle_focused(&ev, &mut fallback, &mut ctx),
+            HandlerResult::Done("fallback")
+        );
+    }
+
+    #[test]
+    fn test_attached_handlers_can_clear_or_replace_themselves() {
+        let a = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: a }));
+        a.set_handler(crate::handlers::from_fn(|_ev, buf| {
+            buf.clear_handler();
+            HandlerResult::Done("once")
+        }));
+        let mut fallback = crate::handlers::from_fn(|_ev, _buf| HandlerResult::Done("fallback"));
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(Some(&ablet), &render);
+        let ev = Event::FocusGained;
+        ablet.focus(&a);
+        let mut handle = || ablet.handle_focused(&ev, &mut fallback, &mut ctx);
+        assert_eq!(handle(), HandlerResult::Done("once"));
+        assert_eq!(handle(), HandlerResult::Done("fallback"));
+
+        a.set_handler(crate::handlers::from_fn(|_ev, buf| {
+            buf.set_handler(crate::handlers::from_fn(|_ev, _buf| {
+                HandlerResult::Done("second")
+            }));
+            HandlerResult::Done("first")
+        }));
+        assert_eq!(handle(), HandlerResult::Done("first"));
+        assert_eq!(handle(), HandlerResult::Done("second"));
+        assert_eq!(handle(), HandlerResult::Done("second"));
+    }
+
+    #[test]
+    fn test_split_input() {
+        let left = Buffer::from_text("a\nb\nc").into_ref();
+        let right = Buffer::from_text("d").into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: left, 1: right })).without_prompt();
+        let input = Buffer::from_text("cd").into_ref();
+        ablet.set_split_input(&left, Some(input.clone()));
+
+        let frame = ablet.render_to_frame(Size { w: 5, h: 3 }).unwrap();
+        let rows = frame.to_lines();
+        assert_eq!(rows, ["a \u{2502}d ", "b \u{2502}  ", "cd\u{2502}  "]);
+        assert!(ablet.buffer_at(BufferPosition::new(2, 1)) == Some(input));
+
+        ablet.set_split_input(&left, None);
+        assert!(ablet.buffer_at(BufferPosition::new(2, 1)) == Some(left));
+    }
+
+    #[test]
+    fn test_focus_follows_mouse_after_delay() {
+        use crossterm::event::{KeyModifiers, MouseEvent};
+
+        let left = Buffer::new().into_ref();
+        let right = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: left, 1: right }));
+        ablet.set_mouse_focus(MouseFocus {
+            click_to_focus: true,
+            follows_mouse: Some(Duration::from_millis(50)),
+            select_and_copy: false,
+        });
+        ablet.render_to_frame(Size { w: 11, h: 6 }).unwrap();
+
+        let mouse = |kind, column, row| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let handle = |ev| ablet.handle_mouse(&ev).unwrap();
+        handle(mouse(MouseEventKind::Down(event::MouseButton::Left), 1, 1));
+        assert!(ablet.focused() == left);
+
+        handle(mouse(MouseEventKind::Moved, 8, 1));
+        assert!(ablet.update_hover_focus().is_some());
+        assert!(ablet.focused() == left);
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(ablet.update_hover_focus().is_none());
+        assert!(ablet.focused() == right);
+
+        // the prompt is below the separator
+        handle(mouse(MouseEventKind::Down(event::MouseButton::Left), 1, 5));
+        assert!(ablet.focused() == *ablet.prompt().buffer());
+    }
+
+    #[test]
+    fn test_mouse_is_ignored_behind_dialogs() {
+        use crossterm::event::{KeyModifiers, MouseEvent};
+
+        let left = Buffer::from_text("abc").into_ref();
+        let right = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: left, 1: right }));
+        ablet.set_mouse_focus(MouseFocus {
+            click_to_focus: true,
+            follows_mouse: Some(Duration::ZERO),
+            select_and_copy: true,
+        });
+        ablet.render_to_frame(Size { w: 11, h: 6 }).unwrap();
+        ablet.focus(&left);
+        *ablet.dialog.lock().unwrap() = Some(Dialog::new("", "sure?".into(), &["yes"]));
+
+        let mouse = |kind, column| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row: 1,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let handle = |ev| ablet.handle_mouse(&ev).unwrap();
+        handle(mouse(MouseEventKind::Moved, 8));
+        assert!(ablet.update_hover_focus().is_none());
+        handle(mouse(MouseEventKind::Down(event::MouseButton::Left), 1));
+        handle(mouse(MouseEventKind::Drag(event::MouseButton::Left), 3));
+        handle(mouse(MouseEventKind::Down(event::MouseButton::Left), 8));
+        assert!(ablet.focused() == left);
+        assert_eq!(left.selected_text(), None);
+        assert!(ablet.focus.lock().unwrap().drag.is_none());
+    }
+
+    #[test]
+    fn test_drag_selects_text() {
+        use crossterm::event::{KeyModifiers, MouseEvent};
+
+        let buf = Buffer::from_text("first line\nsecond line").into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf }));
+        ablet.set_mouse_focus(MouseFocus {
+            select_and_copy: true,
+            ..MouseFocus::default()
+        });
+        ablet.render_to_frame(Size { w: 20, h: 6 }).unwrap();
+
+        let drag = |kind, column, row| {
+            let ev = Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            });
+            ablet.handle_mouse(&ev).unwrap();
+        };
+        drag(MouseEventKind::Down(MouseButton::Left), 6, 0);
+        drag(MouseEventKind::Drag(MouseButton::Left), 2, 1);
+        assert_eq!(buf.selected_text().unwrap(), "line\nse");
+
+        // dragging out of the buffer selects up to its edge
+        drag(MouseEventKind::Drag(MouseButton::Left), 30, 4);
+        assert_eq!(buf.selected_text().unwrap(), "line\nsecond line");
+    }
+
+    #[test]
+    fn test_close_focused() {
+        let left = Buffer::new().into_ref();
+        let middle = Buffer::new().into_ref();
+        let right = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: left, 1: middle, 2: right }));
+        ablet.render_to_frame(Size { w: 20, h: 6 }).unwrap();
+
+        ablet.focus(&middle);
+        middle.insert_char_at_cursor('x');
+        assert!(!ablet.close_focused(|_| false));
+        assert!(ablet.close_focused(|buf| *buf == middle));
+        // the centers of both are 7.5 columns away, so the first one wins
+        assert!(ablet.focused() == left);
+        ablet.render_to_frame(Size { w: 20, h: 6 }).unwrap();
+        assert_eq!(ablet.rects().len(), 3);
+
+        // unmodified buffers are closed without asking
+        assert!(ablet.close_focused(|_| false));
+        assert!(ablet.focused() == right);
+        // the last buffer stays
+        assert!(!ablet.close_focused(|_| true));
+    }
+
+    #[test]
+    fn test_focus_bias() {
+        let left = Buffer::new().into_ref();
+        let right = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: left, 1: right }));
+        ablet.set_focus_bias(Some(3));
+        ablet.render_to_frame(Size { w: 21, h: 6 }).unwrap();
+        let width_of = |buf: &BufferRef| {
+            let rects = ablet.rects();
+            rects.iter().find(|(_, b)| b == buf).unwrap().0.size.w
+        };
+
+        // the prompt is focused, which isn't in the tree
+        assert_eq!(width_of(&left), 10);
+
+        ablet.focus(&right);
+        assert_eq!(width_of(&left), 5);
+        assert_eq!(width_of(&right), 15);
+    }
+
+    #[test]
+    fn test_user_events_wake_the_loop() {
+        let buf = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf }));
+        let sender = ablet.event_sender::<u32>();
+        let start = Instant::now();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            sender.send(1)
+        });
+        // without a terminal, the waiting thread reports it right away
+        let ev = loop {
+            match ablet.wait_for_message(Some(Duration::from_secs(10))) {
+                Some(LoopMessage::User(ev)) => break ev,
+                Some(LoopMessage::TerminalReady) => {}
+                None => panic!("the event didn't arrive"),
+            }
+        };
+        assert_eq!(*ev.downcast::<u32>().unwrap(), 1);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
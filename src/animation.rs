@@ -0,0 +1,80 @@
+//! Contains what's needed for animations, like smooth scrolling.
+//!
+//! Anything that is still animating after a render requests another frame, and
+//! the editing loops render again after [FRAME_INTERVAL], or the requested delay,
+//! if no event arrived until then.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// the time between two frames of an animation
+pub(crate) const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// when the earliest requested frame is due
+static NEXT_FRAME: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// makes the editing loops render again after FRAME_INTERVAL
+pub(crate) fn request_frame() {
+    request_frame_in(FRAME_INTERVAL)
+}
+
+/// makes the editing loops render again after the delay, or earlier if another
+/// frame was requested for then
+pub(crate) fn request_frame_in(delay: Duration) {
+    let due = Instant::now() + delay;
+    let mut next = NEXT_FRAME.lock().unwrap();
+    *next = Some(next.map_or(due, |next| next.min(due)));
+}
+
+/// the time until the requested frame is due, if one was requested
+pub(crate) fn take_frame_request() -> Option<Duration> {
+    let due = NEXT_FRAME.lock().unwrap().take()?;
+    Some(due.saturating_duration_since(Instant::now()))
+}
+
+/// an animated transition of the scroll offset of a view
+#[derive(Clone, Copy)]
+pub(crate) struct ScrollAnimation {
+    from: usize,
+    start: Instant,
+    duration: Duration,
+}
+
+impl ScrollAnimation {
+    pub(crate) fn new(from: usize, duration: Duration) -> Self {
+        Self {
+            from,
+            start: Instant::now(),
+            duration,
+        }
+    }
+
+    /// Returns the offset that should be displayed now, or None if the
+    /// animation reached its target
+    pub(crate) fn offset(&self, to: usize, now: Instant) -> Option<usize> {
+        let t = now.duration_since(self.start).as_secs_f64() / self.duration.as_secs_f64();
+        if t >= 1. {
+            return None;
+        }
+        // ease out, so the view slows down before it arrives
+        let progress = 1. - (1. - t).powi(3);
+        let offset = self.from as f64 + (to as f64 - self.from as f64) * progress;
+        Some(offset.round() as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_animation_eases_out() {
+        let animation = ScrollAnimation::new(0, Duration::from_millis(100));
+        let at = |ms| animation.offset(100, animation.start + Duration::from_millis(ms));
+        assert_eq!(at(0), Some(0));
+        assert_eq!(at(50), Some(88));
+        assert_eq!(at(100), None);
+    }
+}
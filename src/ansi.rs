@@ -0,0 +1,206 @@
+//! Parsing of text with ANSI escape sequences, like the colored output of
+//! cargo or `ls --color`. Only available with the `ansi` feature.
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use crate::{AText, ThemedStyle};
+
+impl AText {
+    /// Converts text with SGR escape sequences (`ESC [ ... m`) to styled text. All
+    /// other escape sequences, and control chars other than newlines and tabs,
+    /// are dropped.
+    pub fn from_ansi(s: &str) -> AText {
+        let mut res = AText::default();
+        let mut style = ContentStyle::default();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        let mut params = String::new();
+                        // a CSI sequence ends with a char in the range @ to ~
+                        let final_char = chars.by_ref().find(|c| {
+                            let is_final = ('@'..='~').contains(c);
+                            if !is_final {
+                                params.push(*c);
+                            }
+                            is_final
+                        });
+                        if final_char == Some('m') {
+                            apply_sgr(&mut style, &params);
+                        }
+                    }
+                    // OSC sequences, like window titles and hyperlinks, end with
+                    // BEL or ESC \
+                    Some(']') => {
+                        while let Some(c) = chars.next() {
+                            if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                                break;
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                '\n' | '\t' => res.push_char_formatted(c, as_option(style)),
+                c if c.is_control() => {}
+                c => res.push_char_formatted(c, as_option(style)),
+            }
+        }
+        res
+    }
+}
+
+fn as_option(style: ContentStyle) -> Option<ThemedStyle> {
+    (style != ContentStyle::default()).then_some(style.into())
+}
+
+/// the underline styles, of which only one can be set at a time
+const UNDERLINES: [Attribute; 5] = [
+    Attribute::Underlined,
+    Attribute::DoubleUnderlined,
+    Attribute::Undercurled,
+    Attribute::Underdotted,
+    Attribute::Underdashed,
+];
+
+fn apply_sgr(style: &mut ContentStyle, params: &str) {
+    // sub parameters, like in `38:5:208`, are treated like parameters, except for
+    // the underline style in `4:3`. The flag marks them
+    let mut params = params
+        .split(';')
+        .flat_map(|group| group.split(':').enumerate())
+        .map(|(i, p)| (p.parse::<u8>().unwrap_or(0), i > 0))
+        .peekable();
+    // `ESC [ m` is a reset
+    let mut next = Some(params.next().map_or(0, |(p, _)| p));
+    while let Some(p) = next {
+        match p {
+            0 => *style = ContentStyle::default(),
+            1 => style.attributes.set(Attribute::Bold),
+            2 => style.attributes.set(Attribute::Dim),
+            3 => style.attributes.set(Attribute::Italic),
+            4 => {
+                let sub = params.next_if(|(_, is_sub)| *is_sub).map(|(s, _)| s);
+                let underline = match sub {
+                    Some(0) => None,
+                    Some(2) => Some(Attribute::DoubleUnderlined),
+                    Some(3) => Some(Attribute::Undercurled),
+                    Some(4) => Some(Attribute::Underdotted),
+                    Some(5) => Some(Attribute::Underdashed),
+                    _ => Some(Attribute::Underlined),
+                };
+                set_underline(style, underline);
+            }
+            5 => style.attributes.set(Attribute::SlowBlink),
+            7 => style.attributes.set(Attribute::Reverse),
+            8 => style.attributes.set(Attribute::Hidden),
+            9 => style.attributes.set(Attribute::CrossedOut),
+            21 => set_underline(style, Some(Attribute::DoubleUnderlined)),
+            22 => {
+                style.attributes.unset(Attribute::Bold);
+                style.attributes.unset(Attribute::Dim);
+            }
+            23 => style.attributes.unset(Attribute::Italic),
+            24 => set_underline(style, None),
+            25 => style.attributes.unset(Attribute::SlowBlink),
+            27 => style.attributes.unset(Attribute::Reverse),
+            28 => style.attributes.unset(Attribute::Hidden),
+            29 => style.attributes.unset(Attribute::CrossedOut),
+            30..=37 => style.foreground_color = Some(basic_color(p - 30)),
+            38 => style.foreground_color = extended_color(&mut params.by_ref().map(|(p, _)| p)),
+            39 => style.foreground_color = None,
+            40..=47 => style.background_color = Some(basic_color(p - 40)),
+            48 => style.background_color = extended_color(&mut params.by_ref().map(|(p, _)| p)),
+            49 => style.background_color = None,
+            58 => style.underline_color = extended_color(&mut params.by_ref().map(|(p, _)| p)),
+            59 => style.underline_color = None,
+            90..=97 => style.foreground_color = Some(basic_color(p - 90 + 8)),
+            100..=107 => style.background_color = Some(basic_color(p - 100 + 8)),
+            _ => {}
+        }
+        next = params.next().map(|(p, _)| p);
+    }
+}
+
+fn set_underline(style: &mut ContentStyle, underline: Option<Attribute>) {
+    for attr in UNDERLINES {
+        style.attributes.unset(attr);
+    }
+    if let Some(attr) = underline {
+        style.attributes.set(attr);
+    }
+}
+
+/// the 16 colors of the basic palette, bright ones start at 8
+fn basic_color(i: u8) -> Color {
+    use Color::*;
+    [
+        Black,
+        DarkRed,
+        DarkGreen,
+        DarkYellow,
+        DarkBlue,
+        DarkMagenta,
+        DarkCyan,
+        Grey,
+        DarkGrey,
+        Red,
+        Green,
+        Yellow,
+        Blue,
+        Magenta,
+        Cyan,
+        White,
+    ][i as usize]
+}
+
+/// parses the rest of a `38;5;n` or `38;2;r;g;b` sequence
+fn extended_color(params: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match params.next()? {
+        5 => params.next().map(Color::AnsiValue),
+        2 => Some(Color::Rgb {
+            r: params.next()?,
+            g: params.next()?,
+            b: params.next()?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+
+    #[test]
+    fn test_from_ansi() {
+        let text = AText::from_ansi(
+            "\x1b[1;32mCompiling\x1b[0m ablet\n\x1b[38;5;208mwarn\x1b[39;4m ing\x1b[m\x1b]8;;http://x\x07!",
+        );
+        assert_eq!(text.as_str(), "Compiling ablet\nwarn ing!");
+
+        let style_at = |i: usize| text.style_map[i].map(|si| text.styles[si].style);
+        assert_eq!(style_at(0), Some(ContentStyle::new().dark_green().bold()));
+        assert_eq!(style_at(9), None);
+        assert_eq!(
+            style_at(16),
+            Some(ContentStyle::new().with(Color::AnsiValue(208)))
+        );
+        assert_eq!(style_at(20), Some(ContentStyle::new().underlined()));
+        assert_eq!(style_at(24), None);
+
+        let text = AText::from_ansi("\x1b[4:3;58;2;255;0;0me\x1b[4;59mu\x1b[24mx");
+        let style_at = |i: usize| text.style_map[i].map(|si| text.styles[si].style);
+        assert_eq!(
+            style_at(0),
+            Some(
+                ContentStyle::new()
+                    .attribute(Attribute::Undercurled)
+                    .underline(Color::Rgb { r: 255, g: 0, b: 0 })
+            )
+        );
+        assert_eq!(style_at(1), Some(ContentStyle::new().underlined()));
+        assert_eq!(style_at(2), None);
+    }
+}
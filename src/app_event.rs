@@ -0,0 +1,101 @@
+//! Events that background threads send to the event loop, see
+//! [Ablet::event_sender](crate::Ablet::event_sender)
+
+use std::{any::Any, marker::PhantomData, sync::mpsc::Sender};
+
+use crossterm::event::Event;
+
+use crate::{AbletCtx, BufferRef};
+
+/// what [Ablet::edit_with_events](crate::Ablet::edit_with_events) hands to its handler
+#[derive(Debug)]
+pub enum AppEvent<E> {
+    Terminal(Event),
+    /// an event that was sent via an [EventSender]
+    User(E),
+    /// sent regularly, if enabled via [Ablet::set_tick](crate::Ablet::set_tick)
+    Tick,
+}
+
+/// how events of any type are sent through the channel of an ablet
+pub(crate) type UserEvent = Box<dyn Any + Send>;
+
+/// what the channel of an ablet carries, which its event loop waits on
+pub(crate) enum LoopMessage {
+    User(UserEvent),
+    /// the terminal has an event to read
+    TerminalReady,
+}
+
+/// Sends events to [Ablet::edit_with_events](crate::Ablet::edit_with_events), which
+/// wakes it up, so it renders again. Clones send to the same loop.
+pub struct EventSender<E> {
+    tx: Sender<LoopMessage>,
+    _event: PhantomData<fn(E)>,
+}
+
+impl<E: Send + 'static> EventSender<E> {
+    pub(crate) fn new(tx: Sender<LoopMessage>) -> Self {
+        Self {
+            tx,
+            _event: PhantomData,
+        }
+    }
+
+    /// returns false if the ablet is gone
+    pub fn send(&self, ev: E) -> bool {
+        self.tx.send(LoopMessage::User(Box::new(ev))).is_ok()
+    }
+}
+
+impl<E> Clone for EventSender<E> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            _event: PhantomData,
+        }
+    }
+}
+
+/// Like [EventHandler](crate::EventHandler), but for [AppEvent]s. It's implemented
+/// for closures, which can hand terminal events on to another handler
+pub trait AppEventHandler<E, T> {
+    fn handle(&mut self, ev: AppEvent<E>, buf: &BufferRef, ctx: &mut AbletCtx) -> Option<T>;
+}
+
+impl<E, T, F> AppEventHandler<E, T> for F
+where
+    F: FnMut(AppEvent<E>, &BufferRef, &mut AbletCtx) -> Option<T>,
+{
+    fn handle(&mut self, ev: AppEvent<E>, buf: &BufferRef, ctx: &mut AbletCtx) -> Option<T> {
+        self(ev, buf, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{split_tree, Ablet, Buffer};
+
+    #[test]
+    fn test_events_are_queued() {
+        let buf = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf }));
+        let sender = ablet.event_sender::<String>();
+        std::thread::spawn(move || sender.send("hello".into()))
+            .join()
+            .unwrap();
+
+        let ev = ablet.read_event(true).unwrap();
+        let AppEvent::User(ev) = ev else {
+            panic!("expected a user event");
+        };
+        assert_eq!(*ev.downcast::<String>().unwrap(), "hello");
+
+        ablet.set_tick(Some(Duration::from_millis(20)));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(matches!(ablet.read_event(true).unwrap(), AppEvent::Tick));
+    }
+}
@@ -0,0 +1,128 @@
+//! Async variants of the editing loops, which await events from an
+//! [EventStream](crossterm::event::EventStream) instead of blocking on them, so
+//! an app can `select!` between terminal input, network messages, and timers in
+//! one task. Dropping the future ends the loop, the state stays in the buffers,
+//! so it can simply be started again.
+
+use std::{io, time::Instant};
+
+use crossterm::event::{Event, EventStream};
+use futures_util::{Stream, StreamExt};
+
+use crate::{animation, stats, Ablet, AbletCtx, BufferRef, EventHandler, HandlerResult, SplitTree};
+
+/// like [edit_buffer](crate::edit_buffer), but async
+pub async fn edit_buffer_async<H: EventHandler<T>, T>(
+    buf: &BufferRef,
+    split_tree: &SplitTree,
+    event_handler: &mut H,
+) -> io::Result<T> {
+    edit_with_async(
+        None,
+        || split_tree.render(),
+        EventStream::new(),
+        buf,
+        event_handler,
+    )
+    .await
+}
+
+impl Ablet {
+    /// like [Ablet::edit_prompt], but async
+    pub async fn edit_prompt_async<H: EventHandler<T>, T>(
+        &self,
+        event_handler: &mut H,
+    ) -> io::Result<T> {
+        edit_with_async(
+            Some(self),
+            || self.render(),
+            EventStream::new(),
+            self.prompt().buffer(),
+            event_handler,
+        )
+        .await
+    }
+}
+
+/// the loop behind [edit_buffer_async] and [Ablet::edit_prompt_async], like
+/// [edit_with](crate::edit_with)
+async fn edit_with_async<H: EventHandler<T>, T>(
+    ablet: Option<&Ablet>,
+    render: impl Fn() -> io::Result<()>,
+    mut events: impl Stream<Item = io::Result<Event>> + Unpin,
+    buf: &BufferRef,
+    event_handler: &mut H,
+) -> io::Result<T> {
+    let mut last_event_received = None;
+    loop {
+        render()?;
+        if let Some(received) = last_event_received.take() {
+            stats::record_latency(Instant::now().duration_since(received));
+        }
+
+        // wake up for the next animation frame, or when the hovered buffer gets focus
+        let deadline = if let Some(timeout) = animation::take_frame_request() {
+            Some(Instant::now() + timeout)
+        } else {
+            ablet.and_then(|ablet| ablet.update_hover_focus())
+        };
+        let ev = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline.into(), events.next()).await {
+                Ok(ev) => ev,
+                Err(_) => continue,
+            },
+            None => events.next().await,
+        };
+        let ev = ev.unwrap_or_else(|| {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the event stream ended",
+            ))
+        })?;
+        last_event_received = Some(Instant::now());
+        if let Some(ablet) = ablet {
+            ablet.handle_resize(&ev);
+            ablet.handle_mouse(&ev)?;
+        }
+        let mut ctx = AbletCtx::new(ablet, &render);
+        if let HandlerResult::Done(res) = event_handler.handle(&ev, buf, &mut ctx) {
+            return Ok(res);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent};
+    use futures_util::stream;
+
+    use super::*;
+    use crate::{split_tree, Buffer, SimpleLineHandler, SimpleLineHandlerResult};
+
+    #[tokio::test]
+    async fn test_handler_gets_events() {
+        let buf = Buffer::new().into_ref();
+        let ablet = Ablet::new(split_tree!(Vertical: { 1: buf }));
+        let events = "hi\n".chars().map(|c| {
+            let code = if c == '\n' {
+                KeyCode::Enter
+            } else {
+                KeyCode::Char(c)
+            };
+            Ok(Event::Key(KeyEvent::from(code)))
+        });
+        let prompt = ablet.prompt().buffer();
+        let res = edit_with_async(
+            Some(&ablet),
+            || Ok(()),
+            stream::iter(events),
+            prompt,
+            &mut SimpleLineHandler::new(),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(res, SimpleLineHandlerResult::LineDone));
+        assert_eq!(prompt.get_doc().0.lock().unwrap().content.text, "hi");
+    }
+}
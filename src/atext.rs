@@ -1,25 +1,70 @@
-use std::{borrow::Cow, collections::HashMap, fmt::Display};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
 
-use crossterm::style::{ContentStyle, StyledContent};
-use itertools::{enumerate, Itertools};
+use crossterm::style::StyledContent;
+use itertools::enumerate;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 
-use crate::{Range, StyledRange};
+use crate::{Range, StyledRange, ThemedStyle};
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone)]
 pub struct AText {
     pub(crate) text: String,
     pub(crate) style_map: Vec<Option<usize>>,
-    pub(crate) styles: Vec<crossterm::style::ContentStyle>,
+    pub(crate) styles: Vec<ThemedStyle>,
+    /// the index of each style in `styles`, so appending styled text doesn't have to
+    /// search them
+    style_index: HashMap<StyleKey, usize>,
+}
+
+/// Makes styles hashable, to look them up in [AText::style_index]. Only the names and
+/// colors are hashed, since crossterm's attributes can't be, which is fine for the few
+/// styles that only differ in their attributes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct StyleKey(ThemedStyle);
+
+impl Hash for StyleKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.name_id().hash(state);
+        self.0.style.foreground_color.hash(state);
+        self.0.style.background_color.hash(state);
+        self.0.style.underline_color.hash(state);
+    }
+}
+
+/// leaves out the style index, which is derived from the styles
+impl std::fmt::Debug for AText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AText")
+            .field("text", &self.text)
+            .field("style_map", &self.style_map)
+            .field("styles", &self.styles)
+            .finish()
+    }
 }
 
 impl AText {
+    fn from_parts(text: String, style_map: Vec<Option<usize>>, styles: Vec<ThemedStyle>) -> Self {
+        let style_index = enumerate(&styles).map(|(i, s)| (StyleKey(*s), i)).collect();
+        Self {
+            text,
+            style_map,
+            styles,
+            style_index,
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
     /// returns a list of pairs (range, style) that fall within the given
     /// range. Assumes self is a single line
-    pub(crate) fn get_range_style_pairs(&self, r: Range<usize>) -> Vec<StyledRange<usize>> {
+    pub(crate) fn get_range_style_pairs(&self, r: Range<usize>) -> Vec<StyledRange<'_, usize>> {
         let mut res = vec![];
         let mut start = r.start;
         let styles_in_range = self.style_map[r.into_native()].chunk_by(|a, b| a == b);
@@ -34,7 +79,7 @@ impl AText {
                 style: if let Some(style) = style {
                     Cow::Borrowed(&self.styles[style])
                 } else {
-                    Cow::Owned(ContentStyle::default())
+                    Cow::Owned(ThemedStyle::default())
                 },
                 range: Range { start, end },
             });
@@ -43,51 +88,99 @@ impl AText {
         res
     }
 
-    /// replaces a part of the string with a new given string. Returns true if
-    /// everything worked. If the range is not contained in the string, the new text
-    /// will be appended
+    /// Replaces a part of the string with a new given string. If the range is not
+    /// contained in the string, the new text will be appended. The text and its
+    /// styles are spliced in place, so this stays fast for large insertions, like pastes
     pub fn replace_range<T: Into<AText>>(&mut self, r: std::ops::Range<usize>, new_text: T) {
-        // * split into 3 parts: pre-range, range, post-range. The middle one might be
-        //   empty, if the range is len 0. The left one might be empty, if the range
-        //   is 0..0, and the right one may be empty if the range is len..len.
-        // * concat pre-range with new text, and the result of that with post-range
-
-        let mut new_text = new_text.into();
-        if r.is_empty() {
-            if r.start == 0 {
-                new_text += self.clone();
-                *self = new_text;
-            } else if r.start >= self.text.len() {
-                self.append_text(new_text);
-            } else {
-                let (Some(l), Some(r)) = self.clone().split_at_index(r.start) else {
-                    panic!("this should be impossible");
-                };
-
-                let mut res = l;
-                res += new_text;
-                res += r;
-                *self = res;
-            }
-        } else if r.start == 0 {
-            new_text += self.clone();
-            *self = new_text;
-        } else if r.start >= self.text.len() {
-            self.append_text(new_text);
+        let start = r.start.min(self.len());
+        let r = start..r.end.clamp(start, self.len());
+        if r == (0..self.len()) {
+            *self = new_text.into();
+            return;
+        }
+        let removed_styles = self.style_map[r.clone()].iter().any(Option::is_some);
+
+        let AText {
+            text,
+            style_map,
+            styles,
+            ..
+        } = new_text.into();
+        let mapping = self.import_styles(styles);
+        let text_tail = self.text.split_off(r.end);
+        self.text.truncate(r.start);
+        self.text.push_str(&text);
+        self.text.push_str(&text_tail);
+
+        let style_map_tail = self.style_map.split_off(r.end);
+        self.style_map.truncate(r.start);
+        if mapping.is_empty() {
+            self.style_map.extend(style_map);
         } else {
-            let (Some(l), Some(_)) = self.clone().split_at_index(r.start) else {
-                panic!("this should be impossible");
-            };
+            self.style_map
+                .extend(style_map.into_iter().map(|si| si.map(|i| mapping[i])));
+        }
+        self.style_map.extend(style_map_tail);
 
-            let (_, mb_r) = self.clone().split_at_index(r.end);
+        if removed_styles {
+            self.drop_unused_styles();
+        }
+    }
 
-            let mut res = l;
-            res += new_text;
-            if let Some(r) = mb_r {
-                res += r;
-            }
-            *self = res;
+    /// inserts the text at the given index, see [AText::replace_range]
+    pub fn insert<T: Into<AText>>(&mut self, index: usize, text: T) {
+        self.replace_range(index..index, text)
+    }
+
+    /// Sets the style of the given range, replacing the styles it had. Ranges
+    /// that reach past the end of the text are clamped
+    pub fn apply_style(&mut self, r: std::ops::Range<usize>, style: impl Into<ThemedStyle>) {
+        let index = self.intern_style(style.into());
+        self.set_style_index(r, Some(index));
+    }
+
+    /// removes the styles from the given range, see [AText::apply_style]
+    pub fn clear_style(&mut self, r: std::ops::Range<usize>) {
+        self.set_style_index(r, None);
+    }
+
+    fn set_style_index(&mut self, r: std::ops::Range<usize>, index: Option<usize>) {
+        let start = r.start.min(self.len());
+        let end = r.end.clamp(start, self.len());
+        self.style_map[start..end].fill(index);
+        self.drop_unused_styles();
+    }
+
+    /// adds the styles that aren't known yet, and returns a mapping from the
+    /// indices of the given styles to the indices in self
+    fn import_styles(&mut self, styles: Vec<ThemedStyle>) -> Vec<usize> {
+        styles
+            .into_iter()
+            .map(|style| self.intern_style(style))
+            .collect()
+    }
+
+    /// the index of the style, which is added if it isn't known yet
+    fn intern_style(&mut self, style: ThemedStyle) -> usize {
+        if let Some(i) = self.style_index.get(&StyleKey(style)) {
+            return *i;
         }
+        self.styles.push(style);
+        self.style_index
+            .insert(StyleKey(style), self.styles.len() - 1);
+        self.styles.len() - 1
+    }
+
+    fn drop_unused_styles(&mut self) {
+        let (styles, mapping) = reduce_styles(&self.styles, &self.style_map);
+        if styles.len() == self.styles.len() {
+            return;
+        }
+        for si in &mut self.style_map {
+            *si = si.map(|i| mapping[&i]);
+        }
+        self.style_index = enumerate(&styles).map(|(i, s)| (StyleKey(*s), i)).collect();
+        self.styles = styles;
     }
 
     /// if index is 0, the result will be (None, Some(self)), if the index is
@@ -103,6 +196,7 @@ impl AText {
                 text,
                 style_map,
                 styles,
+                ..
             } = self;
             let ltext = text[..index].to_string();
             let rtext = text[index..].to_string();
@@ -119,64 +213,42 @@ impl AText {
                 .map(|opt_i| opt_i.map(|i| rstyle_mapping[&i]))
                 .collect();
 
-            let lres = AText {
-                text: ltext,
-                style_map: lstyle_map,
-                styles: lstyles,
-            };
-            let rres = AText {
-                text: rtext,
-                style_map: rstyle_map,
-                styles: rstyles,
-            };
+            let lres = AText::from_parts(ltext, lstyle_map, lstyles);
+            let rres = AText::from_parts(rtext, rstyle_map, rstyles);
             (Some(lres), Some(rres))
         }
     }
 
-    pub fn append_text<T: Into<AText>>(&mut self, other: T) {
-        let AText {
-            text: other_text,
-            style_map: mut other_style_map,
-            styles: other_styles,
-        } = other.into();
-
-        // check whether any of the styles of the new text are already in
-        // this docs styles, if so, store the index
-        let mut mapping = HashMap::new();
-        for (other_index, other_style) in enumerate(other_styles) {
-            if let Some((i, _)) = self
-                .styles
+    /// returns a copy of the given range of the text. Like with slices, the range
+    /// must be within the text
+    pub fn sub_text(&self, r: std::ops::Range<usize>) -> AText {
+        let style_map = self.style_map[r.clone()].to_vec();
+        let (styles, mapping) = reduce_styles(&self.styles, &style_map);
+        AText::from_parts(
+            self.text[r].to_string(),
+            style_map
                 .iter()
-                .find_position(|my_style| *my_style == &other_style)
-            {
-                mapping.insert(other_index, i);
-            } else {
-                mapping.insert(other_index, self.styles.len());
-                self.styles.push(other_style);
-            }
-        }
-
-        // update the new texts style map to point to the styles in this doc
-        for si in &mut other_style_map {
-            *si = si.map(|i| mapping[&i])
-        }
+                .map(|opt_i| opt_i.map(|i| mapping[&i]))
+                .collect(),
+            styles,
+        )
+    }
 
-        self.text.push_str(&other_text);
-        self.style_map.append(&mut other_style_map);
+    /// appends the text in place, only the styles that are new to self are added
+    pub fn append_text<T: Into<AText>>(&mut self, other: T) {
+        let other = other.into();
+        let mapping = self.import_styles(other.styles);
+        self.text.push_str(&other.text);
+        self.style_map
+            .extend(other.style_map.into_iter().map(|si| si.map(|i| mapping[i])));
     }
 
-    pub fn push_char_formatted(&mut self, c: char, style: Option<ContentStyle>) {
+    pub fn push_char_formatted(&mut self, c: char, style: Option<ThemedStyle>) {
         self.text.push(c);
-        if let Some(style) = style {
-            if let Some((i, _)) = self.styles.iter().find_position(|e| *e == &style) {
-                self.style_map.push(Some(i));
-            } else {
-                self.style_map.push(Some(self.styles.len()));
-                self.styles.push(style);
-            }
-        } else {
-            self.style_map.push(None);
-        }
+        let style_index = style.map(|style| self.intern_style(style));
+        // the style map has an entry per byte
+        self.style_map
+            .extend(std::iter::repeat_n(style_index, c.len_utf8()));
     }
 
     pub fn push_char(&mut self, c: char) {
@@ -194,23 +266,55 @@ impl AText {
     pub fn len(&self) -> usize {
         self.text.len()
     }
+
+    /// the text without styles
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// iterates over the chars and their styles
+    pub fn chars_styled(&self) -> impl Iterator<Item = (char, Option<ThemedStyle>)> + '_ {
+        self.text
+            .char_indices()
+            .map(|(i, c)| (c, self.style_map[i].map(|si| self.styles[si])))
+    }
+
+    /// Returns the text in Unicode normalization form C. Each run of equally styled
+    /// text is normalized on its own, so chars with different styles aren't composed
+    pub fn nfc(&self) -> AText {
+        if is_nfc(&self.text) {
+            return self.clone();
+        }
+
+        let mut res = AText::default();
+        let mut start = 0;
+        for chunk in self.style_map.chunk_by(|a, b| a == b) {
+            let end = start + chunk.len();
+            let style = chunk[0].map(|i| self.styles[i]);
+            for c in self.text[start..end].nfc() {
+                res.push_char_formatted(c, style);
+            }
+            start = end;
+        }
+        res
+    }
 }
 
 /// returns a new Style Vec that contains only those elements from styles that are in the new_style_map
 /// as well as a mapping from index in styles to index in the new_styles
 fn reduce_styles(
-    styles: &[ContentStyle],
+    styles: &[ThemedStyle],
     new_style_map: &[Option<usize>],
-) -> (Vec<ContentStyle>, HashMap<usize, usize>) {
-    let remaining_styles = styles
-        .iter()
-        .enumerate()
-        .filter(|(i, _)| new_style_map.contains(&Some(*i)));
+) -> (Vec<ThemedStyle>, HashMap<usize, usize>) {
+    let mut used = vec![false; styles.len()];
+    for i in new_style_map.iter().flatten() {
+        used[*i] = true;
+    }
 
     let mut mapping = HashMap::new();
     let mut new_styles = vec![];
-    for (new_index, (old_index, style)) in enumerate(remaining_styles) {
-        mapping.insert(old_index, new_index);
+    for (old_index, style) in enumerate(styles).filter(|(i, _)| used[*i]) {
+        mapping.insert(old_index, new_styles.len());
         new_styles.push(*style);
     }
     (new_styles, mapping)
@@ -218,22 +322,14 @@ fn reduce_styles(
 
 impl From<&str> for AText {
     fn from(value: &str) -> Self {
-        AText {
-            text: value.into(),
-            style_map: vec![None; value.len()],
-            styles: vec![],
-        }
+        AText::from_parts(value.into(), vec![None; value.len()], vec![])
     }
 }
 
 impl From<String> for AText {
     fn from(value: String) -> Self {
         let len = value.len();
-        AText {
-            text: value,
-            style_map: vec![None; len],
-            styles: vec![],
-        }
+        AText::from_parts(value, vec![None; len], vec![])
     }
 }
 
@@ -241,21 +337,93 @@ impl<T: Display> From<StyledContent<T>> for AText {
     fn from(value: StyledContent<T>) -> Self {
         let c = value.content().to_string();
         let len = c.len();
-        AText {
-            text: c,
-            style_map: vec![Some(0); len],
-            styles: vec![*value.style()],
+        AText::from_parts(c, vec![Some(0); len], vec![(*value.style()).into()])
+    }
+}
+
+impl FromIterator<(char, Option<ThemedStyle>)> for AText {
+    fn from_iter<T: IntoIterator<Item = (char, Option<ThemedStyle>)>>(iter: T) -> Self {
+        let mut res = AText::default();
+        for (c, style) in iter {
+            res.push_char_formatted(c, style);
         }
+        res
     }
 }
 
+/// appends in place, so chains like `a + b + c` don't copy the text for every `+`
 impl<T: Into<AText>> std::ops::Add<T> for AText {
     type Output = Self;
 
+    fn add(mut self, rhs: T) -> Self::Output {
+        self.append_text(rhs);
+        self
+    }
+}
+
+impl<T: Into<AText>> std::ops::Add<T> for &AText {
+    type Output = AText;
+
     fn add(self, rhs: T) -> Self::Output {
-        let mut res = self.clone();
-        res.append_text(rhs);
-        res
+        self.clone() + rhs
+    }
+}
+
+impl<T: Into<AText>> Extend<T> for AText {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for text in iter {
+            self.append_text(text);
+        }
+    }
+}
+
+/// Builds an [AText] from parts, e.g. of a line with many styled fragments.
+/// Parts are appended in place, like with [AText::append_text]
+#[derive(Default)]
+pub struct ATextBuilder {
+    text: AText,
+}
+
+impl ATextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// reserves space for the given number of bytes of text
+    pub fn with_capacity(bytes: usize) -> Self {
+        let mut text = AText::default();
+        text.text.reserve(bytes);
+        text.style_map.reserve(bytes);
+        Self { text }
+    }
+
+    pub fn push(&mut self, part: impl Into<AText>) -> &mut Self {
+        self.text.append_text(part);
+        self
+    }
+
+    pub fn push_styled(&mut self, part: &str, style: impl Into<ThemedStyle>) -> &mut Self {
+        if part.is_empty() {
+            return self;
+        }
+        let style = self.text.intern_style(style.into());
+        self.text.text.push_str(part);
+        self.text
+            .style_map
+            .extend(std::iter::repeat_n(Some(style), part.len()));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn build(self) -> AText {
+        self.text
     }
 }
 
@@ -267,7 +435,7 @@ impl<T: Into<AText>> std::ops::AddAssign<T> for AText {
 
 #[cfg(test)]
 mod tests {
-    use crossterm::style::Stylize;
+    use crossterm::style::{ContentStyle, Stylize};
 
     use super::*;
 
@@ -306,4 +474,82 @@ mod tests {
         foo.replace_range(9..15, "");
         insta::assert_debug_snapshot!(foo);
     }
+
+    #[test]
+    fn test_apply_style() {
+        let mut foo = AText::from("abc") + "def".green();
+        foo.apply_style(2..4, ContentStyle::new().blue());
+        assert_eq!(
+            foo.styles,
+            [ContentStyle::new().green(), ContentStyle::new().blue()]
+        );
+        assert_eq!(
+            foo.style_map,
+            [9, 9, 1, 1, 0, 0].map(|i| (i != 9).then_some(i))
+        );
+
+        // clearing the only green chars drops the style
+        foo.clear_style(4..10);
+        assert_eq!(foo.styles, [ContentStyle::new().blue()]);
+        assert_eq!(
+            foo.style_map,
+            [9, 9, 0, 0, 9, 9].map(|i| (i != 9).then_some(i))
+        );
+    }
+
+    #[test]
+    fn test_insert_merges_styles() {
+        let mut foo = AText::from("ab".green()) + "cd".blue();
+        foo.insert(2, AText::from("x") + "y".blue() + "z".red());
+        assert_eq!(foo.text, "abxyzcd");
+        assert_eq!(foo.styles.len(), 3);
+        assert_eq!(
+            foo.style_map,
+            [0, 0, 9, 1, 2, 1, 1].map(|i| (i != 9).then_some(i))
+        );
+
+        // removing the only red char drops its style
+        foo.replace_range(4..5, "");
+        assert_eq!(foo.styles.len(), 2);
+        assert_eq!(
+            foo.style_map,
+            [0, 0, 9, 1, 1, 1].map(|i| (i != 9).then_some(i))
+        );
+    }
+
+    #[test]
+    fn test_styles_are_interned() {
+        let mut text = AText::from("a".red()) + "b".red().bold() + "c".red();
+        assert_eq!(
+            text.styles,
+            [ContentStyle::new().red(), ContentStyle::new().red().bold()]
+        );
+        assert_eq!(text.style_map, [Some(0), Some(1), Some(0)]);
+
+        // dropping a style re-indexes the remaining ones
+        text.replace_range(0..1, "");
+        text.replace_range(1..2, "");
+        text.append_text("d".red().bold());
+        assert_eq!(text.styles, [ContentStyle::new().red().bold()]);
+        assert_eq!(text.style_map, [Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn test_builder_and_extend() {
+        let mut builder = ATextBuilder::with_capacity(16);
+        builder
+            .push("a")
+            .push_styled("b", ContentStyle::new().red())
+            .push("c".red());
+        let built = builder.build();
+        assert_eq!(built.text, "abc");
+        assert_eq!(built.styles, [ContentStyle::new().red()]);
+        assert_eq!(built.style_map, [None, Some(0), Some(0)]);
+
+        let mut extended = AText::from("a");
+        extended.extend([AText::from("b".red()), AText::from("c".red())]);
+        assert_eq!(extended.style_map, built.style_map);
+        assert_eq!((&extended + "d").text, "abcd");
+        assert_eq!(extended.text, "abc");
+    }
 }
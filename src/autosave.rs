@@ -0,0 +1,176 @@
+//! Contains Autosave, which keeps backups of modified documents, so their changes
+//! can be recovered after a crash
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{shared, DocumentRef, Shared};
+
+/// Writes the content of modified documents to a backup file next to their file, see
+/// [autosave_file_for], once they weren't changed for a while. Call [Autosave::tick]
+/// regularly, e.g. on each [AppEvent::Tick](crate::AppEvent::Tick), see
+/// [Ablet::set_tick](crate::Ablet::set_tick). Once a document is saved and marked
+/// clean, its backup is removed. On the next start, [autosaved_version] tells whether
+/// there is something to recover
+pub struct Autosave {
+    idle: Duration,
+    documents: Vec<Watched>,
+}
+
+struct Watched {
+    doc: DocumentRef,
+    backup: PathBuf,
+    /// when the document was last changed, None once that change is backed up
+    changed_at: Shared<Option<Instant>>,
+    /// whether the backup file exists
+    backed_up: bool,
+}
+
+impl Autosave {
+    /// backs documents up once they weren't changed for the `idle` time
+    pub fn new(idle: Duration) -> Self {
+        Self {
+            idle,
+            documents: vec![],
+        }
+    }
+
+    /// Backs the document up, which is saved in the given file. If it's modified
+    /// already, it's backed up once the idle time is over, even without a change
+    pub fn watch(&mut self, doc: &DocumentRef, file: impl AsRef<Path>) {
+        let changed_at = shared(doc.is_modified().then(Instant::now));
+        let on_change = changed_at.clone();
+        doc.on_change(move || *on_change.lock().unwrap() = Some(Instant::now()));
+        self.documents.push(Watched {
+            doc: doc.clone(),
+            backup: autosave_file_for(file),
+            changed_at,
+            backed_up: false,
+        });
+    }
+
+    /// Writes the backups that are due, and removes the ones of documents that
+    /// aren't modified anymore. If that fails for a document, the others are still
+    /// handled, and the first error is returned
+    pub fn tick(&mut self) -> io::Result<()> {
+        self.tick_at(Instant::now())
+    }
+
+    fn tick_at(&mut self, now: Instant) -> io::Result<()> {
+        let mut res = Ok(());
+        for watched in &mut self.documents {
+            if let Err(e) = watched.tick_at(now, self.idle) {
+                res = res.and(Err(e));
+            }
+        }
+        res
+    }
+}
+
+impl Watched {
+    fn tick_at(&mut self, now: Instant, idle: Duration) -> io::Result<()> {
+        if !self.doc.is_modified() {
+            if self.backed_up {
+                match fs::remove_file(&self.backup) {
+                    // e.g. removed by the user, which is what we wanted anyway
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => self.backed_up = false,
+                }
+            }
+            return Ok(());
+        }
+        let mut changed_at = self.changed_at.lock().unwrap();
+        if changed_at.is_some_and(|t| now.saturating_duration_since(t) >= idle) {
+            let text = self.doc.0.lock().unwrap().content.text.clone();
+            fs::write(&self.backup, text)?;
+            *changed_at = None;
+            self.backed_up = true;
+        }
+        Ok(())
+    }
+}
+
+/// the file next to the given one that [Autosave] backs it up in. For `dir/notes.txt`,
+/// it's `dir/.notes.txt.autosave`
+pub fn autosave_file_for(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.autosave"))
+}
+
+/// Returns the backup of the file, if there is one that is newer than the file, e.g.
+/// to ask the user whether to recover it, when the file is opened
+pub fn autosaved_version(file: impl AsRef<Path>) -> io::Result<Option<String>> {
+    let backup = autosave_file_for(&file);
+    let backup_modified = match fs::metadata(&backup) {
+        Ok(meta) => meta.modified()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let is_newer = match fs::metadata(file) {
+        Ok(meta) => backup_modified > meta.modified()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+        Err(e) => return Err(e),
+    };
+    if is_newer {
+        Ok(Some(fs::read_to_string(backup)?))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_backs_up_modified_documents() {
+        let file = std::env::temp_dir().join(format!("ablet-autosave-{}.txt", std::process::id()));
+        let doc = Document::from_text("a").into_ref();
+        let mut autosave = Autosave::new(Duration::from_secs(1));
+        autosave.watch(&doc, &file);
+
+        doc.replace_range(1..1, "b");
+        let changed = Instant::now();
+        autosave.tick_at(changed).unwrap();
+        assert_eq!(autosaved_version(&file).unwrap(), None);
+        autosave.tick_at(changed + Duration::from_secs(2)).unwrap();
+        // the file doesn't exist, so the backup is newer
+        assert_eq!(autosaved_version(&file).unwrap().as_deref(), Some("ab"));
+
+        doc.mark_clean();
+        autosave.tick_at(changed + Duration::from_secs(3)).unwrap();
+        assert_eq!(autosaved_version(&file).unwrap(), None);
+    }
+
+    #[test]
+    fn test_documents_that_are_modified_already_are_backed_up() {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let files = [0, 1].map(|i| dir.join(format!("ablet-autosave-{pid}-{i}.txt")));
+        let docs = [0, 1].map(|_| Document::from_text("a").into_ref());
+        docs[0].replace_range(1..1, "b");
+        let mut autosave = Autosave::new(Duration::from_secs(1));
+        autosave.watch(&docs[0], &files[0]);
+        autosave.watch(&docs[1], &files[1]);
+
+        autosave
+            .tick_at(Instant::now() + Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(autosaved_version(&files[0]).unwrap().as_deref(), Some("ab"));
+
+        // a missing backup doesn't keep the other documents from being handled
+        fs::remove_file(autosave_file_for(&files[0])).unwrap();
+        docs[0].mark_clean();
+        docs[1].replace_range(1..1, "c");
+        autosave
+            .tick_at(Instant::now() + Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(autosaved_version(&files[1]).unwrap().as_deref(), Some("ac"));
+        fs::remove_file(autosave_file_for(&files[1])).unwrap();
+    }
+}
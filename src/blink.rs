@@ -0,0 +1,110 @@
+//! Simulated blinking, for terminals that ignore the blink attributes. When it's
+//! turned on, cells with [Attribute::SlowBlink] or [Attribute::RapidBlink] are drawn
+//! without the attribute, and their text is hidden every other half period.
+
+use std::{
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use crossterm::style::Attribute;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{animation, BufferPosition, Frame};
+
+/// the period of slow blinking, None if blinking is left to the terminal
+static BLINK_PERIOD: Mutex<Option<Duration>> = Mutex::new(None);
+/// the phases of all blinking cells are relative to this, so they blink in sync
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Simulates blinking text with the given period, instead of sending the blink
+/// attributes to the terminal. Rapid blinking is three times as fast. None turns
+/// it off, which is the default
+pub fn set_blink_fallback(period: Option<Duration>) {
+    *BLINK_PERIOD.lock().unwrap() = period;
+}
+
+/// applies the blink fallback to the frame, and requests a frame for the next toggle
+pub(crate) fn simulate_blink(frame: &mut Frame) {
+    let Some(period) = *BLINK_PERIOD.lock().unwrap() else {
+        return;
+    };
+    if let Some(next_toggle) = blink_at(frame, period, START.elapsed()) {
+        animation::request_frame_in(next_toggle);
+    }
+}
+
+/// Hides or shows the blinking cells, as it's right at `elapsed`. Returns the time
+/// until the next toggle, if there are blinking cells
+fn blink_at(frame: &mut Frame, period: Duration, elapsed: Duration) -> Option<Duration> {
+    let mut next_toggle: Option<Duration> = None;
+    let size = frame.size();
+    for row in 0..size.h {
+        for col in 0..size.w {
+            let Some(cell) = frame.cell_mut(BufferPosition::new(row, col)) else {
+                continue;
+            };
+            let half = if cell.style.attributes.has(Attribute::RapidBlink) {
+                period / 6
+            } else if cell.style.attributes.has(Attribute::SlowBlink) {
+                period / 2
+            } else {
+                continue;
+            };
+            let half = half.max(Duration::from_millis(1));
+            cell.style.attributes.unset(Attribute::SlowBlink);
+            cell.style.attributes.unset(Attribute::RapidBlink);
+            if (elapsed.as_nanos() / half.as_nanos()) % 2 == 1 {
+                cell.symbol = " ".repeat(cell.symbol.width());
+            }
+
+            let until_toggle =
+                half - Duration::from_nanos((elapsed.as_nanos() % half.as_nanos()) as u64);
+            next_toggle = Some(next_toggle.map_or(until_toggle, |t| t.min(until_toggle)));
+        }
+    }
+    next_toggle
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::{ContentStyle, Stylize};
+
+    use super::*;
+    use crate::Size;
+
+    #[test]
+    fn test_blinking_cells_are_hidden_every_other_half_period() {
+        let period = Duration::from_millis(1200);
+        let draw = |elapsed| {
+            let mut frame = Frame::new(Size { w: 3, h: 1 });
+            let col = frame.print(
+                BufferPosition::new(0, 0),
+                "a",
+                ContentStyle::new().slow_blink(),
+                3,
+            );
+            frame.print(
+                BufferPosition::new(0, col),
+                "b",
+                ContentStyle::new().rapid_blink(),
+                3,
+            );
+            let next = blink_at(&mut frame, period, Duration::from_millis(elapsed));
+            let text = frame.to_lines().remove(0);
+            (
+                text,
+                next,
+                frame.cell(BufferPosition::new(0, 0)).unwrap().style,
+            )
+        };
+
+        let (text, next, style) = draw(100);
+        assert_eq!(text, "ab ");
+        assert_eq!(next, Some(Duration::from_millis(100)));
+        assert_eq!(style, ContentStyle::new());
+        assert_eq!(draw(300).0, "a  ");
+        assert_eq!(draw(700).0, "   ");
+        assert_eq!(draw(900).0, " b ");
+    }
+}
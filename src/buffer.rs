@@ -4,29 +4,107 @@
 //! Cursors, Selections and Offsets
 
 use std::{
+    any::Any,
     borrow::Cow,
     io::{self},
-    sync::LazyLock,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
 };
 
 use crossterm::{
-    cursor, queue,
-    style::{ContentStyle, PrintStyledContent, Stylize},
+    event::Event,
+    style::{ContentStyle, Stylize},
 };
 use itertools::Itertools;
 use persistent_structs::PersistentStruct;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::{shared, AText, Document, DocumentRef, Range, Rect, Shared, Size, StyledRange};
+use crate::{
+    animation::{self, ScrollAnimation},
+    document::line_count,
+    filters::InputFilter,
+    named_style, shared, AText, AbletCtx, Cell, Document, DocumentRef, EventHandler, Frame,
+    HandlerResult, Range, Rect, SearchPattern, Shared, Size, StyledRange, TextOffset, ThemedStyle,
+};
 
 static CURSOR_STYLE: LazyLock<ContentStyle> = LazyLock::new(|| ContentStyle::new().reverse());
+/// the cursor in overwrite mode, so it's visible which mode is on
+static OVERWRITE_CURSOR_STYLE: LazyLock<ContentStyle> =
+    LazyLock::new(|| ContentStyle::new().underlined());
+static SELECTION_STYLE: LazyLock<ThemedStyle> = LazyLock::new(|| named_style("selection"));
+static HIGHLIGHT_STYLE: LazyLock<ThemedStyle> =
+    LazyLock::new(|| ContentStyle::new().black().on_dark_yellow().into());
 
 #[derive(Clone)]
 pub struct BufferRef(pub(crate) Shared<Buffer>);
 
 impl BufferRef {
-    pub fn render_at(&self, rect: Rect) -> io::Result<()> {
-        let buffer = self.0.lock().unwrap();
-        buffer.render_at(rect)
+    pub fn render_at(&self, frame: &mut Frame, rect: Rect) -> io::Result<()> {
+        self.0.lock().unwrap().render_at(frame, rect)
+    }
+
+    /// Sets how many lines are kept visible above and below the cursor, when the
+    /// view scrolls to follow it
+    pub fn set_scrolloff(&self, lines: usize) {
+        self.0.lock().unwrap().view.scrolloff = lines;
+    }
+
+    /// Sets how far the view may scroll past the end of the document
+    pub fn set_scroll_past_end(&self, scroll_past_end: ScrollPastEnd) {
+        self.0.lock().unwrap().view.scroll_past_end = scroll_past_end;
+    }
+
+    /// In typewriter mode, the view scrolls with every cursor movement, so the
+    /// cursor line stays in the middle. Near the end of the document, that needs
+    /// [ScrollPastEnd::Middle] or [ScrollPastEnd::Top]
+    pub fn set_typewriter_mode(&self, on: bool) {
+        self.0.lock().unwrap().view.typewriter = on;
+    }
+
+    /// If set, scrolling by more than one line is animated over the given time,
+    /// instead of jumping there at once
+    pub fn set_smooth_scrolling(&self, duration: Option<Duration>) {
+        self.0.lock().unwrap().view.smooth_scrolling = duration;
+    }
+
+    /// moves the cursor down by the height of the buffer
+    pub fn page_down(&self) {
+        self.0.lock().unwrap().page_down()
+    }
+
+    /// moves the cursor up by the height of the buffer
+    pub fn page_up(&self) {
+        self.0.lock().unwrap().page_up()
+    }
+
+    /// moves the cursor to the start of the given line, counting from 0
+    pub fn goto_line(&self, line: usize) {
+        self.0.lock().unwrap().goto_line(line)
+    }
+
+    /// Highlights all matches of the pattern, replacing previous highlights, and
+    /// returns their number. The highlights don't follow changes to the document,
+    /// so they should be renewed or cleared after edits.
+    pub fn highlight_matches(&self, pattern: impl SearchPattern) -> usize {
+        let matches = self.get_doc().find(pattern);
+        let n = matches.len();
+        self.0.lock().unwrap().view.highlights = matches;
+        n
+    }
+
+    pub fn clear_highlights(&self) {
+        self.0.lock().unwrap().view.highlights.clear()
+    }
+
+    /// see [Buffer::goto_next_match]
+    pub fn goto_next_match(&self) -> bool {
+        self.0.lock().unwrap().goto_next_match()
+    }
+
+    /// see [Buffer::goto_prev_match]
+    pub fn goto_prev_match(&self) -> bool {
+        self.0.lock().unwrap().goto_prev_match()
     }
 
     pub fn insert_char_at_cursor(&self, c: char) {
@@ -41,21 +119,143 @@ impl BufferRef {
         self.0.lock().unwrap().insert_text_at_cursor(text)
     }
 
+    /// Adds a filter, which transforms or rejects text that is typed or pasted into
+    /// this buffer. See the [filters](crate::filters) module for common ones
+    pub fn add_input_filter(
+        &self,
+        filter: impl FnMut(AText, &AText) -> Option<AText> + Send + 'static,
+    ) {
+        self.0.lock().unwrap().add_input_filter(filter)
+    }
+
+    pub fn clear_input_filters(&self) {
+        self.0.lock().unwrap().input_filters.clear()
+    }
+
     pub fn get_doc(&self) -> DocumentRef {
         self.0.lock().unwrap().document.clone()
     }
 
+    /// the size the buffer was last rendered with, if it was rendered yet
+    pub fn rendered_size(&self) -> Option<Size> {
+        self.0.lock().unwrap().view.last_rendered_size
+    }
+
+    /// see [Buffer::fit_to]
+    pub(crate) fn fit_to(&self, size: Size) {
+        self.0.lock().unwrap().fit_to(size)
+    }
+
     pub fn set_cursor_visible(&self, v: bool) {
         self.0.lock().unwrap().view.cursor_visible = v;
     }
 
+    /// In overwrite mode, typed chars, see [BufferRef::insert_char_at_cursor], replace
+    /// the characters behind the cursor, up to the end of the line, instead of pushing
+    /// them back. Inserted text, like pastes, yanks and completions, is inserted as
+    /// usual. The cursor is underlined instead of reversed then
+    pub fn set_overwrite(&self, overwrite: bool) {
+        self.0.lock().unwrap().view.overwrite = overwrite;
+    }
+
+    /// whether overwrite mode is on, e.g. to show it in a status line
+    pub fn overwrite(&self) -> bool {
+        self.0.lock().unwrap().view.overwrite
+    }
+
+    /// Shows the mask char instead of each character, e.g. for password input. The
+    /// document keeps the real text
+    pub fn set_mask(&self, mask: Option<char>) {
+        self.0.lock().unwrap().view.mask = mask;
+    }
+
+    pub fn mask(&self) -> Option<char> {
+        self.0.lock().unwrap().view.mask
+    }
+
+    /// Shows the marker on the rows below the end of the document, like vim's `~`,
+    /// so they can be told apart from empty lines. None, the default, leaves them blank
+    pub fn set_end_marker(&self, marker: Option<AText>) {
+        let mut this = self.0.lock().unwrap();
+        this.view.end_marker = marker;
+        this.render_cache = None;
+    }
+
+    /// Redraws changes of the document at most once per interval, e.g. for a chart
+    /// that is updated many times per second, so it doesn't make every frame redraw
+    /// it. In between, the last drawn content is shown, and a frame is requested for
+    /// when the interval is over, so the last change shows up eventually. So is the
+    /// scrolling of [BufferRef::add_line] and [BufferRef::push_line]. Other changes of
+    /// the view, like cursor movements, are drawn right away. None, the default,
+    /// draws every change
+    pub fn set_refresh_interval(&self, interval: Option<Duration>) {
+        self.0.lock().unwrap().refresh_interval = interval;
+    }
+
+    /// Attaches a handler to the buffer, which [Ablet::edit_focused](crate::Ablet::edit_focused)
+    /// hands the events to while the buffer is focused, instead of its own handler. It
+    /// has to have the same result type as that one, otherwise it's ignored
+    pub fn set_handler<T: 'static>(&self, handler: impl EventHandler<T> + Send + 'static) {
+        let handler: AttachedHandler<T> = Box::new(handler);
+        let mut this = self.0.lock().unwrap();
+        this.handler = Some(Box::new(handler));
+        this.handler_generation += 1;
+    }
+
+    /// removes the handler from [BufferRef::set_handler]
+    pub fn clear_handler(&self) {
+        let mut this = self.0.lock().unwrap();
+        this.handler = None;
+        this.handler_generation += 1;
+    }
+
+    /// Hands the event to the attached handler, if there is one with the result type T.
+    /// The buffer isn't locked meanwhile, so the handler can use it
+    pub(crate) fn handle_attached<T: 'static>(
+        &self,
+        ev: &Event,
+        ctx: &mut AbletCtx,
+    ) -> Option<HandlerResult<T>> {
+        let (mut handler, generation) = {
+            let mut this = self.0.lock().unwrap();
+            (this.handler.take()?, this.handler_generation)
+        };
+        let res = handler
+            .downcast_mut::<AttachedHandler<T>>()
+            .map(|h| h.handle(ev, self, ctx));
+        // unless the handler replaced or cleared itself
+        let mut this = self.0.lock().unwrap();
+        if this.handler_generation == generation {
+            this.handler = Some(handler);
+        }
+        res
+    }
+
+    /// see [DocumentRef::add_line], scrolls to the end afterwards
     pub fn add_line(&self, t: impl Into<AText>) {
         self.0.lock().unwrap().add_line(t)
     }
 
+    /// see [DocumentRef::push_line], scrolls to the end afterwards
+    pub fn push_line(&self, t: impl Into<AText>) {
+        self.0.lock().unwrap().push_line(t)
+    }
+
     pub fn move_cursor_by(&self, offset: isize) {
         self.0.lock().unwrap().move_cursor_by(offset)
     }
+    /// moves the cursor up by n lines, trying to stay in the same column, see
+    /// [Buffer::move_cursor_down]
+    pub fn move_cursor_up(&self, n: usize) {
+        self.0.lock().unwrap().move_cursor_up(n)
+    }
+
+    /// moves the cursor down by n lines, trying to stay in the same column, see
+    /// [Buffer::move_cursor_down]
+    pub fn move_cursor_down(&self, n: usize) {
+        self.0.lock().unwrap().move_cursor_down(n)
+    }
+
     pub fn move_cursor_to_line_start(&self) {
         self.0.lock().unwrap().move_cursor_to_line_start()
     }
@@ -63,110 +263,533 @@ impl BufferRef {
     pub fn move_cursor_to_line_end(&self) {
         self.0.lock().unwrap().move_cursor_to_line_end()
     }
+
+    /// replaces all selections with one covering the given range
+    pub fn set_selection(&self, range: std::ops::Range<TextOffset>) {
+        self.0.lock().unwrap().set_selection(range)
+    }
+
+    pub fn clear_selections(&self) {
+        self.0.lock().unwrap().view.selections.clear()
+    }
+
+    /// Moves the head of the last selection to the cursor. If there is no selection,
+    /// one is started at the cursor, so moving the cursor and calling this again
+    /// selects the text in between
+    pub fn extend_selection_to_cursor(&self) {
+        self.0.lock().unwrap().view.extend_selection_to_cursor()
+    }
+
+    /// the text of the last selection, None in masked buffers, so it isn't copied
+    pub fn selected_text(&self) -> Option<String> {
+        if self.0.lock().unwrap().view.mask.is_some() {
+            return None;
+        }
+        let range = self.selection()?;
+        let doc = self.get_doc();
+        let doc = doc.0.lock().unwrap();
+        Some(doc.content.text[Range::from(range).into_native()].to_string())
+    }
+
+    /// see [Buffer::text_offset_at]
+    pub fn text_offset_at(&self, pos: BufferPosition) -> TextOffset {
+        self.0.lock().unwrap().text_offset_at(pos)
+    }
+
+    /// returns the range of the last selection
+    pub fn selection(&self) -> Option<std::ops::Range<TextOffset>> {
+        self.0
+            .lock()
+            .unwrap()
+            .view
+            .selections
+            .last()
+            .map(|s| s.range())
+    }
+
+    /// the position of the cursor in the document
+    pub fn cursor(&self) -> TextOffset {
+        self.0.lock().unwrap().view.cursor
+    }
+
+    /// see [Buffer::set_cursor]
+    pub fn set_cursor(&self, pos: TextOffset) {
+        self.0.lock().unwrap().set_cursor(pos)
+    }
+
+    /// removes the given range from the document and returns it. A cursor
+    /// behind the range moves along with the text
+    pub fn delete_range(&self, range: std::ops::Range<TextOffset>) -> AText {
+        self.0.lock().unwrap().delete_range(range)
+    }
+
+    /// undoes the last change to the document, and puts the cursor behind the
+    /// restored text
+    pub fn undo(&self) {
+        self.0.lock().unwrap().undo()
+    }
+
+    /// redoes the last undone change, and puts the cursor behind it
+    pub fn redo(&self) {
+        self.0.lock().unwrap().redo()
+    }
+}
+
+/// Buffer refs compare by identity: two refs are equal, if they point to the same buffer
+impl PartialEq for BufferRef {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for BufferRef {}
+
+/// shows the identity of the buffer, since printing its content would be too verbose
+impl std::fmt::Debug for BufferRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BufferRef({:p})", Arc::as_ptr(&self.0))
+    }
 }
 
 pub struct Buffer {
     pub(crate) document: DocumentRef,
     pub(crate) view: View,
+    input_filters: Vec<InputFilter>,
+    /// the cells of the last render, which are reused while nothing changed, and
+    /// when they were drawn
+    render_cache: Option<(RenderKey, Vec<Cell>, Instant)>,
+    refresh_interval: Option<Duration>,
+    /// the offset that [Buffer::add_line] or [Buffer::push_line] scrolled to, which is
+    /// held back by the refresh interval, like the lines themselves
+    followed_offset: Option<usize>,
+    /// an [AttachedHandler], see [BufferRef::set_handler]
+    handler: Option<Box<dyn Any + Send>>,
+    /// changes whenever the handler is set or cleared, so a handler that is running
+    /// can tell whether it was
+    handler_generation: u64,
+}
+
+/// the handler from [BufferRef::set_handler], which is stored type-erased, since
+/// buffers don't know the result type of the loop they're edited in
+type AttachedHandler<T> = Box<dyn EventHandler<T> + Send>;
+
+/// Everything a rendered buffer depends on. Settings of the view that can't be
+/// compared cheaply drop the render cache instead when they change
+#[derive(PartialEq, Clone)]
+struct RenderKey {
+    document: usize,
+    revision: u64,
+    /// the size, but not the position, so the cells can be reused when the buffer
+    /// moves, e.g. when another child of a stacked split is expanded
+    size: Size,
+    offset: usize,
+    cursor: TextOffset,
+    cursor_visible: bool,
+    overwrite: bool,
+    mask: Option<char>,
+    selections: Vec<Selection>,
+    highlights: Vec<Range<usize>>,
+    /// the id of the theme that resolved the named styles
+    theme: u64,
 }
 
 impl Buffer {
     pub fn move_cursor_to_line_start(&mut self) {
-        let cursor_pos = self.view.cursor.0;
-        self.document.update_content(|c| {
-            let chars = c.text[..cursor_pos].chars().collect::<Vec<_>>();
-            let nl_pos = chars.iter().rposition(|c| *c == '\n');
-
-            if let Some(pos) = nl_pos {
-                self.view.cursor.0 = pos;
-            } else {
-                self.view.cursor.0 = 0;
-            }
-        })
+        let doc = self.document.0.lock().unwrap();
+        let cursor = self.view.cursor.get();
+        let start = doc.content.text[..cursor]
+            .rfind('\n')
+            .map_or(0, |pos| pos + 1);
+        self.view.cursor = TextOffset::new(start);
+        self.view.scroll_to_cursor(&doc.content.text);
     }
 
     pub fn move_cursor_to_line_end(&mut self) {
-        let cursor_pos = self.view.cursor.0;
-        self.document.update_content(|c| {
-            let nl_offset = c.text.chars().dropping(cursor_pos).position(|c| c == '\n');
+        let doc = self.document.0.lock().unwrap();
+        let cursor = self.view.cursor.get();
+        let end = doc.content.text[cursor..]
+            .find('\n')
+            .map_or(doc.content.len(), |offset| cursor + offset);
+        self.view.cursor = TextOffset::new(end);
+        self.view.scroll_to_cursor(&doc.content.text);
+    }
 
-            if let Some(nl_offset) = nl_offset {
-                self.view.cursor.0 += nl_offset;
-            } else {
-                self.view.cursor.0 = c.len();
+    pub fn move_cursor_up(&mut self, n: usize) {
+        self.move_cursor_vertically(-(n as isize))
+    }
+
+    /// Moves the cursor down by n lines. The column the cursor was in before the
+    /// first of consecutive vertical movements is remembered, so moving across a
+    /// shorter line doesn't pull it to the left permanently
+    pub fn move_cursor_down(&mut self, n: usize) {
+        self.move_cursor_vertically(n as isize)
+    }
+
+    fn move_cursor_vertically(&mut self, n: isize) {
+        let doc = self.document.0.lock().unwrap();
+        let text = &doc.content.text;
+        let cursor = self.view.cursor.get();
+        let lines = get_line_ranges(text);
+        let current = lines
+            .iter()
+            .position(|l| l.start <= cursor && cursor <= l.end)
+            .unwrap_or(0);
+        let col = match self.view.goal {
+            Some(goal) if goal.cursor.get() == cursor => goal.col,
+            _ => text[lines[current].start..cursor].graphemes(true).count(),
+        };
+
+        let target = &lines[current.saturating_add_signed(n).min(lines.len() - 1)];
+        let line = &text[target.start..target.end];
+        let offset = line
+            .grapheme_indices(true)
+            .nth(col)
+            .map_or(line.len(), |(i, _)| i);
+        self.view.cursor = TextOffset::new(target.start + offset);
+        self.view.goal = Some(Goal {
+            cursor: self.view.cursor,
+            col,
+        });
+        self.view.scroll_to_cursor(text);
+    }
+
+    /// Maps a position relative to the top left corner of the buffer, as it was
+    /// rendered last, to the offset of the text there. Positions behind the end of
+    /// a line map to the line end.
+    pub fn text_offset_at(&self, pos: BufferPosition) -> TextOffset {
+        let doc = self.document.0.lock().unwrap();
+        let text = &doc.content.text;
+        let lines = get_line_ranges(text);
+        let Some(line) = lines.get(self.view.offset + pos.row as usize) else {
+            return TextOffset::end_of(text);
+        };
+
+        let mut width = 0;
+        for (i, g) in text[line.into_native()].grapheme_indices(true) {
+            width += self.view.grapheme_width(g);
+            if width > pos.col as usize {
+                return TextOffset::new(line.start + i);
             }
-        })
+        }
+        TextOffset::new(line.end)
+    }
+
+    pub fn page_down(&mut self) {
+        self.move_cursor_down(self.page_height())
+    }
+
+    pub fn page_up(&mut self) {
+        self.move_cursor_up(self.page_height())
+    }
+
+    fn page_height(&self) -> usize {
+        self.view
+            .last_rendered_size
+            .map_or(1, |size| (size.h as usize).max(1))
+    }
+
+    pub fn goto_line(&mut self, line: usize) {
+        let doc = self.document.0.lock().unwrap();
+        let lines = get_line_ranges(&doc.content.text);
+        self.view.cursor = TextOffset::new(lines[line.min(lines.len() - 1)].start);
+        self.view.scroll_to_cursor(&doc.content.text);
+    }
+
+    /// Moves the cursor to the start of the first highlight behind it, wrapping around
+    /// at the end of the document. Returns false if there are no highlights
+    pub fn goto_next_match(&mut self) -> bool {
+        let cursor = self.view.cursor.get();
+        let highlights = &self.view.highlights;
+        let next = highlights
+            .iter()
+            .find(|r| r.start > cursor)
+            .or(highlights.first());
+        self.goto_match(next.map(|r| r.start))
+    }
+
+    /// like [Buffer::goto_next_match], but backwards
+    pub fn goto_prev_match(&mut self) -> bool {
+        let cursor = self.view.cursor.get();
+        let highlights = &self.view.highlights;
+        let prev = highlights
+            .iter()
+            .rfind(|r| r.start < cursor)
+            .or(highlights.last());
+        self.goto_match(prev.map(|r| r.start))
+    }
+
+    fn goto_match(&mut self, start: Option<usize>) -> bool {
+        let Some(start) = start else {
+            return false;
+        };
+        self.set_cursor(TextOffset::new(start));
+        true
     }
 
+    /// puts the cursor at the given position, or the char boundary before it
+    pub fn set_cursor(&mut self, pos: TextOffset) {
+        let doc = self.document.0.lock().unwrap();
+        let text = &doc.content.text;
+        self.view.cursor = pos.clamp_to(text);
+        self.view.goal = None;
+        self.view.scroll_to_cursor(text);
+    }
+
+    /// moves the cursor by the given number of grapheme clusters, i.e. what users
+    /// perceive as characters
     pub fn move_cursor_by(&mut self, offset: isize) {
-        let pos = self.view.cursor.0 as isize;
-        self.view.cursor.0 = (pos + offset)
-            .max(0)
-            .min(self.document.0.lock().unwrap().content.len() as isize)
-            as usize;
+        let doc = self.document.0.lock().unwrap();
+        let text = &doc.content.text;
+        let mut cursor = self.view.cursor.get();
+        for _ in 0..offset.unsigned_abs() {
+            cursor = if offset < 0 {
+                prev_grapheme_boundary(text, cursor)
+            } else {
+                next_grapheme_boundary(text, cursor)
+            };
+        }
+        self.view.cursor = TextOffset::new(cursor);
+        self.view.scroll_to_cursor(text);
     }
 
     pub fn from_text(text: impl Into<AText>) -> Buffer {
-        Self {
-            document: Document::from_text(text).into_ref(),
-            view: View::default(),
-        }
+        Self::from_doc(Document::from_text(text).into_ref())
     }
 
     pub fn from_doc(doc: DocumentRef) -> Buffer {
         Self {
             document: doc,
             view: View::default(),
+            input_filters: vec![],
+            render_cache: None,
+            refresh_interval: None,
+            followed_offset: None,
+            handler: None,
+            handler_generation: 0,
         }
     }
 
     pub fn new() -> Buffer {
-        Self {
-            document: Document::new().into_ref(),
-            view: View::default(),
-        }
+        Self::from_doc(Document::new().into_ref())
     }
 
     pub fn into_ref(self) -> BufferRef {
         BufferRef(shared(self))
     }
 
-    pub fn render_at(&self, rect: Rect) -> io::Result<()> {
-        self.view.render_doc(&self.document, rect)?;
+    /// Renders the buffer, and remembers the size, which is used for scrolling. If
+    /// neither the document nor the view changed since the last render, its cells are
+    /// reused, so panes that didn't change are cheap. See also
+    /// [BufferRef::set_refresh_interval]
+    pub fn render_at(&mut self, frame: &mut Frame, rect: Rect) -> io::Result<()> {
+        self.fit_to(rect.size);
+        let offset = self.view.displayed_offset();
+        let key = RenderKey {
+            document: Arc::as_ptr(&self.document.0) as usize,
+            revision: self.document.revision(),
+            size: rect.size,
+            offset,
+            cursor: self.view.cursor,
+            cursor_visible: self.view.cursor_visible,
+            overwrite: self.view.overwrite,
+            mask: self.view.mask,
+            selections: self.view.selections.clone(),
+            highlights: self.view.highlights.clone(),
+            theme: frame.theme().id(),
+        };
+        if let Some((cached_key, cells, drawn_at)) = &self.render_cache {
+            if *cached_key == key {
+                frame.set_cells_in(rect, cells);
+                return Ok(());
+            }
+            let until_refresh = self
+                .refresh_interval
+                .and_then(|interval| interval.checked_sub(drawn_at.elapsed()))
+                .filter(|d| !d.is_zero());
+            if let Some(until_refresh) = until_refresh {
+                // only the document changed, and the view followed it to its end,
+                // which is drawn once the interval is over
+                let mut key = key.clone();
+                key.revision = cached_key.revision;
+                if self.followed_offset == Some(self.view.offset) {
+                    key.offset = cached_key.offset;
+                }
+                if *cached_key == key {
+                    frame.set_cells_in(rect, cells);
+                    animation::request_frame_in(until_refresh);
+                    return Ok(());
+                }
+            }
+        }
+        self.view.render_doc(&self.document, frame, rect, offset)?;
+        self.render_cache = frame
+            .cells_in(rect)
+            .map(|cells| (key, cells, Instant::now()));
         Ok(())
     }
 
-    pub fn insert_char_at_cursor(&mut self, c: char) {
+    /// Remembers the size the buffer is shown with, and keeps the view valid for it,
+    /// see [View::clamp_to]. After a resize, the cursor is scrolled back into view
+    pub(crate) fn fit_to(&mut self, size: Size) {
+        let resized = self.view.last_rendered_size != Some(size);
+        self.view.last_rendered_size = Some(size);
         self.view
-            .insert_char_at_cursor(c, &mut self.document.0.lock().unwrap());
+            .clamp_to(&self.document.0.lock().unwrap().content.text, resized);
+    }
+
+    pub fn insert_char_at_cursor(&mut self, c: char) {
+        if self.document.is_read_only() {
+            return;
+        }
+        if self.input_filters.is_empty() {
+            self.view
+                .insert_char_at_cursor(c, &mut self.document.0.lock().unwrap());
+        } else {
+            let overwrite = self.view.overwrite;
+            self.insert_filtered(c.to_string(), overwrite);
+        }
     }
 
     pub fn delete_char_before_cursor(&mut self) {
+        if self.document.is_read_only() {
+            return;
+        }
         self.view
             .delete_char_before_cursor(&mut self.document.0.lock().unwrap());
     }
 
+    /// inserts the text after passing it through the input filters, pushing the text
+    /// behind the cursor back even in overwrite mode, since that is for typing only
     pub fn insert_text_at_cursor(&mut self, text: impl Into<AText>) {
-        self.view
-            .insert_text_at_cursor(text, &mut self.document.0.lock().unwrap())
+        if self.document.is_read_only() {
+            return;
+        }
+        self.insert_filtered(text.into(), false)
+    }
+
+    fn insert_filtered(&mut self, text: impl Into<AText>, overwrite: bool) {
+        let mut doc = self.document.0.lock().unwrap();
+        let mut text = text.into();
+        for filter in &mut self.input_filters {
+            let Some(filtered) = filter(text, &doc.content) else {
+                return;
+            };
+            text = filtered;
+        }
+        self.view.replace_at_cursor(text, overwrite, &mut doc)
+    }
+
+    pub fn add_input_filter(
+        &mut self,
+        filter: impl FnMut(AText, &AText) -> Option<AText> + Send + 'static,
+    ) {
+        self.input_filters.push(Box::new(filter));
+    }
+
+    /// Selects the range, or the part of it that is in the text. Its ends are moved
+    /// back onto char boundaries
+    pub fn set_selection(&mut self, range: std::ops::Range<TextOffset>) {
+        let doc = self.document.0.lock().unwrap();
+        let text = &doc.content.text;
+        self.view.selections = vec![Selection {
+            anchor: range.start.clamp_to(text),
+            head: range.end.clamp_to(text),
+        }];
     }
 
+    /// Removes the range, or the part of it that is in the text, and returns it. Its
+    /// ends are moved back onto char boundaries
+    pub fn delete_range(&mut self, range: std::ops::Range<TextOffset>) -> AText {
+        if self.document.is_read_only() {
+            return AText::default();
+        }
+        let mut doc = self.document.0.lock().unwrap();
+        let text = &doc.content.text;
+        let (start, end) = (
+            range.start.clamp_to(text).get(),
+            range.end.clamp_to(text).get(),
+        );
+        if start >= end {
+            return AText::default();
+        }
+
+        let removed = doc.content.sub_text(start..end);
+        doc.replace_range(start..end, "");
+        let cursor = self.view.cursor.get();
+        if cursor >= end {
+            self.view.cursor = TextOffset::new(cursor - (end - start));
+        } else if cursor > start {
+            self.view.cursor = TextOffset::new(start);
+        }
+        self.view.scroll_to_cursor(&doc.content.text);
+        removed
+    }
+
+    pub fn undo(&mut self) {
+        if self.document.is_read_only() {
+            return;
+        }
+        if let Some(pos) = self.document.undo() {
+            self.view.cursor = TextOffset::new(pos);
+            self.view
+                .scroll_to_cursor(&self.document.0.lock().unwrap().content.text);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if self.document.is_read_only() {
+            return;
+        }
+        if let Some(pos) = self.document.redo() {
+            self.view.cursor = TextOffset::new(pos);
+            self.view
+                .scroll_to_cursor(&self.document.0.lock().unwrap().content.text);
+        }
+    }
+
+    /// Scrolls to the end of the document. The empty line behind a trailing line
+    /// break, as [DocumentRef::add_line] leaves it, isn't kept in view, so the last
+    /// row isn't blank
     pub fn scroll_down(&mut self) {
         if let Some(size) = self.view.last_rendered_size {
             let doc = self.document.0.lock().unwrap();
-            let n_lines = doc.content.text.lines().count();
-            self.view.offset = 0.max(n_lines as isize - size.h as isize) as usize;
+            let text = &doc.content.text;
+            let n_lines = line_count(text) - usize::from(text.ends_with('\n'));
+            self.view.offset = n_lines.saturating_sub(size.h as usize);
         }
     }
 
     pub fn add_line(&mut self, t: impl Into<AText>) {
         self.document.add_line(t);
+        self.follow();
+    }
+
+    pub fn push_line(&mut self, t: impl Into<AText>) {
+        self.document.push_line(t);
+        self.follow();
+    }
+
+    /// scrolls to the end after a line was added, see [Buffer::followed_offset]
+    fn follow(&mut self) {
         self.scroll_down();
+        self.followed_offset = Some(self.view.offset);
+    }
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl View {
-    fn render_doc(&self, document: &DocumentRef, rect: Rect) -> io::Result<()> {
+    fn render_doc(
+        &self,
+        document: &DocumentRef,
+        frame: &mut Frame,
+        rect: Rect,
+        offset: usize,
+    ) -> io::Result<()> {
         // * slice into lines, because they are relevant for visibility
         //   and for render slices
         // * check what is visible (because if its outside the buffers size,
@@ -182,129 +805,319 @@ impl View {
         let ranges = get_line_ranges(&atext.text)
             .into_iter()
             // throw away the lines that are before the viewable part
-            .dropping(self.offset)
+            .dropping(offset)
             // throw away the lines that are behind the viewable part
             .take(rect.size.h as usize)
-            .map(|r| r.shortened_to(rect.size.w as usize))
+            .map(|r| {
+                // cut the line behind the last grapheme cluster that fits into the rect
+                let line = &atext.text[r.into_native()];
+                let mut width = 0;
+                let len = line
+                    .grapheme_indices(true)
+                    .find(|(_, g)| {
+                        width += self.grapheme_width(g);
+                        width > rect.size.w as usize
+                    })
+                    .map_or(line.len(), |(i, _)| i);
+                r.shortened_to(len)
+            })
             // after the next call we have lines on level 1 and segments with different styles
             // within one line.
-            .map(|r| atext.get_range_style_pairs(r))
+            .map(|r| (r, atext.get_range_style_pairs(r)))
             // split the selections further if they overlap with a selection
-            .enumerate()
-            .map(|(i, line)| {
+            .map(|(line_range, line)| {
                 // for each selection, get a simple range, which is the part of the selection
                 // that is in the current line
                 let line_selections: Vec<Range<usize>> = self
                     .selections
                     .iter()
-                    .filter_map(|selection| to_line_range(selection, i, rect.size.w as usize))
+                    .filter_map(|selection| {
+                        to_line_range(&Range::from(selection.range()), &line_range)
+                    })
                     .collect();
-                line.into_iter()
-                    .flat_map(|segment| adjust_for_seletions(segment, &line_selections))
-                    .collect::<Vec<StyledRange<usize>>>()
+                // highlights can be stale, if the document changed since they were set
+                let line_highlights: Vec<Range<usize>> = self
+                    .highlights
+                    .iter()
+                    .filter(|r| {
+                        atext.text.is_char_boundary(r.start) && atext.text.is_char_boundary(r.end)
+                    })
+                    .filter_map(|highlight| to_line_range(highlight, &line_range))
+                    .collect();
+                let line = line
+                    .into_iter()
+                    .flat_map(|segment| {
+                        adjust_for_ranges(segment, &line_highlights, &HIGHLIGHT_STYLE)
+                    })
+                    .flat_map(|segment| {
+                        adjust_for_ranges(segment, &line_selections, &SELECTION_STYLE)
+                    })
+                    .collect::<Vec<StyledRange<usize>>>();
+                (line_range, line)
             });
 
-        let mut stdout = io::stdout();
-        for (i_line, line) in ranges.enumerate() {
-            queue!(
-                stdout,
-                cursor::MoveTo(rect.pos.col, rect.pos.row + i_line as u16)
-            )?;
+        let max_col = rect.pos.col + rect.size.w;
+        let mut n_rows = 0;
+        for (i_line, (line_range, line)) in ranges.enumerate() {
+            n_rows += 1;
+            let row = rect.pos.row + i_line as u16;
+            let mut col = rect.pos.col;
             for styled_range in line {
                 // if we are at the cursor, print one char in cursor style, and the rest normally,
                 // otherwise print everything normally
-                if self.cursor_visible && styled_range.range.into_native().contains(&self.cursor.0)
+                if self.cursor_visible
+                    && styled_range
+                        .range
+                        .into_native()
+                        .contains(&self.cursor.get())
                 {
                     // render part before the cursor
                     let (pre_cursor_opt, Some(at_cursor)) =
-                        styled_range.range.split_at_index(self.cursor.0)
+                        styled_range.range.split_at_index(self.cursor.get())
                     else {
                         panic!("This should be impossible (because the cursor is in the range)");
                     };
                     if let Some(pre_cursor) = pre_cursor_opt {
-                        queue!(
-                            stdout,
-                            PrintStyledContent(
-                                styled_range
-                                    .style
-                                    .apply(&atext.text[pre_cursor.into_native()])
-                            )
-                        )?;
+                        col = frame.print(
+                            BufferPosition::new(row, col),
+                            &self.shown(&atext.text[pre_cursor.into_native()]),
+                            *styled_range.style,
+                            max_col,
+                        );
                     }
 
-                    // make a cursor visible at line end, if it is on a new_line
-                    // this might cause a rendering over a border if a line is max length
-                    // and the cursor is at its end
-                    let mut text_under_cursor =
-                        &atext.text[at_cursor.shortened_to(1).into_native()];
-                    if text_under_cursor == "\n" {
-                        text_under_cursor = " \n";
-                    }
-
-                    queue!(
-                        stdout,
-                        PrintStyledContent(CURSOR_STYLE.apply(text_under_cursor)),
-                        PrintStyledContent(
-                            styled_range.style.apply(
-                                &atext.text[at_cursor.update_start(|s| s + 1).into_native()]
-                            )
-                        )
-                    )?;
+                    let cursor_end =
+                        next_grapheme_boundary(&atext.text, self.cursor.get()).min(at_cursor.end);
+                    col = frame.print(
+                        BufferPosition::new(row, col),
+                        &self.shown(&atext.text[at_cursor.start..cursor_end]),
+                        styled_range.style.merge(self.cursor_style()),
+                        max_col,
+                    );
+                    col = frame.print(
+                        BufferPosition::new(row, col),
+                        &self.shown(&atext.text[cursor_end..at_cursor.end]),
+                        *styled_range.style,
+                        max_col,
+                    );
                 } else {
-                    queue!(
-                        stdout,
-                        PrintStyledContent(
-                            styled_range
-                                .style
-                                .apply(&atext.text[styled_range.range.into_native()])
-                        )
-                    )?;
+                    col = frame.print(
+                        BufferPosition::new(row, col),
+                        &self.shown(&atext.text[styled_range.range.into_native()]),
+                        *styled_range.style,
+                        max_col,
+                    );
                 }
             }
+
+            // line ranges don't contain the newline, so a cursor that sits on it, or at the
+            // end of the document, is visualized as a space behind the line
+            if self.cursor_visible && line_range.end == self.cursor.get() {
+                frame.print(
+                    BufferPosition::new(row, col),
+                    " ",
+                    self.cursor_style(),
+                    max_col,
+                );
+            }
         }
 
-        // if the cursor is at the end of the document, append a space to visualize it
-        if self.cursor.0 >= atext.len() && self.cursor_visible {
-            queue!(stdout, PrintStyledContent(CURSOR_STYLE.apply(" ")),)?;
+        if let Some(marker) = &self.end_marker {
+            for row in rect.pos.row + n_rows..rect.pos.row + rect.size.h {
+                let mut col = rect.pos.col;
+                for run in marker.get_range_style_pairs(Range::new(0, marker.len())) {
+                    let text = &marker.text[run.range.into_native()];
+                    col = frame.print(BufferPosition::new(row, col), text, *run.style, max_col);
+                }
+            }
         }
         Ok(())
     }
 
+    /// Keeps the view valid after the document was changed behind its back, or it was
+    /// resized: the cursor and selections are moved into the text and onto char
+    /// boundaries, the offset doesn't scroll past the end, and after a resize, the
+    /// cursor is scrolled back into view
+    fn clamp_to(&mut self, text: &str, resized: bool) {
+        self.cursor = self.cursor.clamp_to(text);
+        for selection in &mut self.selections {
+            selection.anchor = selection.anchor.clamp_to(text);
+            selection.head = selection.head.clamp_to(text);
+        }
+
+        if let Some(size) = self.last_rendered_size {
+            self.offset = self.offset.min(self.max_offset(text, size.h as usize));
+        }
+        if resized && self.cursor_visible {
+            self.scroll_to_cursor(text);
+        }
+    }
+
+    /// Scrolls just enough to show the cursor with `scrolloff` lines of context above
+    /// and below it, if possible. Does nothing before the first render, since
+    /// the height isn't known then
+    fn scroll_to_cursor(&mut self, text: &str) {
+        let Some(h) = self.last_rendered_size.map(|s| s.h as usize) else {
+            return;
+        };
+        if h == 0 {
+            return;
+        }
+
+        let line = text[..self.cursor.get()].matches('\n').count();
+        let max_offset = self.max_offset(text, h);
+        if self.typewriter {
+            let centered = line.saturating_sub((h - 1) / 2).min(max_offset);
+            if centered != self.offset {
+                self.scroll_to(centered);
+            }
+            return;
+        }
+        let scrolloff = self.scrolloff.min((h - 1) / 2);
+        if line < self.offset + scrolloff {
+            self.scroll_to(line.saturating_sub(scrolloff));
+        } else if line + scrolloff >= self.offset + h {
+            // don't scroll further than necessary to show the last line
+            self.scroll_to((line + scrolloff + 1 - h).min(max_offset));
+        }
+    }
+
+    /// the largest offset the view may have with a height of `h`, see [ScrollPastEnd]
+    fn max_offset(&self, text: &str, h: usize) -> usize {
+        let n_lines = line_count(text);
+        let last_line_at_bottom = n_lines.saturating_sub(h);
+        match self.scroll_past_end {
+            ScrollPastEnd::Disabled => last_line_at_bottom,
+            ScrollPastEnd::Middle => (n_lines - 1)
+                .saturating_sub(h.saturating_sub(1) / 2)
+                .max(last_line_at_bottom),
+            ScrollPastEnd::Top => n_lines - 1,
+        }
+    }
+
+    /// sets the offset, and animates the way there if smooth scrolling is on
+    fn scroll_to(&mut self, offset: usize) {
+        if let Some(duration) = self.smooth_scrolling {
+            if offset.abs_diff(self.offset) > 1 {
+                let from = self.displayed_offset();
+                self.scroll_animation = Some(ScrollAnimation::new(from, duration));
+            }
+        }
+        self.offset = offset;
+    }
+
+    /// the offset that is shown, which lags behind `offset` during an animation
+    fn displayed_offset(&mut self) -> usize {
+        let Some(animation) = self.scroll_animation else {
+            return self.offset;
+        };
+        match animation.offset(self.offset, Instant::now()) {
+            Some(offset) => {
+                animation::request_frame();
+                offset
+            }
+            None => {
+                self.scroll_animation = None;
+                self.offset
+            }
+        }
+    }
+
+    /// the text as it is shown, which is one mask char per grapheme cluster if there is a mask
+    fn shown<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self.mask {
+            Some(mask) => text
+                .graphemes(true)
+                .map(|_| mask)
+                .collect::<String>()
+                .into(),
+            None => text.into(),
+        }
+    }
+
+    fn grapheme_width(&self, g: &str) -> usize {
+        match self.mask {
+            Some(mask) => mask.width().unwrap_or(0),
+            None => g.width(),
+        }
+    }
+
+    fn extend_selection_to_cursor(&mut self) {
+        let cursor = self.cursor;
+        match self.selections.last_mut() {
+            Some(selection) => selection.head = cursor,
+            None => self.selections.push(Selection {
+                anchor: cursor,
+                head: cursor,
+            }),
+        }
+    }
+
+    /// in overwrite mode, the char replaces the one behind the cursor
     fn insert_char_at_cursor(&mut self, c: char, doc: &mut Document) {
-        let pos = self.cursor.0;
-        doc.content.replace_range(pos..pos, c.to_string());
-        self.cursor.0 += 1;
+        self.replace_at_cursor(c.to_string(), self.overwrite, doc)
     }
 
+    fn cursor_style(&self) -> ContentStyle {
+        if self.overwrite {
+            *OVERWRITE_CURSOR_STYLE
+        } else {
+            *CURSOR_STYLE
+        }
+    }
+
+    /// The end of the text that typed text replaces in overwrite mode: as many
+    /// grapheme clusters behind the cursor as are inserted, but not the line break
+    fn overwritten_end(&self, text: &str, inserted: &str) -> usize {
+        let pos = self.cursor.get();
+        let n = inserted.graphemes(true).count();
+        text[pos..]
+            .grapheme_indices(true)
+            .take_while(|(_, g)| !g.starts_with(['\n', '\r']))
+            .take(n)
+            .last()
+            .map_or(pos, |(i, g)| pos + i + g.len())
+    }
+
+    /// deletes the grapheme cluster before the cursor
     fn delete_char_before_cursor(&mut self, doc: &mut Document) {
-        let pos = self.cursor.0;
-        doc.content.replace_range((pos - 1)..pos, "");
-        if pos > 0 {
-            self.cursor.0 -= 1;
+        let pos = self.cursor.get();
+        let start = prev_grapheme_boundary(&doc.content.text, pos);
+        if start < pos {
+            doc.replace_range(start..pos, "");
+            self.cursor = TextOffset::new(start);
+            self.scroll_to_cursor(&doc.content.text);
         }
     }
 
-    pub fn insert_text_at_cursor(&mut self, text: impl Into<AText>, doc: &mut Document) {
-        let pos = self.cursor.0;
-        let atext = text.into();
-        self.cursor.0 += atext.len();
-        doc.content.replace_range(pos..pos, atext);
+    /// inserts the text, and replaces as much of the text behind the cursor with it
+    /// if overwrite is set
+    fn replace_at_cursor(&mut self, text: impl Into<AText>, overwrite: bool, doc: &mut Document) {
+        let text = text.into();
+        let pos = self.cursor.get();
+        let end = if overwrite {
+            self.overwritten_end(&doc.content.text, &text.text)
+        } else {
+            pos
+        };
+        self.cursor = TextOffset::new(doc.replace_range(pos..end, text));
+        self.scroll_to_cursor(&doc.content.text);
     }
 }
 
-/// convert selection to simple range, which is the part of the selection
-/// that is in the current line
-fn to_line_range(
-    _selection: &Selection<TextPosition>,
-    _i: usize,
-    _w: usize,
-) -> Option<Range<usize>> {
-    todo!()
+/// returns the part of the range (e.g. of a selection) that is in the given line
+fn to_line_range(range: &Range<usize>, line: &Range<usize>) -> Option<Range<usize>> {
+    let start = range.start.max(line.start);
+    let end = range.end.min(line.end);
+    (start < end).then(|| Range::new(start, end))
 }
 
-fn adjust_for_seletions<'a>(
+/// splits the segment where it overlaps with the ranges, and layers the style over
+/// the overlapping parts
+fn adjust_for_ranges<'a>(
     mut segment: StyledRange<'a, usize>,
     selections: &[Range<usize>],
+    style: &ThemedStyle,
 ) -> Vec<StyledRange<'a, usize>> {
     // when there are multiple selections that might overlap with a range,
     // we must check for each selection, whether it overlaps, and if some
@@ -316,10 +1129,10 @@ fn adjust_for_seletions<'a>(
         use crate::OverlapDescription::*;
         match segment.range.get_overlap_with(current_selection) {
             // no overlap with the current selection, check the rest
-            None => adjust_for_seletions(segment, selections),
+            None => adjust_for_ranges(segment, selections, style),
             // complete overlap with a selection, no need to check remaining selections
             Complete => {
-                *segment.style.to_mut() = segment.style.on_grey();
+                *segment.style.to_mut() = segment.style.merge(*style);
                 vec![segment]
             }
             // remember overlap, and check the remaining unoverlapped space against
@@ -327,11 +1140,15 @@ fn adjust_for_seletions<'a>(
             // ranges won't overlap, it suffices to sort by range start
             Right { old, foreign } | Left { foreign, old } => {
                 let mut found_selection = vec![StyledRange {
-                    style: Cow::Owned(segment.style.on_grey()),
+                    style: Cow::Owned(segment.style.merge(*style)),
                     range: foreign,
                 }];
-                found_selection.extend(adjust_for_seletions(segment.with_range(old), selections));
-                found_selection.sort_unstable_by(|a, b| a.range.start.cmp(&b.range.start));
+                found_selection.extend(adjust_for_ranges(
+                    segment.with_range(old),
+                    selections,
+                    style,
+                ));
+                found_selection.sort_unstable_by_key(|a| a.range.start);
                 found_selection
             }
             Inner {
@@ -341,15 +1158,20 @@ fn adjust_for_seletions<'a>(
             } => {
                 // same as above, but we need to check both free areas now
                 let mut found_selection = vec![StyledRange {
-                    style: Cow::Owned(segment.style.on_grey()),
+                    style: Cow::Owned(segment.style.merge(*style)),
                     range: foreign,
                 }];
-                found_selection.extend(adjust_for_seletions(
+                found_selection.extend(adjust_for_ranges(
                     segment.clone().with_range(old_l),
                     selections,
+                    style,
+                ));
+                found_selection.extend(adjust_for_ranges(
+                    segment.with_range(old_r),
+                    selections,
+                    style,
                 ));
-                found_selection.extend(adjust_for_seletions(segment.with_range(old_r), selections));
-                found_selection.sort_unstable_by(|a, b| a.range.start.cmp(&b.range.start));
+                found_selection.sort_unstable_by_key(|a| a.range.start);
                 found_selection
             }
         }
@@ -358,11 +1180,24 @@ fn adjust_for_seletions<'a>(
     }
 }
 
+fn next_grapheme_boundary(text: &str, pos: usize) -> usize {
+    text[pos..]
+        .graphemes(true)
+        .next()
+        .map_or(pos, |g| pos + g.len())
+}
+
+fn prev_grapheme_boundary(text: &str, pos: usize) -> usize {
+    text[..pos]
+        .graphemes(true)
+        .next_back()
+        .map_or(pos, |g| pos - g.len())
+}
+
 fn get_line_ranges(text: &str) -> Vec<Range<usize>> {
-    let lines = text.chars().filter(|c| *c == '\n').count() + 1;
-    let mut res = Vec::with_capacity(lines);
+    let mut res = Vec::with_capacity(line_count(text));
     let mut current_line_start = 0;
-    for (i, char) in text.chars().enumerate() {
+    for (i, char) in text.char_indices() {
         if char == '\n' {
             res.push(Range::new(current_line_start, i));
             current_line_start = i + 1;
@@ -374,17 +1209,52 @@ fn get_line_ranges(text: &str) -> Vec<Range<usize>> {
 
 #[derive(Default)]
 pub(crate) struct View {
-    selections: Vec<Selection<TextPosition>>,
+    selections: Vec<Selection>,
+    /// ranges that are highlighted, e.g. search matches, sorted and not overlapping
+    highlights: Vec<Range<usize>>,
     // NOT supported yet
     // linewrap: bool,
     offset: usize,
-    cursor: TextPosition,
+    cursor: TextOffset,
     cursor_visible: bool,
     last_rendered_size: Option<Size>,
+    goal: Option<Goal>,
+    /// the number of lines kept visible above and below the cursor when scrolling
+    scrolloff: usize,
+    scroll_past_end: ScrollPastEnd,
+    /// keeps the cursor line in the middle, see [BufferRef::set_typewriter_mode]
+    typewriter: bool,
+    smooth_scrolling: Option<Duration>,
+    scroll_animation: Option<ScrollAnimation>,
+    /// shown instead of each grapheme cluster
+    mask: Option<char>,
+    /// see [BufferRef::set_overwrite]
+    overwrite: bool,
+    /// shown on the rows below the end of the document
+    end_marker: Option<AText>,
 }
 
-#[derive(Default)]
-pub struct TextPosition(usize);
+/// How far a view may scroll past the end of the document, see
+/// [BufferRef::set_scroll_past_end]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScrollPastEnd {
+    /// the last line stays at the bottom of the view
+    #[default]
+    Disabled,
+    /// the last line can be scrolled up to the middle of the view
+    Middle,
+    /// the last line can be scrolled up to the top of the view
+    Top,
+}
+
+/// The column vertical cursor movements try to keep. It's only valid as long as
+/// the cursor stays where the last vertical movement put it
+#[derive(Clone, Copy)]
+struct Goal {
+    cursor: TextOffset,
+    /// in grapheme clusters
+    col: usize,
+}
 
 #[derive(Default, Hash, Clone, Copy, PersistentStruct, PartialEq, Eq, Debug, PartialOrd, Ord)]
 pub struct BufferPosition {
@@ -398,6 +1268,261 @@ impl BufferPosition {
     }
 }
 
-pub struct Selection<T> {
-    _range: Range<T>,
+/// A selected part of the text. The anchor is where the selection started,
+/// the head is the end that moves when the selection is extended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    anchor: TextOffset,
+    head: TextOffset,
+}
+
+impl Selection {
+    pub fn range(&self) -> std::ops::Range<TextOffset> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Color;
+
+    use super::*;
+    use crate::rect;
+
+    #[test]
+    fn test_selection_spans_lines() {
+        let buf = Buffer::from_text("abc\ndef").into_ref();
+        buf.set_selection(TextOffset::new(1)..TextOffset::new(5));
+
+        let mut frame = Frame::new(Size { w: 3, h: 2 });
+        buf.render_at(&mut frame, rect(0, 0, 3, 2)).unwrap();
+        let selected = frame
+            .rows()
+            .map(|row| {
+                row.iter()
+                    .map(|c| c.style.background_color == Some(Color::Grey))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            selected,
+            vec![vec![false, true, true], vec![true, false, false]]
+        );
+    }
+
+    #[test]
+    fn test_vertical_movement_keeps_goal_column() {
+        let mut buf = Buffer::from_text("long line\nab\nanother line");
+        buf.move_cursor_by(6);
+        buf.move_cursor_down(1);
+        assert_eq!(buf.view.cursor.get(), 12);
+        buf.move_cursor_down(1);
+        assert_eq!(buf.view.cursor.get(), 19);
+        buf.move_cursor_up(5);
+        assert_eq!(buf.view.cursor.get(), 6);
+
+        // moving horizontally sets a new goal
+        buf.move_cursor_down(1);
+        buf.move_cursor_by(-1);
+        buf.move_cursor_down(1);
+        assert_eq!(buf.view.cursor.get(), 14);
+    }
+
+    #[test]
+    fn test_cursor_moves_over_grapheme_clusters() {
+        // an e with a combining accent, and a family emoji made of several code points
+        let text = "ae\u{301}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}b";
+        let mut buf = Buffer::from_text(text);
+        buf.move_cursor_by(3);
+        assert_eq!(buf.view.cursor.get(), text.len() - 1);
+
+        buf.move_cursor_by(-1);
+        buf.delete_char_before_cursor();
+        assert_eq!(buf.view.cursor.get(), 1);
+        buf.delete_char_before_cursor();
+        assert_eq!(buf.view.cursor.get(), 0);
+        assert_eq!(
+            buf.document.0.lock().unwrap().content.text,
+            "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}b"
+        );
+    }
+
+    #[test]
+    fn test_scrolloff_keeps_context_around_cursor() {
+        let text = (0..20).map(|i| i.to_string()).join("\n");
+        let mut buf = Buffer::from_text(text);
+        buf.view.scrolloff = 2;
+        let mut frame = Frame::new(Size { w: 5, h: 5 });
+        buf.render_at(&mut frame, Rect::new(0, 0, 5, 5)).unwrap();
+
+        buf.move_cursor_down(2);
+        assert_eq!(buf.view.offset, 0);
+        buf.move_cursor_down(1);
+        assert_eq!(buf.view.offset, 1);
+        buf.move_cursor_down(100);
+        assert_eq!(buf.view.offset, 15);
+        buf.move_cursor_up(2);
+        assert_eq!(buf.view.offset, 15);
+        buf.move_cursor_up(1);
+        assert_eq!(buf.view.offset, 14);
+    }
+
+    #[test]
+    fn test_typewriter_mode_and_scroll_past_end() {
+        let text = (0..20).map(|i| i.to_string()).join("\n");
+        let mut buf = Buffer::from_text(text);
+        buf.view.typewriter = true;
+        let mut frame = Frame::new(Size { w: 5, h: 5 });
+        buf.render_at(&mut frame, Rect::new(0, 0, 5, 5)).unwrap();
+
+        buf.move_cursor_down(1);
+        assert_eq!(buf.view.offset, 0);
+        buf.move_cursor_down(4);
+        assert_eq!(buf.view.offset, 3);
+        buf.move_cursor_down(100);
+        assert_eq!(buf.view.offset, 15);
+
+        buf.view.scroll_past_end = ScrollPastEnd::Middle;
+        buf.move_cursor_up(1);
+        buf.move_cursor_down(1);
+        assert_eq!(buf.view.offset, 17);
+
+        buf.view.typewriter = false;
+        buf.view.scroll_past_end = ScrollPastEnd::Top;
+        buf.view.offset = 100;
+        buf.render_at(&mut frame, Rect::new(0, 0, 5, 5)).unwrap();
+        assert_eq!(buf.view.offset, 19);
+    }
+
+    #[test]
+    fn test_view_stays_valid_after_changes() {
+        let text = (0..20).map(|i| i.to_string()).join("\n");
+        let buf = Buffer::from_text(text).into_ref();
+        buf.set_cursor_visible(true);
+        let mut frame = Frame::new(Size { w: 5, h: 5 });
+        buf.render_at(&mut frame, rect(0, 0, 5, 5)).unwrap();
+        buf.move_cursor_down(12);
+        let view = |buf: &BufferRef| {
+            let buf = buf.0.lock().unwrap();
+            (buf.view.offset, buf.view.cursor.get())
+        };
+        assert_eq!(view(&buf), (8, 26));
+
+        // shrinking the view keeps the cursor visible
+        buf.render_at(&mut frame, rect(0, 0, 5, 2)).unwrap();
+        assert_eq!(view(&buf), (11, 26));
+
+        // the cursor and offset are moved into a shorter text
+        buf.get_doc()
+            .update_content(|text| *text = "a\u{e9}\nb".into());
+        buf.render_at(&mut frame, rect(0, 0, 5, 2)).unwrap();
+        assert_eq!(view(&buf), (0, 5));
+        buf.get_doc()
+            .update_content(|text| *text = "a\u{e9}".into());
+        buf.render_at(&mut frame, rect(0, 0, 5, 2)).unwrap();
+        assert_eq!(view(&buf), (0, 3));
+    }
+
+    #[test]
+    fn test_mask_hides_the_text() {
+        let buf = Buffer::from_text("pa\u{e9}s").into_ref();
+        buf.set_mask(Some('*'));
+        buf.set_selection(TextOffset::new(0)..TextOffset::new(2));
+        let mut frame = Frame::new(Size { w: 6, h: 1 });
+        buf.render_at(&mut frame, rect(0, 0, 6, 1)).unwrap();
+        assert_eq!(frame.to_lines(), ["****  "]);
+        assert_eq!(buf.selected_text(), None);
+        assert_eq!(buf.get_doc().0.lock().unwrap().content.text, "pa\u{e9}s");
+    }
+
+    #[test]
+    fn test_overwrite_mode() {
+        let buf = Buffer::from_text("abc\nd").into_ref();
+        buf.set_overwrite(true);
+        buf.set_cursor(TextOffset::new(1));
+        for c in "xyz!".chars() {
+            buf.insert_char_at_cursor(c);
+        }
+        assert_eq!(buf.get_doc().0.lock().unwrap().content.text, "axyz!\nd");
+        assert_eq!(buf.cursor(), TextOffset::new(5));
+
+        buf.set_cursor_visible(true);
+        let mut frame = Frame::new(Size { w: 6, h: 1 });
+        buf.render_at(&mut frame, rect(0, 0, 6, 1)).unwrap();
+        assert_eq!(
+            frame.cell(BufferPosition::new(0, 5)).unwrap().style,
+            *OVERWRITE_CURSOR_STYLE
+        );
+
+        // inserted text doesn't replace anything
+        buf.set_cursor(TextOffset::new(1));
+        buf.insert_text_at_cursor("--");
+        assert_eq!(buf.get_doc().0.lock().unwrap().content.text, "a--xyz!\nd");
+    }
+
+    #[test]
+    fn test_unchanged_buffers_reuse_their_cells() {
+        let buf = Buffer::from_text("ab").into_ref();
+        let shown = |buf: &BufferRef| {
+            let mut frame = Frame::new(Size { w: 2, h: 1 });
+            buf.render_at(&mut frame, rect(0, 0, 2, 1)).unwrap();
+            frame.to_lines().remove(0)
+        };
+        assert_eq!(shown(&buf), "ab");
+
+        // changes that bypass the document aren't noticed, so the cells are reused
+        buf.get_doc().0.lock().unwrap().content = "xy".into();
+        assert_eq!(shown(&buf), "ab");
+        buf.set_cursor(TextOffset::new(1));
+        assert_eq!(shown(&buf), "xy");
+    }
+
+    #[test]
+    fn test_refresh_interval_holds_back_document_changes() {
+        let buf = Buffer::from_text("ab").into_ref();
+        buf.set_refresh_interval(Some(Duration::from_secs(3600)));
+        let shown = |buf: &BufferRef| {
+            let mut frame = Frame::new(Size { w: 2, h: 1 });
+            buf.render_at(&mut frame, rect(0, 0, 2, 1)).unwrap();
+            frame.to_lines().remove(0)
+        };
+        assert_eq!(shown(&buf), "ab");
+        buf.get_doc().replace_range(0..1, "x");
+        assert_eq!(shown(&buf), "ab");
+        // a change of the view is drawn right away, with the document as it is now
+        buf.set_cursor(TextOffset::new(1));
+        assert_eq!(shown(&buf), "xb");
+    }
+
+    #[test]
+    fn test_refresh_interval_holds_back_following_the_end() {
+        let buf = Buffer::from_text("a\n").into_ref();
+        buf.set_refresh_interval(Some(Duration::from_secs(3600)));
+        let shown = |buf: &BufferRef| {
+            let mut frame = Frame::new(Size { w: 1, h: 1 });
+            buf.render_at(&mut frame, rect(0, 0, 1, 1)).unwrap();
+            frame.to_lines().remove(0)
+        };
+        assert_eq!(shown(&buf), "a");
+        buf.add_line("b");
+        buf.add_line("c");
+        assert_eq!(shown(&buf), "a");
+        // scrolling another way is drawn right away
+        buf.goto_line(1);
+        assert_eq!(shown(&buf), "b");
+    }
+
+    #[test]
+    fn test_end_marker() {
+        let buf = Buffer::from_text("a\n").into_ref();
+        buf.set_end_marker(Some("~".dark_grey().into()));
+        let mut frame = Frame::new(Size { w: 2, h: 4 });
+        buf.render_at(&mut frame, rect(0, 0, 2, 4)).unwrap();
+        // the empty line behind the line break is part of the document
+        assert_eq!(frame.to_lines(), ["a ", "  ", "~ ", "~ "]);
+        assert_eq!(
+            frame.cell(BufferPosition::new(2, 0)).unwrap().style,
+            ContentStyle::new().dark_grey()
+        );
+    }
 }
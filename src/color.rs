@@ -0,0 +1,193 @@
+//! Contains ColorProfile, which tells how many colors the terminal supports, so
+//! colors that it can't show are replaced by the closest ones it can show.
+
+use crossterm::style::{Color, ContentStyle};
+
+/// The colors a terminal can show, see [detect_color_profile]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, PartialOrd, Ord)]
+pub enum ColorProfile {
+    /// the 16 named colors
+    Ansi16,
+    /// the 256 indexed colors
+    Ansi256,
+    /// any RGB color
+    TrueColor,
+}
+
+/// the 16 named colors, in the order of their indices
+const NAMED: [Color; 16] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+/// the RGB values of the named colors, as xterm shows them
+const NAMED_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// the levels of the components in the 6x6x6 color cube of the indexed colors
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorProfile {
+    /// the closest color to the given one that the profile contains
+    pub fn downgrade(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorProfile::TrueColor, _) => color,
+            (ColorProfile::Ansi256, Color::Rgb { r, g, b }) => {
+                Color::AnsiValue(nearest_256(r, g, b))
+            }
+            (ColorProfile::Ansi16, Color::Rgb { r, g, b }) => NAMED[nearest_16(r, g, b)],
+            (ColorProfile::Ansi16, Color::AnsiValue(i)) if i < 16 => NAMED[i as usize],
+            (ColorProfile::Ansi16, Color::AnsiValue(i)) => {
+                let (r, g, b) = indexed_rgb(i);
+                NAMED[nearest_16(r, g, b)]
+            }
+            _ => color,
+        }
+    }
+
+    /// downgrades all colors of the style
+    pub fn downgrade_style(self, mut style: ContentStyle) -> ContentStyle {
+        style.foreground_color = style.foreground_color.map(|c| self.downgrade(c));
+        style.background_color = style.background_color.map(|c| self.downgrade(c));
+        style.underline_color = style.underline_color.map(|c| self.downgrade(c));
+        style
+    }
+}
+
+/// Guesses from the environment which colors the terminal supports: COLORTERM, and
+/// the name of the terminal in TERM, since there is no terminfo entry for RGB colors
+/// that terminals agree on. Unknown terminals are assumed to support the 256 indexed
+/// colors, like nearly all terminals in use do, unless TERM says otherwise
+pub fn detect_color_profile() -> ColorProfile {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    let colorterm = var("COLORTERM");
+    let term = var("TERM");
+    let truecolor_term = ["direct", "kitty", "wezterm", "foot", "alacritty", "ghostty"]
+        .iter()
+        .any(|t| term.contains(t));
+    if colorterm == "truecolor" || colorterm == "24bit" || truecolor_term {
+        ColorProfile::TrueColor
+    } else if [
+        "linux",
+        "vt100",
+        "vt220",
+        "ansi",
+        "xterm-color",
+        "xterm-16color",
+    ]
+    .contains(&term.as_str())
+    {
+        ColorProfile::Ansi16
+    } else {
+        ColorProfile::Ansi256
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).unsigned_abs().pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn nearest_16(r: u8, g: u8, b: u8) -> usize {
+    (0..16)
+        .min_by_key(|&i| distance(NAMED_RGB[i], (r, g, b)))
+        .unwrap()
+}
+
+/// the closest of the color cube and the grey ramp of the indexed colors
+fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| match v {
+        0..48 => 0,
+        48..115 => 1,
+        _ => (v - 35) / 40,
+    };
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let grey = 232 + (avg.saturating_sub(3) / 10).min(23) as u8;
+    [cube, grey]
+        .into_iter()
+        .min_by_key(|&i| distance(indexed_rgb(i), (r, g, b)))
+        .unwrap()
+}
+
+/// the RGB value of an indexed color
+fn indexed_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..16 => NAMED_RGB[i as usize],
+        16..232 => {
+            let i = i - 16;
+            let level = |l: u8| CUBE_LEVELS[l as usize];
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let v = 8 + 10 * (i - 232);
+            (v, v, v)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downgrade() {
+        let orange = Color::Rgb {
+            r: 255,
+            g: 135,
+            b: 0,
+        };
+        assert_eq!(ColorProfile::TrueColor.downgrade(orange), orange);
+        assert_eq!(
+            ColorProfile::Ansi256.downgrade(orange),
+            Color::AnsiValue(208)
+        );
+        assert_eq!(ColorProfile::Ansi16.downgrade(orange), Color::DarkYellow);
+        let grey = Color::Rgb {
+            r: 100,
+            g: 100,
+            b: 100,
+        };
+        assert_eq!(ColorProfile::Ansi256.downgrade(grey), Color::AnsiValue(241));
+        assert_eq!(
+            ColorProfile::Ansi16.downgrade(Color::AnsiValue(9)),
+            Color::Red
+        );
+        assert_eq!(
+            ColorProfile::Ansi16.downgrade(Color::AnsiValue(21)),
+            Color::DarkBlue
+        );
+        // named colors are left as they are
+        assert_eq!(ColorProfile::Ansi16.downgrade(Color::Cyan), Color::Cyan);
+    }
+}
@@ -0,0 +1,134 @@
+//! Contains the WordCompleter, which completes the word before the cursor with
+//! words that are already in the document, like dabbrev in emacs
+
+use crate::{BufferRef, TextOffset};
+
+/// Completes the word before the cursor. The candidates are the words of the
+/// document, the nearest ones before the cursor first, then the ones behind it,
+/// followed by the words of an optional dictionary. Completing repeatedly cycles
+/// through them, until [WordCompleter::reset] is called.
+#[derive(Default)]
+pub struct WordCompleter {
+    dictionary: Vec<String>,
+    state: Option<CompletionState>,
+}
+
+struct CompletionState {
+    /// where the completed word starts
+    start: usize,
+    /// the first candidate is what the user typed
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl WordCompleter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dictionary(dictionary: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            dictionary: dictionary.into_iter().map(Into::into).collect(),
+            state: None,
+        }
+    }
+
+    /// Replaces the word before the cursor with the next candidate, or the previous one if
+    /// `forward` is false. Returns false if there was nothing to complete
+    pub fn complete(&mut self, buf: &BufferRef, forward: bool) -> bool {
+        let cursor = buf.cursor().get();
+        let state = match self.state.take() {
+            Some(state) if state.start + state.candidates[state.index].len() == cursor => state,
+            _ => {
+                let doc = buf.get_doc();
+                let doc = doc.0.lock().unwrap();
+                let text = &doc.content.text;
+                let start = word_start(text, cursor);
+                CompletionState {
+                    start,
+                    candidates: candidates(text, start, cursor, &self.dictionary),
+                    index: 0,
+                }
+            }
+        };
+        if state.candidates.len() < 2 {
+            return false;
+        }
+
+        let n = state.candidates.len();
+        let index = if forward {
+            (state.index + 1) % n
+        } else {
+            (state.index + n - 1) % n
+        };
+        buf.delete_range(TextOffset::new(state.start)..buf.cursor());
+        buf.insert_text_at_cursor(state.candidates[index].as_str());
+        self.state = Some(CompletionState { index, ..state });
+        true
+    }
+
+    /// ends the current completion, so the next one starts from the word before the cursor
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn word_start(text: &str, pos: usize) -> usize {
+    text[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map_or(pos, |(i, _)| i)
+}
+
+/// returns the prefix, followed by the words that start with it, without duplicates
+fn candidates(text: &str, start: usize, end: usize, dictionary: &[String]) -> Vec<String> {
+    let prefix = &text[start..end];
+    let words = |s: &str| {
+        s.split(|c| !is_word_char(c))
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    let mut res = vec![prefix.to_string()];
+    let before = words(&text[..start]).into_iter().rev();
+    let after = words(&text[end..]);
+    for word in before.chain(after).chain(dictionary.iter().cloned()) {
+        if word.starts_with(prefix) && !res.contains(&word) {
+            res.push(word);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, TextOffset};
+
+    #[test]
+    fn test_completion_cycles_through_candidates() {
+        let buf = Buffer::from_text("foobar fooqux\nfo fooqux").into_ref();
+        buf.move_cursor_by(16);
+        let mut completer = WordCompleter::with_dictionary(["food"]);
+
+        let text = || buf.get_doc().0.lock().unwrap().content.text.clone();
+        assert!(completer.complete(&buf, true));
+        assert_eq!(text(), "foobar fooqux\nfooqux fooqux");
+        assert!(completer.complete(&buf, true));
+        assert_eq!(text(), "foobar fooqux\nfoobar fooqux");
+        assert!(completer.complete(&buf, true));
+        assert_eq!(text(), "foobar fooqux\nfood fooqux");
+        assert!(completer.complete(&buf, true));
+        assert_eq!(text(), "foobar fooqux\nfo fooqux");
+        assert!(completer.complete(&buf, false));
+        assert_eq!(text(), "foobar fooqux\nfood fooqux");
+        assert_eq!(buf.cursor(), TextOffset::new(18));
+    }
+}
@@ -0,0 +1,60 @@
+//! Contains AbletCtx, which gives event handlers access to the rest of the ui
+
+use std::io;
+
+use crate::{copy_to_clipboard, AText, Ablet, BufferRef};
+
+/// Passed to [EventHandler::handle](crate::EventHandler::handle), so handlers can
+/// do more than edit their buffer. The focus and the status line belong to an
+/// [Ablet], so they only have an effect in [Ablet::edit_prompt].
+pub struct AbletCtx<'a> {
+    ablet: Option<&'a Ablet>,
+    render: &'a dyn Fn() -> io::Result<()>,
+}
+
+impl<'a> AbletCtx<'a> {
+    /// `render` is called by [AbletCtx::render]. This is only needed for custom
+    /// event loops, the ones of the crate create their context themselves
+    pub fn new(ablet: Option<&'a Ablet>, render: &'a dyn Fn() -> io::Result<()>) -> Self {
+        Self { ablet, render }
+    }
+
+    pub fn ablet(&self) -> Option<&'a Ablet> {
+        self.ablet
+    }
+
+    /// the focused buffer, see [Ablet::focused]
+    pub fn focused(&self) -> Option<BufferRef> {
+        self.ablet.map(Ablet::focused)
+    }
+
+    /// see [Ablet::focus]
+    pub fn focus(&mut self, buf: &BufferRef) {
+        if let Some(ablet) = self.ablet {
+            ablet.focus(buf);
+        }
+    }
+
+    /// See [Ablet::set_status]. Without an Ablet, e.g. in
+    /// [edit_buffer](crate::edit_buffer), the status isn't shown anywhere
+    pub fn set_status(&mut self, text: impl Into<AText>) {
+        if let Some(ablet) = self.ablet {
+            ablet.set_status(text);
+        }
+    }
+
+    /// see [Ablet::status], None without an Ablet
+    pub fn status(&self) -> Option<AText> {
+        self.ablet.map(Ablet::status)
+    }
+
+    pub fn copy_to_clipboard(&self, text: &str) -> io::Result<()> {
+        copy_to_clipboard(text)
+    }
+
+    /// Renders right away. The event loop renders after each event anyway, so this is
+    /// only needed to show progress during a long running handler
+    pub fn render(&self) -> io::Result<()> {
+        (self.render)()
+    }
+}
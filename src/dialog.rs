@@ -0,0 +1,204 @@
+//! Contains the Dialog, a box with a message and choices, see [Ablet::dialog](crate::Ablet::dialog)
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEventKind},
+    style::{ContentStyle, Stylize},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    wrap, AText, AbletCtx, BufferPosition, BufferRef, EventHandler, Frame, HandlerResult, Range,
+    Rect, Shared, WrapMode,
+};
+
+/// the widest a dialog gets, so long messages are wrapped into a readable box
+const MAX_WIDTH: usize = 60;
+
+pub(crate) struct Dialog {
+    title: String,
+    message: AText,
+    choices: Vec<String>,
+    selected: usize,
+}
+
+impl Dialog {
+    pub(crate) fn new(title: &str, message: AText, choices: &[&str]) -> Self {
+        Self {
+            title: title.to_string(),
+            message,
+            choices: choices.iter().map(|c| c.to_string()).collect(),
+            selected: 0,
+        }
+    }
+
+    /// Draws a bordered box in the middle of the frame, with the title in the top
+    /// border, the wrapped message, and the choices below it
+    pub(crate) fn draw(&self, frame: &mut Frame) {
+        let size = frame.size();
+        if size.h < 5 || size.w < 5 {
+            return;
+        }
+        let choices_w = self.choices.iter().map(|c| c.width() + 2).sum::<usize>()
+            + 2 * self.choices.len().saturating_sub(1);
+        let message_w = self
+            .message
+            .text
+            .split('\n')
+            .map(|line| line.width())
+            .max()
+            .unwrap_or(0)
+            .min(MAX_WIDTH);
+        // the inner width, between the borders and a space of padding on each side
+        let inner_w = message_w
+            .max(choices_w)
+            .max(self.title.width() + 2)
+            .min((size.w as usize).saturating_sub(4));
+        let lines = wrap(&self.message, inner_w, WrapMode::Word);
+        let box_w = inner_w as u16 + 4;
+        let box_h = (lines.len() as u16 + 4).min(size.h);
+        let top = (size.h - box_h) / 2;
+        let left = (size.w.saturating_sub(box_w)) / 2;
+        let right = left + box_w;
+        let style = ContentStyle::default();
+        draw_box(frame, Rect::new(top, left, box_w, box_h), &self.title);
+
+        let inner_right = right - 2;
+        for (row, line) in (top + 1..top + box_h - 3).zip(&lines) {
+            let mut col = left + 2;
+            for run in line.get_range_style_pairs(Range::new(0, line.len())) {
+                let text = &line.text[run.range.into_native()];
+                col = frame.print(BufferPosition::new(row, col), text, *run.style, inner_right);
+            }
+        }
+
+        let mut col = left + 2 + (inner_w.saturating_sub(choices_w) / 2) as u16;
+        let row = top + box_h - 2;
+        for (i, choice) in self.choices.iter().enumerate() {
+            if i > 0 {
+                col += 2;
+            }
+            let style = if i == self.selected {
+                style.reverse()
+            } else {
+                style
+            };
+            let label = format!(" {choice} ");
+            col = frame.print(BufferPosition::new(row, col), &label, style, inner_right);
+        }
+    }
+}
+
+/// Draws a border around the rect, with the title in the top border, and clears
+/// the inside. Used for the boxes that are drawn on top of the splits
+pub(crate) fn draw_box(frame: &mut Frame, rect: Rect, title: &str) {
+    let (top, left) = (rect.pos.row, rect.pos.col);
+    let (w, h) = (rect.size.w, rect.size.h);
+    if w < 2 || h < 2 {
+        return;
+    }
+    let right = left + w;
+    let style = ContentStyle::default();
+
+    let horizontal = "\u{2500}".repeat(w as usize - 2);
+    let border = |frame: &mut Frame, row: u16, l: &str, r: &str| {
+        let line = format!("{l}{horizontal}{r}");
+        frame.print(BufferPosition::new(row, left), &line, style, right);
+    };
+    border(frame, top, "\u{250c}", "\u{2510}");
+    if !title.is_empty() {
+        let title = format!(" {title} ");
+        frame.print(
+            BufferPosition::new(top, left + 2),
+            &title,
+            style.bold(),
+            right - 1,
+        );
+    }
+    let blank = format!("\u{2502}{}\u{2502}", " ".repeat(w as usize - 2));
+    for row in top + 1..top + h - 1 {
+        frame.print(BufferPosition::new(row, left), &blank, style, right);
+    }
+    border(frame, top + h - 1, "\u{2514}", "\u{2518}");
+}
+
+/// Moves the selection of the shown dialog with Left/Right and Tab/BackTab, and
+/// returns the index of the selected choice on Enter, or None on Esc
+pub(crate) struct DialogHandler(pub(crate) Shared<Option<Dialog>>);
+
+impl EventHandler<Option<usize>> for DialogHandler {
+    fn handle(
+        &mut self,
+        ev: &Event,
+        _buf: &BufferRef,
+        _ctx: &mut AbletCtx,
+    ) -> HandlerResult<Option<usize>> {
+        let Event::Key(ke) = ev else {
+            return HandlerResult::Ignored;
+        };
+        if ke.kind == KeyEventKind::Release {
+            return HandlerResult::Ignored;
+        }
+        let mut dialog = self.0.lock().unwrap();
+        let Some(dialog) = dialog.as_mut() else {
+            return HandlerResult::Ignored;
+        };
+        match ke.code {
+            KeyCode::Left | KeyCode::BackTab => dialog.selected = dialog.selected.saturating_sub(1),
+            KeyCode::Right | KeyCode::Tab => {
+                dialog.selected = (dialog.selected + 1).min(dialog.choices.len().saturating_sub(1))
+            }
+            KeyCode::Enter if !dialog.choices.is_empty() => {
+                return HandlerResult::Done(Some(dialog.selected))
+            }
+            KeyCode::Esc => return HandlerResult::Done(None),
+            _ => return HandlerResult::Ignored,
+        }
+        HandlerResult::Handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEvent;
+
+    use super::*;
+    use crate::{shared, Buffer, Size};
+
+    #[test]
+    fn test_dialog() {
+        let dialog = shared(Some(Dialog::new(
+            "Quit",
+            "There are unsaved changes".into(),
+            &["Save", "Discard", "Cancel"],
+        )));
+        let mut frame = Frame::new(Size { w: 40, h: 7 });
+        dialog.lock().unwrap().as_ref().unwrap().draw(&mut frame);
+        let rows = frame.to_lines();
+        let top = format!("\u{250c}\u{2500} Quit {}\u{2510}", "\u{2500}".repeat(22));
+        assert_eq!(rows[1].trim(), top);
+        assert_eq!(
+            rows[2].trim(),
+            "\u{2502} There are unsaved changes   \u{2502}"
+        );
+        assert_eq!(
+            rows[4].trim(),
+            "\u{2502}  Save    Discard    Cancel  \u{2502}"
+        );
+        // the first choice is selected
+        assert_eq!(
+            frame.rows().nth(4).unwrap()[7].style,
+            ContentStyle::new().reverse()
+        );
+
+        let mut handler = DialogHandler(dialog);
+        let buf = Buffer::new().into_ref();
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let mut press = |code| handler.handle(&Event::Key(KeyEvent::from(code)), &buf, &mut ctx);
+        assert_eq!(press(KeyCode::Right), HandlerResult::Handled);
+        assert_eq!(press(KeyCode::Right), HandlerResult::Handled);
+        assert_eq!(press(KeyCode::Right), HandlerResult::Handled);
+        assert_eq!(press(KeyCode::Enter), HandlerResult::Done(Some(2)));
+        assert_eq!(press(KeyCode::Esc), HandlerResult::Done(None));
+    }
+}
@@ -1,14 +1,74 @@
-use crate::{shared, AText, Shared};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
 
+use log::error;
+use unicode_normalization::char::canonical_combining_class;
+
+use crate::{
+    history::{Edit, History, UndoGrouping},
+    shared, AText, Debounce, Range, SearchPattern, Shared, Transcript,
+};
+
+/// how long the source of a derived document has to stay unchanged before the derived
+/// document is recomputed
+const DERIVE_DELAY: Duration = Duration::from_millis(50);
+
+type ChangeCallback = Box<dyn FnMut() + Send>;
+
+/// The text of a document consists of lines that are separated by `\n`, so a document
+/// that ends with a line break ends with an empty line, and an empty document has one
+/// empty line. [DocumentRef::add_line] terminates the lines it adds, like a log file,
+/// [DocumentRef::push_line] only separates them.
 #[derive(Default)]
 pub struct Document {
     pub(crate) content: AText,
+    history: History,
+    normalization: Normalization,
+    /// whether the content was edited since it was created, or marked as clean
+    modified: bool,
+    /// counts the changes of the content, including its styles
+    revision: u64,
+    /// read-only documents can't be edited through buffers
+    read_only: bool,
+    /// called whenever the content changes, while the document is locked
+    on_change: Vec<ChangeCallback>,
+    /// the transcript that lines added to the document are recorded in, and the
+    /// name they're recorded under
+    transcript: Option<(Transcript, String)>,
+    /// applied to lines that are added via [DocumentRef::add_line]
+    #[cfg(feature = "regex")]
+    highlight_rules: Option<crate::HighlightRules>,
+}
+
+/// How text that is inserted into a document is normalized
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Normalization {
+    /// the text is inserted as is
+    #[default]
+    None,
+    /// The text is converted to Unicode normalization form C, so e.g. an `e` followed
+    /// by a combining acute accent becomes an `é`, regardless of whether it was typed,
+    /// pasted, or came from a program
+    Nfc,
 }
 
 impl Document {
     pub fn from_text(text: impl Into<AText>) -> Document {
         Self {
             content: text.into(),
+            history: History::default(),
+            normalization: Normalization::None,
+            modified: false,
+            revision: 0,
+            read_only: false,
+            on_change: Vec::new(),
+            transcript: None,
+            #[cfg(feature = "regex")]
+            highlight_rules: None,
         }
     }
 
@@ -19,21 +79,371 @@ impl Document {
     pub fn into_ref(self) -> DocumentRef {
         DocumentRef(shared(self))
     }
+
+    /// Replaces the range with the new text, and records the change in the
+    /// history, so it can be undone. Like [AText::replace_range], a range
+    /// behind the end of the text appends to it. Returns the position behind
+    /// the new text, which may differ from its original length due to normalization
+    pub fn replace_range(
+        &mut self,
+        r: std::ops::Range<usize>,
+        new_text: impl Into<AText>,
+    ) -> usize {
+        let new_end = self.replace_range_by(r, new_text, true);
+        self.modified = true;
+        new_end
+    }
+
+    /// Like [Document::replace_range], but only edits by the user are recorded in the
+    /// history. Updates by the program, like added lines, can't be undone, so undoing
+    /// in a log pane doesn't remove its lines
+    fn replace_range_by(
+        &mut self,
+        r: std::ops::Range<usize>,
+        new_text: impl Into<AText>,
+        user: bool,
+    ) -> usize {
+        let len = self.content.len();
+        let mut r = r.start.min(len)..r.end.min(len);
+        let mut new_text = new_text.into();
+        if self.normalization == Normalization::Nfc {
+            // combining chars must be normalized together with the char they combine with
+            let starts_with_combining_char = new_text
+                .text
+                .chars()
+                .next()
+                .is_some_and(|c| canonical_combining_class(c) != 0);
+            let prev_char = self.content.text[..r.start].chars().next_back();
+            if let (true, Some(prev_char)) = (starts_with_combining_char, prev_char) {
+                let prev_start = r.start - prev_char.len_utf8();
+                new_text = self.content.sub_text(prev_start..r.start) + new_text;
+                r.start = prev_start;
+            }
+            new_text = new_text.nfc();
+        }
+
+        let edit = Edit {
+            pos: r.start,
+            removed: self.content.sub_text(r.clone()),
+            inserted: new_text,
+        };
+        let new_end = r.start + edit.inserted.len();
+        if user {
+            self.content.replace_range(r, edit.inserted.clone());
+            self.history.record(edit, Instant::now());
+        } else {
+            self.content.replace_range(r, edit.inserted);
+        }
+        self.changed();
+        new_end
+    }
+
+    /// Whether the content was edited since the document was created, or
+    /// [Document::mark_clean] was called, e.g. to ask before closing it. Updates by the
+    /// program, through [DocumentRef::add_line], [DocumentRef::push_line] or
+    /// [DocumentRef::update_content], don't count
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// A number that changes whenever the content or its styles change, e.g. to
+    /// tell whether something that was derived from it is outdated
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// marks the content as unchanged, e.g. after it was saved
+    pub fn mark_clean(&mut self) {
+        self.modified = false;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Read-only documents can't be edited through buffers, but can still be
+    /// changed programmatically, e.g. via [DocumentRef::add_line]
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// sets how text that is inserted from now on is normalized
+    pub fn set_normalization(&mut self, normalization: Normalization) {
+        self.normalization = normalization;
+    }
+
+    /// sets how typing is grouped into undo steps, see [UndoGrouping]
+    pub fn set_undo_grouping(&mut self, grouping: UndoGrouping) {
+        self.history.set_grouping(grouping);
+    }
+
+    /// Reverts the last change. Returns the position behind the restored text,
+    /// which is where a cursor should go, or None if there was nothing to undo.
+    /// Consecutive single char insertions are undone in steps, as configured via
+    /// [Document::set_undo_grouping].
+    pub fn undo(&mut self) -> Option<usize> {
+        let edit = self.history.pop_undo()?;
+        let pos = self.apply(edit.clone().inverted());
+        self.history.push_redo(edit);
+        Some(pos)
+    }
+
+    /// Reapplies the last undone change. Returns the position behind the inserted text
+    pub fn redo(&mut self) -> Option<usize> {
+        let edit = self.history.pop_redo()?;
+        let pos = self.apply(edit.clone());
+        self.history.push_undo(edit);
+        Some(pos)
+    }
+
+    /// Writes the undo history to the file, e.g. to [undo_file_for] the file of the
+    /// document, so it can be restored in a later session with [Document::load_undo].
+    /// Styles aren't kept
+    pub fn save_undo(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.history.to_file_content(&self.content.text))
+    }
+
+    /// Restores the undo history from a file that was written by [Document::save_undo].
+    /// It's only restored if the content is the same as when it was saved, otherwise
+    /// the history is kept, and false is returned
+    pub fn load_undo(&mut self, path: impl AsRef<Path>) -> io::Result<bool> {
+        let content = fs::read_to_string(path)?;
+        self.history.load_file_content(&content, &self.content.text)
+    }
+
+    /// applies the highlight rules to the range, if there are any
+    #[cfg_attr(not(feature = "regex"), allow(unused_variables))]
+    fn highlight(&mut self, r: std::ops::Range<usize>) {
+        #[cfg(feature = "regex")]
+        if let Some(rules) = &self.highlight_rules {
+            rules.apply(&mut self.content, r);
+        }
+    }
+
+    fn apply(&mut self, edit: Edit) -> usize {
+        let end = edit.pos + edit.removed.len();
+        let new_end = edit.pos + edit.inserted.len();
+        self.content.replace_range(edit.pos..end, edit.inserted);
+        self.modified = true;
+        self.changed();
+        new_end
+    }
+
+    /// bumps the revision, and notifies the callbacks
+    fn changed(&mut self) {
+        self.revision += 1;
+        for f in &mut self.on_change {
+            f();
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct DocumentRef(pub(crate) Shared<Document>);
 
 impl DocumentRef {
+    /// Appends the text, and a line break behind it, so the document ends with an empty
+    /// line afterwards
     pub fn add_line<T: Into<AText>>(&self, t: T) {
+        self.append_line(t.into(), true)
+    }
+
+    /// Appends the text as the new last line. A line break is put before it, unless the
+    /// document is empty or ends with one, but none behind it, so the document doesn't
+    /// end with an empty line
+    pub fn push_line<T: Into<AText>>(&self, t: T) {
+        self.append_line(t.into(), false)
+    }
+
+    fn append_line(&self, t: AText, terminate: bool) {
+        let mut this = self.0.lock().unwrap();
+        if let Some((transcript, source)) = &this.transcript {
+            transcript.record(source, t.clone());
+        }
+        let len = this.content.len();
+        let (start, text) = if terminate {
+            (len, t + "\n")
+        } else if len == 0 || this.content.text.ends_with('\n') {
+            (len, t)
+        } else {
+            (len + 1, AText::from("\n") + t)
+        };
+        let end = this.replace_range_by(len..len, text, false);
+        this.highlight(start..end);
+    }
+
+    /// adds a line break at the end, unless the document is empty or ends with one already
+    pub fn ensure_trailing_newline(&self) {
         let mut this = self.0.lock().unwrap();
-        this.content.append_text(t);
-        this.content.push_char('\n');
+        let len = this.content.len();
+        if len > 0 && !this.content.text.ends_with('\n') {
+            this.replace_range_by(len..len, "\n", false);
+        }
+    }
+
+    /// the number of lines, see [Document] for how they're counted
+    pub fn line_count(&self) -> usize {
+        line_count(&self.0.lock().unwrap().content.text)
     }
 
+    /// Applies the rules to the content, and to every line that is added via
+    /// [DocumentRef::add_line] from now on
+    #[cfg(feature = "regex")]
+    pub fn set_highlight_rules(&self, rules: crate::HighlightRules) {
+        let mut this = self.0.lock().unwrap();
+        this.highlight_rules = Some(rules);
+        let len = this.content.len();
+        this.highlight(0..len);
+        this.changed();
+    }
+
+    /// records all lines that are added via [DocumentRef::add_line] from now on in
+    /// the transcript, under the given name
+    pub fn record_to(&self, transcript: &Transcript, source: impl Into<String>) {
+        self.0.lock().unwrap().transcript = Some((transcript.clone(), source.into()));
+    }
+
+    pub fn stop_recording(&self) {
+        self.0.lock().unwrap().transcript = None;
+    }
+
+    /// Gives direct access to the content, for updates by the program, which don't
+    /// mark the document as modified. Changes made this way bypass the history, which
+    /// is therefore cleared.
     pub fn update_content<T>(&self, f: impl FnOnce(&mut AText) -> T) -> T {
         let mut this = self.0.lock().unwrap();
-        f(&mut this.content)
+        this.history.clear();
+        let res = f(&mut this.content);
+        this.changed();
+        res
+    }
+
+    /// Calls `f` whenever the content changes. It's called while the document is
+    /// locked, so it must not access the document, or render it
+    pub fn on_change(&self, f: impl FnMut() + Send + 'static) {
+        self.0.lock().unwrap().on_change.push(Box::new(f));
+    }
+
+    /// Creates a read-only document with the result of `f` for the content of this
+    /// one, which is recomputed whenever this one changes, after it didn't change for
+    /// a moment. E.g. for a pane with a live word count, or a filtered view of another
+    /// pane. The derived document isn't rendered when it's recomputed, use
+    /// [DocumentRef::derive_and_render] for that.
+    pub fn derive(&self, f: impl Fn(&AText) -> AText + Send + Sync + 'static) -> DocumentRef {
+        self.derive_and_render(f, || Ok(()))
+    }
+
+    /// like [DocumentRef::derive], but calls `render` after each recomputation
+    pub fn derive_and_render(
+        &self,
+        f: impl Fn(&AText) -> AText + Send + Sync + 'static,
+        render: impl Fn() -> io::Result<()> + Send + Sync + 'static,
+    ) -> DocumentRef {
+        self.derive_with(f, render, Debounce::new(DERIVE_DELAY))
+    }
+
+    /// like [DocumentRef::derive_and_render], with the debounce that delays the
+    /// recomputations, so tests can poll it
+    fn derive_with(
+        &self,
+        f: impl Fn(&AText) -> AText + Send + Sync + 'static,
+        render: impl Fn() -> io::Result<()> + Send + Sync + 'static,
+        debounce: Debounce,
+    ) -> DocumentRef {
+        let mut derived = Document::from_text(f(&self.0.lock().unwrap().content));
+        derived.set_read_only(true);
+        let derived = derived.into_ref();
+
+        // weak, so the documents don't keep each other alive
+        let source = Arc::downgrade(&self.0);
+        let target = Arc::downgrade(&derived.0);
+        let (f, render) = (Arc::new(f), Arc::new(render));
+        self.on_change(move || {
+            let (source, target) = (source.clone(), target.clone());
+            let (f, render) = (f.clone(), render.clone());
+            debounce.call(move || {
+                let (Some(source), Some(target)) = (Weak::upgrade(&source), Weak::upgrade(&target))
+                else {
+                    return;
+                };
+                let text = f(&source.lock().unwrap().content);
+                DocumentRef(target).update_content(|content| *content = text);
+                if let Err(e) = render() {
+                    error!("Rendering a derived document failed: {e}");
+                }
+            });
+        });
+        derived
+    }
+
+    /// see [Document::replace_range]
+    pub fn replace_range(&self, r: std::ops::Range<usize>, new_text: impl Into<AText>) -> usize {
+        self.0.lock().unwrap().replace_range(r, new_text)
+    }
+
+    /// see [Document::is_modified]
+    pub fn is_modified(&self) -> bool {
+        self.0.lock().unwrap().is_modified()
+    }
+
+    /// see [Document::mark_clean]
+    pub fn mark_clean(&self) {
+        self.0.lock().unwrap().mark_clean()
+    }
+
+    /// see [Document::revision]
+    pub fn revision(&self) -> u64 {
+        self.0.lock().unwrap().revision()
+    }
+
+    /// see [Document::is_read_only]
+    pub fn is_read_only(&self) -> bool {
+        self.0.lock().unwrap().is_read_only()
+    }
+
+    /// see [Document::set_read_only]
+    pub fn set_read_only(&self, read_only: bool) {
+        self.0.lock().unwrap().set_read_only(read_only)
+    }
+
+    /// see [Document::set_normalization]
+    pub fn set_normalization(&self, normalization: Normalization) {
+        self.0.lock().unwrap().set_normalization(normalization)
+    }
+
+    /// see [Document::set_undo_grouping]
+    pub fn set_undo_grouping(&self, grouping: UndoGrouping) {
+        self.0.lock().unwrap().set_undo_grouping(grouping)
+    }
+
+    /// see [Document::save_undo]
+    pub fn save_undo(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.0.lock().unwrap().save_undo(path)
+    }
+
+    /// see [Document::load_undo]
+    pub fn load_undo(&self, path: impl AsRef<Path>) -> io::Result<bool> {
+        self.0.lock().unwrap().load_undo(path)
+    }
+
+    /// see [Document::undo]
+    pub fn undo(&self) -> Option<usize> {
+        self.0.lock().unwrap().undo()
+    }
+
+    /// see [Document::redo]
+    pub fn redo(&self) -> Option<usize> {
+        self.0.lock().unwrap().redo()
+    }
+
+    /// returns the ranges of all matches of the pattern in the text, e.g. to select
+    /// or highlight them
+    pub fn find(&self, pattern: impl SearchPattern) -> Vec<Range<usize>> {
+        pattern.find_in(&self.0.lock().unwrap().content.text)
     }
 
     pub fn take(&self) -> AText {
@@ -44,3 +454,187 @@ impl DocumentRef {
         })
     }
 }
+
+/// the file next to the given one that its undo history is saved in, see
+/// [Document::save_undo]. For `dir/notes.txt`, it's `dir/.notes.txt.undo`
+pub fn undo_file_for(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.undo"))
+}
+
+/// the number of lines, which are separated by `\n`
+pub(crate) fn line_count(text: &str) -> usize {
+    text.matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_semantics() {
+        let doc = Document::new().into_ref();
+        doc.push_line("a");
+        doc.push_line("b");
+        assert_eq!(doc.0.lock().unwrap().content.text, "a\nb");
+        assert_eq!(doc.line_count(), 2);
+        doc.ensure_trailing_newline();
+        doc.ensure_trailing_newline();
+        doc.add_line("c");
+        assert_eq!(doc.0.lock().unwrap().content.text, "a\nb\nc\n");
+        assert_eq!(doc.line_count(), 4);
+        // a trailing empty line is used by the next pushed line
+        doc.push_line("d");
+        assert_eq!(doc.0.lock().unwrap().content.text, "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut doc = Document::from_text("Hello");
+        for c in [' ', 'y', 'o', 'u'] {
+            let len = doc.content.len();
+            doc.replace_range(len..len, c.to_string());
+        }
+        doc.replace_range(0..5, "Bye");
+        assert_eq!(doc.content.text, "Bye you");
+
+        assert_eq!(doc.undo(), Some(5));
+        assert_eq!(doc.content.text, "Hello you");
+        // the single char insertions are undone in one step
+        assert_eq!(doc.undo(), Some(5));
+        assert_eq!(doc.content.text, "Hello");
+        assert_eq!(doc.undo(), None);
+
+        assert_eq!(doc.redo(), Some(9));
+        assert_eq!(doc.content.text, "Hello you");
+        assert_eq!(doc.redo(), Some(3));
+        assert_eq!(doc.content.text, "Bye you");
+        assert_eq!(doc.redo(), None);
+    }
+
+    #[test]
+    fn test_undo_is_restored_from_file() {
+        let path = std::env::temp_dir().join(format!("ablet-undo-{}.txt", std::process::id()));
+        let undo_file = undo_file_for(&path);
+        assert_eq!(
+            undo_file.file_name().unwrap().to_string_lossy(),
+            format!(".ablet-undo-{}.txt.undo", std::process::id())
+        );
+
+        let mut doc = Document::from_text("a");
+        doc.replace_range(1..1, "b\nc");
+        doc.replace_range(0..1, "");
+        doc.undo();
+        doc.save_undo(&undo_file).unwrap();
+
+        let mut restored = Document::from_text("ab\nc");
+        assert!(restored.load_undo(&undo_file).unwrap());
+        assert_eq!(restored.redo(), Some(0));
+        assert_eq!(restored.content.text, "b\nc");
+        restored.undo();
+        restored.undo();
+        assert_eq!(restored.content.text, "a");
+
+        // the history of another text isn't restored
+        let mut other = Document::from_text("x");
+        assert!(!other.load_undo(&undo_file).unwrap());
+        assert_eq!(other.undo(), None);
+        std::fs::remove_file(&undo_file).unwrap();
+    }
+
+    #[test]
+    fn test_modified() {
+        let doc = Document::from_text("a").into_ref();
+        assert!(!doc.is_modified());
+        doc.replace_range(1..1, "b");
+        assert!(doc.is_modified());
+        doc.mark_clean();
+        doc.undo();
+        assert!(doc.is_modified());
+    }
+
+    #[test]
+    fn test_program_updates_dont_mark_modified() {
+        let doc = Document::new().into_ref();
+        let revision = || doc.0.lock().unwrap().revision();
+        doc.add_line("a");
+        doc.push_line("b");
+        doc.ensure_trailing_newline();
+        doc.update_content(|text| text.push_char('c'));
+        assert_eq!(revision(), 4);
+        assert!(!doc.is_modified());
+        doc.replace_range(0..0, "x");
+        assert!(doc.is_modified());
+    }
+
+    #[test]
+    fn test_program_updates_cant_be_undone() {
+        let doc = Document::from_text("log\n").into_ref();
+        doc.add_line("a");
+        doc.push_line("b");
+        doc.ensure_trailing_newline();
+        assert_eq!(doc.undo(), None);
+        assert!(!doc.is_modified());
+        assert_eq!(doc.0.lock().unwrap().content.text, "log\na\nb\n");
+    }
+
+    #[test]
+    fn test_nfc_composes_with_existing_text() {
+        let mut doc = Document::from_text("cafe");
+        doc.set_normalization(Normalization::Nfc);
+        assert_eq!(doc.replace_range(4..4, "\u{301}"), 5);
+        assert_eq!(doc.content.text, "caf\u{e9}");
+
+        doc.replace_range(5..5, " nai\u{308}ve");
+        assert_eq!(doc.content.text, "caf\u{e9} na\u{ef}ve");
+
+        assert_eq!(doc.undo(), Some(5));
+        assert_eq!(doc.content.text, "caf\u{e9}");
+    }
+
+    #[test]
+    fn test_find() {
+        let doc = Document::from_text("one two\none").into_ref();
+        assert_eq!(doc.find("one"), [Range::new(0, 3), Range::new(8, 11)]);
+    }
+
+    #[test]
+    fn test_add_line_is_recorded() {
+        let transcript = Transcript::new();
+        let doc = Document::new().into_ref();
+        doc.add_line("before");
+        doc.record_to(&transcript, "log");
+        doc.add_line("recorded");
+        doc.stop_recording();
+        doc.add_line("after");
+
+        let entries = transcript.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source, "log");
+        assert_eq!(entries[0].text.as_str(), "recorded");
+    }
+
+    #[test]
+    fn test_derive() {
+        let source = Document::from_text("one two").into_ref();
+        let debounce = Debounce::polled(DERIVE_DELAY);
+        let count = source.derive_with(
+            |text| format!("{} words", text.text.split_whitespace().count()).into(),
+            || Ok(()),
+            debounce.clone(),
+        );
+        assert!(count.is_read_only());
+        assert_eq!(count.0.lock().unwrap().content.text, "2 words");
+
+        source.add_line(" three");
+        source.add_line("four");
+        let start = Instant::now();
+        assert!(!debounce.poll_at(start));
+        assert_eq!(count.0.lock().unwrap().content.text, "2 words");
+
+        assert!(debounce.poll_at(start + DERIVE_DELAY));
+        assert_eq!(count.0.lock().unwrap().content.text, "4 words");
+        assert!(!debounce.is_pending());
+    }
+}
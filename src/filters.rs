@@ -0,0 +1,68 @@
+//! Contains input filters, which transform or reject text that is typed or pasted
+//! into a buffer, before it reaches the document. They are added via
+//! [BufferRef::add_input_filter](crate::BufferRef::add_input_filter), and applied
+//! in the order they were added.
+
+use crate::AText;
+
+/// Gets the text that is about to be inserted, and the current content of the document,
+/// and returns the text that should be inserted instead, or None to reject it
+pub type InputFilter = Box<dyn FnMut(AText, &AText) -> Option<AText> + Send>;
+
+/// converts all inserted text to upper case
+pub fn uppercase() -> impl FnMut(AText, &AText) -> Option<AText> + Send {
+    |text, _| {
+        Some(
+            text.chars_styled()
+                .flat_map(|(c, style)| c.to_uppercase().map(move |c| (c, style)))
+                .collect(),
+        )
+    }
+}
+
+/// removes line breaks, e.g. for single line prompts
+pub fn strip_newlines() -> impl FnMut(AText, &AText) -> Option<AText> + Send {
+    |text, _| {
+        Some(
+            text.chars_styled()
+                .filter(|(c, _)| !matches!(c, '\n' | '\r'))
+                .collect(),
+        )
+    }
+}
+
+/// Limits the document to `max` chars. Text that would exceed it is cut off,
+/// and rejected entirely, if the document is already full
+pub fn max_len(max: usize) -> impl FnMut(AText, &AText) -> Option<AText> + Send {
+    move |text, content| {
+        let remaining = max.checked_sub(content.text.chars().count())?;
+        let res = text.chars_styled().take(remaining).collect::<AText>();
+        (!res.is_empty()).then_some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use crate::{Buffer, TextOffset};
+
+    use super::*;
+
+    #[test]
+    fn test_filters_are_chained() {
+        let buf = Buffer::from_text("ab").into_ref();
+        buf.move_cursor_by(2);
+        buf.add_input_filter(strip_newlines());
+        buf.add_input_filter(uppercase());
+        buf.add_input_filter(max_len(5));
+
+        buf.insert_text_at_cursor(AText::from("c\nd") + "ef".green());
+        buf.insert_char_at_cursor('g');
+        let doc = buf.get_doc();
+        let content = doc.0.lock().unwrap();
+        assert_eq!(content.content.text, "abCDE");
+        assert_eq!(content.content.styles.len(), 1);
+        assert_eq!(buf.cursor(), TextOffset::new(5));
+    }
+}
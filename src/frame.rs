@@ -0,0 +1,288 @@
+//! Contains the Frame, an in-memory grid of styled cells.
+//!
+//! A render pass draws all buffers and borders into a Frame, which is then
+//! drawn through a [Renderer] in one go.
+
+use std::{io, sync::Arc};
+
+use crossterm::style::ContentStyle;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{theme::default_theme, BufferPosition, Rect, Renderer, Size, Theme, ThemedStyle};
+
+/// A single column of the terminal. A wide char, like most CJK chars and emoji,
+/// is stored in the cell where it starts, and is followed by a cell with an empty
+/// symbol, which it covers
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell {
+    /// a grapheme cluster
+    pub symbol: String,
+    pub style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: " ".into(),
+            style: ContentStyle::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    size: Size,
+    cells: Vec<Cell>,
+    /// resolves the named styles that are printed into the frame
+    theme: Arc<Theme>,
+}
+
+/// frames are equal if their cells are, whatever theme drew them
+impl PartialEq for Frame {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size && self.cells == other.cells
+    }
+}
+
+impl Frame {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            cells: vec![Cell::default(); size.w as usize * size.h as usize],
+            theme: default_theme(),
+        }
+    }
+
+    /// the theme that the named styles are drawn with, see [Theme::new] for the default
+    pub fn with_theme(mut self, theme: Arc<Theme>) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// a blank frame of the size, with the same theme
+    pub(crate) fn blank(&self, size: Size) -> Frame {
+        Frame::new(size).with_theme(self.theme.clone())
+    }
+
+    pub fn cell(&self, pos: BufferPosition) -> Option<&Cell> {
+        self.index_of(pos).map(|i| &self.cells[i])
+    }
+
+    pub fn cell_mut(&mut self, pos: BufferPosition) -> Option<&mut Cell> {
+        self.index_of(pos).map(|i| &mut self.cells[i])
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+        self.cells.chunks(self.size.w.max(1) as usize)
+    }
+
+    /// the symbols of each row, joined, to compare frames with text in tests
+    #[cfg(test)]
+    pub(crate) fn to_lines(&self) -> Vec<String> {
+        self.rows()
+            .map(|row| row.iter().map(|c| c.symbol.as_str()).collect())
+            .collect()
+    }
+
+    /// Writes `text` into the frame, starting at `pos`. Everything at or right of
+    /// `max_col` is clipped, including wide chars that would only fit partially.
+    /// Returns the column after the last written char. Named styles are resolved by
+    /// the frame's theme.
+    pub fn print(
+        &mut self,
+        pos: BufferPosition,
+        text: &str,
+        style: impl Into<ThemedStyle>,
+        max_col: u16,
+    ) -> u16 {
+        let style = self.theme.resolve(style);
+        let max_col = max_col.min(self.size.w);
+        let mut col = pos.col;
+        for symbol in text.graphemes(true) {
+            // zero width clusters, like control chars, would misalign everything behind them
+            let width = symbol.width().min(2) as u16;
+            if width == 0 {
+                continue;
+            }
+            if col + width > max_col {
+                break;
+            }
+            self.put(pos.with_col(col), symbol, style);
+            if width == 2 {
+                self.put(pos.with_col(col + 1), "", style);
+            }
+            col += width;
+        }
+        col
+    }
+
+    /// sets the symbol of a cell, and blanks the other half of wide chars it overwrites
+    fn put(&mut self, pos: BufferPosition, symbol: &str, style: ContentStyle) {
+        let Some(i) = self.index_of(pos) else {
+            return;
+        };
+        if !symbol.is_empty() && self.cells[i].symbol.is_empty() && pos.col > 0 {
+            self.cells[i - 1].symbol = " ".into();
+        }
+        if pos.col + 1 < self.size.w && self.cells[i + 1].symbol.is_empty() {
+            self.cells[i + 1].symbol = " ".into();
+        }
+        self.cells[i] = Cell {
+            symbol: symbol.into(),
+            style,
+        };
+    }
+
+    /// draws the whole frame through the renderer, row by row, changing the
+    /// style only between runs of differently styled cells, and flushes it
+    pub fn draw(&self, renderer: &mut dyn Renderer) -> io::Result<()> {
+        renderer.begin_frame()?;
+        for (row_i, row) in self.rows().enumerate() {
+            renderer.move_to(BufferPosition::new(row_i as u16, 0))?;
+            for run in row.chunk_by(|a, b| a.style == b.style) {
+                let text = run.iter().map(|c| c.symbol.as_str()).collect::<String>();
+                renderer.set_style(&run[0].style)?;
+                renderer.print(&text)?;
+            }
+        }
+        renderer.set_style(&ContentStyle::default())?;
+        renderer.end_frame()?;
+        renderer.flush()
+    }
+
+    /// Returns the cells that differ from `old`, as runs of equally styled cells
+    /// within a row, together with the position of the first cell of each run.
+    /// If `old` has a different size, everything that isn't blank is returned.
+    pub fn diff<'a>(&'a self, old: &Frame) -> Vec<(BufferPosition, &'a [Cell])> {
+        let blank;
+        let old = if old.size == self.size {
+            old
+        } else {
+            blank = Frame::new(self.size);
+            &blank
+        };
+
+        let mut res = vec![];
+        for (row_i, (row, old_row)) in self.rows().zip(old.rows()).enumerate() {
+            let mut col = 0;
+            while col < row.len() {
+                if row[col] == old_row[col] {
+                    col += 1;
+                    continue;
+                }
+                let start = col;
+                while col < row.len()
+                    && row[col] != old_row[col]
+                    && row[col].style == row[start].style
+                {
+                    col += 1;
+                }
+                res.push((
+                    BufferPosition::new(row_i as u16, start as u16),
+                    &row[start..col],
+                ));
+            }
+        }
+        res
+    }
+
+    /// the cells in the rect, row by row, or None if it's not within the frame
+    pub(crate) fn cells_in(&self, rect: Rect) -> Option<Vec<Cell>> {
+        if rect.pos.col + rect.size.w > self.size.w {
+            return None;
+        }
+        let mut res = Vec::with_capacity(rect.size.w as usize * rect.size.h as usize);
+        for row in rect.pos.row..rect.pos.row + rect.size.h {
+            let start = self.index_of(BufferPosition::new(row, rect.pos.col))?;
+            res.extend_from_slice(&self.cells[start..start + rect.size.w as usize]);
+        }
+        Some(res)
+    }
+
+    /// puts the cells that [Frame::cells_in] returned for the rect back in place
+    pub(crate) fn set_cells_in(&mut self, rect: Rect, cells: &[Cell]) {
+        let w = rect.size.w as usize;
+        for (i, row) in cells.chunks(w.max(1)).enumerate() {
+            let pos = BufferPosition::new(rect.pos.row + i as u16, rect.pos.col);
+            if let Some(start) = self.index_of(pos) {
+                self.cells[start..start + row.len()].clone_from_slice(row);
+            }
+        }
+    }
+
+    fn index_of(&self, pos: BufferPosition) -> Option<usize> {
+        if pos.row < self.size.h && pos.col < self.size.w {
+            Some(pos.row as usize * self.size.w as usize + pos.col as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_tree, Buffer};
+
+    #[test]
+    fn test_print_clips() {
+        let mut frame = Frame::new(Size { w: 6, h: 1 });
+        let next = frame.print(
+            BufferPosition::new(0, 2),
+            "hello",
+            ContentStyle::default(),
+            5,
+        );
+        assert_eq!(next, 5);
+        assert_eq!(frame.to_lines(), vec!["  hel "]);
+    }
+
+    #[test]
+    fn test_print_wide_chars() {
+        let mut frame = Frame::new(Size { w: 6, h: 1 });
+        let next = frame.print(
+            BufferPosition::new(0, 0),
+            "a\u{4E2D}\u{6587}",
+            ContentStyle::default(),
+            6,
+        );
+        assert_eq!(next, 5);
+        // the second wide char doesn't fit anymore
+        let next = frame.print(
+            BufferPosition::new(0, next),
+            "\u{1F600}",
+            ContentStyle::default(),
+            6,
+        );
+        assert_eq!(next, 5);
+        assert_eq!(frame.to_lines(), vec!["a\u{4E2D}\u{6587} "]);
+
+        // overwriting half of a wide char blanks the other half
+        frame.print(BufferPosition::new(0, 2), "x", ContentStyle::default(), 6);
+        assert_eq!(frame.to_lines(), vec!["a x\u{6587} "]);
+    }
+
+    #[test]
+    fn test_render_to_frame() {
+        let left = Buffer::from_text("first\nsecond line").into_ref();
+        let right = Buffer::from_text("right").into_ref();
+        let tree = split_tree! {
+            Horizontal: {
+                1: left,
+                1: right,
+            }
+        };
+
+        let frame = tree.render_to_frame(Size { w: 13, h: 3 }).unwrap();
+        insta::assert_debug_snapshot!(frame.to_lines());
+    }
+}
@@ -0,0 +1,163 @@
+//! Keeps the last rendered frames around, so they can be inspected
+//! after the fact. Only available with the `debug` feature.
+//!
+//! Every call to [SplitTree::render](crate::SplitTree::render) records the frame
+//! it wrote to the terminal. [inspect_frame_history] then lets you step through
+//! them, which helps with diagnosing flicker and wrong partial updates.
+
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{LazyLock, Mutex},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode},
+    style::{ContentStyle, Stylize},
+};
+
+use crate::{BufferPosition, CrosstermRenderer, Frame};
+
+const DEFAULT_CAPACITY: usize = 64;
+
+static HISTORY: LazyLock<Mutex<FrameHistory>> =
+    LazyLock::new(|| Mutex::new(FrameHistory::new(DEFAULT_CAPACITY)));
+
+/// A ring of the last `capacity` rendered frames, oldest first
+pub struct FrameHistory {
+    frames: VecDeque<Frame>,
+    capacity: usize,
+}
+
+impl FrameHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.frames.len() >= self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.frames.len() > capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// returns the frame that was rendered `n` frames before the newest one
+    pub fn nth_newest(&self, n: usize) -> Option<&Frame> {
+        self.frames
+            .len()
+            .checked_sub(n + 1)
+            .map(|i| &self.frames[i])
+    }
+}
+
+pub(crate) fn record(frame: &Frame) {
+    HISTORY.lock().unwrap().push(frame.clone());
+}
+
+/// sets how many frames are kept, the default is 64
+pub fn set_frame_history_capacity(capacity: usize) {
+    HISTORY.lock().unwrap().set_capacity(capacity);
+}
+
+/// returns a copy of the recorded frames, oldest first
+pub fn frame_history() -> Vec<Frame> {
+    HISTORY.lock().unwrap().frames.iter().cloned().collect()
+}
+
+/// Takes over the terminal and shows the recorded frames with an overlay in the
+/// last row. Left/Right step through the frames, `d` toggles highlighting of the
+/// cells that changed compared to the previous frame, and Esc or `q` return.
+///
+/// Frames rendered while the inspector is open are not recorded, and the newest
+/// frame is shown again when it returns.
+pub fn inspect_frame_history() -> io::Result<()> {
+    let frames = frame_history();
+    let Some(newest) = frames.last() else {
+        return Ok(());
+    };
+
+    let mut back = 0;
+    let mut show_diff = false;
+    loop {
+        let i = frames.len() - 1 - back;
+        let prev = i.checked_sub(1).map(|i| &frames[i]);
+        render_inspected_frame(&frames[i], prev, show_diff, back, frames.len())?;
+
+        if let Event::Key(ke) = event::read()? {
+            match ke.code {
+                KeyCode::Left | KeyCode::Char('h') => back = (back + 1).min(frames.len() - 1),
+                KeyCode::Right | KeyCode::Char('l') => back = back.saturating_sub(1),
+                KeyCode::Home => back = frames.len() - 1,
+                KeyCode::End => back = 0,
+                KeyCode::Char('d') => show_diff = !show_diff,
+                KeyCode::Esc | KeyCode::Char('q') => break,
+                _ => {}
+            }
+        }
+    }
+
+    newest.draw(&mut CrosstermRenderer::stdout())
+}
+
+fn render_inspected_frame(
+    frame: &Frame,
+    prev: Option<&Frame>,
+    show_diff: bool,
+    back: usize,
+    n_frames: usize,
+) -> io::Result<()> {
+    let mut frame = frame.clone();
+    let size = frame.size();
+
+    if show_diff {
+        for row in 0..size.h {
+            for col in 0..size.w {
+                let pos = BufferPosition::new(row, col);
+                let old = prev.and_then(|p| p.cell(pos));
+                let cell = frame.cell_mut(pos).unwrap();
+                if old != Some(cell) {
+                    cell.style = cell.style.on_dark_red();
+                }
+            }
+        }
+    }
+
+    if size.h > 0 {
+        let info = format!(
+            " frame -{back} ({}/{n_frames}) | Left/Right: step, d: diff{}, q: quit ",
+            n_frames - back,
+            if show_diff { " (on)" } else { "" }
+        );
+        let row = BufferPosition::new(size.h - 1, 0);
+        frame.print(
+            row,
+            &" ".repeat(size.w as usize),
+            ContentStyle::new().reverse(),
+            size.w,
+        );
+        frame.print(row, &info, ContentStyle::new().reverse(), size.w);
+    }
+
+    frame.draw(&mut CrosstermRenderer::stdout())
+}
@@ -0,0 +1,249 @@
+//! Combinators for event handlers, so handlers for different parts of an app can be
+//! written separately, and combined, instead of matching on everything in one.
+//!
+//! A handler that returns [HandlerResult::Ignored] for an event passes it on, so
+//! handlers that only react to a few keys, like key bindings, should come first.
+//! Events that a handler acts on, without being done, are [HandlerResult::Handled],
+//! and aren't passed on. Handlers of different
+//! types can be stored together as `Box<dyn EventHandler<T>>`, and closures become
+//! handlers with [from_fn].
+
+use std::marker::PhantomData;
+
+use crossterm::event::Event;
+
+use crate::{AbletCtx, BufferRef, EventHandler, HandlerResult};
+
+/// see [EventHandler::or_else]
+pub struct OrElse<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> OrElse<A, B> {
+    pub(crate) fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+}
+
+impl<T, A: EventHandler<T>, B: EventHandler<T>> EventHandler<T> for OrElse<A, B> {
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, ctx: &mut AbletCtx) -> HandlerResult<T> {
+        match self.first.handle(ev, buf, ctx) {
+            HandlerResult::Ignored => self.second.handle(ev, buf, ctx),
+            res => res,
+        }
+    }
+}
+
+/// see [EventHandler::map]
+pub struct Map<H, F, T> {
+    handler: H,
+    f: F,
+    _result: PhantomData<fn() -> T>,
+}
+
+impl<H, F, T> Map<H, F, T> {
+    pub(crate) fn new(handler: H, f: F) -> Self {
+        Self {
+            handler,
+            f,
+            _result: PhantomData,
+        }
+    }
+}
+
+impl<T, U, H: EventHandler<T>, F: FnMut(T) -> U> EventHandler<U> for Map<H, F, T> {
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, ctx: &mut AbletCtx) -> HandlerResult<U> {
+        self.handler.handle(ev, buf, ctx).map(&mut self.f)
+    }
+}
+
+/// see [from_fn]
+pub struct FromFn<F>(F);
+
+/// Turns the closure into a handler. It gets the event and the buffer that is edited,
+/// like [EventHandler::handle]
+pub fn from_fn<T, F: FnMut(&Event, &BufferRef) -> HandlerResult<T>>(f: F) -> FromFn<F> {
+    FromFn(f)
+}
+
+impl<T, F: FnMut(&Event, &BufferRef) -> HandlerResult<T>> EventHandler<T> for FromFn<F> {
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, _ctx: &mut AbletCtx) -> HandlerResult<T> {
+        (self.0)(ev, buf)
+    }
+}
+
+/// Handlers that can be pushed and popped while the app runs, e.g. for a dialog
+/// that takes over the keys while it's shown. Events go to the topmost handler
+/// first, and on to the next one down while they're ignored
+pub struct HandlerStack<T> {
+    handlers: Vec<Box<dyn EventHandler<T> + Send>>,
+}
+
+impl<T> Default for HandlerStack<T> {
+    fn default() -> Self {
+        Self { handlers: vec![] }
+    }
+}
+
+impl<T> HandlerStack<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, handler: impl EventHandler<T> + Send + 'static) {
+        self.handlers.push(Box::new(handler));
+    }
+
+    /// removes the topmost handler, and returns false if there was none
+    pub fn pop(&mut self) -> bool {
+        self.handlers.pop().is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.handlers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+impl<T> EventHandler<T> for HandlerStack<T> {
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, ctx: &mut AbletCtx) -> HandlerResult<T> {
+        for handler in self.handlers.iter_mut().rev() {
+            match handler.handle(ev, buf, ctx) {
+                HandlerResult::Ignored => {}
+                res => return res,
+            }
+        }
+        HandlerResult::Ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    use super::*;
+    use crate::{Buffer, SimpleLineHandler, SimpleLineHandlerResult};
+
+    /// returns the char of the key, if it's the given one
+    struct Key(char);
+
+    impl EventHandler<char> for Key {
+        fn handle(
+            &mut self,
+            ev: &Event,
+            _buf: &BufferRef,
+            _ctx: &mut AbletCtx,
+        ) -> HandlerResult<char> {
+            match ev {
+                Event::Key(ke) if ke.code == KeyCode::Char(self.0) => HandlerResult::Done(self.0),
+                _ => HandlerResult::Ignored,
+            }
+        }
+    }
+
+    #[test]
+    fn test_combinators() {
+        let buf = Buffer::new().into_ref();
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let key = |c| Event::Key(KeyEvent::from(KeyCode::Char(c)));
+
+        let line_handler = SimpleLineHandler::new().map(|res| match res {
+            SimpleLineHandlerResult::LineDone => "done".to_string(),
+            SimpleLineHandlerResult::Abort => "abort".to_string(),
+        });
+        let mut handler = Key('q').map(|c| c.to_string()).or_else(line_handler);
+        assert_eq!(
+            handler.handle(&key('q'), &buf, &mut ctx).done().as_deref(),
+            Some("q")
+        );
+        assert_eq!(
+            handler.handle(&key('a'), &buf, &mut ctx),
+            HandlerResult::Handled
+        );
+        let enter = Event::Key(KeyEvent::from(KeyCode::Enter));
+        assert_eq!(
+            handler.handle(&enter, &buf, &mut ctx).done().as_deref(),
+            Some("done")
+        );
+        assert_eq!(buf.get_doc().0.lock().unwrap().content.text, "a");
+
+        let mut stack = HandlerStack::new();
+        stack.push(Key('a'));
+        stack.push(Key('b'));
+        assert_eq!(
+            stack.handle(&key('a'), &buf, &mut ctx),
+            HandlerResult::Done('a')
+        );
+        stack.pop();
+        assert_eq!(
+            stack.handle(&key('b'), &buf, &mut ctx),
+            HandlerResult::Ignored
+        );
+    }
+
+    #[test]
+    fn test_boxed_handlers() {
+        let buf = Buffer::new().into_ref();
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let key = |c| Event::Key(KeyEvent::from(KeyCode::Char(c)));
+
+        let mut handlers: Vec<Box<dyn EventHandler<char>>> = vec![
+            Box::new(Key('a')),
+            Box::new(from_fn(|ev, _buf| match ev {
+                Event::Key(ke) if ke.code == KeyCode::Char('b') => HandlerResult::Done('B'),
+                _ => HandlerResult::Ignored,
+            })),
+        ];
+        let mut results = |c| {
+            handlers
+                .iter_mut()
+                .filter_map(|h| h.handle(&key(c), &buf, &mut ctx).done())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(results('a'), ['a']);
+        assert_eq!(results('b'), ['B']);
+
+        // boxed handlers are handlers too
+        let mut handler = handlers.remove(0).or_else(handlers.remove(0));
+        assert_eq!(
+            handler.handle(&key('b'), &buf, &mut ctx),
+            HandlerResult::Done('B')
+        );
+    }
+
+    #[test]
+    fn test_handled_events_are_not_passed_on() {
+        let buf = Buffer::new().into_ref();
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let key = |c| Event::Key(KeyEvent::from(KeyCode::Char(c)));
+
+        // like a key binding that acts on x, without ending the loop
+        let binding = from_fn(|ev, buf: &BufferRef| match ev {
+            Event::Key(ke) if ke.code == KeyCode::Char('x') => {
+                buf.insert_text_at_cursor("[x]");
+                HandlerResult::Handled
+            }
+            _ => HandlerResult::Ignored,
+        });
+        let mut handler = binding.or_else(SimpleLineHandler::new());
+        assert!(matches!(
+            handler.handle(&key('x'), &buf, &mut ctx),
+            HandlerResult::Handled
+        ));
+        handler.handle(&key('a'), &buf, &mut ctx);
+        assert_eq!(buf.get_doc().0.lock().unwrap().content.text, "[x]a");
+
+        let mut stack = HandlerStack::new();
+        stack.push(SimpleLineHandler::new());
+        stack.push(handler);
+        stack.handle(&key('x'), &buf, &mut ctx);
+        assert_eq!(buf.get_doc().0.lock().unwrap().content.text, "[x]a[x]");
+    }
+}
@@ -0,0 +1,124 @@
+//! Contains HighlightRules, which style the parts of lines that match regexes, e.g.
+//! the timestamp and the level of log lines
+
+use crossterm::style::ContentStyle;
+use regex::Regex;
+
+use crate::AText;
+
+/// A regex, and the styles for its capture groups. Group 0 is the whole match
+#[derive(Clone, Debug)]
+pub struct HighlightRule {
+    regex: Regex,
+    styles: Vec<(usize, ContentStyle)>,
+}
+
+impl HighlightRule {
+    pub fn new(regex: Regex) -> Self {
+        Self {
+            regex,
+            styles: vec![],
+        }
+    }
+
+    /// styles the capture group with the given index
+    pub fn group(mut self, group: usize, style: ContentStyle) -> Self {
+        self.styles.push((group, style));
+        self
+    }
+
+    /// styles the capture group with the given name, if the regex has it
+    pub fn named_group(self, name: &str, style: ContentStyle) -> Self {
+        match self.regex.capture_names().position(|n| n == Some(name)) {
+            Some(group) => self.group(group, style),
+            None => self,
+        }
+    }
+}
+
+/// Rules that are applied in order, so later rules win where they overlap. Set via
+/// [DocumentRef::set_highlight_rules](crate::DocumentRef::set_highlight_rules)
+#[derive(Clone, Debug, Default)]
+pub struct HighlightRules(Vec<HighlightRule>);
+
+impl HighlightRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, rule: HighlightRule) -> Self {
+        self.0.push(rule);
+        self
+    }
+
+    /// Styles the matches in the given range of the text, replacing the styles they
+    /// had. Matches are searched line by line
+    pub fn apply(&self, text: &mut AText, range: std::ops::Range<usize>) {
+        let content = text.text[range.clone()].to_string();
+        let mut line_start = range.start;
+        for line in content.split_inclusive('\n') {
+            for rule in &self.0 {
+                for caps in rule.regex.captures_iter(line) {
+                    for (group, style) in &rule.styles {
+                        if let Some(m) = caps.get(*group) {
+                            text.apply_style(line_start + m.start()..line_start + m.end(), *style);
+                        }
+                    }
+                }
+            }
+            line_start += line.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crossterm::style::{Color, Stylize};
+
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_groups_are_styled() {
+        let rules = HighlightRules::new().with(
+            HighlightRule::new(Regex::new(r"^(?<time>\d\d:\d\d) (ERROR|INFO)").unwrap())
+                .named_group("time", ContentStyle::new().grey())
+                .group(2, ContentStyle::new().red()),
+        );
+        let doc = Document::from_text("10:00 INFO before\n").into_ref();
+        doc.set_highlight_rules(rules);
+        doc.add_line("10:01 ERROR after");
+
+        let doc = doc.0.lock().unwrap();
+        let colors = doc
+            .content
+            .chars_styled()
+            .map(|(_, style)| style.and_then(|s| s.style.foreground_color))
+            .collect::<Vec<_>>();
+        let grey = Some(Color::Grey);
+        let red = Some(Color::Red);
+        assert_eq!(
+            colors[..11],
+            [grey, grey, grey, grey, grey, None, red, red, red, red, None]
+        );
+        assert_eq!(colors[18..24], [grey, grey, grey, grey, grey, None]);
+        assert_eq!(colors[24..30], [red, red, red, red, red, None]);
+    }
+
+    #[test]
+    fn test_setting_rules_notifies_on_change() {
+        let doc = Document::from_text("10:00 INFO").into_ref();
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counter = changes.clone();
+        doc.on_change(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        doc.set_highlight_rules(HighlightRules::new());
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,290 @@
+//! Contains the edit history of a Document, which is used for undo and redo
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    input_history::{escape, unescape},
+    AText,
+};
+
+/// the maximum number of undo steps a document keeps
+const UNDO_LIMIT: usize = 1000;
+/// the first line of an undo file, with the version of its format
+const UNDO_FILE_HEADER: &str = "ablet-undo 1";
+
+/// A single change to a document: at `pos`, `removed` was replaced by `inserted`
+#[derive(Clone, Debug)]
+pub(crate) struct Edit {
+    pub(crate) pos: usize,
+    pub(crate) removed: AText,
+    pub(crate) inserted: AText,
+}
+
+/// How consecutive single char insertions, i.e. typing, are grouped into undo steps
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UndoGrouping {
+    /// they are undone in one step, until something else is done
+    #[default]
+    Consecutive,
+    /// every word is undone on its own, together with the whitespace behind it
+    Words,
+    /// a new step starts when typing pauses for longer than the given time
+    Pause(Duration),
+}
+
+impl Edit {
+    fn is_single_char_insertion(&self) -> bool {
+        self.removed.is_empty() && self.inserted.text.chars().count() == 1
+    }
+
+    fn inserts_whitespace(&self) -> bool {
+        self.inserted.text.chars().all(char::is_whitespace)
+    }
+
+    /// the edit that reverts this one
+    pub(crate) fn inverted(self) -> Edit {
+        Edit {
+            pos: self.pos,
+            removed: self.inserted,
+            inserted: self.removed,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// whether the newest undo step consists of single char insertions, and
+    /// the next one may be added to it
+    group_open: bool,
+    grouping: UndoGrouping,
+    /// when the last edit was recorded
+    last_record: Option<Instant>,
+}
+
+impl History {
+    pub(crate) fn set_grouping(&mut self, grouping: UndoGrouping) {
+        self.grouping = grouping;
+    }
+
+    pub(crate) fn record(&mut self, edit: Edit, now: Instant) {
+        self.redo.clear();
+        let is_single_char_insertion = edit.is_single_char_insertion();
+        let since_last = self.last_record.map(|t| now.saturating_duration_since(t));
+        self.last_record = Some(now);
+        match self.undo.last_mut() {
+            Some(last)
+                if self.group_open
+                    && is_single_char_insertion
+                    && last.pos + last.inserted.len() == edit.pos
+                    && continues_group(self.grouping, last, &edit, since_last) =>
+            {
+                last.inserted += edit.inserted;
+            }
+            _ => {
+                self.undo.push(edit);
+                if self.undo.len() > UNDO_LIMIT {
+                    self.undo.remove(0);
+                }
+            }
+        }
+        self.group_open = is_single_char_insertion;
+    }
+
+    pub(crate) fn pop_undo(&mut self) -> Option<Edit> {
+        self.group_open = false;
+        self.undo.pop()
+    }
+
+    pub(crate) fn pop_redo(&mut self) -> Option<Edit> {
+        self.group_open = false;
+        self.redo.pop()
+    }
+
+    pub(crate) fn push_undo(&mut self, edit: Edit) {
+        self.undo.push(edit);
+    }
+
+    pub(crate) fn push_redo(&mut self, edit: Edit) {
+        self.redo.push(edit);
+    }
+
+    /// The steps as the content of an undo file. The text they belong to is recorded
+    /// as a hash, so they aren't restored onto another one. Styles aren't kept
+    pub(crate) fn to_file_content(&self, text: &str) -> String {
+        let mut res = format!("{UNDO_FILE_HEADER}\n{:016x}\n", text_hash(text));
+        for (kind, steps) in [('u', &self.undo), ('r', &self.redo)] {
+            for edit in steps {
+                res += &format!("{kind} {}\n", edit.pos);
+                res += &escape(&edit.removed.text);
+                res.push('\n');
+                res += &escape(&edit.inserted.text);
+                res.push('\n');
+            }
+        }
+        res
+    }
+
+    /// Replaces the steps with the ones of an undo file, if it belongs to the text.
+    /// Returns whether it does
+    pub(crate) fn load_file_content(&mut self, content: &str, text: &str) -> io::Result<bool> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid undo file");
+        // not `lines()`, which would drop a '\r' at the end of a step's text
+        let mut lines = content.split_terminator('\n');
+        if lines.next() != Some(UNDO_FILE_HEADER) {
+            return Err(invalid());
+        }
+        let hash = lines
+            .next()
+            .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+            .ok_or_else(invalid)?;
+        if hash != text_hash(text) {
+            return Ok(false);
+        }
+
+        let (mut undo, mut redo) = (vec![], vec![]);
+        while let Some(line) = lines.next() {
+            let (kind, pos) = line.split_once(' ').ok_or_else(invalid)?;
+            let pos = pos.parse().map_err(|_| invalid())?;
+            let (Some(removed), Some(inserted)) = (lines.next(), lines.next()) else {
+                return Err(invalid());
+            };
+            let edit = Edit {
+                pos,
+                removed: unescape(removed).into(),
+                inserted: unescape(inserted).into(),
+            };
+            match kind {
+                "u" => undo.push(edit),
+                "r" => redo.push(edit),
+                _ => return Err(invalid()),
+            }
+        }
+        if !replays(&undo, text, true) || !replays(&redo, text, false) {
+            return Err(invalid());
+        }
+        self.undo = undo;
+        self.redo = redo;
+        self.group_open = false;
+        Ok(true)
+    }
+
+    /// forgets all steps, but keeps the grouping
+    pub(crate) fn clear(&mut self) {
+        *self = Self {
+            grouping: self.grouping,
+            ..Self::default()
+        };
+    }
+}
+
+/// Whether the steps can be undone, or redone, one after the other, starting with the
+/// last one on the text, i.e. whether each one's text is where it says it is
+fn replays(steps: &[Edit], text: &str, undo: bool) -> bool {
+    let mut text = text.to_string();
+    for edit in steps.iter().rev() {
+        let (old, new) = match undo {
+            true => (&edit.inserted.text, &edit.removed.text),
+            false => (&edit.removed.text, &edit.inserted.text),
+        };
+        let range = edit.pos..edit.pos + old.len();
+        if text.get(range.clone()) != Some(old.as_str()) {
+            return false;
+        }
+        text.replace_range(range, new);
+    }
+    true
+}
+
+/// FNV-1a, since the hashers of std may change between Rust versions
+fn text_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// whether the single char insertion `next` may be added to the step `group`
+fn continues_group(
+    grouping: UndoGrouping,
+    group: &Edit,
+    next: &Edit,
+    since_last: Option<Duration>,
+) -> bool {
+    match grouping {
+        UndoGrouping::Consecutive => true,
+        // a word starts after whitespace
+        UndoGrouping::Words => {
+            let after_whitespace = group.inserted.text.ends_with(char::is_whitespace);
+            !after_whitespace || next.inserts_whitespace()
+        }
+        UndoGrouping::Pause(pause) => since_last.is_some_and(|d| d <= pause),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grouping() {
+        let steps = |grouping, pauses: &[u64]| {
+            let mut history = History::default();
+            history.set_grouping(grouping);
+            let mut now = Instant::now();
+            for (i, (c, pause)) in "ab  cd".chars().zip(pauses).enumerate() {
+                now += Duration::from_millis(*pause);
+                let edit = Edit {
+                    pos: i,
+                    removed: AText::default(),
+                    inserted: c.to_string().into(),
+                };
+                history.record(edit, now);
+            }
+            let mut steps = vec![];
+            while let Some(edit) = history.pop_undo() {
+                steps.insert(0, edit.inserted.text);
+            }
+            steps
+        };
+        let pauses = [0, 10, 10, 500, 10, 10];
+        assert_eq!(steps(UndoGrouping::Consecutive, &pauses), ["ab  cd"]);
+        assert_eq!(steps(UndoGrouping::Words, &pauses), ["ab  ", "cd"]);
+        assert_eq!(
+            steps(UndoGrouping::Pause(Duration::from_millis(100)), &pauses),
+            ["ab ", " cd"]
+        );
+    }
+
+    #[test]
+    fn test_undo_files_are_checked() {
+        let edit = |pos, removed: &str, inserted: &str| Edit {
+            pos,
+            removed: removed.into(),
+            inserted: inserted.into(),
+        };
+        let text = "añb\r";
+        let mut history = History::default();
+        history.push_undo(edit(3, "", "b\r"));
+        history.push_redo(edit(0, "a", "x"));
+        let content = history.to_file_content(text);
+
+        let mut loaded = History::default();
+        assert!(loaded.load_file_content(&content, text).unwrap());
+        assert_eq!(loaded.pop_undo().unwrap().inserted.text, "b\r");
+        assert_eq!(loaded.pop_redo().unwrap().inserted.text, "x");
+
+        // inside of the ñ, behind the end, and where the text isn't the step's
+        for bad in [edit(2, "", "b"), edit(9, "", ""), edit(0, "", "b")] {
+            let mut history = History::default();
+            history.push_undo(bad);
+            let content = history.to_file_content(text);
+            assert!(History::default()
+                .load_file_content(&content, text)
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Contains the InputHistory, which stores submitted input, so it can be recalled
+
+use std::{fs, io, path::Path};
+
+use crate::Shared;
+
+/// The lines that were submitted in a [LineEditor](crate::LineEditor), oldest first.
+/// Clones share the same history, so several editors can use one.
+#[derive(Clone, Default)]
+pub struct InputHistory(Shared<HistoryState>);
+
+#[derive(Default)]
+struct HistoryState {
+    entries: Vec<String>,
+    /// removes older copies of an entry that is pushed again
+    dedup: bool,
+    /// drops the oldest entries beyond it
+    max_len: Option<usize>,
+}
+
+impl InputHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// If set, pushing an entry that is in the history already removes the older
+    /// copy, so each entry is only in the history once
+    pub fn set_dedup(&self, dedup: bool) {
+        self.0.lock().unwrap().dedup = dedup;
+    }
+
+    /// limits the number of entries, the oldest ones are dropped beyond it
+    pub fn set_max_len(&self, max_len: Option<usize>) {
+        let mut state = self.0.lock().unwrap();
+        state.max_len = max_len;
+        state.truncate();
+    }
+
+    /// adds an entry, unless it's empty
+    pub fn push(&self, entry: impl Into<String>) {
+        let entry = entry.into();
+        if entry.is_empty() {
+            return;
+        }
+        let mut state = self.0.lock().unwrap();
+        if state.dedup {
+            state.entries.retain(|e| *e != entry);
+        }
+        state.entries.push(entry);
+        state.truncate();
+    }
+
+    pub fn get(&self, i: usize) -> Option<String> {
+        self.0.lock().unwrap().entries.get(i).cloned()
+    }
+
+    pub fn entries(&self) -> Vec<String> {
+        self.0.lock().unwrap().entries.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().entries.clear()
+    }
+
+    /// the index of the newest entry before `before` that contains the query
+    pub fn find_back(&self, query: &str, before: usize) -> Option<usize> {
+        let state = self.0.lock().unwrap();
+        state.entries[..before.min(state.entries.len())]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+
+    /// Writes the entries to the file, one per line. Line breaks and backslashes
+    /// in entries are escaped
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut content = String::new();
+        for entry in &self.0.lock().unwrap().entries {
+            content += &escape(entry);
+            content.push('\n');
+        }
+        fs::write(path, content)
+    }
+
+    /// Pushes the entries of a file that was written by [InputHistory::save], so
+    /// they're older than the current ones
+    pub fn load(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let content = fs::read_to_string(path)?;
+        let mut state = self.0.lock().unwrap();
+        let current = std::mem::take(&mut state.entries);
+        drop(state);
+        for entry in content.lines().map(unescape).chain(current) {
+            self.push(entry);
+        }
+        Ok(())
+    }
+}
+
+impl HistoryState {
+    fn truncate(&mut self) {
+        if let Some(max_len) = self.max_len {
+            let excess = self.entries.len().saturating_sub(max_len);
+            self.entries.drain(..excess);
+        }
+    }
+}
+
+/// escapes line breaks and backslashes, so the text fits into one line
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// reverts [escape]
+pub(crate) fn unescape(line: &str) -> String {
+    let mut res = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some(c) => res.push(c),
+            None => res.push('\\'),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let history = InputHistory::new();
+        history.set_dedup(true);
+        history.set_max_len(Some(3));
+        for entry in ["a", "c", "a", "b\\n", "(+ 1\n 2)"] {
+            history.push(entry);
+        }
+        assert_eq!(history.entries(), ["a", "b\\n", "(+ 1\n 2)"]);
+
+        let path = std::env::temp_dir().join(format!("ablet-history-{}", std::process::id()));
+        history.save(&path).unwrap();
+        let loaded = InputHistory::new();
+        loaded.push("new");
+        loaded.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.entries(), ["a", "b\\n", "(+ 1\n 2)", "new"]);
+    }
+}
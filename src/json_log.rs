@@ -0,0 +1,265 @@
+//! Contains the JsonLogBuffer, which shows JSON log lines, like the ones written by
+//! `tracing_subscriber::fmt().json()`, as aligned, styled columns
+
+use crate::{logger::styled_level, shared, table::fit_to_width, AText, Buffer, BufferRef, Shared};
+use crossterm::style::{StyledContent, Stylize};
+use log::Level;
+use serde_json::Value;
+
+/// A buffer that parses each added line as JSON, and shows the configured fields of
+/// it in columns. Lines that can't be parsed are shown as they are. Clones share
+/// the same state, so lines can be added from background threads.
+#[derive(Clone)]
+pub struct JsonLogBuffer {
+    buffer: BufferRef,
+    details: BufferRef,
+    state: Shared<State>,
+}
+
+/// A field that is shown as a column. Nested fields are addressed with dots, like
+/// `fields.message`
+#[derive(Clone, Debug)]
+pub struct JsonColumn {
+    pub field: String,
+    /// values are padded or cut to this width, None means the rest of the line
+    pub width: Option<usize>,
+}
+
+impl JsonColumn {
+    pub fn new(field: impl Into<String>, width: Option<usize>) -> Self {
+        Self {
+            field: field.into(),
+            width,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    columns: Vec<JsonColumn>,
+    /// field and value, a line is only shown if it matches all of them
+    filters: Vec<(String, String)>,
+    records: Vec<Record>,
+    /// the indices of the records that are shown, one per line of the buffer
+    shown: Vec<usize>,
+}
+
+struct Record {
+    raw: String,
+    value: Option<Value>,
+}
+
+impl Default for JsonLogBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonLogBuffer {
+    /// shows the level, target, and message of the records, as written by tracing
+    pub fn new() -> Self {
+        Self::with_columns(vec![
+            JsonColumn::new("level", Some(5)),
+            JsonColumn::new("target", Some(20)),
+            JsonColumn::new("fields.message", None),
+        ])
+    }
+
+    pub fn with_columns(columns: Vec<JsonColumn>) -> Self {
+        let buffer = Buffer::new().into_ref();
+        buffer.set_cursor_visible(false);
+        Self {
+            buffer,
+            details: Buffer::new().into_ref(),
+            state: shared(State {
+                columns,
+                ..State::default()
+            }),
+        }
+    }
+
+    /// the buffer that shows the records, to be put into a split tree
+    pub fn buffer(&self) -> &BufferRef {
+        &self.buffer
+    }
+
+    /// the buffer that shows the record of the line at the cursor, see
+    /// [JsonLogBuffer::show_details]
+    pub fn details_buffer(&self) -> &BufferRef {
+        &self.details
+    }
+
+    pub fn add_line(&self, line: &str) {
+        let mut state = self.state.lock().unwrap();
+        let record = Record {
+            raw: line.to_string(),
+            value: serde_json::from_str(line).ok(),
+        };
+        if state.matches_filters(&record) {
+            let idx = state.records.len();
+            state.shown.push(idx);
+            self.buffer.add_line(state.format(&record));
+        }
+        state.records.push(record);
+    }
+
+    /// Only shows the records where the field has the given value, in addition to
+    /// the existing filters. Lines that aren't JSON are hidden by any filter
+    pub fn add_filter(&self, field: impl Into<String>, value: impl Into<String>) {
+        self.state
+            .lock()
+            .unwrap()
+            .filters
+            .push((field.into(), value.into()));
+        self.refresh();
+    }
+
+    pub fn clear_filters(&self) {
+        self.state.lock().unwrap().filters.clear();
+        self.refresh();
+    }
+
+    /// the raw JSON of the line at the cursor
+    pub fn selected_raw(&self) -> Option<String> {
+        let line = {
+            let doc = self.buffer.get_doc();
+            let doc = doc.0.lock().unwrap();
+            let cursor = self.buffer.cursor().get().min(doc.content.len());
+            doc.content.text[..cursor].matches('\n').count()
+        };
+        let state = self.state.lock().unwrap();
+        let idx = *state.shown.get(line)?;
+        Some(state.records[idx].raw.clone())
+    }
+
+    /// Shows the record of the line at the cursor, pretty printed, in the
+    /// [details buffer](JsonLogBuffer::details_buffer)
+    pub fn show_details(&self) {
+        let Some(raw) = self.selected_raw() else {
+            return;
+        };
+        let text = serde_json::from_str::<Value>(&raw)
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .unwrap_or(raw);
+        self.details
+            .get_doc()
+            .update_content(|content| *content = text.into());
+        self.details.goto_line(0);
+    }
+
+    /// rebuilds the shown lines, after the filters changed
+    fn refresh(&self) {
+        let mut state = self.state.lock().unwrap();
+        let shown = (0..state.records.len())
+            .filter(|&i| state.matches_filters(&state.records[i]))
+            .collect::<Vec<_>>();
+        let text = AText::from_multiple(
+            shown
+                .iter()
+                .map(|&i| state.format(&state.records[i]) + "\n"),
+        );
+        state.shown = shown;
+        self.buffer
+            .get_doc()
+            .update_content(|content| *content = text);
+    }
+}
+
+impl State {
+    fn matches_filters(&self, record: &Record) -> bool {
+        self.filters.iter().all(|(field, expected)| {
+            let value = record.value.as_ref().and_then(|v| lookup(v, field));
+            value.is_some_and(|v| display(v) == *expected)
+        })
+    }
+
+    fn format(&self, record: &Record) -> AText {
+        let Some(value) = &record.value else {
+            return AText::from(record.raw.as_str());
+        };
+        let mut res = AText::default();
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                res.push_char(' ');
+            }
+            let text = lookup(value, &column.field)
+                .map(display)
+                .unwrap_or_default();
+            let text = match column.width {
+                Some(width) => fit_to_width(&text, width),
+                None => text,
+            };
+            res += style_field(&column.field, text);
+        }
+        res
+    }
+}
+
+/// the value at the dotted path
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+/// strings without quotes, everything else as JSON
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn style_field(field: &str, text: String) -> StyledContent<String> {
+    match text.trim().parse::<Level>() {
+        Ok(level) if field == "level" => styled_level(level, text),
+        _ => text.stylize(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_and_filters() {
+        let log = JsonLogBuffer::new();
+        log.add_line(r#"{"level":"INFO","target":"app","fields":{"message":"started"}}"#);
+        log.add_line("not json");
+        log.add_line(r#"{"level":"ERROR","target":"app::db","fields":{"message":"lost"}}"#);
+        let text = || {
+            log.buffer()
+                .get_doc()
+                .0
+                .lock()
+                .unwrap()
+                .content
+                .text
+                .clone()
+        };
+        assert_eq!(
+            text(),
+            "INFO  app                  started\n\
+             not json\n\
+             ERROR app::db              lost\n"
+        );
+
+        log.add_filter("level", "ERROR");
+        assert_eq!(text(), "ERROR app::db              lost\n");
+        assert_eq!(
+            log.selected_raw().unwrap(),
+            r#"{"level":"ERROR","target":"app::db","fields":{"message":"lost"}}"#
+        );
+
+        log.show_details();
+        let details = log.details_buffer().get_doc();
+        assert!(details
+            .0
+            .lock()
+            .unwrap()
+            .content
+            .text
+            .contains("\n  \"level\": \"ERROR\""));
+
+        log.clear_filters();
+        assert_eq!(text().lines().count(), 3);
+    }
+}
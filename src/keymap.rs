@@ -0,0 +1,264 @@
+//! Contains the KeyMap, which maps keys, and sequences of them, to actions
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use thiserror::Error;
+
+use crate::{AbletCtx, BufferRef, EventHandler, HandlerResult};
+
+/// A key with modifiers, like `C-a`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum KeyMapError {
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+    #[error("empty key sequence")]
+    EmptySequence,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    /// Parses emacs notation: a char or a key name, like `Enter`, `Tab`, `Esc`, `Left`,
+    /// `PageUp`, or `F5`, with any of the prefixes `C-` for control, `M-` for alt,
+    /// and `S-` for shift, e.g. `C-M-x`
+    pub fn parse(s: &str) -> Result<Self, KeyMapError> {
+        let invalid = || KeyMapError::InvalidKey(s.to_string());
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        while let Some((prefix, tail)) = rest.split_once('-').filter(|(_, t)| !t.is_empty()) {
+            modifiers |= match prefix {
+                "C" => KeyModifiers::CONTROL,
+                "M" => KeyModifiers::ALT,
+                "S" => KeyModifiers::SHIFT,
+                _ => return Err(invalid()),
+            };
+            rest = tail;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest {
+                "Enter" => KeyCode::Enter,
+                "Tab" => KeyCode::Tab,
+                "Esc" => KeyCode::Esc,
+                "Backspace" => KeyCode::Backspace,
+                "Delete" => KeyCode::Delete,
+                "Insert" => KeyCode::Insert,
+                "Space" => KeyCode::Char(' '),
+                "Left" => KeyCode::Left,
+                "Right" => KeyCode::Right,
+                "Up" => KeyCode::Up,
+                "Down" => KeyCode::Down,
+                "Home" => KeyCode::Home,
+                "End" => KeyCode::End,
+                "PageUp" => KeyCode::PageUp,
+                "PageDown" => KeyCode::PageDown,
+                _ => match rest.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                    Some(n) => KeyCode::F(n),
+                    None => return Err(invalid()),
+                },
+            },
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl From<&KeyEvent> for KeyChord {
+    /// Shift is dropped from chars, since it's part of the char already, so `A` is
+    /// matched by the chord `A`, not `S-A`
+    fn from(ke: &KeyEvent) -> Self {
+        let mut modifiers = ke.modifiers;
+        if let KeyCode::Char(_) = ke.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self::new(ke.code, modifiers)
+    }
+}
+
+/// what [KeyMap::lookup] found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyLookup<A> {
+    /// the key completed a binding
+    Action(A),
+    /// the key is the start of a sequence, and more keys are needed
+    Pending,
+    /// the key isn't bound, and an unfinished sequence was discarded
+    Unbound,
+}
+
+/// Maps keys, and sequences of them, like `C-x C-s`, to actions. As an
+/// [EventHandler], it returns the action of the completed binding, and None for
+/// other events, so it can be combined with another handler via
+/// [EventHandler::or_else]
+#[derive(Clone, Debug)]
+pub struct KeyMap<A> {
+    bindings: Vec<(Vec<KeyChord>, A)>,
+    /// the keys of the unfinished sequence so far
+    pending: Vec<KeyChord>,
+}
+
+impl<A> Default for KeyMap<A> {
+    fn default() -> Self {
+        Self {
+            bindings: vec![],
+            pending: vec![],
+        }
+    }
+}
+
+impl<A: Clone> KeyMap<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds the keys, separated by spaces, see [KeyChord::parse], replacing an
+    /// existing binding of the same keys
+    pub fn bind(&mut self, keys: &str, action: A) -> Result<(), KeyMapError> {
+        let chords = keys
+            .split_whitespace()
+            .map(KeyChord::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.bind_chords(chords, action)
+    }
+
+    pub fn bind_chords(&mut self, chords: Vec<KeyChord>, action: A) -> Result<(), KeyMapError> {
+        if chords.is_empty() {
+            return Err(KeyMapError::EmptySequence);
+        }
+        self.unbind_chords(&chords);
+        self.bindings.push((chords, action));
+        Ok(())
+    }
+
+    /// removes the binding of the keys, if there is one
+    pub fn unbind(&mut self, keys: &str) -> Result<(), KeyMapError> {
+        let chords = keys
+            .split_whitespace()
+            .map(KeyChord::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.unbind_chords(&chords);
+        Ok(())
+    }
+
+    fn unbind_chords(&mut self, chords: &[KeyChord]) {
+        self.bindings.retain(|(keys, _)| keys != chords);
+    }
+
+    /// Feeds the key to the map, which remembers it, if it starts a sequence. Keys
+    /// other than chars, like Enter or Left, whose modifiers aren't bound, are looked
+    /// up without them, so e.g. `S-Enter` does what `Enter` does, unless it's bound
+    pub fn lookup(&mut self, key: impl Into<KeyChord>) -> KeyLookup<A> {
+        let key = key.into();
+        let mut keys = std::mem::take(&mut self.pending);
+        keys.push(key);
+        let mut res = self.find(&keys);
+        let is_char = matches!(key.code, KeyCode::Char(_));
+        if matches!(res, KeyLookup::Unbound) && !is_char && !key.modifiers.is_empty() {
+            *keys.last_mut().unwrap() = KeyChord::new(key.code, KeyModifiers::NONE);
+            res = self.find(&keys);
+        }
+        if matches!(res, KeyLookup::Pending) {
+            self.pending = keys;
+        }
+        res
+    }
+
+    fn find(&self, keys: &[KeyChord]) -> KeyLookup<A> {
+        let mut is_prefix = false;
+        for (bound, action) in &self.bindings {
+            if bound == keys {
+                return KeyLookup::Action(action.clone());
+            }
+            is_prefix |= bound.starts_with(keys);
+        }
+        if is_prefix {
+            KeyLookup::Pending
+        } else {
+            KeyLookup::Unbound
+        }
+    }
+}
+
+impl<A: Clone> EventHandler<A> for KeyMap<A> {
+    fn handle(&mut self, ev: &Event, _buf: &BufferRef, _ctx: &mut AbletCtx) -> HandlerResult<A> {
+        match ev {
+            Event::Key(ke) if ke.kind != KeyEventKind::Release => match self.lookup(ke) {
+                KeyLookup::Action(action) => HandlerResult::Done(action),
+                // the key starts a sequence, so it's used up
+                KeyLookup::Pending => HandlerResult::Handled,
+                KeyLookup::Unbound => HandlerResult::Ignored,
+            },
+            _ => HandlerResult::Ignored,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequences() {
+        let mut map = KeyMap::new();
+        map.bind("C-x C-s", "save").unwrap();
+        map.bind("C-M-x", "eval").unwrap();
+        map.bind("F5", "reload").unwrap();
+        assert_eq!(
+            map.bind("C-x Hyper", "nope"),
+            Err(KeyMapError::InvalidKey("Hyper".into()))
+        );
+
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        assert_eq!(map.lookup(&ctrl('x')), KeyLookup::Pending);
+        assert_eq!(map.lookup(&ctrl('s')), KeyLookup::Action("save"));
+        assert_eq!(map.lookup(&ctrl('x')), KeyLookup::Pending);
+        assert_eq!(map.lookup(&ctrl('a')), KeyLookup::Unbound);
+        assert_eq!(map.lookup(&ctrl('s')), KeyLookup::Unbound);
+
+        let ctrl_alt_x = KeyEvent::new(
+            KeyCode::Char('x'),
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+        );
+        assert_eq!(map.lookup(&ctrl_alt_x), KeyLookup::Action("eval"));
+        assert_eq!(
+            map.lookup(KeyChord::new(KeyCode::F(5), KeyModifiers::NONE)),
+            KeyLookup::Action("reload")
+        );
+    }
+
+    #[test]
+    fn test_unbound_modifiers_of_named_keys_are_ignored() {
+        let mut map = KeyMap::new();
+        map.bind("Enter", "submit").unwrap();
+        map.bind("Left", "left").unwrap();
+        map.bind("C-Left", "word left").unwrap();
+        map.bind("a", "a").unwrap();
+
+        let key = |code, modifiers| KeyChord::new(code, modifiers);
+        assert_eq!(
+            map.lookup(key(KeyCode::Enter, KeyModifiers::SHIFT)),
+            KeyLookup::Action("submit")
+        );
+        assert_eq!(
+            map.lookup(key(KeyCode::Left, KeyModifiers::CONTROL)),
+            KeyLookup::Action("word left")
+        );
+        assert_eq!(
+            map.lookup(key(KeyCode::Left, KeyModifiers::ALT)),
+            KeyLookup::Action("left")
+        );
+        // chars with modifiers are different keys
+        assert_eq!(
+            map.lookup(key(KeyCode::Char('a'), KeyModifiers::CONTROL)),
+            KeyLookup::Unbound
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Contains the KillRing, which stores killed text for yanking
+
+use crate::{AText, Shared};
+
+/// the maximum number of entries a kill ring keeps
+const KILL_RING_LIMIT: usize = 60;
+
+/// Stores killed text, so it can be yanked again. Clones share the same ring,
+/// so giving handlers clones of the same ring lets them share killed text,
+/// e.g. between a prompt and the buffers.
+#[derive(Clone, Default)]
+pub struct KillRing(Shared<Ring>);
+
+#[derive(Default)]
+struct Ring {
+    entries: Vec<AText>,
+    /// the entry that was yanked last
+    yank_index: usize,
+}
+
+impl KillRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a new entry, which will be the next one to be yanked
+    pub fn push(&self, text: impl Into<AText>) {
+        let mut ring = self.0.lock().unwrap();
+        ring.entries.push(text.into());
+        if ring.entries.len() > KILL_RING_LIMIT {
+            ring.entries.remove(0);
+        }
+        ring.yank_index = ring.entries.len() - 1;
+    }
+
+    /// Adds the text to the newest entry, which is used for consecutive kills.
+    /// If `prepend` is true, it's put in front of it, which is used for backward kills.
+    pub fn extend_last(&self, text: impl Into<AText>, prepend: bool) {
+        let mut ring = self.0.lock().unwrap();
+        match ring.entries.last_mut() {
+            Some(last) if prepend => *last = text.into() + last.clone(),
+            Some(last) => *last += text.into(),
+            None => {
+                drop(ring);
+                self.push(text);
+            }
+        }
+    }
+
+    /// returns the newest entry
+    pub fn yank(&self) -> Option<AText> {
+        let mut ring = self.0.lock().unwrap();
+        ring.yank_index = ring.entries.len().checked_sub(1)?;
+        ring.entries.last().cloned()
+    }
+
+    /// returns the entry before the one that was yanked last, wrapping around
+    /// to the newest one after the oldest
+    pub fn yank_pop(&self) -> Option<AText> {
+        let mut ring = self.0.lock().unwrap();
+        let len = ring.entries.len();
+        if len == 0 {
+            return None;
+        }
+        ring.yank_index = (ring.yank_index + len - 1) % len;
+        Some(ring.entries[ring.yank_index].clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yank_pop_cycles() {
+        let ring = KillRing::new();
+        assert!(ring.yank().is_none());
+
+        ring.push("one");
+        ring.push("two");
+        ring.extend_last(" three", false);
+        let shared = ring.clone();
+
+        assert_eq!(shared.yank().unwrap().text, "two three");
+        assert_eq!(shared.yank_pop().unwrap().text, "one");
+        assert_eq!(shared.yank_pop().unwrap().text, "two three");
+    }
+}
@@ -0,0 +1,207 @@
+//! Contains the serializable mirror of a SplitTree. Only available with
+//! the `serde` feature.
+//!
+//! A split tree holds BufferRefs, which can't be serialized, so a [Layout]
+//! refers to buffers by their name in a [BufferRegistry] instead. Use
+//! [SplitTree::to_layout] to persist a tree and [SplitTree::from_layout] to
+//! restore it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BufferRegistry, Orientation, RegistryError, Split, SplitContent, SplitSize, SplitTree, Stack,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Layout {
+    pub orientation: Orientation,
+    pub root: LayoutSplit,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayoutSplit {
+    pub sizes: Vec<SplitSize>,
+    pub content: Vec<LayoutContent>,
+    /// see [Split::with_orientation]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<Orientation>,
+    /// see [Split::stacked]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack: Option<Stack>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum LayoutContent {
+    /// a buffer, by its name in the registry
+    Leaf(String),
+    Branch(LayoutSplit),
+}
+
+impl SplitTree {
+    /// Converts the tree into a serializable Layout. Fails if a buffer
+    /// in the tree is not in the registry
+    pub fn to_layout(&self, registry: &BufferRegistry) -> Result<Layout, RegistryError> {
+        Ok(Layout {
+            orientation: self.top_orientation,
+            root: self.root.to_layout(registry)?,
+        })
+    }
+
+    /// Builds a tree from a Layout. Fails if the layout refers to a name
+    /// that is not in the registry, or a split of it is empty, or doesn't have
+    /// a size for each of its children
+    pub fn from_layout(
+        layout: &Layout,
+        registry: &BufferRegistry,
+    ) -> Result<SplitTree, RegistryError> {
+        Ok(SplitTree::new(
+            Split::from_layout(&layout.root, registry)?,
+            layout.orientation,
+        ))
+    }
+}
+
+impl Split {
+    fn to_layout(&self, registry: &BufferRegistry) -> Result<LayoutSplit, RegistryError> {
+        let content = self
+            .content
+            .iter()
+            .map(|c| match c {
+                SplitContent::Leaf(buf) => Ok(LayoutContent::Leaf(registry.name_of(buf)?.into())),
+                SplitContent::Branch(split) => {
+                    Ok(LayoutContent::Branch(split.to_layout(registry)?))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(LayoutSplit {
+            sizes: self.sizes.clone(),
+            content,
+            orientation: self.orientation,
+            stack: self.stack.clone(),
+        })
+    }
+
+    fn from_layout(
+        layout: &LayoutSplit,
+        registry: &BufferRegistry,
+    ) -> Result<Split, RegistryError> {
+        let invalid = |msg: String| Err(RegistryError::InvalidLayout(msg));
+        let n = layout.content.len();
+        if n == 0 {
+            return invalid("a split without children".into());
+        }
+        if layout.sizes.len() != n {
+            return invalid(format!("{} sizes for {n} children", layout.sizes.len()));
+        }
+        if let Some(stack) = layout.stack.as_ref().filter(|s| s.active >= n) {
+            return invalid(format!("child {} of {n} is expanded", stack.active));
+        }
+        let content = layout
+            .content
+            .iter()
+            .map(|c| match c {
+                LayoutContent::Leaf(name) => Ok(SplitContent::Leaf(registry.get(name)?.clone())),
+                LayoutContent::Branch(split) => {
+                    Ok(SplitContent::Branch(Split::from_layout(split, registry)?))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        let mut split = Split::new(layout.sizes.clone(), content);
+        split.orientation = layout.orientation;
+        split.stack = layout.stack.clone();
+        Ok(split)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_tree, Buffer};
+
+    #[test]
+    fn test_layout_roundtrip() {
+        let main = Buffer::new().into_ref();
+        let side = Buffer::new().into_ref();
+        let prompt = Buffer::new().into_ref();
+        let tree = split_tree! {
+            Vertical: {
+                3: {
+                    2: main,
+                    1: side,
+                },
+                1!: prompt,
+            }
+        };
+
+        let mut registry = BufferRegistry::new();
+        registry.register("main", main);
+        registry.register("side", side);
+        registry.register("prompt", prompt);
+
+        let json = serde_json::to_string_pretty(&tree.to_layout(&registry).unwrap()).unwrap();
+        insta::assert_snapshot!(json);
+
+        let layout: Layout = serde_json::from_str(&json).unwrap();
+        let restored = SplitTree::from_layout(&layout, &registry).unwrap();
+
+        let rects = |tree: &SplitTree| {
+            let mut rects = tree
+                .compute_rects((80, 24))
+                .unwrap()
+                .rects
+                .into_iter()
+                .collect::<Vec<_>>();
+            rects.sort_unstable_by_key(|(rect, _)| *rect);
+            rects
+        };
+        assert!(rects(&tree) == rects(&restored));
+    }
+
+    #[test]
+    fn test_layout_unknown_buffer() {
+        let buf = Buffer::new().into_ref();
+        let tree = split_tree! {
+            Vertical: {
+                1: buf,
+            }
+        };
+        assert!(matches!(
+            tree.to_layout(&BufferRegistry::new()),
+            Err(RegistryError::UnregisteredBuffer)
+        ));
+    }
+
+    #[test]
+    fn test_layout_invalid_split() {
+        let mut registry = BufferRegistry::new();
+        registry.register("main", Buffer::new().into_ref());
+        let layout = |sizes: Vec<SplitSize>, content: Vec<&str>| Layout {
+            orientation: Orientation::Vertical,
+            root: LayoutSplit {
+                sizes,
+                content: content
+                    .into_iter()
+                    .map(|name| LayoutContent::Leaf(name.into()))
+                    .collect(),
+                orientation: None,
+                stack: None,
+            },
+        };
+        let err = |layout| SplitTree::from_layout(&layout, &registry).err().unwrap();
+        assert!(matches!(
+            err(layout(vec![SplitSize::Proportion(1)], vec!["main", "main"])),
+            RegistryError::InvalidLayout(msg) if msg == "1 sizes for 2 children"
+        ));
+        assert!(matches!(
+            err(layout(vec![], vec![])),
+            RegistryError::InvalidLayout(_)
+        ));
+
+        let mut stacked = layout(vec![SplitSize::Proportion(1)], vec!["main"]);
+        stacked.root.stack = Some(Stack {
+            active: 1,
+            titles: vec![],
+        });
+        assert!(matches!(err(stacked), RegistryError::InvalidLayout(_)));
+    }
+}
@@ -3,9 +3,10 @@ use std::{
     io::{self},
     ops::Sub,
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::event::{self, Event};
 use derive_more::derive::Constructor;
 use persistent_structs::PersistentStruct;
 
@@ -28,6 +29,11 @@ impl Rect {
             size: Size { w, h },
         }
     }
+
+    pub fn contains(&self, pos: BufferPosition) -> bool {
+        (self.pos.row..self.pos.row + self.size.h).contains(&pos.row)
+            && (self.pos.col..self.pos.col + self.size.w).contains(&pos.col)
+    }
 }
 
 pub fn rect(row: u16, col: u16, w: u16, h: u16) -> Rect {
@@ -35,6 +41,7 @@ pub fn rect(row: u16, col: u16, w: u16, h: u16) -> Rect {
 }
 
 #[derive(Hash, Clone, Copy, PersistentStruct, PartialEq, Eq, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Size {
     pub w: u16,
     pub h: u16,
@@ -52,6 +59,7 @@ pub enum BufferType {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Orientation {
     Horizontal,
     Vertical,
@@ -113,7 +121,7 @@ impl<T: RangeCompatibleNumber<T>> Range<T> {
     }
 
     pub fn get_overlap_with(&self, foreign: &Range<T>) -> OverlapDescription<T> {
-        if foreign.start > self.end || self.start > foreign.end {
+        if foreign.start >= self.end || self.start >= foreign.end {
             return OverlapDescription::None;
         }
 
@@ -193,7 +201,7 @@ pub enum OverlapDescription<T> {
 
 #[derive(PersistentStruct, Clone)]
 pub struct StyledRange<'a, T> {
-    pub(crate) style: Cow<'a, crossterm::style::ContentStyle>,
+    pub(crate) style: Cow<'a, ThemedStyle>,
     pub(crate) range: Range<T>,
 }
 
@@ -207,74 +215,284 @@ macro_rules! with_cleanup {
     }};
 }
 
-pub trait EventHandler<T> {
-    fn handle(&mut self, ev: &Event, buf: &BufferRef) -> Option<T>;
+/// What an [EventHandler] did with an event
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HandlerResult<T> {
+    /// the handler acted on the event, and the loop goes on
+    Handled,
+    /// the handler didn't use the event, so combined handlers pass it on, see the
+    /// [handlers] module. Otherwise the loop goes on, like for [HandlerResult::Handled]
+    Ignored,
+    /// the loop ends with the result
+    Done(T),
 }
 
-pub struct SimpleLineHandler;
+impl<T> HandlerResult<T> {
+    /// the result, if the handler is done
+    pub fn done(self) -> Option<T> {
+        match self {
+            HandlerResult::Done(res) => Some(res),
+            HandlerResult::Handled | HandlerResult::Ignored => None,
+        }
+    }
 
-pub enum SimpleLineHandlerResult {
-    LineDone,
-    Abort,
-}
+    pub fn is_ignored(&self) -> bool {
+        matches!(self, HandlerResult::Ignored)
+    }
 
-impl EventHandler<SimpleLineHandlerResult> for SimpleLineHandler {
-    fn handle(&mut self, ev: &Event, buf: &BufferRef) -> Option<SimpleLineHandlerResult> {
-        match ev {
-            Event::Key(ke) => match ke.code {
-                KeyCode::Char('c') if ke.modifiers.contains(KeyModifiers::CONTROL) => {
-                    return Some(SimpleLineHandlerResult::Abort);
-                }
-                KeyCode::Char('a') if ke.modifiers.contains(KeyModifiers::CONTROL) => {
-                    buf.move_cursor_to_line_start()
-                }
-                KeyCode::Char('e') if ke.modifiers.contains(KeyModifiers::CONTROL) => {
-                    buf.move_cursor_to_line_end()
-                }
-                KeyCode::Char(c) => buf.insert_char_at_cursor(c),
-                KeyCode::Backspace => buf.delete_char_before_cursor(),
-                KeyCode::Left => buf.move_cursor_by(-1),
-                KeyCode::Right => buf.move_cursor_by(1),
-                KeyCode::Enter => return Some(SimpleLineHandlerResult::LineDone),
-                _ => {}
-            },
-            Event::Paste(text) => buf.insert_text_at_cursor(text.as_str()),
-            _ => {}
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> HandlerResult<U> {
+        match self {
+            HandlerResult::Handled => HandlerResult::Handled,
+            HandlerResult::Ignored => HandlerResult::Ignored,
+            HandlerResult::Done(res) => HandlerResult::Done(f(res)),
         }
-        None
+    }
+}
+
+pub trait EventHandler<T> {
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, ctx: &mut AbletCtx) -> HandlerResult<T>;
+
+    /// passes the events that this handler ignores on to the other one, see the
+    /// [handlers] module
+    fn or_else<H: EventHandler<T>>(self, other: H) -> handlers::OrElse<Self, H>
+    where
+        Self: Sized,
+    {
+        handlers::OrElse::new(self, other)
+    }
+
+    /// converts the results of the handler
+    fn map<U, F: FnMut(T) -> U>(self, f: F) -> handlers::Map<Self, F, T>
+    where
+        Self: Sized,
+    {
+        handlers::Map::new(self, f)
+    }
+}
+
+impl<T, H: EventHandler<T> + ?Sized> EventHandler<T> for &mut H {
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, ctx: &mut AbletCtx) -> HandlerResult<T> {
+        (**self).handle(ev, buf, ctx)
+    }
+}
+
+/// so handlers of different types can be stored together as `Box<dyn EventHandler<T>>`
+impl<T, H: EventHandler<T> + ?Sized> EventHandler<T> for Box<H> {
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, ctx: &mut AbletCtx) -> HandlerResult<T> {
+        (**self).handle(ev, buf, ctx)
     }
 }
 
 /// runs a loop that renders the split, hands of events to the handler,
-/// and returns when the handler returns Some(T)
+/// and returns when the handler returns [HandlerResult::Done]
 pub fn edit_buffer<H: EventHandler<T>, T>(
     buf: &BufferRef,
     split_tree: &SplitTree,
     event_handler: &mut H,
 ) -> io::Result<T> {
+    edit_with(
+        None,
+        || split_tree.render(),
+        event::read,
+        buf,
+        |ev, buf, ctx| event_handler.handle(&ev, buf, ctx).done(),
+    )
+}
+
+/// the loop behind [edit_buffer], [Ablet::edit_prompt], and [Ablet::edit_with_events]
+pub(crate) fn edit_with<E, T>(
+    ablet: Option<&Ablet>,
+    render: impl Fn() -> io::Result<()>,
+    mut read_event: impl FnMut() -> io::Result<E>,
+    buf: &BufferRef,
+    mut handle: impl FnMut(E, &BufferRef, &mut AbletCtx) -> Option<T>,
+) -> io::Result<T> {
+    let mut last_event_received = None;
     loop {
-        split_tree.render()?;
-        let ev = event::read()?;
-        if let Some(res) = event_handler.handle(&ev, buf) {
+        render()?;
+        if let Some(received) = last_event_received.take() {
+            stats::record_latency(Instant::now().duration_since(received));
+        }
+
+        // while something is animating, render a new frame whenever no event arrives in time
+        if let Some(timeout) = animation::take_frame_request() {
+            if !event::poll(timeout)? {
+                continue;
+            }
+        }
+        let ev = read_event()?;
+        last_event_received = Some(Instant::now());
+        let mut ctx = AbletCtx::new(ablet, &render);
+        if let Some(res) = handle(ev, buf, &mut ctx) {
             return Ok(res);
         }
     }
 }
 
+#[cfg(feature = "tokio")]
+mod async_edit;
+#[cfg(feature = "tokio")]
+pub use async_edit::edit_buffer_async;
+
+mod ablet_type;
+pub use ablet_type::{Ablet, MouseFocus, PromptPosition};
+
+mod app_event;
+pub use app_event::{AppEvent, AppEventHandler, EventSender};
+
+mod context;
+pub use context::AbletCtx;
+
+mod prompt;
+pub use prompt::Prompt;
+
+mod clipboard;
+pub use clipboard::copy_to_clipboard;
+
 mod termutils;
-pub use termutils::{with_setup_terminal, SetupError};
+pub use termutils::{set_mouse_capture, with_setup_terminal, SetupError, TerminalGuard};
 
 mod splittree;
-pub use splittree::{Split, SplitContent, SplitSize, SplitTree};
+pub use splittree::{Split, SplitContent, SplitSize, SplitTree, Stack};
 
 mod document;
-pub use document::{Document, DocumentRef};
+pub use document::{undo_file_for, Document, DocumentRef, Normalization};
+
+mod writer;
+pub use writer::LineWriter;
+
+mod history;
+pub use history::UndoGrouping;
+
+mod autosave;
+pub use autosave::{autosave_file_for, autosaved_version, Autosave};
+
+mod animation;
+
+mod throttle;
+pub use throttle::{Debounce, Throttle};
+
+pub mod filters;
+
+pub mod handlers;
+
+mod search;
+pub use search::{SearchHandler, SearchPattern, SearchResult};
+
+#[cfg(feature = "regex")]
+mod highlight;
+#[cfg(feature = "regex")]
+pub use highlight::{HighlightRule, HighlightRules};
+
+mod transcript;
+pub use transcript::{Transcript, TranscriptEntry};
+
+#[cfg(feature = "json")]
+mod json_log;
+#[cfg(feature = "json")]
+pub use json_log::{JsonColumn, JsonLogBuffer};
+
+mod table;
+pub use table::Table;
+
+mod scroll_view;
+pub use scroll_view::{ScrollView, Widget};
+
+mod logger;
+pub use logger::BufferLogger;
+
+#[cfg(feature = "tracing")]
+mod tracing_layer;
+#[cfg(feature = "tracing")]
+pub use tracing_layer::BufferLayer;
+
+mod timer;
+pub use timer::TimerText;
+
+mod dialog;
+mod palette;
+mod preview;
+pub use preview::{Preview, PreviewHandler};
+
+mod tab_completion;
+pub use tab_completion::{Completion, TabCompletion};
+
+mod keymap;
+pub use keymap::{KeyChord, KeyLookup, KeyMap, KeyMapError};
+
+mod line_editor;
+pub use line_editor::{LineAction, LineEditor, SimpleLineHandler, SimpleLineHandlerResult};
+
+mod input_history;
+pub use input_history::InputHistory;
+
+mod completion;
+pub use completion::WordCompleter;
+
+mod killring;
+pub use killring::KillRing;
 
 mod buffer;
-pub use buffer::{Buffer, BufferPosition, BufferRef};
+pub use buffer::{Buffer, BufferPosition, BufferRef, ScrollPastEnd};
+mod text_offset;
+pub use text_offset::TextOffset;
+
+mod registry;
+pub use registry::{BufferRegistry, RegistryError};
+
+mod quickfix;
+pub use quickfix::{Quickfix, QuickfixEntry};
+mod slideshow;
+pub use slideshow::Slideshow;
+
+#[cfg(feature = "serde")]
+mod layout;
+#[cfg(feature = "serde")]
+pub use layout::{Layout, LayoutContent, LayoutSplit};
+
+mod style;
+pub use style::ContentStyleExt;
+mod color;
+pub use color::{detect_color_profile, ColorProfile};
+mod theme;
+pub use theme::{named_style, Theme, ThemedStyle};
 
 mod atext;
-pub use atext::AText;
+pub use atext::{AText, ATextBuilder};
+mod wrap;
+pub use wrap::{wrap, wrap_justified, WrapMode};
+
+#[cfg(feature = "ansi")]
+mod ansi;
+
+#[cfg(feature = "markdown")]
+mod markdown;
+
+mod frame;
+pub use frame::{Cell, Frame};
+
+mod stats;
+pub use stats::{render_stats, reset_render_stats, set_latency_indicator, RenderStats};
+
+mod blink;
+pub use blink::set_blink_fallback;
+mod renderer;
+pub use renderer::{supports_styled_underlines, CrosstermRenderer, FrameRenderer, Renderer};
+
+#[cfg(feature = "debug")]
+mod frame_history;
+#[cfg(feature = "debug")]
+pub use frame_history::{
+    frame_history, inspect_frame_history, set_frame_history_capacity, FrameHistory,
+};
+
+#[cfg(feature = "remote")]
+pub mod remote;
+
+#[cfg(feature = "web")]
+pub mod web;
+
+pub mod prelude;
 
 /// crossterms event module, use this to get inputs
 pub use crossterm::event as ctevent;
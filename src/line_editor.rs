@@ -0,0 +1,670 @@
+//! Contains the LineEditor, a line editor with the bindings of GNU readline
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEvent, KeyModifiers},
+    style::Stylize,
+};
+
+use crate::{
+    AText, AbletCtx, BufferRef, EventHandler, HandlerResult, InputHistory, KeyLookup, KeyMap,
+    KillRing, TextOffset, WordCompleter,
+};
+
+/// A line editor with the bindings of GNU readline, which can be changed via
+/// [LineEditor::keymap_mut]. Submitted lines go to an [InputHistory], which Up and
+/// Down, and C-r for a reverse search, go through. Killed text goes to a [KillRing].
+/// Both can be shared with other editors, see [LineEditor::with_kill_ring] and
+/// [LineEditor::with_history]
+pub struct LineEditor {
+    keymap: KeyMap<LineAction>,
+    kill_ring: KillRing,
+    /// the last handled key was a kill, so the next kill extends its entry
+    last_was_kill: bool,
+    /// the length of the last yanked text, if the last handled key was a yank
+    last_yank_len: Option<usize>,
+    completer: WordCompleter,
+    /// decides whether the input is complete on Enter, see
+    /// [LineEditor::set_input_complete]
+    input_complete: Option<InputCompleteCheck>,
+    /// checks submitted input, see [LineEditor::set_validator]
+    validator: Option<Validator>,
+    /// the error of the validator that the status shows, which the next key clears,
+    /// unless something else was shown meanwhile
+    shown_error: Option<String>,
+    history: InputHistory,
+    /// the history entry that is shown, while going through the history
+    history_pos: Option<usize>,
+    /// the input from before going through the history, which is restored behind
+    /// its newest entry
+    draft: String,
+    search: Option<ReverseSearch>,
+}
+
+/// the former name of the [LineEditor]
+pub type SimpleLineHandler = LineEditor;
+
+type InputCompleteCheck = Box<dyn FnMut(&str) -> bool + Send>;
+type Validator = Box<dyn FnMut(&str) -> Result<(), String> + Send>;
+
+pub enum SimpleLineHandlerResult {
+    LineDone,
+    Abort,
+}
+
+/// the state of a reverse search through the history, started with C-r
+struct ReverseSearch {
+    query: String,
+    /// the history entry that matches the query, which is shown
+    found: Option<usize>,
+    /// the input from before the search, which is restored if it's canceled
+    original: String,
+}
+
+/// What the keys of a [LineEditor] do. Keys that aren't bound insert their char
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineAction {
+    Abort,
+    /// submits the input, unless it's incomplete, see [LineEditor::set_input_complete]
+    Submit,
+    LineStart,
+    LineEnd,
+    KillToLineEnd,
+    KillToLineStart,
+    KillWordBack,
+    KillWordForward,
+    WordBack,
+    WordForward,
+    CompleteNext,
+    CompletePrev,
+    Yank,
+    /// replaces the text that was just yanked with the previous kill ring entry
+    YankPop,
+    DeleteCharBefore,
+    DeleteCharAfter,
+    Left,
+    Right,
+    /// moves the cursor up, or to the previous history entry from the first line
+    Up,
+    /// moves the cursor down, or to the next history entry from the last line
+    Down,
+    HistoryPrev,
+    HistoryNext,
+    /// starts a search through the history, or goes to the next older match
+    ReverseSearch,
+    PageUp,
+    PageDown,
+    /// toggles overwrite mode, see [BufferRef::set_overwrite](crate::BufferRef::set_overwrite),
+    /// and shows OVERWRITE in the status while it's on
+    ToggleOverwrite,
+}
+
+impl LineAction {
+    /// the readline bindings a [LineEditor] starts with. Unlike readline, C-n and C-p
+    /// complete words, M-n and M-p go through the history instead
+    pub fn default_keymap() -> KeyMap<LineAction> {
+        use LineAction::*;
+        let mut keymap = KeyMap::new();
+        for (keys, action) in [
+            ("C-c", Abort),
+            ("Enter", Submit),
+            ("C-a", LineStart),
+            ("Home", LineStart),
+            ("C-e", LineEnd),
+            ("End", LineEnd),
+            ("C-k", KillToLineEnd),
+            ("C-u", KillToLineStart),
+            ("C-w", KillWordBack),
+            ("M-Backspace", KillWordBack),
+            ("M-d", KillWordForward),
+            ("M-b", WordBack),
+            ("C-Left", WordBack),
+            ("M-f", WordForward),
+            ("C-Right", WordForward),
+            ("C-n", CompleteNext),
+            ("C-p", CompletePrev),
+            ("C-y", Yank),
+            ("M-y", YankPop),
+            ("Backspace", DeleteCharBefore),
+            ("C-h", DeleteCharBefore),
+            ("Delete", DeleteCharAfter),
+            ("C-d", DeleteCharAfter),
+            ("Left", Left),
+            ("C-b", Left),
+            ("Right", Right),
+            ("C-f", Right),
+            ("Up", Up),
+            ("Down", Down),
+            ("M-p", HistoryPrev),
+            ("M-n", HistoryNext),
+            ("C-r", ReverseSearch),
+            ("PageUp", PageUp),
+            ("PageDown", PageDown),
+            ("Insert", ToggleOverwrite),
+        ] {
+            keymap
+                .bind(keys, action)
+                .expect("the default bindings are valid");
+        }
+        keymap
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self {
+            keymap: LineAction::default_keymap(),
+            kill_ring: KillRing::default(),
+            last_was_kill: false,
+            last_yank_len: None,
+            completer: WordCompleter::default(),
+            input_complete: None,
+            validator: None,
+            shown_error: None,
+            history: InputHistory::default(),
+            history_pos: None,
+            draft: String::new(),
+            search: None,
+        }
+    }
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the bindings of the keys, which start as [LineAction::default_keymap]
+    pub fn keymap_mut(&mut self) -> &mut KeyMap<LineAction> {
+        &mut self.keymap
+    }
+
+    pub fn with_kill_ring(kill_ring: KillRing) -> Self {
+        Self {
+            kill_ring,
+            ..Self::default()
+        }
+    }
+
+    pub fn kill_ring(&self) -> &KillRing {
+        &self.kill_ring
+    }
+
+    pub fn with_history(history: InputHistory) -> Self {
+        Self {
+            history,
+            ..Self::default()
+        }
+    }
+
+    pub fn history(&self) -> &InputHistory {
+        &self.history
+    }
+
+    /// Sets a check that is run on Enter, with the whole input. If it returns false,
+    /// the input is continued on a new line instead of being submitted, which is
+    /// what REPLs do for unbalanced parentheses or a missing `;`
+    pub fn set_input_complete(&mut self, f: impl FnMut(&str) -> bool + Send + 'static) {
+        self.input_complete = Some(Box::new(f));
+    }
+
+    /// Sets a check that is run on Enter, after the one of [LineEditor::set_input_complete].
+    /// If it returns an error, the input isn't submitted, and the error is shown in the
+    /// status of the [Ablet](crate::Ablet) until the next key is pressed, see
+    /// [Ablet::set_status](crate::Ablet::set_status) for where it's shown
+    pub fn set_validator(&mut self, f: impl FnMut(&str) -> Result<(), String> + Send + 'static) {
+        self.validator = Some(Box::new(f));
+    }
+
+    /// sets the completer used for C-n and C-p, e.g. one with a dictionary
+    pub fn set_completer(&mut self, completer: WordCompleter) {
+        self.completer = completer;
+    }
+
+    /// kills the range, and extends the newest kill ring entry if the previous key
+    /// was a kill as well
+    fn kill(
+        &mut self,
+        buf: &BufferRef,
+        range: std::ops::Range<TextOffset>,
+        backwards: bool,
+        extend: bool,
+    ) {
+        let killed = buf.delete_range(range);
+        // like the history, the kill ring doesn't get masked input, like passwords
+        if buf.mask().is_some() {
+            return;
+        }
+        // nothing was killed, e.g. by C-k at the end of the input, so the ring is
+        // left as it is, and a kill before still continues
+        if killed.is_empty() {
+            self.last_was_kill = extend;
+            return;
+        }
+        self.last_was_kill = true;
+        if extend {
+            self.kill_ring.extend_last(killed, backwards);
+        } else {
+            self.kill_ring.push(killed);
+        }
+    }
+
+    fn yank(&mut self, buf: &BufferRef, text: Option<AText>) {
+        if let Some(text) = text {
+            self.last_yank_len = Some(text.len());
+            buf.insert_text_at_cursor(text);
+        }
+    }
+
+    fn history_prev(&mut self, buf: &BufferRef) {
+        let pos = self.history_pos.unwrap_or(self.history.len());
+        let Some(entry) = pos.checked_sub(1).and_then(|i| self.history.get(i)) else {
+            return;
+        };
+        if self.history_pos.is_none() {
+            self.draft = input(buf);
+        }
+        self.history_pos = Some(pos - 1);
+        set_input(buf, entry);
+    }
+
+    fn history_next(&mut self, buf: &BufferRef) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+        match self.history.get(pos + 1) {
+            Some(entry) => {
+                self.history_pos = Some(pos + 1);
+                set_input(buf, entry);
+            }
+            None => {
+                self.history_pos = None;
+                set_input(buf, std::mem::take(&mut self.draft));
+            }
+        }
+    }
+
+    /// Handles a key during a reverse search. Returns false if the key ends the
+    /// search, and should be handled as usual
+    fn handle_search_key(&mut self, ke: &KeyEvent, buf: &BufferRef, ctx: &mut AbletCtx) -> bool {
+        let Some(search) = &mut self.search else {
+            return false;
+        };
+        let n_entries = self.history.len();
+        // searches from the shown match on, so it stays if it still matches
+        let mut search_from = search.found.map_or(n_entries, |i| i + 1);
+        let is_char = ke.modifiers.difference(KeyModifiers::SHIFT).is_empty();
+        match ke.code {
+            KeyCode::Char(c) if is_char => search.query.push(c),
+            KeyCode::Backspace => {
+                search.query.pop();
+                search_from = n_entries;
+            }
+            KeyCode::Esc => {
+                set_input(buf, std::mem::take(&mut search.original));
+                self.end_search(ctx);
+                return true;
+            }
+            KeyCode::Char('g') if ke.modifiers == KeyModifiers::CONTROL => {
+                set_input(buf, std::mem::take(&mut search.original));
+                self.end_search(ctx);
+                return true;
+            }
+            _ => match self.keymap.lookup(ke) {
+                KeyLookup::Action(LineAction::ReverseSearch) => {
+                    search_from = search.found.unwrap_or(n_entries);
+                }
+                _ => {
+                    self.history_pos = search.found;
+                    self.end_search(ctx);
+                    return false;
+                }
+            },
+        }
+
+        let found = self.history.find_back(&search.query, search_from);
+        if let Some(i) = found {
+            search.found = Some(i);
+            set_input(buf, self.history.get(i).unwrap_or_default());
+        }
+        let failing = if found.is_none() { "failing " } else { "" };
+        ctx.set_status(format!("({failing}reverse-i-search)`{}'", search.query));
+        true
+    }
+
+    fn end_search(&mut self, ctx: &mut AbletCtx) {
+        self.search = None;
+        ctx.set_status("");
+    }
+}
+
+impl EventHandler<SimpleLineHandlerResult> for LineEditor {
+    fn handle(
+        &mut self,
+        ev: &Event,
+        buf: &BufferRef,
+        ctx: &mut AbletCtx,
+    ) -> HandlerResult<SimpleLineHandlerResult> {
+        let last_was_kill = std::mem::take(&mut self.last_was_kill);
+        let last_yank_len = self.last_yank_len.take();
+        let ke = match ev {
+            Event::Key(ke) => ke,
+            Event::Paste(text) => {
+                buf.insert_text_at_cursor(text.as_str());
+                return HandlerResult::Handled;
+            }
+            _ => return HandlerResult::Ignored,
+        };
+        if let Some(error) = self.shown_error.take() {
+            if ctx.status().is_some_and(|status| status.text == error) {
+                ctx.set_status("");
+            }
+        }
+        if self.handle_search_key(ke, buf, ctx) {
+            return HandlerResult::Handled;
+        }
+
+        let action = match self.keymap.lookup(ke) {
+            KeyLookup::Action(action) => action,
+            KeyLookup::Pending => return HandlerResult::Handled,
+            KeyLookup::Unbound => {
+                self.completer.reset();
+                if let KeyCode::Char(c) = ke.code {
+                    buf.insert_char_at_cursor(c);
+                    return HandlerResult::Handled;
+                }
+                return HandlerResult::Ignored;
+            }
+        };
+        if !matches!(action, LineAction::CompleteNext | LineAction::CompletePrev) {
+            self.completer.reset();
+        }
+
+        match action {
+            LineAction::Abort => return HandlerResult::Done(SimpleLineHandlerResult::Abort),
+            LineAction::LineStart => buf.move_cursor_to_line_start(),
+            LineAction::LineEnd => buf.move_cursor_to_line_end(),
+            LineAction::KillToLineEnd => {
+                let start = buf.cursor();
+                buf.move_cursor_to_line_end();
+                let mut end = buf.cursor();
+                if start == end {
+                    // at the end of a line, kill the line break
+                    end = end.forward(1, &input(buf));
+                }
+                self.kill(buf, start..end, false, last_was_kill);
+            }
+            LineAction::KillToLineStart => {
+                let end = buf.cursor();
+                buf.move_cursor_to_line_start();
+                self.kill(buf, buf.cursor()..end, true, last_was_kill);
+            }
+            LineAction::KillWordBack => {
+                let end = buf.cursor();
+                let start = TextOffset::new(prev_word_start(&input(buf), end.get()));
+                self.kill(buf, start..end, true, last_was_kill);
+            }
+            LineAction::KillWordForward => {
+                let start = buf.cursor();
+                let end = TextOffset::new(next_word_end(&input(buf), start.get()));
+                self.kill(buf, start..end, false, last_was_kill);
+            }
+            LineAction::WordBack => {
+                let start = prev_word_start(&input(buf), buf.cursor().get());
+                buf.set_cursor(TextOffset::new(start))
+            }
+            LineAction::WordForward => {
+                let end = next_word_end(&input(buf), buf.cursor().get());
+                buf.set_cursor(TextOffset::new(end))
+            }
+            LineAction::CompleteNext => {
+                self.completer.complete(buf, true);
+            }
+            LineAction::CompletePrev => {
+                self.completer.complete(buf, false);
+            }
+            LineAction::Yank => self.yank(buf, self.kill_ring.yank()),
+            LineAction::YankPop => {
+                // only cycles directly after a yank, like in emacs
+                if let Some(len) = last_yank_len {
+                    let end = buf.cursor();
+                    buf.delete_range(end.back(len, &input(buf))..end);
+                    self.yank(buf, self.kill_ring.yank_pop());
+                }
+            }
+            LineAction::DeleteCharBefore => buf.delete_char_before_cursor(),
+            LineAction::DeleteCharAfter => {
+                let pos = buf.cursor();
+                buf.move_cursor_by(1);
+                if buf.cursor() > pos {
+                    buf.delete_char_before_cursor();
+                }
+            }
+            LineAction::Left => buf.move_cursor_by(-1),
+            LineAction::Right => buf.move_cursor_by(1),
+            LineAction::Up => {
+                if input(buf)[..buf.cursor().get()].contains('\n') {
+                    buf.move_cursor_up(1);
+                } else {
+                    self.history_prev(buf);
+                }
+            }
+            LineAction::Down => {
+                if input(buf)[buf.cursor().get()..].contains('\n') {
+                    buf.move_cursor_down(1);
+                } else {
+                    self.history_next(buf);
+                }
+            }
+            LineAction::HistoryPrev => self.history_prev(buf),
+            LineAction::HistoryNext => self.history_next(buf),
+            LineAction::ReverseSearch => {
+                self.search = Some(ReverseSearch {
+                    query: String::new(),
+                    found: None,
+                    original: input(buf),
+                });
+                ctx.set_status("(reverse-i-search)`'");
+            }
+            LineAction::PageUp => buf.page_up(),
+            LineAction::PageDown => buf.page_down(),
+            LineAction::ToggleOverwrite => {
+                let overwrite = !buf.overwrite();
+                buf.set_overwrite(overwrite);
+                ctx.set_status(if overwrite { "OVERWRITE" } else { "" });
+            }
+            LineAction::Submit => {
+                let input = input(buf);
+                let is_complete = self.input_complete.as_mut().is_none_or(|f| f(&input));
+                if is_complete {
+                    if let Some(Err(e)) = self.validator.as_mut().map(|f| f(&input)) {
+                        ctx.set_status(e.clone().red());
+                        self.shown_error = Some(e);
+                        return HandlerResult::Handled;
+                    }
+                    // masked input, like passwords, isn't recorded
+                    if buf.mask().is_none() {
+                        self.history.push(input);
+                    }
+                    self.history_pos = None;
+                    return HandlerResult::Done(SimpleLineHandlerResult::LineDone);
+                }
+                buf.insert_char_at_cursor('\n');
+            }
+        }
+        HandlerResult::Handled
+    }
+}
+
+fn input(buf: &BufferRef) -> String {
+    buf.get_doc().0.lock().unwrap().content.text.clone()
+}
+
+/// replaces the input, and puts the cursor behind it
+fn set_input(buf: &BufferRef, text: String) {
+    buf.delete_range(TextOffset::ZERO..TextOffset::end_of(&input(buf)));
+    buf.insert_text_at_cursor(text);
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// the start of the word before the position, like readline's backward-word
+fn prev_word_start(text: &str, pos: usize) -> usize {
+    let mut start = pos;
+    let mut in_word = false;
+    for (i, c) in text[..pos].char_indices().rev() {
+        if is_word_char(c) {
+            in_word = true;
+        } else if in_word {
+            break;
+        }
+        start = i;
+    }
+    start
+}
+
+/// the end of the word behind the position, like readline's forward-word
+fn next_word_end(text: &str, pos: usize) -> usize {
+    let mut in_word = false;
+    for (i, c) in text[pos..].char_indices() {
+        if is_word_char(c) {
+            in_word = true;
+        } else if in_word {
+            return pos + i;
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, TextOffset};
+
+    fn press(editor: &mut LineEditor, buf: &BufferRef, keys: &str) {
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        for key in keys.split(' ') {
+            let chord = crate::KeyChord::parse(key).unwrap();
+            let ev = Event::Key(KeyEvent::new(chord.code, chord.modifiers));
+            editor.handle(&ev, buf, &mut ctx);
+        }
+    }
+
+    #[test]
+    fn test_history_and_words() {
+        let mut editor = LineEditor::new();
+        let buf = Buffer::new().into_ref();
+        for line in ["cargo build", "cargo test", "ls"] {
+            set_input(&buf, line.into());
+            press(&mut editor, &buf, "Enter");
+        }
+        assert_eq!(
+            editor.history().entries(),
+            ["cargo build", "cargo test", "ls"]
+        );
+
+        set_input(&buf, "draft".into());
+        press(&mut editor, &buf, "Up Up");
+        assert_eq!(input(&buf), "cargo test");
+        press(&mut editor, &buf, "Down Down");
+        assert_eq!(input(&buf), "draft");
+
+        press(&mut editor, &buf, "C-r b u");
+        assert_eq!(input(&buf), "cargo build");
+        press(&mut editor, &buf, "Esc");
+        assert_eq!(input(&buf), "draft");
+        press(&mut editor, &buf, "C-r c C-r C-e");
+        assert_eq!(input(&buf), "cargo build");
+
+        press(&mut editor, &buf, "M-b C-w");
+        assert_eq!(input(&buf), "build");
+        press(&mut editor, &buf, "M-f C-y");
+        assert_eq!(input(&buf), "buildcargo ");
+    }
+
+    #[test]
+    fn test_invalid_input_isnt_submitted() {
+        let mut editor = LineEditor::new();
+        editor.set_validator(|input| match input.parse::<u16>() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("not a port: {e}")),
+        });
+        let buf = Buffer::new().into_ref();
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let enter = Event::Key(KeyEvent::from(KeyCode::Enter));
+
+        set_input(&buf, "80a".into());
+        assert!(editor.handle(&enter, &buf, &mut ctx).done().is_none());
+        assert_eq!(input(&buf), "80a");
+        press(&mut editor, &buf, "Backspace");
+        assert!(matches!(
+            editor.handle(&enter, &buf, &mut ctx),
+            HandlerResult::Done(SimpleLineHandlerResult::LineDone)
+        ));
+        assert_eq!(editor.history().entries(), ["80"]);
+    }
+
+    #[test]
+    fn test_overwrite_mode_only_replaces_typed_chars() {
+        let mut editor = LineEditor::new();
+        let buf = Buffer::new().into_ref();
+        set_input(&buf, "foo fo".into());
+        press(&mut editor, &buf, "Insert C-a x");
+        assert!(buf.overwrite());
+        assert_eq!(input(&buf), "xoo fo");
+        press(&mut editor, &buf, "C-k C-y C-y");
+        assert_eq!(input(&buf), "xoo fooo fo");
+
+        set_input(&buf, "foobar f baz".into());
+        buf.set_cursor(TextOffset::new(8));
+        press(&mut editor, &buf, "C-n");
+        assert_eq!(input(&buf), "foobar foobar baz");
+    }
+
+    #[test]
+    fn test_only_the_validators_error_is_cleared() {
+        let mut editor = LineEditor::new();
+        editor.set_validator(|_| Err("invalid".into()));
+        let buf = Buffer::new().into_ref();
+        let ablet = crate::Ablet::new(crate::split_tree!(Horizontal: { 1: buf }));
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(Some(&ablet), &render);
+        let enter = Event::Key(KeyEvent::from(KeyCode::Enter));
+        let a = Event::Key(KeyEvent::from(KeyCode::Char('a')));
+
+        editor.handle(&enter, &buf, &mut ctx);
+        assert_eq!(ablet.status().text, "invalid");
+        editor.handle(&a, &buf, &mut ctx);
+        assert_eq!(ablet.status().text, "");
+
+        editor.handle(&enter, &buf, &mut ctx);
+        ablet.set_status("saved");
+        editor.handle(&a, &buf, &mut ctx);
+        assert_eq!(ablet.status().text, "saved");
+    }
+
+    #[test]
+    fn test_empty_kills_arent_pushed() {
+        let mut editor = LineEditor::new();
+        let buf = Buffer::new().into_ref();
+        set_input(&buf, "foo bar".into());
+        press(&mut editor, &buf, "C-w C-k C-w");
+        assert_eq!(editor.kill_ring().len(), 1);
+        press(&mut editor, &buf, "C-y");
+        assert_eq!(input(&buf), "foo bar");
+    }
+
+    #[test]
+    fn test_masked_input_isnt_killed_to_the_ring() {
+        let mut editor = LineEditor::new();
+        let buf = Buffer::new().into_ref();
+        buf.set_mask(Some('*'));
+        set_input(&buf, "secret pass".into());
+        press(&mut editor, &buf, "C-w C-u");
+        assert_eq!(input(&buf), "");
+        assert!(editor.kill_ring().is_empty());
+    }
+}
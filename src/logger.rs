@@ -0,0 +1,105 @@
+//! Contains the BufferLogger, a backend for the log crate that writes into a buffer
+
+use crossterm::style::{ContentStyle, StyledContent, Stylize};
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::{AText, BufferRef};
+
+/// Writes log records into a buffer, one line per record, like
+/// `WARN  app::net: connection lost`, with the level colored. The buffer scrolls
+/// down as lines are added.
+///
+/// Records must not be logged while the buffer is locked, e.g. from a custom
+/// renderer that draws it, since that would dead lock.
+pub struct BufferLogger {
+    buffer: BufferRef,
+    level: LevelFilter,
+}
+
+impl BufferLogger {
+    /// logs records of level Info and above
+    pub fn new(buffer: BufferRef) -> Self {
+        Self {
+            buffer,
+            level: LevelFilter::Info,
+        }
+    }
+
+    pub fn with_level(self, level: LevelFilter) -> Self {
+        Self { level, ..self }
+    }
+
+    /// installs the logger as the global logger, which can only be done once
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        log::set_logger(Box::leak(Box::new(self)))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    fn format(record: &Record) -> AText {
+        AText::from(styled_level(
+            record.level(),
+            format!("{:<5}", record.level()),
+        )) + " "
+            + record.target().dark_grey()
+            + ": "
+            + record.args().to_string()
+    }
+}
+
+impl Log for BufferLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.buffer.add_line(Self::format(record));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// the text, colored according to the level
+pub(crate) fn styled_level(level: Level, text: String) -> StyledContent<String> {
+    let style = ContentStyle::new();
+    let style = match level {
+        Level::Error => style.red().bold(),
+        Level::Warn => style.yellow(),
+        Level::Info => style.green(),
+        Level::Debug => style.blue(),
+        Level::Trace => style.dark_grey(),
+    };
+    style.apply(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Color;
+
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn test_records_are_formatted() {
+        let buf = Buffer::new().into_ref();
+        let logger = BufferLogger::new(buf.clone());
+        let record = |level| {
+            Record::builder()
+                .level(level)
+                .target("app::net")
+                .args(format_args!("connection lost"))
+                .build()
+        };
+        logger.log(&record(Level::Warn));
+        logger.log(&record(Level::Debug));
+
+        let doc = buf.get_doc();
+        let doc = doc.0.lock().unwrap();
+        assert_eq!(doc.content.text, "WARN  app::net: connection lost\n");
+        let (_, style) = doc.content.chars_styled().next().unwrap();
+        assert_eq!(style.unwrap().style.foreground_color, Some(Color::Yellow));
+    }
+}
@@ -0,0 +1,205 @@
+//! Conversion of Markdown to styled text. Only available with the `markdown` feature.
+
+use crossterm::style::{ContentStyle, Stylize};
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+
+use crate::AText;
+
+impl AText {
+    /// Converts Markdown to styled text: headings are bold, emphasis is italic,
+    /// inline code and code blocks are colored, lists get bullets or numbers,
+    /// and quotes are marked with a bar. Things that don't work in a terminal,
+    /// like images and html, are rendered as their text.
+    pub fn from_markdown(markdown: &str) -> AText {
+        let mut writer = Writer::default();
+        for event in Parser::new(markdown) {
+            writer.handle(event);
+        }
+        writer.res
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    res: AText,
+    /// the style of the innermost inline element
+    styles: Vec<ContentStyle>,
+    /// the next number of each open list, None for bullet lists
+    lists: Vec<Option<u64>>,
+    quote_depth: usize,
+    /// newlines that are written before the next text, so there are none at the end
+    pending_newlines: usize,
+    at_line_start: bool,
+    /// the marker of a list item was just written, so its first block must not
+    /// start a new line
+    item_started: bool,
+}
+
+impl Writer {
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => self.text(&text),
+            Event::Code(code) => {
+                self.push_style(|s| s.dark_yellow());
+                self.text(&code);
+                self.styles.pop();
+            }
+            Event::SoftBreak => self.text(" "),
+            Event::HardBreak => self.pending_newlines += 1,
+            Event::Rule => {
+                self.block();
+                self.push_style(|s| s.dark_grey());
+                self.text(&"\u{2500}".repeat(20));
+                self.styles.pop();
+            }
+            Event::TaskListMarker(done) => self.text(if done { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.block(),
+            Tag::Heading { level, .. } => {
+                self.block();
+                self.push_style(|s| match level {
+                    HeadingLevel::H1 => s.bold().underlined().dark_cyan(),
+                    _ => s.bold().dark_cyan(),
+                });
+            }
+            Tag::BlockQuote(_) => {
+                self.block();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.block();
+                self.push_style(|s| s.dark_yellow());
+            }
+            Tag::List(start) => {
+                if self.lists.is_empty() {
+                    self.block();
+                } else {
+                    self.line_break();
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.line_break();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "\u{2022} ".to_string(),
+                };
+                // the marker replaces the indentation of the innermost list
+                self.flush_newlines();
+                let prefix = self.line_prefix();
+                let prefix = prefix.strip_suffix("  ").unwrap_or(&prefix);
+                self.res.append_text(prefix.to_string() + &marker);
+                self.at_line_start = false;
+                self.item_started = true;
+            }
+            Tag::Emphasis => self.push_style(|s| s.italic()),
+            Tag::Strong => self.push_style(|s| s.bold()),
+            Tag::Strikethrough => self.push_style(|s| s.crossed_out()),
+            Tag::Link { .. } => self.push_style(|s| s.underlined().dark_blue()),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(_)
+            | TagEnd::CodeBlock
+            | TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::Strikethrough
+            | TagEnd::Link => {
+                self.styles.pop();
+            }
+            TagEnd::BlockQuote(_) => self.quote_depth -= 1,
+            TagEnd::List(_) => {
+                self.lists.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn push_style(&mut self, f: impl FnOnce(ContentStyle) -> ContentStyle) {
+        let current = self.styles.last().copied().unwrap_or_default();
+        self.styles.push(f(current));
+    }
+
+    /// starts a new block, separated by a blank line from the previous one
+    fn block(&mut self) {
+        if !std::mem::take(&mut self.item_started) && !self.res.is_empty() {
+            self.pending_newlines = self.pending_newlines.max(2);
+        }
+    }
+
+    fn line_break(&mut self) {
+        if !std::mem::take(&mut self.item_started) && !self.res.is_empty() {
+            self.pending_newlines = self.pending_newlines.max(1);
+        }
+    }
+
+    /// the quote bars and list indentation at the start of each line
+    fn line_prefix(&self) -> String {
+        "\u{2502} ".repeat(self.quote_depth) + &"  ".repeat(self.lists.len())
+    }
+
+    fn flush_newlines(&mut self) {
+        for _ in 0..std::mem::take(&mut self.pending_newlines) {
+            self.res.push_char('\n');
+            self.at_line_start = true;
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let style = self.styles.last().copied().map(Into::into);
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.pending_newlines += 1;
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            self.flush_newlines();
+            if std::mem::take(&mut self.at_line_start) {
+                self.res.append_text(self.line_prefix());
+            }
+            self.item_started = false;
+            for c in line.chars() {
+                self.res.push_char_formatted(c, style);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_markdown() {
+        let text = AText::from_markdown(
+            "# Title\n\nSome *fancy* `code`.\n\n- one\n- two\n  1. nested\n\n> quoted\n> text\n\n```\nfn main() {}\n```\n",
+        );
+        assert_eq!(
+            text.as_str(),
+            "Title\n\nSome fancy code.\n\n\u{2022} one\n\u{2022} two\n  1. nested\n\n\u{2502} quoted text\n\nfn main() {}"
+        );
+
+        let style_at = |i: usize| text.style_map[i].map(|si| text.styles[si].style);
+        assert_eq!(
+            style_at(0),
+            Some(ContentStyle::new().bold().underlined().dark_cyan())
+        );
+        assert_eq!(style_at(12), Some(ContentStyle::new().italic()));
+        assert_eq!(style_at(18), Some(ContentStyle::new().dark_yellow()));
+    }
+}
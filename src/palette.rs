@@ -0,0 +1,261 @@
+//! Contains the command palette, see [Ablet::open_palette](crate::Ablet::open_palette)
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEventKind, KeyModifiers},
+    style::{ContentStyle, Stylize},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    dialog::draw_box, AbletCtx, BufferPosition, BufferRef, ContentStyleExt, EventHandler, Frame,
+    HandlerResult, Rect, Shared,
+};
+
+/// the most commands that are shown at once, the list scrolls to the selected one
+const MAX_SHOWN: usize = 10;
+const MAX_WIDTH: usize = 60;
+const MIN_WIDTH: usize = 20;
+
+pub(crate) struct Palette {
+    commands: Vec<String>,
+    query: String,
+    /// the commands that match the query, best first, with the byte offsets of the
+    /// matched chars
+    matches: Vec<(usize, Vec<usize>)>,
+    /// the index into `matches`
+    selected: usize,
+}
+
+impl Palette {
+    pub(crate) fn new(commands: &[&str]) -> Self {
+        let mut res = Self {
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            query: String::new(),
+            matches: vec![],
+            selected: 0,
+        };
+        res.update_matches();
+        res
+    }
+
+    fn update_matches(&mut self) {
+        let mut scored: Vec<_> = self
+            .commands
+            .iter()
+            .enumerate()
+            .filter_map(|(i, command)| {
+                let (score, positions) = fuzzy_match(&self.query, command)?;
+                Some((score, i, positions))
+            })
+            .collect();
+        // the sort is stable, so equally good matches keep their order
+        scored.sort_by_key(|(score, ..)| std::cmp::Reverse(*score));
+        self.matches = scored.into_iter().map(|(_, i, pos)| (i, pos)).collect();
+        self.selected = 0;
+    }
+
+    /// Draws the query and the matching commands in a box at the top of the frame,
+    /// with the matched chars highlighted
+    pub(crate) fn draw(&self, frame: &mut Frame) {
+        let size = frame.size();
+        if size.h < 4 || size.w < 5 {
+            return;
+        }
+        let inner_w = self
+            .commands
+            .iter()
+            .map(|c| c.width())
+            .max()
+            .unwrap_or(0)
+            .clamp(MIN_WIDTH, MAX_WIDTH)
+            .min(size.w as usize - 4);
+        let n_shown = self.matches.len().min(MAX_SHOWN).min(size.h as usize - 3);
+        let box_w = inner_w as u16 + 4;
+        let box_h = n_shown as u16 + 3;
+        let top = (size.h - box_h) / 4;
+        let left = (size.w - box_w) / 2;
+        let inner_right = left + box_w - 2;
+        draw_box(frame, Rect::new(top, left, box_w, box_h), "");
+
+        let style = ContentStyle::default();
+        let col = frame.print(
+            BufferPosition::new(top + 1, left + 2),
+            "> ",
+            style.dark_grey(),
+            inner_right,
+        );
+        let col = frame.print(
+            BufferPosition::new(top + 1, col),
+            &self.query,
+            style,
+            inner_right,
+        );
+        frame.print(
+            BufferPosition::new(top + 1, col),
+            " ",
+            style.reverse(),
+            inner_right,
+        );
+
+        let first = self.selected.saturating_sub(n_shown.saturating_sub(1));
+        let rows = (top + 2..).zip(first..first + n_shown);
+        for (row, i) in rows {
+            let (command, positions) = (&self.commands[self.matches[i].0], &self.matches[i].1);
+            let base = if i == self.selected {
+                style.reverse()
+            } else {
+                style
+            };
+            let blank = " ".repeat(inner_w + 2);
+            frame.print(
+                BufferPosition::new(row, left + 1),
+                &blank,
+                base,
+                inner_right + 1,
+            );
+            let mut col = left + 2;
+            for (start, g) in command.grapheme_indices(true) {
+                let matched = positions
+                    .iter()
+                    .any(|p| (start..start + g.len()).contains(p));
+                let style = if matched {
+                    base.merge(ContentStyle::new().yellow().bold())
+                } else {
+                    base
+                };
+                col = frame.print(BufferPosition::new(row, col), g, style, inner_right);
+            }
+        }
+    }
+}
+
+/// Edits the query of the shown palette and moves the selection with Up/Down, C-p/C-n
+/// and Tab. Returns the index of the selected command on Enter, or None on Esc
+pub(crate) struct PaletteHandler(pub(crate) Shared<Option<Palette>>);
+
+impl EventHandler<Option<usize>> for PaletteHandler {
+    fn handle(
+        &mut self,
+        ev: &Event,
+        _buf: &BufferRef,
+        _ctx: &mut AbletCtx,
+    ) -> HandlerResult<Option<usize>> {
+        let Event::Key(ke) = ev else {
+            return HandlerResult::Ignored;
+        };
+        if ke.kind == KeyEventKind::Release {
+            return HandlerResult::Ignored;
+        }
+        let mut palette = self.0.lock().unwrap();
+        let Some(palette) = palette.as_mut() else {
+            return HandlerResult::Ignored;
+        };
+        let ctrl = ke.modifiers.contains(KeyModifiers::CONTROL);
+        let n = palette.matches.len();
+        match ke.code {
+            KeyCode::Esc => return HandlerResult::Done(None),
+            KeyCode::Char('c') if ctrl => return HandlerResult::Done(None),
+            KeyCode::Enter if n > 0 => {
+                return HandlerResult::Done(Some(palette.matches[palette.selected].0))
+            }
+            KeyCode::Up | KeyCode::BackTab => palette.selected = palette.selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => palette.selected = palette.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab => {
+                palette.selected = (palette.selected + 1).min(n.saturating_sub(1))
+            }
+            KeyCode::Char('n') if ctrl => {
+                palette.selected = (palette.selected + 1).min(n.saturating_sub(1))
+            }
+            KeyCode::Backspace => {
+                palette.query.pop();
+                palette.update_matches();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                palette.query.push(c);
+                palette.update_matches();
+            }
+            _ => return HandlerResult::Ignored,
+        }
+        HandlerResult::Handled
+    }
+}
+
+/// Matches the chars of the query, ignoring case, in order against the candidate.
+/// Returns a score, which is higher for matches that are consecutive or at the start
+/// of words, and the byte offsets of the matched chars in the candidate
+fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let mut positions = vec![];
+    let mut score = 0;
+    let mut chars = candidate.char_indices();
+    let mut prev: Option<(usize, char)> = None;
+    for q in query.chars().flat_map(char::to_lowercase) {
+        loop {
+            let (i, c) = chars.next()?;
+            let word_start = prev.is_none_or(|(_, p)| !p.is_alphanumeric())
+                || prev.is_some_and(|(_, p)| p.is_lowercase() && c.is_uppercase());
+            let consecutive = prev.is_some_and(|(p, _)| positions.last() == Some(&p));
+            prev = Some((i, c));
+            if c.to_lowercase().eq(std::iter::once(q)) {
+                score += 1 + if consecutive { 5 } else { 0 } + if word_start { 3 } else { 0 };
+                positions.push(i);
+                break;
+            }
+        }
+    }
+    // shorter candidates win ties
+    Some((score * 100 - candidate.len() as i64, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEvent;
+
+    use super::*;
+    use crate::{shared, Buffer};
+
+    #[test]
+    fn test_palette() {
+        assert_eq!(
+            fuzzy_match("oF", "Open File").map(|m| m.1),
+            Some(vec![0, 5])
+        );
+        assert_eq!(fuzzy_match("fo", "Open File"), None);
+
+        let commands = [
+            "Close Buffer",
+            "Open File",
+            "Open Folder",
+            "Format Document",
+        ];
+        let palette = shared(Some(Palette::new(&commands)));
+        let mut handler = PaletteHandler(palette.clone());
+        let buf = Buffer::new().into_ref();
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let mut press = |code| handler.handle(&Event::Key(KeyEvent::from(code)), &buf, &mut ctx);
+
+        for c in "of".chars() {
+            press(KeyCode::Char(c));
+        }
+        let matched = |palette: &Shared<Option<Palette>>| {
+            let palette = palette.lock().unwrap();
+            let palette = palette.as_ref().unwrap();
+            palette
+                .matches
+                .iter()
+                .map(|(i, _)| commands[*i])
+                .collect::<Vec<_>>()
+        };
+        // matches at the start of words are better, and shorter commands win ties
+        assert_eq!(
+            matched(&palette),
+            ["Open File", "Open Folder", "Close Buffer"]
+        );
+        assert_eq!(press(KeyCode::Down), HandlerResult::Handled);
+        assert_eq!(press(KeyCode::Enter), HandlerResult::Done(Some(2)));
+        press(KeyCode::Backspace);
+        assert_eq!(matched(&palette).len(), 4);
+        assert_eq!(press(KeyCode::Esc), HandlerResult::Done(None));
+    }
+}
@@ -0,0 +1,10 @@
+//! The types most apps need, to import them at once with `use ablet::prelude::*;`
+
+pub use crossterm::style::{ContentStyle, Stylize};
+
+pub use crate::{
+    handlers::{self, HandlerStack},
+    split_tree, with_setup_terminal, AText, ATextBuilder, Ablet, AbletCtx, AppEvent, Buffer,
+    BufferRef, ContentStyleExt, Document, DocumentRef, EventHandler, HandlerResult, LineEditor,
+    SimpleLineHandler, SimpleLineHandlerResult, SplitTree,
+};
@@ -0,0 +1,151 @@
+//! Contains the Preview, which shows the item that is selected in a list in another
+//! buffer, like the file preview of a file picker
+
+use std::{io, sync::Arc, time::Duration};
+
+use crossterm::event::Event;
+use log::error;
+
+use crate::{AText, AbletCtx, BufferRef, Debounce, EventHandler, HandlerResult};
+
+/// how long the selection has to stay the same before the preview is loaded
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+type Loader<K> = Arc<dyn Fn(&K) -> AText + Send + Sync>;
+type RenderFn = Arc<dyn Fn() -> io::Result<()> + Send + Sync>;
+
+/// Loads the content for the selected item into a preview buffer. Loading happens on
+/// another thread, once the selection didn't change for a moment, so scrolling quickly
+/// through a list doesn't load every item on the way
+pub struct Preview<K> {
+    target: BufferRef,
+    load: Loader<K>,
+    render: RenderFn,
+    debounce: Debounce,
+    selected: Option<K>,
+}
+
+impl<K: PartialEq + Clone + Send + 'static> Preview<K> {
+    /// `load` creates the content for an item, and `render` is called after it was
+    /// put into `target`
+    pub fn new(
+        target: BufferRef,
+        load: impl Fn(&K) -> AText + Send + Sync + 'static,
+        render: impl Fn() -> io::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            target,
+            load: Arc::new(load),
+            render: Arc::new(render),
+            debounce: Debounce::new(DEFAULT_DELAY),
+            selected: None,
+        }
+    }
+
+    /// sets how long the selection has to stay the same before the preview is loaded
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.debounce = Debounce::new(delay);
+        self
+    }
+
+    pub fn target(&self) -> &BufferRef {
+        &self.target
+    }
+
+    /// Shows the item in the preview, after the delay, unless another one is selected
+    /// before. Selecting the shown item again does nothing
+    pub fn select(&mut self, item: K) {
+        if self.selected.as_ref() == Some(&item) {
+            return;
+        }
+        self.selected = Some(item.clone());
+        let (target, load, render) = (self.target.clone(), self.load.clone(), self.render.clone());
+        self.debounce.call(move || {
+            let text = load(&item);
+            target.get_doc().update_content(|content| *content = text);
+            target.goto_line(0);
+            if let Err(e) = render() {
+                error!("Rendering a preview failed: {e}");
+            }
+        });
+    }
+
+    /// Wraps the handler of the list, and after every event selects what `selected`
+    /// returns for the list buffer, e.g. the row of a [Table](crate::Table)
+    pub fn follow<H, F>(self, handler: H, selected: F) -> PreviewHandler<K, H, F>
+    where
+        F: FnMut(&BufferRef) -> Option<K>,
+    {
+        PreviewHandler {
+            preview: self,
+            handler,
+            selected,
+        }
+    }
+}
+
+/// see [Preview::follow]
+pub struct PreviewHandler<K, H, F> {
+    preview: Preview<K>,
+    handler: H,
+    selected: F,
+}
+
+impl<K, H, F> PreviewHandler<K, H, F> {
+    pub fn preview(&mut self) -> &mut Preview<K> {
+        &mut self.preview
+    }
+}
+
+impl<T, K, H, F> EventHandler<T> for PreviewHandler<K, H, F>
+where
+    K: PartialEq + Clone + Send + 'static,
+    H: EventHandler<T>,
+    F: FnMut(&BufferRef) -> Option<K>,
+{
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, ctx: &mut AbletCtx) -> HandlerResult<T> {
+        let res = self.handler.handle(ev, buf, ctx);
+        if let Some(item) = (self.selected)(buf) {
+            self.preview.select(item);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn test_only_the_last_selection_is_loaded() {
+        let target = Buffer::new().into_ref();
+        let loads = Arc::new(AtomicUsize::new(0));
+        let loads2 = loads.clone();
+        let mut preview = Preview::new(
+            target.clone(),
+            move |item: &usize| {
+                loads2.fetch_add(1, Ordering::SeqCst);
+                AText::from(format!("item {item}"))
+            },
+            || Ok(()),
+        )
+        .with_delay(Duration::from_millis(30));
+
+        for item in [1, 2, 3, 3] {
+            preview.select(item);
+        }
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(target.get_doc().0.lock().unwrap().content.text, "item 3");
+
+        preview.select(3);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,134 @@
+//! Contains the Prompt, the input line at the bottom of an [Ablet](crate::Ablet)
+
+use std::io;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    document::line_count, shared, AText, Buffer, BufferPosition, BufferRef, Frame, InputHistory,
+    LineEditor, Range, Rect, Shared,
+};
+
+/// A buffer for user input, which is rendered below the splits. It's as high as
+/// its content, so multi-line input makes it grow. Clones share the same state.
+#[derive(Clone)]
+pub struct Prompt {
+    buffer: BufferRef,
+    history: InputHistory,
+    prefix: Shared<AText>,
+}
+
+impl Prompt {
+    pub fn new() -> Self {
+        let buffer = Buffer::new().into_ref();
+        buffer.set_cursor_visible(true);
+        Self {
+            buffer,
+            history: InputHistory::new(),
+            prefix: shared(AText::default()),
+        }
+    }
+
+    pub fn buffer(&self) -> &BufferRef {
+        &self.buffer
+    }
+
+    /// the lines that were submitted in the editors from [Prompt::line_editor], which
+    /// can be saved and loaded to keep them between runs
+    pub fn history(&self) -> &InputHistory {
+        &self.history
+    }
+
+    /// a [LineEditor] that records submitted lines in the history of the prompt
+    pub fn line_editor(&self) -> LineEditor {
+        LineEditor::with_history(self.history.clone())
+    }
+
+    /// Sets a prefix, like `❯ `, that is shown in front of the input. It isn't part of
+    /// the document, so the cursor can't enter it, and editing can't change it. It
+    /// should be a single line
+    pub fn set_prefix(&self, prefix: impl Into<AText>) {
+        *self.prefix.lock().unwrap() = prefix.into();
+    }
+
+    pub fn prefix(&self) -> AText {
+        self.prefix.lock().unwrap().clone()
+    }
+
+    /// the part of the rect of the prompt that is left for the input, right of the prefix.
+    /// At least one column is kept for the input
+    pub(crate) fn input_rect(&self, rect: Rect) -> Rect {
+        let prefix_w = self.prefix.lock().unwrap().text.width();
+        let prefix_w = prefix_w.min(rect.size.w.saturating_sub(1) as usize) as u16;
+        Rect::new(
+            rect.pos.row,
+            rect.pos.col + prefix_w,
+            rect.size.w - prefix_w,
+            rect.size.h,
+        )
+    }
+
+    /// draws the prefix, and the input right of it
+    pub(crate) fn render_at(&self, frame: &mut Frame, rect: Rect) -> io::Result<()> {
+        let input_rect = self.input_rect(rect);
+        let prefix = self.prefix.lock().unwrap();
+        let mut col = rect.pos.col;
+        for run in prefix.get_range_style_pairs(Range::new(0, prefix.len())) {
+            col = frame.print(
+                BufferPosition::new(rect.pos.row, col),
+                &prefix.text[run.range.into_native()],
+                *run.style,
+                input_rect.pos.col,
+            );
+        }
+        drop(prefix);
+        self.buffer.render_at(frame, input_rect)
+    }
+
+    /// the number of lines of the input
+    pub fn height(&self) -> u16 {
+        let doc = self.buffer.get_doc();
+        let n_lines = line_count(&doc.0.lock().unwrap().content.text);
+        n_lines.min(u16::MAX as usize) as u16
+    }
+}
+
+impl Default for Prompt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+    use crate::Size;
+
+    #[test]
+    fn test_prefix_is_not_editable() {
+        let prompt = Prompt::new();
+        prompt.set_prefix("sql> ".green());
+        prompt.buffer().insert_text_at_cursor("x");
+        prompt.buffer().delete_char_before_cursor();
+        prompt.buffer().delete_char_before_cursor();
+        prompt.buffer().move_cursor_by(-3);
+        prompt.buffer().insert_text_at_cursor("ab");
+
+        let mut frame = Frame::new(Size { w: 10, h: 1 });
+        prompt
+            .render_at(&mut frame, Rect::new(0, 0, 10, 1))
+            .unwrap();
+        assert_eq!(frame.to_lines()[0].trim_end(), "sql> ab");
+        let row = frame.rows().next().unwrap();
+        assert_eq!(
+            row[0].style.foreground_color,
+            Some(crossterm::style::Color::Green)
+        );
+        assert_eq!(
+            prompt.input_rect(Rect::new(0, 0, 3, 1)),
+            Rect::new(0, 2, 1, 1)
+        );
+    }
+}
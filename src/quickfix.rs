@@ -0,0 +1,274 @@
+//! Contains the Quickfix list, a list of places in documents to go through, like the
+//! results of a search through many documents, see [Ablet::search_all](crate::Ablet::search_all),
+//! or diagnostics
+
+use crossterm::style::{ContentStyle, Stylize};
+
+use crate::{ATextBuilder, Ablet, Buffer, BufferRef, BufferRegistry, SearchPattern, TextOffset};
+
+/// A place in a document, e.g. a search match
+#[derive(Clone)]
+pub struct QuickfixEntry {
+    /// the name of the buffer, e.g. in the registry
+    pub name: String,
+    pub buffer: BufferRef,
+    pub range: std::ops::Range<TextOffset>,
+    /// the line of the range, starting at 0
+    pub line: usize,
+    /// the line of the range, without surrounding whitespace
+    pub excerpt: String,
+}
+
+impl QuickfixEntry {
+    /// An entry for the range, with the line and excerpt taken from the buffer's
+    /// document. The range is moved into the text, and onto char boundaries
+    pub fn new(
+        name: impl Into<String>,
+        buffer: &BufferRef,
+        range: std::ops::Range<TextOffset>,
+    ) -> Self {
+        let doc = buffer.get_doc();
+        let doc = doc.0.lock().unwrap();
+        let text = &doc.content.text;
+        let range = range.start.clamp_to(text)..range.end.clamp_to(text);
+        let start = range.start.get();
+        let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+        Self {
+            name: name.into(),
+            buffer: buffer.clone(),
+            range,
+            line: text[..start].matches('\n').count(),
+            excerpt: text[line_start..line_end].trim().to_string(),
+        }
+    }
+}
+
+/// A list of places in documents, and a read-only buffer that lists them, one per
+/// line, as `name:line: excerpt`. Show the list in a split, and go through the
+/// entries with [Quickfix::next_result] and [Quickfix::prev_result], or jump to the
+/// one on the line of the list's cursor with [Quickfix::jump_to_selected]
+pub struct Quickfix {
+    entries: Vec<QuickfixEntry>,
+    list: BufferRef,
+    /// the entry that was jumped to last
+    current: Option<usize>,
+}
+
+impl Quickfix {
+    /// a list of the entries, e.g. of diagnostics or the results of a grep-like tool
+    pub fn new(entries: Vec<QuickfixEntry>) -> Self {
+        let mut list = ATextBuilder::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if i > 0 {
+                list.push("\n");
+            }
+            // a name with a newline would take up two lines of the list
+            let name = entry.name.replace('\n', "\\n");
+            let location = format!("{name}:{}:", entry.line + 1);
+            list.push_styled(&location, ContentStyle::new().dark_cyan());
+            list.push(format!(" {}", entry.excerpt));
+        }
+        let list = Buffer::from_text(list.build()).into_ref();
+        list.get_doc().set_read_only(true);
+        Self {
+            entries,
+            list,
+            current: None,
+        }
+    }
+
+    pub fn entries(&self) -> &[QuickfixEntry] {
+        &self.entries
+    }
+
+    /// the buffer that lists the matches
+    pub fn list(&self) -> &BufferRef {
+        &self.list
+    }
+
+    /// the entry on the line of the list's cursor
+    pub fn selected(&self) -> Option<&QuickfixEntry> {
+        let doc = self.list.get_doc();
+        let doc = doc.0.lock().unwrap();
+        let line = doc.content.text[..self.list.cursor().get()]
+            .matches('\n')
+            .count();
+        self.entries.get(line)
+    }
+
+    /// the entry that was jumped to last
+    pub fn current(&self) -> Option<&QuickfixEntry> {
+        self.entries.get(self.current?)
+    }
+
+    /// Jumps to the entry after the current one, or the first one, see
+    /// [Ablet::jump_to]. Wraps around at the end
+    pub fn next_result(&mut self, ablet: &Ablet) -> Option<&QuickfixEntry> {
+        let n = self.entries.len();
+        let next = self.current.map_or(0, |i| (i + 1) % n.max(1));
+        self.jump(ablet, next)
+    }
+
+    /// Jumps to the entry before the current one, or the last one. Wraps around at
+    /// the start
+    pub fn prev_result(&mut self, ablet: &Ablet) -> Option<&QuickfixEntry> {
+        let n = self.entries.len();
+        let prev = match self.current {
+            Some(i) if i > 0 => i - 1,
+            _ => n.saturating_sub(1),
+        };
+        self.jump(ablet, prev)
+    }
+
+    /// jumps to the entry on the line of the list's cursor
+    pub fn jump_to_selected(&mut self, ablet: &Ablet) -> Option<&QuickfixEntry> {
+        let doc = self.list.get_doc();
+        let line = doc.0.lock().unwrap().content.text[..self.list.cursor().get()]
+            .matches('\n')
+            .count();
+        self.jump(ablet, line)
+    }
+
+    /// jumps to the entry, and moves the list's cursor to its line
+    fn jump(&mut self, ablet: &Ablet, i: usize) -> Option<&QuickfixEntry> {
+        let entry = self.entries.get(i)?;
+        self.current = Some(i);
+        let doc = self.list.get_doc();
+        let line_start = doc
+            .0
+            .lock()
+            .unwrap()
+            .content
+            .text
+            .split_inclusive('\n')
+            .take(i)
+            .map(str::len)
+            .sum::<usize>();
+        self.list.set_cursor(TextOffset::new(line_start));
+        ablet.jump_to(entry);
+        Some(entry)
+    }
+}
+
+/// Searches the documents of all buffers in the registry, in parallel. The entries are
+/// ordered by the names of the buffers, and by their position
+pub(crate) fn search_all(
+    registry: &BufferRegistry,
+    pattern: &(impl SearchPattern + Sync),
+) -> Quickfix {
+    let mut buffers: Vec<_> = registry.iter().collect();
+    buffers.sort_by_key(|(name, _)| *name);
+
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let searches: Vec<_> = buffers
+            .iter()
+            .map(|(_, buf)| {
+                scope.spawn(|| {
+                    let doc = buf.get_doc();
+                    let doc = doc.0.lock().unwrap();
+                    pattern.find_in(&doc.content.text)
+                })
+            })
+            .collect();
+        searches
+            .into_iter()
+            .map(|search| search.join().expect("searching doesn't panic"))
+            .collect()
+    });
+
+    let entries = buffers
+        .into_iter()
+        .zip(results)
+        .flat_map(|((name, buf), matches)| {
+            matches.into_iter().map(move |range| {
+                let range = TextOffset::new(range.start)..TextOffset::new(range.end);
+                QuickfixEntry::new(name, buf, range)
+            })
+        })
+        .collect();
+    Quickfix::new(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_tree, Ablet, Size, TextOffset};
+
+    #[test]
+    fn test_search_all() {
+        let a = Buffer::from_text("foo\nbar foo").into_ref();
+        let b = Buffer::from_text("nothing").into_ref();
+        let c = Buffer::from_text("  foo").into_ref();
+        let mut registry = BufferRegistry::new();
+        registry.register("c", c.clone());
+        registry.register("a", a.clone());
+        registry.register("b", b.clone());
+
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: a, 1: c }));
+        let quickfix = ablet.search_all(&registry, &"foo");
+        let list = quickfix
+            .list()
+            .get_doc()
+            .0
+            .lock()
+            .unwrap()
+            .content
+            .text
+            .clone();
+        assert_eq!(list, "a:1: foo\na:2: bar foo\nc:1: foo");
+
+        ablet.render_to_frame(Size { w: 20, h: 5 }).unwrap();
+        quickfix.list().move_cursor_down(1);
+        let entry = quickfix.selected().unwrap();
+        assert_eq!(entry.range, TextOffset::new(8)..TextOffset::new(11));
+        ablet.jump_to(entry);
+        assert!(ablet.focused() == a);
+        assert_eq!(a.cursor(), TextOffset::new(8));
+        assert_eq!(a.selection(), Some(TextOffset::new(8)..TextOffset::new(11)));
+    }
+
+    #[test]
+    fn test_next_and_prev_result() {
+        let a = Buffer::from_text("x\n  error here").into_ref();
+        let b = Buffer::from_text("warning").into_ref();
+        let ablet = Ablet::new(split_tree!(Horizontal: { 1: a, 1: b }));
+        let mut quickfix = Quickfix::new(vec![
+            QuickfixEntry::new("a", &a, TextOffset::new(4)..TextOffset::new(9)),
+            QuickfixEntry::new("b", &b, TextOffset::new(0)..TextOffset::new(7)),
+        ]);
+        assert_eq!(quickfix.entries()[0].excerpt, "error here");
+
+        assert_eq!(quickfix.prev_result(&ablet).unwrap().name, "b");
+        assert!(ablet.focused() == b);
+        assert_eq!(quickfix.next_result(&ablet).unwrap().name, "a");
+        assert!(ablet.focused() == a);
+        assert_eq!(a.cursor(), TextOffset::new(4));
+        // the list's cursor follows
+        assert_eq!(quickfix.selected().unwrap().name, "a");
+        assert_eq!(quickfix.next_result(&ablet).unwrap().name, "b");
+        assert_eq!(quickfix.current().unwrap().name, "b");
+    }
+
+    #[test]
+    fn test_entries_are_kept_on_their_line() {
+        let a = Buffer::from_text("añ\nb").into_ref();
+        let quickfix = Quickfix::new(vec![
+            QuickfixEntry::new("two\nlines", &a, TextOffset::new(2)..TextOffset::new(9)),
+            QuickfixEntry::new("b", &a, TextOffset::new(4)..TextOffset::new(5)),
+        ]);
+        let entry = &quickfix.entries()[0];
+        assert_eq!(entry.range, TextOffset::new(1)..TextOffset::new(5));
+        assert_eq!(entry.excerpt, "añ");
+        let list = quickfix
+            .list()
+            .get_doc()
+            .0
+            .lock()
+            .unwrap()
+            .content
+            .text
+            .clone();
+        assert_eq!(list, "two\\nlines:1: añ\nb:2: b");
+    }
+}
@@ -0,0 +1,55 @@
+//! Contains the BufferRegistry, which gives buffers names
+
+use std::collections::HashMap;
+
+use crate::BufferRef;
+
+#[derive(thiserror::Error, Debug)]
+pub enum RegistryError {
+    #[error("A buffer in the split tree is not registered")]
+    UnregisteredBuffer,
+
+    #[error("There is no buffer named \"{0}\" in the registry")]
+    UnknownName(String),
+
+    #[error("Invalid layout: {0}")]
+    InvalidLayout(String),
+}
+
+/// Maps names to buffers. This is used wherever a buffer must be referred to
+/// by something other than a BufferRef, e.g. in a serialized layout.
+#[derive(Default, Clone)]
+pub struct BufferRegistry {
+    buffers: HashMap<String, BufferRef>,
+}
+
+impl BufferRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// registers a buffer under the given name, replacing a buffer
+    /// that was registered under the same name before
+    pub fn register(&mut self, name: impl Into<String>, buf: BufferRef) {
+        self.buffers.insert(name.into(), buf);
+    }
+
+    pub fn get(&self, name: &str) -> Result<&BufferRef, RegistryError> {
+        self.buffers
+            .get(name)
+            .ok_or_else(|| RegistryError::UnknownName(name.to_string()))
+    }
+
+    /// the names and buffers, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &BufferRef)> {
+        self.buffers.iter().map(|(name, buf)| (name.as_str(), buf))
+    }
+
+    pub fn name_of(&self, buf: &BufferRef) -> Result<&str, RegistryError> {
+        self.buffers
+            .iter()
+            .find(|(_, b)| *b == buf)
+            .map(|(name, _)| name.as_str())
+            .ok_or(RegistryError::UnregisteredBuffer)
+    }
+}
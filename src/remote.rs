@@ -0,0 +1,315 @@
+//! An experimental backend that renders to a terminal on another machine.
+//! Only available with the `remote` feature.
+//!
+//! The app side is a [RemoteRenderer], which keeps track of what the client
+//! currently shows and only sends the cells that changed. The client side is
+//! [run_client], which applies them to its local terminal, and sends back its
+//! size and all terminal events, which the app reads through [RemoteEvents].
+//! Both directions are JSON lines, and work on anything that implements
+//! Read/Write, usually the two halves of a TcpStream.
+//!
+//! ```no_run
+//! use std::{io, net::TcpListener};
+//! use ablet::{remote, split_tree, Buffer};
+//!
+//! fn main() -> io::Result<()> {
+//!     let buf = Buffer::from_text("Hello from the server").into_ref();
+//!     let tree = split_tree!(Vertical: { 1: buf });
+//!
+//!     let (stream, _) = TcpListener::bind("127.0.0.1:7777")?.accept()?;
+//!     let (mut renderer, mut events) = remote::accept(stream.try_clone()?, stream)?;
+//!     loop {
+//!         tree.render_with(&mut renderer)?;
+//!         events.read()?;
+//!     }
+//! }
+//! ```
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crossterm::{
+    event::{self, Event},
+    style::{Attribute, Attributes, Color, ContentStyle},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{BufferPosition, CrosstermRenderer, Frame, FrameRenderer, Renderer, Size};
+
+/// Messages from the app to the client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    /// the client should clear its screen, the following updates assume this size
+    Clear(Size),
+    Cells(Vec<CellRun>),
+}
+
+/// Messages from the client to the app
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Size(Size),
+    Event(Event),
+}
+
+/// A run of equally styled cells in a row
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CellRun {
+    pub row: u16,
+    pub col: u16,
+    pub text: String,
+    pub style: RemoteStyle,
+}
+
+/// ContentStyle isn't serializable, so this is sent instead
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RemoteStyle {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub underline: Option<Color>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl From<&ContentStyle> for RemoteStyle {
+    fn from(style: &ContentStyle) -> Self {
+        Self {
+            foreground: style.foreground_color,
+            background: style.background_color,
+            underline: style.underline_color,
+            attributes: Attribute::iterator()
+                .filter(|a| style.attributes.has(*a))
+                .collect(),
+        }
+    }
+}
+
+impl From<&RemoteStyle> for ContentStyle {
+    fn from(style: &RemoteStyle) -> Self {
+        ContentStyle {
+            foreground_color: style.foreground,
+            background_color: style.background,
+            underline_color: style.underline,
+            attributes: Attributes::from(style.attributes.as_slice()),
+        }
+    }
+}
+
+/// Waits for the client to announce its size, and returns the two ends of the
+/// connection for the app.
+pub fn accept<R: Read, W: Write>(
+    from_client: R,
+    to_client: W,
+) -> io::Result<(RemoteRenderer<W>, RemoteEvents<R>)> {
+    let mut events = RemoteEvents {
+        reader: BufReader::new(from_client),
+        size: Arc::new(Mutex::new(Size { w: 0, h: 0 })),
+    };
+    let Event::Resize(w, h) = events.read()? else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the client must send its size first",
+        ));
+    };
+    let renderer = RemoteRenderer {
+        out: to_client,
+        canvas: FrameRenderer::new(Size { w, h }),
+        client_size: events.size.clone(),
+        sent: None,
+    };
+    Ok((renderer, events))
+}
+
+/// A Renderer that sends everything that changed since the last flush to the client
+pub struct RemoteRenderer<W: Write> {
+    out: W,
+    canvas: FrameRenderer,
+    client_size: Arc<Mutex<Size>>,
+    /// what the client currently shows
+    sent: Option<Frame>,
+}
+
+impl<W: Write> RemoteRenderer<W> {
+    fn send(&mut self, msg: &ServerMessage) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, msg)?;
+        self.out.write_all(b"\n")
+    }
+}
+
+impl<W: Write> Renderer for RemoteRenderer<W> {
+    fn size(&self) -> io::Result<Size> {
+        Ok(*self.client_size.lock().unwrap())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.canvas.clear()
+    }
+
+    fn move_to(&mut self, pos: BufferPosition) -> io::Result<()> {
+        self.canvas.move_to(pos)
+    }
+
+    fn set_style(&mut self, style: &ContentStyle) -> io::Result<()> {
+        self.canvas.set_style(style)
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        // the size is only updated here, so the client size can't change in the middle of
+        // a frame
+        let size = self.size()?;
+        if self.canvas.frame().size() != size {
+            self.canvas.resize(size);
+        }
+        self.canvas.print(text)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let frame = self.canvas.frame().clone();
+        let sent = match self.sent.take() {
+            Some(sent) if sent.size() == frame.size() => sent,
+            _ => {
+                self.send(&ServerMessage::Clear(frame.size()))?;
+                Frame::new(frame.size())
+            }
+        };
+
+        let runs = frame
+            .diff(&sent)
+            .into_iter()
+            .map(|(pos, cells)| CellRun {
+                row: pos.row,
+                col: pos.col,
+                text: cells.iter().map(|c| c.symbol.as_str()).collect(),
+                style: (&cells[0].style).into(),
+            })
+            .collect::<Vec<_>>();
+        if !runs.is_empty() {
+            self.send(&ServerMessage::Cells(runs))?;
+        }
+        self.sent = Some(frame);
+        self.out.flush()
+    }
+}
+
+/// The events the client sends. Size changes of the client are turned into
+/// Resize events, and picked up by the corresponding [RemoteRenderer]
+pub struct RemoteEvents<R: Read> {
+    reader: BufReader<R>,
+    size: Arc<Mutex<Size>>,
+}
+
+impl<R: Read> RemoteEvents<R> {
+    /// blocks until the client sends the next event
+    pub fn read(&mut self) -> io::Result<Event> {
+        match read_message(&mut self.reader)? {
+            ClientMessage::Size(size) => {
+                *self.size.lock().unwrap() = size;
+                Ok(Event::Resize(size.w, size.h))
+            }
+            ClientMessage::Event(ev) => Ok(ev),
+        }
+    }
+}
+
+/// Connects the local terminal to an app. This announces the terminal size, forwards all
+/// terminal events from a background thread, and draws what the app sends, until the
+/// app closes the connection. Setting up the terminal is up to the caller, e.g. via
+/// [with_setup_terminal](crate::with_setup_terminal) or a [TerminalGuard](crate::TerminalGuard).
+pub fn run_client<R: Read, W: Write + Send + 'static>(
+    from_server: R,
+    mut to_server: W,
+) -> io::Result<()> {
+    let size: Size = crossterm::terminal::size()?.into();
+    send_client_message(&mut to_server, &ClientMessage::Size(size))?;
+
+    thread::spawn(move || -> io::Result<()> {
+        loop {
+            let msg = match event::read()? {
+                Event::Resize(w, h) => ClientMessage::Size(Size { w, h }),
+                ev => ClientMessage::Event(ev),
+            };
+            send_client_message(&mut to_server, &msg)?;
+        }
+    });
+
+    let mut reader = BufReader::new(from_server);
+    let mut renderer = CrosstermRenderer::stdout();
+    loop {
+        match read_message(&mut reader) {
+            Ok(msg) => apply(&msg, &mut renderer)?,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// applies a message from the app to a renderer
+pub fn apply(msg: &ServerMessage, renderer: &mut dyn Renderer) -> io::Result<()> {
+    match msg {
+        ServerMessage::Clear(_) => renderer.clear()?,
+        ServerMessage::Cells(runs) => {
+            for run in runs {
+                renderer.move_to(BufferPosition::new(run.row, run.col))?;
+                renderer.set_style(&(&run.style).into())?;
+                renderer.print(&run.text)?;
+            }
+            renderer.set_style(&ContentStyle::default())?;
+        }
+    }
+    renderer.flush()
+}
+
+fn send_client_message(out: &mut impl Write, msg: &ClientMessage) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, msg)?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+fn read_message<T: for<'de> Deserialize<'de>>(reader: &mut impl BufRead) -> io::Result<T> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crossterm::style::Stylize;
+
+    use super::*;
+    use crate::{split_tree, AText, Buffer};
+
+    #[test]
+    fn test_client_sees_server_frames() {
+        let size = Size { w: 20, h: 4 };
+        let hello = serde_json::to_string(&ClientMessage::Size(size)).unwrap() + "\n";
+        let (mut renderer, _) = accept(Cursor::new(hello), vec![]).unwrap();
+
+        let buf = Buffer::from_text(AText::from("Hello ") + "World".green()).into_ref();
+        let tree = split_tree!(Vertical: { 1: buf });
+        tree.render_with(&mut renderer).unwrap();
+        buf.get_doc().update_content(|text| text.push_char('!'));
+        tree.render_with(&mut renderer).unwrap();
+
+        // only the changed cell is sent the second time
+        let last_msg = String::from_utf8(renderer.out.clone()).unwrap();
+        let last_msg = last_msg.lines().last().unwrap();
+        let ServerMessage::Cells(runs) = serde_json::from_str(last_msg).unwrap() else {
+            panic!("expected cells");
+        };
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].text, "!");
+
+        let mut client = FrameRenderer::new(size);
+        let mut reader = Cursor::new(renderer.out);
+        while let Ok(msg) = read_message::<ServerMessage>(&mut reader) {
+            apply(&msg, &mut client).unwrap();
+        }
+
+        assert_eq!(client.frame(), &tree.render_to_frame(size).unwrap());
+    }
+}
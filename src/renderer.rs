@@ -0,0 +1,444 @@
+//! Contains the Renderer trait, the boundary between ablet and the terminal.
+//!
+//! Everything ablet draws ends up as a [Frame](crate::Frame), which is then
+//! drawn through a Renderer. The default one emits escape sequences via crossterm,
+//! alternative backends (a different terminal library, a remote connection, ...)
+//! can be implemented out of tree by implementing this trait.
+
+use std::{
+    io::{self, Write},
+    sync::LazyLock,
+};
+
+use crossterm::{
+    cursor, queue,
+    style::{
+        Attribute, Attributes, Color, ContentStyle, Print, SetAttribute, SetAttributes,
+        SetBackgroundColor, SetForegroundColor, SetStyle, SetUnderlineColor,
+    },
+    terminal::{BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate},
+};
+
+use itertools::Itertools;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{detect_color_profile, BufferPosition, ColorProfile, Frame, Size};
+
+pub trait Renderer {
+    /// the size of the output area, in cells
+    fn size(&self) -> io::Result<Size>;
+
+    /// clears the whole output area
+    fn clear(&mut self) -> io::Result<()>;
+
+    /// moves the position where the next print will start
+    fn move_to(&mut self, pos: BufferPosition) -> io::Result<()>;
+
+    /// sets the style for everything printed afterwards. The style replaces the
+    /// previous one completely, it is not layered on top of it.
+    fn set_style(&mut self, style: &ContentStyle) -> io::Result<()>;
+
+    /// prints text at the current position. The text never contains newlines
+    fn print(&mut self, text: &str) -> io::Result<()>;
+
+    /// makes sure everything printed so far is visible
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Called before a frame is drawn, e.g. to start a synchronized update, so the
+    /// frame shows up at once. Does nothing by default
+    fn begin_frame(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// called after a frame is drawn, before it's flushed. Does nothing by default
+    fn end_frame(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The default renderer, writes crossterm commands to any io::Write,
+/// usually stdout. The output is collected, and written at once on flush. Style
+/// changes only send what changed, and cursor moves that wouldn't change anything
+/// are left out
+pub struct CrosstermRenderer<W: Write> {
+    out: W,
+    /// the output since the last flush
+    pending: Vec<u8>,
+    /// the style the terminal uses, as far as it's known
+    style: Option<ContentStyle>,
+    /// the position of the terminal's cursor, as far as it's known
+    pos: Option<BufferPosition>,
+    /// see [CrosstermRenderer::with_styled_underlines]
+    styled_underlines: bool,
+    /// see [CrosstermRenderer::with_synchronized_output]
+    synchronized_output: bool,
+    /// see [CrosstermRenderer::with_color_profile]
+    color_profile: ColorProfile,
+}
+
+impl CrosstermRenderer<io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> CrosstermRenderer<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            pending: vec![],
+            style: None,
+            pos: None,
+            styled_underlines: *STYLED_UNDERLINES,
+            synchronized_output: true,
+            color_profile: *COLOR_PROFILE,
+        }
+    }
+
+    /// Whether curly, dotted and dashed underlines, and underline colors, are sent to
+    /// the terminal. Otherwise, they are drawn as plain underlines. By default, this is
+    /// what [supports_styled_underlines] detects
+    pub fn with_styled_underlines(mut self, styled_underlines: bool) -> Self {
+        self.styled_underlines = styled_underlines;
+        self
+    }
+
+    /// The colors the terminal supports, others are replaced by the closest ones it
+    /// supports. By default, this is what [detect_color_profile] detects
+    pub fn with_color_profile(mut self, color_profile: ColorProfile) -> Self {
+        self.color_profile = color_profile;
+        self
+    }
+
+    /// Whether frames are wrapped in the escape sequences of synchronized updates, so
+    /// the terminal shows them at once, instead of tearing when a frame is written in
+    /// many parts. Terminals that don't support them ignore them, so it's on by default
+    pub fn with_synchronized_output(mut self, synchronized_output: bool) -> Self {
+        self.synchronized_output = synchronized_output;
+        self
+    }
+
+    /// returns the writer, after writing the output that wasn't flushed yet to it
+    pub fn into_inner(mut self) -> W {
+        if let Err(e) = self.out.write_all(&self.pending) {
+            log::error!("couldn't write the pending output: {e}");
+        }
+        self.out
+    }
+}
+
+impl<W: Write> Renderer for CrosstermRenderer<W> {
+    fn size(&self) -> io::Result<Size> {
+        Ok(crossterm::terminal::size()?.into())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        queue!(self.pending, Clear(ClearType::All))
+    }
+
+    fn move_to(&mut self, pos: BufferPosition) -> io::Result<()> {
+        if self.pos == Some(pos) {
+            return Ok(());
+        }
+        self.pos = Some(pos);
+        queue!(self.pending, cursor::MoveTo(pos.col, pos.row))
+    }
+
+    fn set_style(&mut self, style: &ContentStyle) -> io::Result<()> {
+        let mut style = self.color_profile.downgrade_style(*style);
+        if !self.styled_underlines {
+            plain_underlines(&mut style);
+        }
+        let prev = self.style.replace(style);
+        match prev {
+            Some(prev) if prev == style => Ok(()),
+            Some(prev) if style_transition(prev, style, &mut self.pending)? => Ok(()),
+            _ => queue!(
+                self.pending,
+                SetAttribute(Attribute::Reset),
+                SetStyle(style)
+            ),
+        }
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        if let Some(pos) = &mut self.pos {
+            pos.col = pos.col.saturating_add(text.width() as u16);
+        }
+        queue!(self.pending, Print(text))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.write_all(&self.pending)?;
+        self.pending.clear();
+        self.out.flush()
+    }
+
+    fn begin_frame(&mut self) -> io::Result<()> {
+        // anything else that writes to the terminal may have changed them
+        self.style = None;
+        self.pos = None;
+        if self.synchronized_output {
+            queue!(self.pending, BeginSynchronizedUpdate)?;
+        }
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        if self.synchronized_output {
+            queue!(self.pending, EndSynchronizedUpdate)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the SGR sequences that turn the style `from` into `to`, sending only the
+/// colors and attributes that changed. Returns false without writing anything if an
+/// attribute of `from` can't be turned off on its own, so the style has to be reset
+fn style_transition(from: ContentStyle, to: ContentStyle, out: &mut Vec<u8>) -> io::Result<bool> {
+    let removed: Vec<_> = Attribute::iterator()
+        .filter(|a| from.attributes.has(*a) && !to.attributes.has(*a))
+        .collect();
+    let Some(offs) = removed
+        .iter()
+        .map(|a| turned_off_by(*a))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Ok(false);
+    };
+
+    // the attributes that are still on after the offs, the others are set again
+    let mut added = Attributes::default();
+    for a in Attribute::iterator() {
+        let still_on =
+            from.attributes.has(a) && !turned_off_by(a).is_some_and(|off| offs.contains(&off));
+        if to.attributes.has(a) && !still_on {
+            added.set(a);
+        }
+    }
+    for off in offs.into_iter().unique() {
+        queue!(out, SetAttribute(off))?;
+    }
+    queue!(out, SetAttributes(added))?;
+
+    let reset = |c: Option<Color>| c.unwrap_or(Color::Reset);
+    if from.foreground_color != to.foreground_color {
+        queue!(out, SetForegroundColor(reset(to.foreground_color)))?;
+    }
+    if from.background_color != to.background_color {
+        queue!(out, SetBackgroundColor(reset(to.background_color)))?;
+    }
+    if from.underline_color != to.underline_color {
+        queue!(out, SetUnderlineColor(reset(to.underline_color)))?;
+    }
+    Ok(true)
+}
+
+/// the attribute that turns the attribute off, if there is one
+fn turned_off_by(attr: Attribute) -> Option<Attribute> {
+    use Attribute::*;
+    Some(match attr {
+        Bold | Dim => NormalIntensity,
+        Italic => NoItalic,
+        Underlined | DoubleUnderlined | Undercurled | Underdotted | Underdashed => NoUnderline,
+        SlowBlink | RapidBlink => NoBlink,
+        Reverse => NoReverse,
+        Hidden => NoHidden,
+        CrossedOut => NotCrossedOut,
+        Framed | Encircled => NotFramedOrEncircled,
+        OverLined => NotOverLined,
+        _ => return None,
+    })
+}
+
+/// detected once, since renderers are created for every frame by some backends
+static STYLED_UNDERLINES: LazyLock<bool> = LazyLock::new(supports_styled_underlines);
+static COLOR_PROFILE: LazyLock<ColorProfile> = LazyLock::new(detect_color_profile);
+
+/// Guesses from the environment whether the terminal supports curly, dotted and
+/// dashed underlines, and underline colors. Terminals that don't may show garbage
+/// for them, so unknown ones are assumed not to
+pub fn supports_styled_underlines() -> bool {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    let known_term = [
+        "kitty",
+        "wezterm",
+        "foot",
+        "alacritty",
+        "ghostty",
+        "contour",
+    ]
+    .iter()
+    .any(|t| term.contains(t));
+    let known_program =
+        ["WezTerm", "ghostty", "iTerm.app", "vscode"].contains(&var("TERM_PROGRAM").as_str());
+    // VTE based terminals, like the GNOME terminal, support them since 0.51.2
+    let vte = var("VTE_VERSION").parse::<u32>().is_ok_and(|v| v >= 5102);
+    known_term || known_program || vte
+}
+
+/// turns the underline styles into plain underlines, and drops the underline color
+fn plain_underlines(style: &mut ContentStyle) {
+    let styled = [
+        Attribute::Undercurled,
+        Attribute::Underdotted,
+        Attribute::Underdashed,
+    ];
+    if styled.iter().any(|attr| style.attributes.has(*attr)) {
+        for attr in styled {
+            style.attributes.unset(attr);
+        }
+        style.attributes.set(Attribute::Underlined);
+    }
+    style.underline_color = None;
+}
+
+/// A renderer that draws into a [Frame] instead of a terminal. Useful to
+/// capture output, e.g. for tests or to forward it somewhere else.
+pub struct FrameRenderer {
+    frame: Frame,
+    pos: BufferPosition,
+    style: ContentStyle,
+}
+
+impl FrameRenderer {
+    pub fn new(size: Size) -> Self {
+        Self {
+            frame: Frame::new(size),
+            pos: BufferPosition::default(),
+            style: ContentStyle::default(),
+        }
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    /// resizes the frame, which clears it
+    pub fn resize(&mut self, size: Size) {
+        self.frame = Frame::new(size);
+    }
+}
+
+impl Renderer for FrameRenderer {
+    fn size(&self) -> io::Result<Size> {
+        Ok(self.frame.size())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.frame = Frame::new(self.frame.size());
+        Ok(())
+    }
+
+    fn move_to(&mut self, pos: BufferPosition) -> io::Result<()> {
+        self.pos = pos;
+        Ok(())
+    }
+
+    fn set_style(&mut self, style: &ContentStyle) -> io::Result<()> {
+        self.style = *style;
+        Ok(())
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        let w = self.frame.size().w;
+        self.pos.col = self.frame.print(self.pos, text, self.style, w);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// draws a finished frame, together with everything that is drawn on top of all
+/// frames, like the latency indicator
+pub(crate) fn present(mut frame: Frame, renderer: &mut dyn Renderer) -> io::Result<()> {
+    crate::stats::draw_latency_indicator(&mut frame);
+    crate::blink::simulate_blink(&mut frame);
+    frame.draw(renderer)?;
+
+    #[cfg(feature = "debug")]
+    crate::frame_history::record(&frame);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::{Color, Stylize};
+
+    use super::*;
+
+    #[test]
+    fn test_plain_underlines_fallback() {
+        let style = ContentStyle::new()
+            .attribute(Attribute::Undercurled)
+            .underline(Color::Red);
+        let mut renderer = CrosstermRenderer::new(vec![]).with_styled_underlines(false);
+        renderer.set_style(&style).unwrap();
+        let plain = String::from_utf8(renderer.into_inner()).unwrap();
+        let mut renderer = CrosstermRenderer::new(vec![]).with_styled_underlines(true);
+        renderer.set_style(&style).unwrap();
+        let styled = String::from_utf8(renderer.into_inner()).unwrap();
+
+        assert!(plain.contains("\x1b[4m") && !plain.contains("58;"));
+        assert!(styled.contains("\x1b[4:3m") && styled.contains("58;"));
+    }
+
+    #[test]
+    fn test_frames_are_synchronized_updates() {
+        let frame = Frame::new(Size { w: 2, h: 1 });
+        let mut renderer = CrosstermRenderer::new(vec![]);
+        frame.draw(&mut renderer).unwrap();
+        let out = String::from_utf8(renderer.into_inner()).unwrap();
+        assert!(out.starts_with("\x1b[?2026h") && out.ends_with("\x1b[?2026l"));
+
+        let mut renderer = CrosstermRenderer::new(vec![]).with_synchronized_output(false);
+        frame.draw(&mut renderer).unwrap();
+        let out = String::from_utf8(renderer.into_inner()).unwrap();
+        assert!(!out.contains("2026"));
+    }
+
+    #[test]
+    fn test_redundant_styles_and_moves_are_left_out() {
+        let mut frame = Frame::new(Size { w: 4, h: 2 });
+        frame.print(
+            BufferPosition::new(0, 0),
+            "ab",
+            ContentStyle::new().red(),
+            4,
+        );
+        let mut renderer = CrosstermRenderer::new(vec![]).with_synchronized_output(false);
+        frame.draw(&mut renderer).unwrap();
+        let out = String::from_utf8(renderer.into_inner()).unwrap();
+        // the second row, and the reset at the end, have the style the first row
+        // ended with, and the cursor is only moved to the start of the rows
+        assert_eq!(out.matches("\x1b[0m").count(), 1);
+        assert_eq!(out.matches("\x1b[39m").count(), 1);
+        assert_eq!(out.matches('H').count(), 2);
+    }
+
+    #[test]
+    fn test_minimal_style_transitions() {
+        let transition = |from: ContentStyle, to: ContentStyle| {
+            let mut out = vec![];
+            style_transition(from, to, &mut out)
+                .unwrap()
+                .then(|| String::from_utf8(out).unwrap())
+        };
+        let base = ContentStyle::new().bold().dim().red();
+        assert_eq!(
+            transition(base, base.italic().on_blue()).as_deref(),
+            Some("\x1b[3m\x1b[48;5;12m")
+        );
+        // turning bold off turns dim off too, so it's set again
+        assert_eq!(
+            transition(base, ContentStyle::new().dim()).as_deref(),
+            Some("\x1b[22m\x1b[2m\x1b[39m")
+        );
+        let fraktur = ContentStyle::new().attribute(Attribute::Fraktur);
+        assert_eq!(transition(fraktur, ContentStyle::new()), None);
+    }
+}
@@ -0,0 +1,376 @@
+//! Contains the ScrollView, which shows a part of a [Widget] that is bigger than the
+//! space it gets, with scrollbars
+
+use std::io;
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEventKind, KeyModifiers, MouseEventKind},
+    style::{ContentStyle, Stylize},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    shared, AbletCtx, BufferPosition, BufferRef, Cell, EventHandler, Frame, HandlerResult, Rect,
+    Shared, Size,
+};
+
+/// how many rows or columns the mouse wheel scrolls
+const WHEEL_STEP: isize = 3;
+
+/// Something that draws itself into a frame, e.g. to be shown in a [ScrollView]
+pub trait Widget {
+    /// the size the widget needs to be shown completely
+    fn size(&self) -> Size;
+
+    /// draws the widget into the rect of the frame, which has the widget's size
+    fn render_at(&self, frame: &mut Frame, rect: Rect) -> io::Result<()>;
+}
+
+impl Widget for Frame {
+    fn size(&self) -> Size {
+        Frame::size(self)
+    }
+
+    fn render_at(&self, frame: &mut Frame, rect: Rect) -> io::Result<()> {
+        let all = Rect::new(0, 0, self.size().w, self.size().h);
+        if let Some(cells) = self.cells_in(all) {
+            frame.set_cells_in(rect, &cells);
+        }
+        Ok(())
+    }
+}
+
+/// A closure that returns the frame to show. It's called for the size, and again to
+/// draw it, so it should be cheap
+impl<F: Fn() -> Frame> Widget for F {
+    fn size(&self) -> Size {
+        self().size()
+    }
+
+    fn render_at(&self, frame: &mut Frame, rect: Rect) -> io::Result<()> {
+        Widget::render_at(&self(), frame, rect)
+    }
+}
+
+/// Shows the part of a widget that fits into the rect it's rendered at, scrolled
+/// vertically and horizontally, with a scrollbar on the right or at the bottom while
+/// the widget doesn't fit. It scrolls independently of the text scrolling of buffers.
+/// Clones share the scroll position.
+///
+/// As an [EventHandler], the arrow keys scroll by one row or column, PageUp and
+/// PageDown by a page, Home and End to the top and bottom, and the mouse wheel by
+/// three rows, or columns with Shift, while the mouse is over the view.
+#[derive(Clone)]
+pub struct ScrollView<W> {
+    widget: W,
+    state: Shared<State>,
+}
+
+struct State {
+    /// the row and column of the widget that is shown in the top left corner
+    offset: BufferPosition,
+    /// the size of the widget at the last render
+    content: Size,
+    /// the part of the rect of the last render that shows the widget, without the
+    /// scrollbars
+    viewport: Rect,
+}
+
+impl<W: Widget> ScrollView<W> {
+    pub fn new(widget: W) -> Self {
+        Self {
+            widget,
+            state: shared(State {
+                offset: BufferPosition::default(),
+                content: Size { w: 0, h: 0 },
+                viewport: Rect::new(0, 0, 0, 0),
+            }),
+        }
+    }
+
+    pub fn widget(&self) -> &W {
+        &self.widget
+    }
+
+    /// the row and column of the widget that is shown in the top left corner
+    pub fn offset(&self) -> BufferPosition {
+        self.state.lock().unwrap().offset
+    }
+
+    /// Scrolls so the row and column are in the top left corner, or as close as the
+    /// size of the widget allows. It's clamped to the size of the last render
+    pub fn scroll_to(&self, row: u16, col: u16) {
+        let mut state = self.state.lock().unwrap();
+        state.offset = BufferPosition::new(row, col);
+        state.clamp();
+    }
+
+    /// scrolls by the rows and columns, negative ones scroll up or left
+    pub fn scroll_by(&self, rows: isize, cols: isize) {
+        let offset = self.offset();
+        let add =
+            |v: u16, d: isize| (v as isize).saturating_add(d).clamp(0, u16::MAX as isize) as u16;
+        self.scroll_to(add(offset.row, rows), add(offset.col, cols));
+    }
+
+    /// draws the visible part of the widget into the rect, with scrollbars, if it
+    /// doesn't fit
+    pub fn render_at(&self, frame: &mut Frame, rect: Rect) -> io::Result<()> {
+        let content = self.widget.size();
+        let mut drawn = frame.blank(content);
+        self.widget
+            .render_at(&mut drawn, Rect::new(0, 0, content.w, content.h))?;
+
+        let (viewport, v_bar, h_bar) = viewport_of(content, rect);
+        let mut state = self.state.lock().unwrap();
+        state.content = content;
+        state.viewport = viewport;
+        state.clamp();
+        let offset = state.offset;
+        drop(state);
+
+        let visible = Rect {
+            pos: offset,
+            size: Size {
+                w: viewport.size.w.min(content.w),
+                h: viewport.size.h.min(content.h),
+            },
+        };
+        if let Some(mut cells) = drawn.cells_in(visible) {
+            for row in cells.chunks_mut(visible.size.w.max(1) as usize) {
+                clip_wide_chars(row);
+            }
+            frame.set_cells_in(
+                Rect {
+                    pos: rect.pos,
+                    ..visible
+                },
+                &cells,
+            );
+        }
+
+        if v_bar {
+            let thumb = thumb(viewport.size.h, content.h, offset.row);
+            let col = viewport.pos.col + viewport.size.w;
+            for i in 0..viewport.size.h {
+                let pos = BufferPosition::new(viewport.pos.row + i, col);
+                draw_bar_cell(frame, pos, thumb.contains(&i), "\u{2502}");
+            }
+        }
+        if h_bar {
+            let thumb = thumb(viewport.size.w, content.w, offset.col);
+            let row = viewport.pos.row + viewport.size.h;
+            for i in 0..viewport.size.w {
+                let pos = BufferPosition::new(row, viewport.pos.col + i);
+                draw_bar_cell(frame, pos, thumb.contains(&i), "\u{2500}");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl State {
+    fn clamp(&mut self) {
+        let max_row = self.content.h.saturating_sub(self.viewport.size.h);
+        let max_col = self.content.w.saturating_sub(self.viewport.size.w);
+        self.offset.row = self.offset.row.min(max_row);
+        self.offset.col = self.offset.col.min(max_col);
+    }
+}
+
+impl<W: Widget> EventHandler<()> for ScrollView<W> {
+    fn handle(&mut self, ev: &Event, _buf: &BufferRef, _ctx: &mut AbletCtx) -> HandlerResult<()> {
+        let (viewport, content) = {
+            let state = self.state.lock().unwrap();
+            (state.viewport, state.content)
+        };
+        let page = viewport.size.h.max(1) as isize;
+        let (rows, cols) = match ev {
+            Event::Key(ke) if ke.kind != KeyEventKind::Release => match ke.code {
+                KeyCode::Up => (-1, 0),
+                KeyCode::Down => (1, 0),
+                KeyCode::Left => (0, -1),
+                KeyCode::Right => (0, 1),
+                KeyCode::PageUp => (-page, 0),
+                KeyCode::PageDown => (page, 0),
+                KeyCode::Home => (-(content.h as isize), 0),
+                KeyCode::End => (content.h as isize, 0),
+                _ => return HandlerResult::Ignored,
+            },
+            Event::Mouse(me) if viewport.contains(BufferPosition::new(me.row, me.column)) => {
+                let shift = me.modifiers.contains(KeyModifiers::SHIFT);
+                match me.kind {
+                    MouseEventKind::ScrollUp if shift => (0, -WHEEL_STEP),
+                    MouseEventKind::ScrollDown if shift => (0, WHEEL_STEP),
+                    MouseEventKind::ScrollUp => (-WHEEL_STEP, 0),
+                    MouseEventKind::ScrollDown => (WHEEL_STEP, 0),
+                    MouseEventKind::ScrollLeft => (0, -WHEEL_STEP),
+                    MouseEventKind::ScrollRight => (0, WHEEL_STEP),
+                    _ => return HandlerResult::Ignored,
+                }
+            }
+            _ => return HandlerResult::Ignored,
+        };
+        self.scroll_by(rows, cols);
+        HandlerResult::Handled
+    }
+}
+
+/// The part of the rect that shows the content, and whether there is a vertical and a
+/// horizontal scrollbar. Each scrollbar takes space that the other one might need
+fn viewport_of(content: Size, rect: Rect) -> (Rect, bool, bool) {
+    let Size { w, h } = rect.size;
+    let v_bar = content.h > h;
+    let h_bar = content.w > w.saturating_sub(v_bar as u16);
+    let v_bar = content.h > h.saturating_sub(h_bar as u16);
+    let size = Size {
+        w: w.saturating_sub(v_bar as u16),
+        h: h.saturating_sub(h_bar as u16),
+    };
+    (Rect { size, ..rect }, v_bar, h_bar)
+}
+
+/// the cells of a scrollbar that are covered by its thumb
+fn thumb(track: u16, content: u16, offset: u16) -> std::ops::Range<u16> {
+    let (track, content, offset) = (track as u32, content as u32, offset as u32);
+    let len = (track * track / content.max(1)).clamp(1, track);
+    let max_offset = content.saturating_sub(track);
+    let start = (offset * (track - len) + max_offset / 2)
+        .checked_div(max_offset)
+        .unwrap_or(0);
+    start as u16..(start + len) as u16
+}
+
+fn draw_bar_cell(frame: &mut Frame, pos: BufferPosition, on_thumb: bool, track: &str) {
+    let (symbol, style) = if on_thumb {
+        ("\u{2588}", ContentStyle::new())
+    } else {
+        (track, ContentStyle::new().dark_grey())
+    };
+    frame.print(pos, symbol, style, pos.col + 1);
+}
+
+/// blanks the halves of wide chars that were cut off at the edges of a row
+fn clip_wide_chars(row: &mut [Cell]) {
+    if let Some(first) = row.first_mut().filter(|c| c.symbol.is_empty()) {
+        first.symbol = " ".into();
+    }
+    if let Some(last) = row.last_mut().filter(|c| c.symbol.width() > 1) {
+        last.symbol = " ".into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyEvent, MouseEvent};
+
+    use super::*;
+    use crate::{Buffer, Table};
+
+    fn numbered(size: Size) -> Frame {
+        let mut frame = Frame::new(size);
+        for row in 0..size.h {
+            let line = (0..size.w)
+                .map(|col| char::from(b'a' + row as u8 + col as u8))
+                .collect::<String>();
+            frame.print(
+                BufferPosition::new(row, 0),
+                &line,
+                ContentStyle::new(),
+                size.w,
+            );
+        }
+        frame
+    }
+
+    #[test]
+    fn test_scrolled_view_is_clipped() {
+        let view = ScrollView::new(numbered(Size { w: 8, h: 6 }));
+        let render = |view: &ScrollView<Frame>| {
+            let mut frame = Frame::new(Size { w: 6, h: 5 });
+            view.render_at(&mut frame, Rect::new(1, 1, 5, 4)).unwrap();
+            frame.to_lines()
+        };
+        assert_eq!(
+            render(&view),
+            vec![
+                "      ",
+                " abcd\u{2588}",
+                " bcde\u{2502}",
+                " cdef\u{2502}",
+                " \u{2588}\u{2588}\u{2500}\u{2500} ",
+            ]
+        );
+
+        view.scroll_by(2, 10);
+        assert_eq!(view.offset(), BufferPosition::new(2, 4));
+        assert_eq!(
+            render(&view),
+            vec![
+                "      ",
+                " ghij\u{2502}",
+                " hijk\u{2588}",
+                " ijkl\u{2502}",
+                " \u{2500}\u{2500}\u{2588}\u{2588} ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keys_and_wheel_scroll() {
+        let mut view = ScrollView::new(|| numbered(Size { w: 4, h: 10 }));
+        let mut frame = Frame::new(Size { w: 5, h: 3 });
+        view.render_at(&mut frame, Rect::new(0, 0, 5, 3)).unwrap();
+
+        let buf = Buffer::new().into_ref();
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let mut handle = |view: &mut ScrollView<_>, ev| view.handle(&ev, &buf, &mut ctx);
+        let key = |code| Event::Key(KeyEvent::from(code));
+        let wheel = |kind, row| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column: 1,
+                row,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+
+        assert_eq!(
+            handle(&mut view, key(KeyCode::Down)),
+            HandlerResult::Handled
+        );
+        assert_eq!(
+            handle(&mut view, key(KeyCode::PageDown)),
+            HandlerResult::Handled
+        );
+        assert_eq!(view.offset(), BufferPosition::new(4, 0));
+        handle(&mut view, key(KeyCode::End));
+        assert_eq!(view.offset(), BufferPosition::new(7, 0));
+        handle(&mut view, wheel(MouseEventKind::ScrollUp, 1));
+        assert_eq!(view.offset(), BufferPosition::new(4, 0));
+        // outside of the view
+        let res = handle(&mut view, wheel(MouseEventKind::ScrollUp, 3));
+        assert_eq!(res, HandlerResult::Ignored);
+        assert_eq!(
+            handle(&mut view, key(KeyCode::Char('x'))),
+            HandlerResult::Ignored
+        );
+        handle(&mut view, key(KeyCode::Home));
+        assert_eq!(view.offset(), BufferPosition::new(0, 0));
+    }
+
+    #[test]
+    fn test_table_columns_are_scrolled() {
+        let table = Table::new(vec!["first".into(), "second".into()]);
+        table.add_row(vec!["1".into(), "2".into()]);
+        let view = ScrollView::new(table);
+        let mut frame = Frame::new(Size { w: 8, h: 3 });
+        view.render_at(&mut frame, Rect::new(0, 0, 8, 3)).unwrap();
+        // to the end of the last column, behind `first` and the separator
+        view.scroll_by(0, 100);
+        assert_eq!(view.offset(), BufferPosition::new(0, 8));
+        view.render_at(&mut frame, Rect::new(0, 0, 8, 3)).unwrap();
+        assert_eq!(frame.to_lines()[..2], [" second ", " 2      "]);
+    }
+}
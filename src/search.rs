@@ -0,0 +1,154 @@
+//! Searching documents, and an interactive search for buffers
+
+use crossterm::event::{Event, KeyCode};
+
+use crate::{
+    AbletCtx, BufferRef, EventHandler, HandlerResult, Range, SimpleLineHandler,
+    SimpleLineHandlerResult,
+};
+
+/// Something that can be searched for in a document, see [DocumentRef::find](crate::DocumentRef::find).
+/// With the `regex` feature, it's implemented for `regex::Regex`.
+pub trait SearchPattern {
+    /// returns the byte ranges of all non-overlapping, non-empty matches in order
+    fn find_in(&self, text: &str) -> Vec<Range<usize>>;
+}
+
+impl SearchPattern for &str {
+    fn find_in(&self, text: &str) -> Vec<Range<usize>> {
+        if self.is_empty() {
+            return vec![];
+        }
+        text.match_indices(self)
+            .map(|(start, m)| Range::new(start, start + m.len()))
+            .collect()
+    }
+}
+
+impl SearchPattern for String {
+    fn find_in(&self, text: &str) -> Vec<Range<usize>> {
+        self.as_str().find_in(text)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl SearchPattern for regex::Regex {
+    fn find_in(&self, text: &str) -> Vec<Range<usize>> {
+        self.find_iter(text)
+            .filter(|m| !m.is_empty())
+            .map(|m| Range::new(m.start(), m.end()))
+            .collect()
+    }
+}
+
+/// An incremental search, meant to be used on a prompt: every change to the query
+/// highlights its matches in the target buffer. Enter moves the cursor of the target to
+/// the next match and keeps the highlights, so the app can bind e.g. `n` and `N` to
+/// [BufferRef::goto_next_match] and [BufferRef::goto_prev_match]. Escape and C-c
+/// clear them. The query is edited like with a [SimpleLineHandler], and stays in
+/// the prompt, so it should be taken afterwards.
+pub struct SearchHandler {
+    target: BufferRef,
+    line_handler: SimpleLineHandler,
+}
+
+pub enum SearchResult {
+    Done,
+    Abort,
+}
+
+impl SearchHandler {
+    pub fn new(target: BufferRef) -> Self {
+        Self {
+            target,
+            line_handler: SimpleLineHandler::new(),
+        }
+    }
+}
+
+impl EventHandler<SearchResult> for SearchHandler {
+    fn handle(
+        &mut self,
+        ev: &Event,
+        buf: &BufferRef,
+        ctx: &mut AbletCtx,
+    ) -> HandlerResult<SearchResult> {
+        if let Event::Key(ke) = ev {
+            if ke.code == KeyCode::Esc {
+                self.target.clear_highlights();
+                return HandlerResult::Done(SearchResult::Abort);
+            }
+        }
+
+        match self.line_handler.handle(ev, buf, ctx) {
+            HandlerResult::Done(SimpleLineHandlerResult::LineDone) => {
+                self.target.goto_next_match();
+                HandlerResult::Done(SearchResult::Done)
+            }
+            HandlerResult::Done(SimpleLineHandlerResult::Abort) => {
+                self.target.clear_highlights();
+                HandlerResult::Done(SearchResult::Abort)
+            }
+            HandlerResult::Ignored => HandlerResult::Ignored,
+            HandlerResult::Handled => {
+                let query = buf.get_doc().0.lock().unwrap().content.text.clone();
+                self.target.highlight_matches(query);
+                HandlerResult::Handled
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_str() {
+        assert_eq!(
+            "ab".find_in("abcab\nxab"),
+            [Range::new(0, 2), Range::new(3, 5), Range::new(7, 9)]
+        );
+        assert!("".find_in("abc").is_empty());
+    }
+
+    #[test]
+    fn test_search_handler() {
+        use crossterm::event::{KeyEvent, KeyModifiers};
+
+        use crate::{Buffer, TextOffset};
+
+        let target = Buffer::from_text("foo bar\nfoo baz\nfoo").into_ref();
+        let prompt = Buffer::new().into_ref();
+        let mut handler = SearchHandler::new(target.clone());
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+
+        for c in "foo".chars() {
+            assert!(handler
+                .handle(&key(KeyCode::Char(c)), &prompt, &mut ctx)
+                .done()
+                .is_none());
+        }
+        assert!(matches!(
+            handler.handle(&key(KeyCode::Enter), &prompt, &mut ctx),
+            HandlerResult::Done(SearchResult::Done)
+        ));
+        assert_eq!(target.cursor(), TextOffset::new(8));
+
+        target.goto_next_match();
+        assert_eq!(target.cursor(), TextOffset::new(16));
+        target.goto_next_match();
+        assert_eq!(target.cursor(), TextOffset::new(0));
+        target.goto_prev_match();
+        assert_eq!(target.cursor(), TextOffset::new(16));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_find_regex() {
+        let re = regex::Regex::new(r"\d+|x*").unwrap();
+        assert_eq!(re.find_in("a12b3"), [Range::new(1, 3), Range::new(4, 5)]);
+    }
+}
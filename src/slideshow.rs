@@ -0,0 +1,258 @@
+//! Contains Slideshow, which shows slides full-screen, e.g. for a talk in the terminal
+
+use std::io;
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEventKind},
+    style::{ContentStyle, Stylize},
+};
+
+use crate::{
+    split_tree, AText, ATextBuilder, Ablet, AbletCtx, Buffer, BufferRef, EventHandler,
+    HandlerResult, SplitTree, TextOffset,
+};
+
+/// A deck of slides, of which one is shown at a time, with a progress bar below it, and
+/// optionally the speaker notes of the slide in a second split. Go through them with
+/// [Slideshow::next_slide] and [Slideshow::prev_slide], or run it with
+/// [Slideshow::run], which binds them to keys. It's an [EventHandler] too, for apps
+/// with their own loop
+pub struct Slideshow {
+    slides: Vec<AText>,
+    notes: Vec<AText>,
+    current: usize,
+    slide_buf: BufferRef,
+    progress_buf: BufferRef,
+    notes_buf: Option<BufferRef>,
+}
+
+impl Slideshow {
+    /// Shows the first of the slides. There must be at least one
+    pub fn new(slides: Vec<AText>) -> Self {
+        assert!(!slides.is_empty(), "a slideshow needs slides");
+        let read_only = || {
+            let buf = Buffer::new().into_ref();
+            buf.get_doc().set_read_only(true);
+            buf
+        };
+        let res = Self {
+            slides,
+            notes: vec![],
+            current: 0,
+            slide_buf: read_only(),
+            progress_buf: read_only(),
+            notes_buf: None,
+        };
+        res.show();
+        res
+    }
+
+    /// Slides in Markdown, separated by lines that are `---`. The notes of a slide
+    /// follow a line that is `???`, like in remark.js
+    #[cfg(feature = "markdown")]
+    pub fn from_markdown(markdown: &str) -> Self {
+        let mut slides = vec![];
+        let mut notes = vec![];
+        for part in split_at_lines(markdown, "---") {
+            let mut part = split_at_lines(&part, "???").into_iter();
+            slides.push(AText::from_markdown(&part.next().unwrap_or_default()));
+            notes.push(AText::from_markdown(&part.collect::<Vec<_>>().join("\n")));
+        }
+        let show_notes = notes.iter().any(|n| !n.is_empty());
+        let res = Self::new(slides);
+        if show_notes {
+            res.with_notes(notes)
+        } else {
+            res
+        }
+    }
+
+    /// shows the notes of the slides, in the order of the slides, in a second split
+    pub fn with_notes(mut self, notes: Vec<AText>) -> Self {
+        self.notes = notes;
+        let buf = Buffer::new().into_ref();
+        buf.get_doc().set_read_only(true);
+        self.notes_buf = Some(buf);
+        self.show();
+        self
+    }
+
+    /// the split tree with the slide, the progress bar, and the notes, if there are any
+    pub fn tree(&self) -> SplitTree {
+        let slide = self.slide_buf.clone();
+        let progress = self.progress_buf.clone();
+        match self.notes_buf.clone() {
+            Some(notes) => split_tree!(Vertical: {
+                3: slide,
+                1!: progress,
+                1: notes,
+            }),
+            None => split_tree!(Vertical: {
+                1: slide,
+                1!: progress,
+            }),
+        }
+    }
+
+    /// the index of the shown slide
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn len(&self) -> usize {
+        self.slides.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slides.is_empty()
+    }
+
+    /// shows the next slide, returns false if the last one is shown already
+    pub fn next_slide(&mut self) -> bool {
+        self.go_to(self.current + 1)
+    }
+
+    /// shows the previous slide, returns false if the first one is shown already
+    pub fn prev_slide(&mut self) -> bool {
+        self.current > 0 && self.go_to(self.current - 1)
+    }
+
+    /// shows the slide with the index, returns false if there is none
+    pub fn go_to(&mut self, i: usize) -> bool {
+        if i >= self.slides.len() || i == self.current {
+            return i == self.current;
+        }
+        self.current = i;
+        self.show();
+        true
+    }
+
+    /// Shows the slides full-screen, until q or Esc is pressed. Right, Space and
+    /// PageDown go to the next slide, Left, Backspace and PageUp to the previous one,
+    /// and Home and End to the first and last one. Expects the terminal to be set up
+    /// like [with_setup_terminal](crate::with_setup_terminal) does it
+    pub fn run(&mut self) -> io::Result<()> {
+        let ablet = Ablet::new(self.tree()).without_prompt();
+        ablet.edit_focused(self)
+    }
+
+    fn show(&self) {
+        let set = |buf: &BufferRef, text: AText| {
+            buf.get_doc().update_content(|content| *content = text);
+            buf.set_cursor(TextOffset::ZERO);
+        };
+        set(&self.slide_buf, self.slides[self.current].clone());
+        if let Some(notes_buf) = &self.notes_buf {
+            let notes = self.notes.get(self.current).cloned().unwrap_or_default();
+            set(notes_buf, notes);
+        }
+        set(&self.progress_buf, self.progress_bar());
+    }
+
+    /// `━━━━──── 3/8`, the bar is filled up to the shown slide
+    fn progress_bar(&self) -> AText {
+        const WIDTH: usize = 20;
+        let n = self.slides.len();
+        let filled = (self.current + 1) * WIDTH / n;
+        let style = ContentStyle::new().dark_grey();
+        let mut res = ATextBuilder::new();
+        res.push_styled(&"\u{2501}".repeat(filled), ContentStyle::new().green());
+        res.push_styled(&"\u{2500}".repeat(WIDTH - filled), style);
+        res.push_styled(&format!(" {}/{n}", self.current + 1), style);
+        res.build()
+    }
+}
+
+impl EventHandler<()> for Slideshow {
+    fn handle(&mut self, ev: &Event, _buf: &BufferRef, _ctx: &mut AbletCtx) -> HandlerResult<()> {
+        let Event::Key(ke) = ev else {
+            return HandlerResult::Ignored;
+        };
+        if ke.kind == KeyEventKind::Release {
+            return HandlerResult::Ignored;
+        }
+        match ke.code {
+            KeyCode::Char('q') | KeyCode::Esc => return HandlerResult::Done(()),
+            KeyCode::Right | KeyCode::Char(' ') | KeyCode::PageDown => {
+                self.next_slide();
+            }
+            KeyCode::Left | KeyCode::Backspace | KeyCode::PageUp => {
+                self.prev_slide();
+            }
+            KeyCode::Home => {
+                self.go_to(0);
+            }
+            KeyCode::End => {
+                self.go_to(self.slides.len() - 1);
+            }
+            _ => return HandlerResult::Ignored,
+        }
+        HandlerResult::Handled
+    }
+}
+
+/// splits the text at the lines that are the separator, ignoring surrounding spaces
+#[cfg(feature = "markdown")]
+fn split_at_lines(text: &str, separator: &str) -> Vec<String> {
+    let mut res = vec![String::new()];
+    for line in text.lines() {
+        if line.trim() == separator {
+            res.push(String::new());
+        } else {
+            let part = res.last_mut().unwrap();
+            part.push_str(line);
+            part.push('\n');
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEvent;
+
+    use super::*;
+
+    fn text(buf: &BufferRef) -> String {
+        buf.get_doc().0.lock().unwrap().content.text.clone()
+    }
+
+    #[test]
+    fn test_slideshow() {
+        let mut show = Slideshow::new(vec!["a".into(), "b".into(), "c".into()])
+            .with_notes(vec!["note a".into()]);
+        assert_eq!(text(&show.slide_buf), "a");
+        assert_eq!(text(show.notes_buf.as_ref().unwrap()), "note a");
+        assert!(text(&show.progress_buf).ends_with(" 1/3"));
+
+        let buf = Buffer::new().into_ref();
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let mut press = |show: &mut Slideshow, code| {
+            show.handle(&Event::Key(KeyEvent::from(code)), &buf, &mut ctx)
+        };
+        assert_eq!(press(&mut show, KeyCode::Right), HandlerResult::Handled);
+        assert_eq!(text(&show.slide_buf), "b");
+        // slides without notes have empty ones
+        assert_eq!(text(show.notes_buf.as_ref().unwrap()), "");
+        assert_eq!(press(&mut show, KeyCode::End), HandlerResult::Handled);
+        assert!(!show.next_slide());
+        assert_eq!(show.current(), 2);
+        assert!(text(&show.progress_buf).ends_with(" 3/3"));
+        assert_eq!(press(&mut show, KeyCode::Home), HandlerResult::Handled);
+        assert!(!show.prev_slide());
+        assert_eq!(
+            press(&mut show, KeyCode::Char('q')),
+            HandlerResult::Done(())
+        );
+    }
+
+    #[cfg(feature = "markdown")]
+    #[test]
+    fn test_slides_from_markdown() {
+        let show = Slideshow::from_markdown("# One\n???\nsay hi\n---\n# Two\n");
+        assert_eq!(show.len(), 2);
+        assert_eq!(text(&show.slide_buf), "One");
+        assert_eq!(text(show.notes_buf.as_ref().unwrap()), "say hi");
+    }
+}
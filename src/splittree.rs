@@ -1,18 +1,19 @@
 use std::{
+    cmp::Reverse,
     collections::HashMap,
-    io::{self, Write},
-    iter,
+    fmt::Write as _,
+    io::{self},
+    thread,
+    time::Duration,
 };
 
-use crossterm::{
-    cursor, execute, queue,
-    style::Print,
-    terminal::{Clear, ClearType},
-};
-use derive_more::Constructor;
+use crossterm::style::{ContentStyle, Stylize};
 use itertools::{enumerate, izip, Itertools};
 
-use crate::{BufferPosition, BufferRef, Orientation, Rect, Size};
+use crate::{
+    named_style, renderer::present, throttle::log_render_error, BufferPosition, BufferRef,
+    BufferRegistry, CrosstermRenderer, Frame, Orientation, Rect, Renderer, Size, Throttle,
+};
 
 /// How window is subdivided into splits.
 ///
@@ -22,68 +23,256 @@ use crate::{BufferPosition, BufferRef, Orientation, Rect, Size};
 /// horizontal
 ///
 /// Splits are ephemeral --- there are no SplitRefs, you can get-set the whole tree at once.
-#[derive(Constructor, Clone)]
+#[derive(Clone, Debug)]
 pub struct SplitTree {
-    root: Split,
-    top_orientation: Orientation,
+    pub(crate) root: Split,
+    pub(crate) top_orientation: Orientation,
+    /// used by [SplitTree::render_at_most_every], shared by clones of the tree
+    render_throttle: Throttle,
 }
 
 pub(crate) struct SplitMap {
     pub(crate) rects: HashMap<Rect, BufferRef>,
     pub(crate) border_map: BorderMap,
+    /// the title bars of stacked splits
+    pub(crate) title_bars: Vec<TitleBar>,
+}
+
+/// the title bar of a child of a stacked split
+pub(crate) struct TitleBar {
+    pub(crate) rect: Rect,
+    pub(crate) title: String,
+    pub(crate) expanded: bool,
+    /// the first buffer of the child, activating it expands the child
+    pub(crate) target: BufferRef,
 }
 
 impl SplitTree {
     const MIN_SPLIT_SIZE: Size = Size { w: 1, h: 1 };
 
+    pub fn new(root: Split, top_orientation: Orientation) -> Self {
+        Self {
+            root,
+            top_orientation,
+            render_throttle: Throttle::new(Duration::ZERO),
+        }
+    }
+
     /// Returns a map from rects to buffer refs, unless there is less than MIN_SPLIT_SIZE
     /// cells of space for a rect
     pub(crate) fn compute_rects(&self, term_size: (u16, u16)) -> Option<SplitMap> {
-        self.root.compute_rects(
-            Rect {
-                pos: BufferPosition::new(0, 0),
-                size: term_size.into(),
-            },
-            Self::MIN_SPLIT_SIZE,
-            self.top_orientation,
-        )
+        self.compute_rects_in(Rect {
+            pos: BufferPosition::new(0, 0),
+            size: term_size.into(),
+        })
     }
 
+    /// Like [SplitTree::compute_rects], but for the tree placed at the area. The border
+    /// map stays relative to the area
+    pub(crate) fn compute_rects_in(&self, area: Rect) -> Option<SplitMap> {
+        self.root
+            .compute_rects(area, Self::MIN_SPLIT_SIZE, self.top_orientation)
+    }
+
+    /// the first buffer of the tree, e.g. to focus it initially
+    pub(crate) fn first_buffer(&self) -> BufferRef {
+        self.root.content[0].first_buffer()
+    }
+
+    /// renders the tree to the terminal
     pub fn render(&self) -> io::Result<()> {
-        let term_size = crossterm::terminal::size()?;
+        // which makes a render that render_at_most_every deferred unnecessary
+        self.render_throttle.mark_run();
+        self.render_with(&mut CrosstermRenderer::stdout())
+    }
 
-        queue!(io::stdout(), Clear(ClearType::All))?;
-        let Some(SplitMap {
-            rects, border_map, ..
-        }) = self.compute_rects(term_size)
-        else {
-            return render_screen_too_small_info();
-        };
+    /// Renders the tree to the terminal, but at most once per interval. Renders
+    /// that come in too early are coalesced into one that happens when the interval
+    /// is over, unless the tree is rendered otherwise meanwhile, e.g. by the editing
+    /// loop. Meant for threads that render on every update, so they don't flood the
+    /// terminal with redraws. Each tree, and its clones, is throttled on its own
+    pub fn render_at_most_every(&self, interval: Duration) -> io::Result<()> {
+        let tree = self.clone();
+        let deferred = Box::new(move || log_render_error(tree.render()));
+        match self.render_throttle.schedule(interval, deferred) {
+            Some(_) => self.render(),
+            None => Ok(()),
+        }
+    }
+
+    /// renders the tree through the given renderer, using its size
+    pub fn render_with(&self, renderer: &mut dyn Renderer) -> io::Result<()> {
+        crate::timer::update_shown_timers();
+        let frame = self.render_to_frame(renderer.size()?)?;
+        present(frame, renderer)
+    }
+
+    /// draws all buffers and borders of the tree into a new frame of the given size
+    pub fn render_to_frame(&self, size: Size) -> io::Result<Frame> {
+        let mut frame = Frame::new(size);
+        self.draw_into(&mut frame, Rect::new(0, 0, size.w, size.h))?;
+        Ok(frame)
+    }
+
+    /// Renders the tree, and every collapsed child of its stacked splits, into a scratch
+    /// frame of the given size on a background thread. This fills the render caches of
+    /// the buffers, so showing the tree, e.g. when switching to it like to a workspace,
+    /// or expanding a child, is instantaneous, even for huge documents. The cells are
+    /// drawn anew once a document changes. Use [Ablet::prerender](crate::Ablet::prerender)
+    /// for trees that are shown in an Ablet
+    pub fn prerender(&self, size: Size) -> thread::JoinHandle<io::Result<()>> {
+        let area = Rect::new(0, 0, size.w, size.h);
+        let maps = self
+            .expansions()
+            .iter()
+            .filter_map(|tree| tree.compute_rects_in(area))
+            .collect();
+        prerender_maps(maps, size)
+    }
+
+    /// the tree, and a copy of it for each child of its stacked splits, with the child
+    /// expanded
+    pub(crate) fn expansions(&self) -> Vec<SplitTree> {
+        let mut targets = vec![];
+        self.root.stack_targets(&mut targets);
+        let mut res = vec![self.clone()];
+        for target in targets {
+            let mut tree = self.clone();
+            tree.activate(&target);
+            res.push(tree);
+        }
+        res
+    }
+
+    /// Expands the children of stacked splits that contain the buffer, so it becomes
+    /// visible. Returns false if the buffer isn't in the tree
+    pub fn activate(&mut self, buf: &BufferRef) -> bool {
+        self.root.activate(buf)
+    }
+
+    /// Expands the next (or previous) child of the innermost stacked split that contains
+    /// the buffer, wrapping around, and returns its first buffer, which should be focused
+    pub fn cycle_stack(&mut self, buf: &BufferRef, forward: bool) -> Option<BufferRef> {
+        self.root.cycle_stack(buf, forward)
+    }
+
+    /// Shows the new buffer wherever the old one is shown, keeping the sizes and
+    /// everything else as is. Returns false if the old buffer isn't in the tree
+    pub fn replace_buffer(&mut self, old: &BufferRef, new: &BufferRef) -> bool {
+        self.root.replace_buffer(old, new)
+    }
+
+    /// Removes the buffer from the tree. Splits that are left with a single child are
+    /// replaced by it. Returns false if the buffer isn't in the tree, or is the only
+    /// thing in it, since a tree can't be empty
+    pub fn remove(&mut self, buf: &BufferRef) -> bool {
+        let mut root = self.root.clone();
+        if !root.remove(buf, self.top_orientation) || root.content.is_empty() {
+            return false;
+        }
+        self.root = root;
+        true
+    }
+
+    /// returns a copy of the tree, where the proportions of the splits that contain
+    /// the buffer are multiplied by the factor
+    pub(crate) fn biased_towards(&self, buf: &BufferRef, factor: u16) -> SplitTree {
+        SplitTree {
+            root: self.root.biased_towards(buf, factor),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a human-readable dump of the tree, with the sizes as in [split_tree],
+    /// the names of the buffers in the registry, and the rects they get at the given
+    /// size. Meant for debugging layouts that don't fit or look wrong.
+    pub fn describe(&self, size: Size, registry: Option<&BufferRegistry>) -> String {
+        let split_map = self.compute_rects((size.w, size.h));
+        let orientation = self.root.orientation.unwrap_or(self.top_orientation);
+        let mut res = format!("{orientation:?} split at {}x{}", size.w, size.h);
+        if split_map.is_none() {
+            res.push_str(", doesn't fit");
+        }
+        res.push('\n');
+        describe_split(
+            &self.root,
+            orientation,
+            1,
+            split_map.as_ref(),
+            registry,
+            &mut res,
+        );
+        res
+    }
+
+    /// draws the tree into the area of the frame
+    pub(crate) fn draw_into(&self, frame: &mut Frame, area: Rect) -> io::Result<()> {
+        match self.compute_rects_in(area) {
+            Some(split_map) => split_map.draw_into(frame, area),
+            None => {
+                render_screen_too_small_info(frame);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// renders the buffers of the maps into a scratch frame on a background thread, see
+/// [SplitTree::prerender]
+pub(crate) fn prerender_maps(
+    maps: Vec<SplitMap>,
+    size: Size,
+) -> thread::JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let mut frame = Frame::new(size);
+        for (rect, buffer) in maps.into_iter().flat_map(|map| map.rects) {
+            buffer.render_at(&mut frame, rect)?;
+        }
+        Ok(())
+    })
+}
+
+impl SplitMap {
+    /// draws the buffers, borders and title bars into the area the map was computed for
+    pub(crate) fn draw_into(self, frame: &mut Frame, area: Rect) -> io::Result<()> {
+        let SplitMap {
+            rects,
+            border_map,
+            title_bars,
+        } = self;
 
         for (rect, buffer) in rects {
-            buffer.render_at(rect)?;
+            buffer.render_at(frame, rect)?;
         }
 
-        let mut stdout = io::stdout();
+        let border = named_style("border");
         for (row_i, row) in enumerate(border_map.0) {
             for (col_i, field) in enumerate(row) {
+                let pos =
+                    BufferPosition::new(area.pos.row + row_i as u16, area.pos.col + col_i as u16);
+                let max_col = area.pos.col + area.size.w;
                 if field.in_vertical_border {
-                    queue!(
-                        stdout,
-                        cursor::MoveTo(col_i as u16, row_i as u16),
-                        Print("\u{2502}")
-                    )?;
+                    frame.print(pos, "\u{2502}", border, max_col);
                 } else if field.in_horizontal_border {
-                    queue!(
-                        stdout,
-                        cursor::MoveTo(col_i as u16, row_i as u16),
-                        Print("\u{2500}")
-                    )?;
+                    frame.print(pos, "\u{2500}", border, max_col);
                 }
             }
         }
 
-        stdout.flush()
+        for bar in title_bars {
+            let marker = if bar.expanded { '\u{25be}' } else { '\u{25b8}' };
+            let modified = if bar.target.get_doc().is_modified() {
+                " *"
+            } else {
+                ""
+            };
+            let title = format!("{}{modified}", bar.title);
+            let text = format!("{marker} {title:w$}", w = bar.rect.size.w as usize);
+            let max_col = bar.rect.pos.col + bar.rect.size.w;
+            frame.print(bar.rect.pos, &text, ContentStyle::new().reverse(), max_col);
+        }
+
+        Ok(())
     }
 }
 
@@ -130,227 +319,526 @@ pub struct BorderInfo {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SplitSize {
     Proportion(u16),
     Fixed(u16),
 }
 
-#[derive(Constructor, Clone)]
+#[derive(Clone, Debug)]
 pub struct Split {
-    sizes: Vec<SplitSize>,
-    content: Vec<SplitContent>,
+    pub(crate) sizes: Vec<SplitSize>,
+    pub(crate) content: Vec<SplitContent>,
+    /// overrides the orientation, which is the flip of the parent's otherwise
+    pub(crate) orientation: Option<Orientation>,
+    pub(crate) stack: Option<Stack>,
+}
+
+/// Makes a split stacked, see [Split::stacked]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stack {
+    /// the index of the expanded child
+    pub active: usize,
+    /// the titles of the children. Children without one show their number
+    pub titles: Vec<String>,
 }
 
 impl Split {
-    pub(crate) fn compute_rects(
-        &self,
-        rect: Rect,
-        min_split_size: Size,
-        orientation: Orientation,
-    ) -> Option<SplitMap> {
-        assert!(!self.sizes.is_empty(), "emtpy splits aren't allowed");
+    pub fn new(sizes: Vec<SplitSize>, content: Vec<SplitContent>) -> Self {
+        Self {
+            sizes,
+            content,
+            orientation: None,
+            stack: None,
+        }
+    }
 
-        let fixed_sizes = self
-            .sizes
-            .iter()
-            .enumerate()
-            .filter_map(|(i, x)| {
-                if let SplitSize::Fixed(x) = x {
-                    // the first elem in a split will have the specified size
-                    // all others will have an extra separator
-                    if i == 0 {
-                        Some(*x)
-                    } else {
-                        Some(*x + 1)
+    /// Stacks the children on top of each other, like an accordion: only the active
+    /// one is expanded, the others are collapsed to a title bar of one row. The sizes
+    /// and orientation are ignored. Clicking a title bar expands its child, and
+    /// [SplitTree::activate] and [SplitTree::cycle_stack] can be bound to keys
+    pub fn stacked(mut self, titles: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.stack = Some(Stack {
+            active: 0,
+            titles: titles.into_iter().map(Into::into).collect(),
+        });
+        self
+    }
+
+    /// Gives the split a fixed orientation, instead of the flip of its parent's,
+    /// which allows nesting splits of the same direction
+    pub fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = Some(orientation);
+        self
+    }
+
+    fn activate(&mut self, buf: &BufferRef) -> bool {
+        let mut found = false;
+        for (i, content) in enumerate(&mut self.content) {
+            let contains_buf = match content {
+                SplitContent::Leaf(leaf) => leaf == buf,
+                SplitContent::Branch(split) => split.activate(buf),
+            };
+            if let (true, Some(stack)) = (contains_buf, &mut self.stack) {
+                stack.active = i;
+            }
+            found |= contains_buf;
+        }
+        found
+    }
+
+    /// collects the first buffers of the children of stacked splits
+    fn stack_targets(&self, res: &mut Vec<BufferRef>) {
+        for content in &self.content {
+            if self.stack.is_some() {
+                res.push(content.first_buffer());
+            }
+            if let SplitContent::Branch(split) = content {
+                split.stack_targets(res);
+            }
+        }
+    }
+
+    fn cycle_stack(&mut self, buf: &BufferRef, forward: bool) -> Option<BufferRef> {
+        let i = self.content.iter().position(|content| match content {
+            SplitContent::Leaf(leaf) => leaf == buf,
+            SplitContent::Branch(split) => split.contains(buf),
+        })?;
+        if let SplitContent::Branch(split) = &mut self.content[i] {
+            if let Some(next) = split.cycle_stack(buf, forward) {
+                return Some(next);
+            }
+        }
+
+        let len = self.content.len();
+        let stack = self.stack.as_mut()?;
+        stack.active = if forward {
+            (i + 1) % len
+        } else {
+            (i + len - 1) % len
+        };
+        Some(self.content[stack.active].first_buffer())
+    }
+
+    fn replace_buffer(&mut self, old: &BufferRef, new: &BufferRef) -> bool {
+        let mut replaced = false;
+        for content in &mut self.content {
+            match content {
+                SplitContent::Leaf(leaf) if leaf == old => {
+                    *leaf = new.clone();
+                    replaced = true;
+                }
+                SplitContent::Leaf(_) => {}
+                SplitContent::Branch(split) => replaced |= split.replace_buffer(old, new),
+            }
+        }
+        replaced
+    }
+
+    /// the orientation that children get, if they don't override it, given the one
+    /// this split gets if it doesn't override it
+    fn child_orientation(&self, orientation: Orientation) -> Orientation {
+        if self.stack.is_some() {
+            Orientation::Horizontal
+        } else {
+            self.orientation.unwrap_or(orientation).flip()
+        }
+    }
+
+    /// see [SplitTree::remove], `orientation` is like in [Split::compute_rects]
+    fn remove(&mut self, buf: &BufferRef, orientation: Orientation) -> bool {
+        let child_orientation = self.child_orientation(orientation);
+        let mut removed = false;
+        let mut i = 0;
+        while i < self.content.len() {
+            let remove_child = match &mut self.content[i] {
+                SplitContent::Leaf(leaf) => leaf == buf,
+                SplitContent::Branch(split) => {
+                    removed |= split.remove(buf, child_orientation);
+                    split.content.is_empty()
+                }
+            };
+            if remove_child {
+                removed = true;
+                self.content.remove(i);
+                self.sizes.remove(i);
+                if let Some(stack) = &mut self.stack {
+                    if i < stack.titles.len() {
+                        stack.titles.remove(i);
+                    }
+                    if stack.active > i || stack.active >= self.content.len() {
+                        stack.active = stack.active.saturating_sub(1);
                     }
-                } else {
-                    None
                 }
-            })
-            .sum::<u16>();
+                continue;
+            }
 
-        let sum_proportions = self
-            .sizes
-            .iter()
-            .filter_map(|x| {
-                if let SplitSize::Proportion(h) = x {
-                    Some(h)
-                } else {
-                    None
+            if let SplitContent::Branch(split) = &mut self.content[i] {
+                if split.content.len() == 1 {
+                    let grandchild_orientation = split.child_orientation(child_orientation);
+                    let mut child = split.content.remove(0);
+                    // keep the orientation the child had below the removed split
+                    if let SplitContent::Branch(child) = &mut child {
+                        child.orientation.get_or_insert(grandchild_orientation);
+                    }
+                    self.content[i] = child;
                 }
-            })
-            .sum::<u16>() as f32;
-
-        let size_by_frac = |frac| match orientation {
-            Orientation::Horizontal => rect
-                .size
-                .update_w(|w| ((w as f32 - fixed_sizes as f32) * frac) as u16),
-            Orientation::Vertical => rect
-                .size
-                .update_h(|h| ((h as f32 - fixed_sizes as f32) * frac) as u16),
-        };
+            }
+            i += 1;
+        }
+        removed
+    }
 
-        let position_by_offset = |offset| match orientation {
-            Orientation::Horizontal => rect.pos.update_col(|c| c + offset),
-            Orientation::Vertical => rect.pos.update_row(|r| r + offset),
-        };
+    fn contains(&self, buf: &BufferRef) -> bool {
+        self.content.iter().any(|content| match content {
+            SplitContent::Leaf(leaf) => leaf == buf,
+            SplitContent::Branch(split) => split.contains(buf),
+        })
+    }
 
-        // all but the first split will get an additional border.
-        // This will happen later in the loop. The size in the relevant dimension will be reduces
-        // by one, and the offset will be increased by one, if a border is required.
-        // To make sure the splits have the sizes specified by the user, we need to add one
-        // in the relevant dimension to all but the first split for all fixed sizes
-        let split_sizes = {
-            let head_split_size = match self.sizes[0] {
-                SplitSize::Proportion(x) => size_by_frac(x as f32 / sum_proportions),
-                SplitSize::Fixed(x) => match orientation {
-                    Orientation::Horizontal => rect.size.with_w(x),
-                    Orientation::Vertical => rect.size.with_h(x),
-                },
+    fn biased_towards(&self, buf: &BufferRef, factor: u16) -> Split {
+        let mut res = self.clone();
+        for (size, content) in izip!(&mut res.sizes, &mut res.content) {
+            let contains_buf = match content {
+                SplitContent::Leaf(leaf) => leaf == buf,
+                SplitContent::Branch(split) if split.contains(buf) => {
+                    *split = split.biased_towards(buf, factor);
+                    true
+                }
+                SplitContent::Branch(_) => false,
             };
+            if let (true, SplitSize::Proportion(p)) = (contains_buf, size) {
+                *p = p.saturating_mul(factor);
+            }
+        }
+        res
+    }
 
-            let tail_split_sizes = self.sizes[1..].iter().map(|x| match x {
-                SplitSize::Proportion(x) => size_by_frac(*x as f32 / sum_proportions),
-                SplitSize::Fixed(x) => match orientation {
-                    Orientation::Horizontal => rect.size.with_w(*x + 1),
-                    Orientation::Vertical => rect.size.with_h(*x + 1),
-                },
-            });
-
-            iter::once(head_split_size).chain(tail_split_sizes)
-        };
-        // Prepare a list of bools that will be zipped with the content in the next loop,
-        // that tells us whether we're dealing with the last dynamically sized element in
-        // the split.
-        let mut is_last_dynamically_sized_elem = vec![false; self.sizes.len()];
-        let i_last_dynamically_sized_elem_from_back = self
-            .sizes
-            .iter()
-            .rev()
-            .find_position(|x| matches!(**x, SplitSize::Proportion(_)));
-        if let Some((i_from_back, _)) = i_last_dynamically_sized_elem_from_back {
-            let i = is_last_dynamically_sized_elem.len() - 1 - i_from_back;
-            is_last_dynamically_sized_elem[i] = true;
+    /// `orientation` is the one the split gets if it doesn't override it
+    pub(crate) fn compute_rects(
+        &self,
+        rect: Rect,
+        min_split_size: Size,
+        orientation: Orientation,
+    ) -> Option<SplitMap> {
+        assert!(!self.sizes.is_empty(), "emtpy splits aren't allowed");
+        if let Some(stack) = &self.stack {
+            return self.compute_stacked_rects(stack, rect, min_split_size);
         }
+        let orientation = self.orientation.unwrap_or(orientation);
 
-        let is_fixed_size = self.sizes.iter().map(|x| match x {
-            SplitSize::Proportion(_) => false,
-            SplitSize::Fixed(_) => true,
-        });
+        let extent = match orientation {
+            Orientation::Horizontal => rect.size.w,
+            Orientation::Vertical => rect.size.h,
+        };
 
         // iter over content to compute the split rects
-        let mut rects = HashMap::new();
-        let mut border_map = BorderMap::new(rect.size);
-        let mut current_offset = 0u16;
-        let mut used_dynamic_space = 0u16;
-        for (i, (content, mut elem_size, elem_is_last_dynamic_elem, elem_is_fixed_size)) in izip!(
-            &self.content,
-            split_sizes,
-            is_last_dynamically_sized_elem,
-            is_fixed_size
-        )
-        .enumerate()
+        let mut split_map = SplitMap::new(rect.size);
+        let mut offset = 0u16;
+        for (i, (content, elem_extent)) in
+            izip!(&self.content, distribute_sizes(&self.sizes, extent)).enumerate()
         {
-            let mut elem_pos = position_by_offset(current_offset);
+            let (elem_pos, elem_size) = match orientation {
+                Orientation::Horizontal => (
+                    rect.pos.update_col(|c| c + offset),
+                    rect.size.with_w(elem_extent),
+                ),
+                Orientation::Vertical => (
+                    rect.pos.update_row(|r| r + offset),
+                    rect.size.with_h(elem_extent),
+                ),
+            };
+            offset += elem_extent;
 
-            // because of how float to unsigned conversions work, the actual space used will be less or equal to
-            // the available space, so if we're at the last element, we add the remaining space
-            if elem_is_last_dynamic_elem {
+            // all elems but the first are separated from the previous one by a border,
+            // which takes their first row/col
+            let content_rect = if i == 0 {
+                Rect {
+                    pos: elem_pos,
+                    size: elem_size,
+                }
+            } else {
                 match orientation {
-                    Orientation::Horizontal => {
-                        let space_for_dynamic_buffers = rect.size.w - fixed_sizes;
-                        let dead_space =
-                            space_for_dynamic_buffers - used_dynamic_space - elem_size.w;
-                        elem_size.w += dead_space;
-                    }
-                    Orientation::Vertical => {
-                        let space_for_dynamic_buffers = rect.size.h - fixed_sizes;
-                        let dead_space =
-                            space_for_dynamic_buffers - used_dynamic_space - elem_size.h;
-                        elem_size.h += dead_space;
-                    }
+                    Orientation::Horizontal => Rect {
+                        pos: elem_pos.update_col(|c| c + 1),
+                        size: elem_size.update_w(|w| w.saturating_sub(1)),
+                    },
+                    Orientation::Vertical => Rect {
+                        pos: elem_pos.update_row(|r| r + 1),
+                        size: elem_size.update_h(|h| h.saturating_sub(1)),
+                    },
                 }
-            }
-
-            // update offset depending on orientation
-            let elem_offset = match orientation {
-                Orientation::Horizontal => elem_size.w,
-                Orientation::Vertical => elem_size.h,
             };
-            current_offset += elem_offset;
 
-            if !elem_is_fixed_size {
-                used_dynamic_space += elem_offset;
+            // make sure there is enought space for the elem
+            if content_rect.size.w < min_split_size.w || content_rect.size.h < min_split_size.h {
+                return None;
             }
 
-            // for all elems but the first we add a border between the current and the last elem
-            // and cut of the first row/col of the current elem for that
+            // the border map is relative to the rect of this split
+            let relative = relative_to(rect);
             if i > 0 {
                 match orientation {
-                    Orientation::Horizontal => {
-                        border_map.add_vertical(elem_pos, elem_size.h);
-                        elem_pos.col += 1;
-                        elem_size.w -= 1;
-                    }
-                    Orientation::Vertical => {
-                        border_map.add_horizontal(elem_pos, elem_size.w);
-                        elem_pos.row += 1;
-                        elem_size.h -= 1;
-                    }
-                };
+                    Orientation::Horizontal => split_map
+                        .border_map
+                        .add_vertical(relative(elem_pos), elem_size.h),
+                    Orientation::Vertical => split_map
+                        .border_map
+                        .add_horizontal(relative(elem_pos), elem_size.w),
+                }
             }
 
-            // make sure there is enought space for the elem
-            if elem_size.w < min_split_size.w || elem_size.h < min_split_size.h {
-                return None;
+            // now we know the contents rect, so lets process the content
+            split_map.add_content(
+                content,
+                content_rect,
+                rect,
+                min_split_size,
+                orientation.flip(),
+            )?;
+        }
+
+        Some(split_map)
+    }
+
+    fn compute_stacked_rects(
+        &self,
+        stack: &Stack,
+        rect: Rect,
+        min_split_size: Size,
+    ) -> Option<SplitMap> {
+        // one row per title bar, and the rest for the expanded child
+        let n_bars = self.content.len() as u16;
+        let expanded_h = rect.size.h.checked_sub(n_bars)?;
+        if expanded_h < min_split_size.h || rect.size.w < min_split_size.w {
+            return None;
+        }
+
+        let mut split_map = SplitMap::new(rect.size);
+        let mut row = rect.pos.row;
+        for (i, content) in enumerate(&self.content) {
+            let expanded = i == stack.active.min(self.content.len() - 1);
+            split_map.title_bars.push(TitleBar {
+                rect: Rect::new(row, rect.pos.col, rect.size.w, 1),
+                title: stack
+                    .titles
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| (i + 1).to_string()),
+                expanded,
+                target: content.first_buffer(),
+            });
+            row += 1;
+
+            if expanded {
+                let content_rect = Rect::new(row, rect.pos.col, rect.size.w, expanded_h);
+                split_map.add_content(
+                    content,
+                    content_rect,
+                    rect,
+                    min_split_size,
+                    Orientation::Horizontal,
+                )?;
+                row += expanded_h;
             }
+        }
+        Some(split_map)
+    }
+}
 
-            let rect = Rect {
-                pos: elem_pos,
-                size: elem_size,
-            };
+/// Distributes the available space among the elements of a split. The returned sizes
+/// include the border in front of all but the first element. Fixed sizes are honored,
+/// and the rest, minus the borders, is shared proportionally. If the fixed sizes don't
+/// fit, they are shrunk proportionally instead, and nothing is left for the others.
+fn distribute_sizes(sizes: &[SplitSize], available: u16) -> Vec<u16> {
+    let borders = sizes.len() as u32 - 1;
+    let content_space = (available as u32).saturating_sub(borders);
+    let fixed = sizes
+        .iter()
+        .map(|size| match size {
+            SplitSize::Fixed(x) => *x as u32,
+            SplitSize::Proportion(_) => 0,
+        })
+        .collect_vec();
+    let proportions = sizes
+        .iter()
+        .map(|size| match size {
+            SplitSize::Proportion(p) => *p as u32,
+            SplitSize::Fixed(_) => 0,
+        })
+        .collect_vec();
 
-            // now we know the contents rect, so lets process the content
-            match content {
-                SplitContent::Leaf(buffer) => {
-                    rects.insert(rect, buffer.clone());
-                }
-                SplitContent::Branch(next_split) => {
-                    let SplitMap {
-                        rects: inner_rects,
-                        border_map: inner_border_map,
-                    } = next_split.compute_rects(rect, min_split_size, orientation.flip())?;
-                    border_map.update(inner_border_map, rect.pos);
-                    rects.extend(inner_rects.into_iter())
-                }
+    let fixed = if fixed.iter().sum::<u32>() > content_space {
+        share_proportionally(content_space, &fixed)
+    } else {
+        fixed
+    };
+    let dynamic = share_proportionally(content_space - fixed.iter().sum::<u32>(), &proportions);
+    enumerate(izip!(fixed, dynamic))
+        .map(|(i, (fixed, dynamic))| (fixed + dynamic + u32::from(i > 0)) as u16)
+        .collect()
+}
+
+/// Splits the total into parts proportional to the weights: each part gets the integer
+/// part of its share, and what's left over goes to the parts with the largest fractional
+/// parts, so they add up to the total, unless all weights are 0
+fn share_proportionally(total: u32, weights: &[u32]) -> Vec<u32> {
+    let sum_weights = weights.iter().sum::<u32>();
+    if sum_weights == 0 {
+        return vec![0; weights.len()];
+    }
+
+    let mut res = weights
+        .iter()
+        .map(|w| total * w / sum_weights)
+        .collect_vec();
+    let left_over = total - res.iter().sum::<u32>();
+    let by_remainder = enumerate(weights)
+        .sorted_by_key(|(i, w)| (Reverse(total * *w % sum_weights), *i))
+        .map(|(i, _)| i);
+    for i in by_remainder.take(left_over as usize) {
+        res[i] += 1;
+    }
+    res
+}
+
+impl SplitMap {
+    fn new(size: Size) -> Self {
+        Self {
+            rects: HashMap::new(),
+            border_map: BorderMap::new(size),
+            title_bars: vec![],
+        }
+    }
+
+    /// adds the content of a split, which gets the content rect, to the map of the
+    /// split, which has the given rect
+    fn add_content(
+        &mut self,
+        content: &SplitContent,
+        content_rect: Rect,
+        rect: Rect,
+        min_split_size: Size,
+        orientation: Orientation,
+    ) -> Option<()> {
+        match content {
+            SplitContent::Leaf(buffer) => {
+                self.rects.insert(content_rect, buffer.clone());
+            }
+            SplitContent::Branch(next_split) => {
+                let SplitMap {
+                    rects,
+                    border_map,
+                    title_bars,
+                } = next_split.compute_rects(content_rect, min_split_size, orientation)?;
+                self.border_map
+                    .update(border_map, relative_to(rect)(content_rect.pos));
+                self.rects.extend(rects);
+                self.title_bars.extend(title_bars);
             }
         }
+        Some(())
+    }
+}
+
+/// converts absolute positions to positions relative to the rect
+fn relative_to(rect: Rect) -> impl Fn(BufferPosition) -> BufferPosition {
+    move |pos| BufferPosition::new(pos.row - rect.pos.row, pos.col - rect.pos.col)
+}
 
-        Some(SplitMap { rects, border_map })
+fn describe_split(
+    split: &Split,
+    orientation: Orientation,
+    depth: usize,
+    split_map: Option<&SplitMap>,
+    registry: Option<&BufferRegistry>,
+    out: &mut String,
+) {
+    let indent = "  ".repeat(depth);
+    for (size, content) in izip!(&split.sizes, &split.content) {
+        let size = match size {
+            SplitSize::Proportion(x) => format!("{x}"),
+            SplitSize::Fixed(x) => format!("{x}!"),
+        };
+        match content {
+            SplitContent::Leaf(buf) => {
+                let name = registry
+                    .and_then(|r| r.name_of(buf).ok())
+                    .map(|name| format!("\"{name}\""))
+                    .unwrap_or_else(|| format!("{buf:?}"));
+                _ = write!(out, "{indent}{size}: {name}");
+                // a buffer can be shown in several splits, then all of its rects are listed
+                if let Some(split_map) = split_map {
+                    let rects = split_map
+                        .rects
+                        .iter()
+                        .filter(|(_, b)| *b == buf)
+                        .map(|(rect, _)| rect)
+                        .sorted();
+                    for rect in rects {
+                        _ = write!(
+                            out,
+                            " [{}x{} at {},{}]",
+                            rect.size.w, rect.size.h, rect.pos.row, rect.pos.col
+                        );
+                    }
+                }
+                out.push('\n');
+            }
+            SplitContent::Branch(split) if split.stack.is_some() => {
+                _ = writeln!(out, "{indent}{size}: stacked split");
+                describe_split(
+                    split,
+                    Orientation::Vertical,
+                    depth + 1,
+                    split_map,
+                    registry,
+                    out,
+                );
+            }
+            SplitContent::Branch(split) => {
+                let orientation = split.orientation.unwrap_or(orientation.flip());
+                _ = writeln!(out, "{indent}{size}: {orientation:?} split");
+                describe_split(split, orientation, depth + 1, split_map, registry, out);
+            }
+        }
     }
 }
 
-fn render_screen_too_small_info() -> Result<(), io::Error> {
-    execute!(
-        io::stdout(),
-        cursor::MoveTo(0, 0),
-        Print("The terminal window is too small to render the ui, please enlarge")
-    )
+pub(crate) fn render_screen_too_small_info(frame: &mut Frame) {
+    let w = frame.size().w;
+    frame.print(
+        BufferPosition::new(0, 0),
+        "The terminal window is too small to render the ui, please enlarge",
+        ContentStyle::default(),
+        w,
+    );
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum SplitContent {
     Leaf(BufferRef),
     Branch(Split),
 }
 
+impl SplitContent {
+    fn first_buffer(&self) -> BufferRef {
+        match self {
+            SplitContent::Leaf(buf) => buf.clone(),
+            SplitContent::Branch(split) => split.content[0].first_buffer(),
+        }
+    }
+}
+
 /// Define a split tree.
 ///
 /// The definition starts with a Orientation, then come a list of sizes, a size
 /// can either be proportional, in which case its absolut size is computed based
 /// on the available space, or absolut, when marked with a `!`. Sub splits can
 /// be opened with a pair or braces, and will have the flipped orientation of
-/// the parent
+/// the parent, unless they are preceded by an orientation of their own, like
+/// `1: Vertical: { ... }`, or by `Stacked:`, see [Split::stacked]
 ///
 /// ```no_run
 /// use ablet::{split_tree, Buffer};
@@ -406,6 +894,26 @@ macro_rules! split_tree {
         iter::once(SplitSize::Proportion($proportional))
     };
 
+    (@entries_to_sizes, $fixed:literal ! : $orientation:ident : $content:tt $(, $($tail:tt)*)?) => {
+        split_tree!(@entries_to_sizes, $fixed!: $content $(, $($tail)*)?)
+    };
+
+    (@entries_to_sizes, $proportional:literal : $orientation:ident : $content:tt $(, $($tail:tt)*)?) => {
+        split_tree!(@entries_to_sizes, $proportional: $content $(, $($tail)*)?)
+    };
+
+    (@entries_to_contents, $size:literal $(!)? : Stacked : { $($entries:tt)+ } $(, $($tail:tt)*)?) => {
+        iter::once(SplitContent::Branch(
+            split_tree!(@entries_to_split, $($entries)+).stacked(iter::empty::<String>())
+        )).chain(split_tree!(@entries_to_contents, $($($tail)*)?))
+    };
+
+    (@entries_to_contents, $size:literal $(!)? : $orientation:ident : { $($entries:tt)+ } $(, $($tail:tt)*)?) => {
+        iter::once(SplitContent::Branch(
+            split_tree!(@entries_to_split, $($entries)+).with_orientation(Orientation::$orientation)
+        )).chain(split_tree!(@entries_to_contents, $($($tail)*)?))
+    };
+
     (@entries_to_contents, $size:literal $(!)? : $buf_ref:ident, $($tail:tt)*) => {
         iter::once(SplitContent::Leaf($buf_ref.clone())).chain(split_tree!(@entries_to_contents, $($tail)*))
     };
@@ -426,7 +934,10 @@ macro_rules! split_tree {
 #[cfg(test)]
 mod tests {
 
-    use crate::{split_tree, Buffer};
+    use itertools::Itertools;
+
+    use super::{distribute_sizes, SplitContent, SplitSize};
+    use crate::{Buffer, BufferRegistry, Size};
 
     #[test]
     pub fn test_splits_valid() {
@@ -444,8 +955,7 @@ mod tests {
         );
 
         let Some(split_map) = tree.compute_rects((40, 40)) else {
-            assert!(false, "unexpected None");
-            return;
+            panic!("unexpected None");
         };
 
         let mut rects = split_map.rects.keys().collect::<Vec<_>>();
@@ -453,4 +963,247 @@ mod tests {
 
         insta::assert_debug_snapshot!(rects);
     }
+
+    #[test]
+    pub fn test_describe() {
+        let main = Buffer::new().into_ref();
+        let side = Buffer::new().into_ref();
+        let mut registry = BufferRegistry::new();
+        registry.register("main", main.clone());
+        registry.register("side", side.clone());
+
+        let tree = split_tree! (
+            Vertical: {
+                2: {
+                    1: main,
+                    1: side,
+                },
+                1!: main,
+            }
+        );
+
+        insta::assert_snapshot!(tree.describe(Size { w: 40, h: 20 }, Some(&registry)));
+        insta::assert_snapshot!(tree.describe(Size { w: 40, h: 2 }, Some(&registry)));
+    }
+
+    #[test]
+    pub fn test_distribute_sizes() {
+        use SplitSize::*;
+
+        let fixed_sizes = [None, Some(Fixed(2))];
+        for n in 1..=3 {
+            for proportions in (0..n).map(|_| 1..=4u16).multi_cartesian_product() {
+                for fixed in fixed_sizes {
+                    let sizes = proportions
+                        .iter()
+                        .map(|p| Proportion(*p))
+                        .chain(fixed)
+                        .collect::<Vec<_>>();
+                    let borders = sizes.len() as u16 - 1;
+                    let fixed_total = if fixed.is_some() { 2 } else { 0 };
+                    let sum_proportions = proportions.iter().sum::<u16>() as f32;
+
+                    for available in 0..(borders + fixed_total) {
+                        let res = distribute_sizes(&sizes, available);
+                        if available >= borders {
+                            assert_eq!(res.iter().sum::<u16>(), available, "{sizes:?}");
+                        }
+                    }
+
+                    for available in (borders + fixed_total)..60 {
+                        let res = distribute_sizes(&sizes, available);
+                        assert_eq!(res.iter().sum::<u16>(), available, "{sizes:?}");
+                        if fixed.is_some() {
+                            assert_eq!(res[res.len() - 1], 3, "{sizes:?} {available}");
+                        }
+
+                        // every share is its exact value, rounded up or down
+                        let dynamic = (available - borders - fixed_total) as f32;
+                        for (i, p) in proportions.iter().enumerate() {
+                            let share = (res[i] - (i > 0) as u16) as f32;
+                            let exact = dynamic * *p as f32 / sum_proportions;
+                            assert!(
+                                (share - exact).abs() < 1.0,
+                                "{sizes:?} at {available}: {res:?}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_equal_proportions_get_equal_space() {
+        let buf = Buffer::new().into_ref();
+        let tree = split_tree!(Horizontal: { 1: buf, 1: buf, 1: buf });
+
+        // 2 borders and 3 * 6 cells
+        let split_map = tree.compute_rects((20, 5)).unwrap();
+        let widths = split_map.rects.keys().map(|r| r.size.w).collect_vec();
+        assert_eq!(widths, [6, 6, 6]);
+    }
+
+    #[test]
+    pub fn test_fixed_sizes_larger_than_space() {
+        let a = Buffer::new().into_ref();
+        let b = Buffer::new().into_ref();
+
+        // the fixed sizes are shrunk to fit
+        let tree = split_tree!(Vertical: { 5!: a, 5!: b });
+        let split_map = tree.compute_rects((10, 6)).unwrap();
+        let mut heights = split_map.rects.keys().map(|r| r.size.h).collect_vec();
+        heights.sort_unstable();
+        assert_eq!(heights, [2, 3]);
+
+        // no space is left for the proportional split
+        let tree = split_tree!(Horizontal: { 1: a, 10!: b, 1: { 1: a, 1!: b } });
+        assert!(tree.compute_rects((8, 3)).is_none());
+        assert!(tree.compute_rects((0, 0)).is_none());
+        tree.render_to_frame(Size { w: 8, h: 3 }).unwrap();
+    }
+
+    #[test]
+    pub fn test_nested_borders() {
+        let a = Buffer::new().into_ref();
+        let b = Buffer::new().into_ref();
+        let c = Buffer::new().into_ref();
+        let tree = split_tree!(Horizontal: { 1: a, 1: { 1: b, 1: c } });
+
+        // the borders of the inner split are right of the outer one, not on top of it
+        let border_map = tree.compute_rects((5, 3)).unwrap().border_map;
+        let borders = border_map
+            .0
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|b| match (b.in_vertical_border, b.in_horizontal_border) {
+                        (true, _) => '|',
+                        (_, true) => '-',
+                        _ => ' ',
+                    })
+                    .collect::<String>()
+            })
+            .collect_vec();
+        assert_eq!(borders, ["  |  ", "  |--", "  |  "]);
+    }
+
+    #[test]
+    pub fn test_orientation_override() {
+        let buf = Buffer::new().into_ref();
+        let tree = split_tree!(Horizontal: {
+            1: buf,
+            1: Horizontal: {
+                1: buf,
+                1!: buf,
+            },
+        });
+
+        // all three buffers are next to each other
+        let split_map = tree.compute_rects((20, 4)).unwrap();
+        let mut rects = split_map
+            .rects
+            .keys()
+            .map(|r| (r.pos.col, r.size.w))
+            .collect_vec();
+        rects.sort_unstable();
+        assert_eq!(rects, [(0, 10), (11, 7), (19, 1)]);
+        assert!(split_map.rects.keys().all(|r| r.size.h == 4));
+    }
+
+    #[test]
+    pub fn test_remove() {
+        let a = Buffer::new().into_ref();
+        let b = Buffer::new().into_ref();
+        let c = Buffer::new().into_ref();
+        let mut tree = split_tree!(Horizontal: { 1: a, 2: { 1: b, 1: { 1: c, 1: a } } });
+        assert!(tree.remove(&b));
+        assert!(tree.remove(&a));
+        // the splits with a single child are gone, and c is the only leaf left
+        assert!(matches!(&tree.root.content[..], [SplitContent::Leaf(leaf)] if *leaf == c));
+        assert!(!tree.remove(&c));
+        assert!(!tree.remove(&a));
+    }
+
+    #[test]
+    pub fn test_replace_buffer() {
+        let a = Buffer::new().into_ref();
+        let b = Buffer::new().into_ref();
+        let c = Buffer::new().into_ref();
+        let mut tree = split_tree!(Horizontal: { 1: a, 2: { 1: b, 1: a } });
+        assert!(tree.replace_buffer(&a, &c));
+        assert!(!tree.replace_buffer(&a, &c));
+        let [SplitContent::Leaf(first), SplitContent::Branch(split)] = &tree.root.content[..]
+        else {
+            panic!("the structure of the tree changed");
+        };
+        assert!(*first == c);
+        assert!(
+            matches!(&split.content[..], [SplitContent::Leaf(l), SplitContent::Leaf(r)] if *l == b && *r == c)
+        );
+    }
+
+    #[test]
+    pub fn test_stacked_split() {
+        let side = Buffer::new().into_ref();
+        let a = Buffer::new().into_ref();
+        let b = Buffer::new().into_ref();
+        let mut tree = split_tree!(Horizontal: {
+            1: side,
+            1: Stacked: {
+                1: a,
+                1: b,
+            },
+        });
+
+        let split_map = tree.compute_rects((21, 10)).unwrap();
+        let a_rect = split_map
+            .rects
+            .iter()
+            .find(|(_, buf)| **buf == a)
+            .unwrap()
+            .0;
+        assert_eq!(*a_rect, crate::rect(1, 11, 10, 8));
+        assert!(!split_map.rects.values().any(|buf| *buf == b));
+        let bars = split_map
+            .title_bars
+            .iter()
+            .map(|bar| (bar.rect.pos.row, bar.title.as_str(), bar.expanded))
+            .collect_vec();
+        assert_eq!(bars, [(0, "1", true), (9, "2", false)]);
+
+        assert_eq!(tree.cycle_stack(&a, true), Some(b.clone()));
+        let split_map = tree.compute_rects((21, 10)).unwrap();
+        let b_rect = split_map
+            .rects
+            .iter()
+            .find(|(_, buf)| **buf == b)
+            .unwrap()
+            .0;
+        assert_eq!(*b_rect, crate::rect(2, 11, 10, 8));
+
+        assert!(tree.activate(&a));
+        assert!(tree
+            .compute_rects((21, 10))
+            .unwrap()
+            .rects
+            .values()
+            .any(|buf| *buf == a));
+    }
+
+    #[test]
+    pub fn test_trees_are_throttled_on_their_own() {
+        let buf = Buffer::new().into_ref();
+        let a = split_tree!(Vertical: { 1: buf });
+        let b = split_tree!(Vertical: { 1: buf });
+        let interval = std::time::Duration::from_secs(60);
+        let schedule = |tree: &super::SplitTree| {
+            tree.render_throttle
+                .schedule(interval, Box::new(|| {}))
+                .is_some()
+        };
+        assert!(schedule(&a));
+        assert!(!schedule(&a.clone()));
+        assert!(schedule(&b));
+    }
 }
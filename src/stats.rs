@@ -0,0 +1,127 @@
+//! Contains RenderStats, which measure the input latency of the editing loop.
+//!
+//! The latency is the time from receiving an event to having flushed the frame
+//! that reflects it. It's recorded by [edit_buffer](crate::edit_buffer), and can
+//! be shown in the top right corner of the screen via [set_latency_indicator].
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+    time::Duration,
+};
+
+use crossterm::style::{ContentStyle, Stylize};
+
+use crate::{BufferPosition, Frame};
+
+/// the number of samples that are kept
+const N_SAMPLES: usize = 1000;
+
+static STATS: LazyLock<Mutex<RenderStats>> = LazyLock::new(|| Mutex::new(RenderStats::default()));
+static SHOW_INDICATOR: AtomicBool = AtomicBool::new(false);
+
+/// The input latencies of the last 1000 events
+#[derive(Default, Clone, Debug)]
+pub struct RenderStats {
+    samples: VecDeque<Duration>,
+}
+
+impl RenderStats {
+    pub fn record(&mut self, latency: Duration) {
+        if self.samples.len() == N_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the latency that `p` percent of the samples are less or equal to,
+    /// or None if there are no samples yet. `p` is clamped to 0..=100
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted = self.samples.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let rank = (p.clamp(0., 100.) / 100. * sorted.len() as f64).ceil() as usize;
+        Some(sorted[rank.max(1) - 1])
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.samples.iter().max().copied()
+    }
+}
+
+pub(crate) fn record_latency(latency: Duration) {
+    STATS.lock().unwrap().record(latency);
+}
+
+/// returns a copy of the stats that were collected so far
+pub fn render_stats() -> RenderStats {
+    STATS.lock().unwrap().clone()
+}
+
+pub fn reset_render_stats() {
+    *STATS.lock().unwrap() = RenderStats::default();
+}
+
+/// Shows the median and 99th percentile input latency in the top right corner
+/// of the screen
+pub fn set_latency_indicator(visible: bool) {
+    SHOW_INDICATOR.store(visible, Ordering::Relaxed);
+}
+
+pub(crate) fn draw_latency_indicator(frame: &mut Frame) {
+    if !SHOW_INDICATOR.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let stats = STATS.lock().unwrap();
+    let fmt = |d: Option<Duration>| {
+        d.map_or("-".to_string(), |d| {
+            format!("{:.1}ms", d.as_secs_f64() * 1000.)
+        })
+    };
+    let text = format!(
+        " p50 {} p99 {} ",
+        fmt(stats.percentile(50.)),
+        fmt(stats.percentile(99.))
+    );
+    let w = frame.size().w;
+    let col = w.saturating_sub(text.len() as u16);
+    frame.print(
+        BufferPosition::new(0, col),
+        &text,
+        ContentStyle::new().reverse(),
+        w,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let mut stats = RenderStats::default();
+        assert_eq!(stats.percentile(50.), None);
+
+        for ms in (1..=10).rev() {
+            stats.record(Duration::from_millis(ms));
+        }
+        assert_eq!(stats.percentile(0.), Some(Duration::from_millis(1)));
+        assert_eq!(stats.percentile(50.), Some(Duration::from_millis(5)));
+        assert_eq!(stats.percentile(99.), Some(Duration::from_millis(10)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(10)));
+    }
+}
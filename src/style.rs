@@ -0,0 +1,37 @@
+//! Contains ContentStyleExt, which layers styles over each other
+
+use crossterm::style::ContentStyle;
+
+pub trait ContentStyleExt {
+    /// Layers `top` over self: the colors that are set in `top` replace the ones
+    /// in self, the others are kept, and the attributes of both are combined.
+    /// E.g. merging a style that only sets a background keeps the foreground.
+    fn merge(self, top: ContentStyle) -> ContentStyle;
+}
+
+impl ContentStyleExt for ContentStyle {
+    fn merge(mut self, top: ContentStyle) -> ContentStyle {
+        self.foreground_color = top.foreground_color.or(self.foreground_color);
+        self.background_color = top.background_color.or(self.background_color);
+        self.underline_color = top.underline_color.or(self.underline_color);
+        self.attributes.extend(top.attributes);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::{Attribute, Color, Stylize};
+
+    use super::*;
+
+    #[test]
+    fn test_merge_keeps_unset_parts() {
+        let base = ContentStyle::new().green().on_blue().bold();
+        let merged = base.merge(ContentStyle::new().on_grey().italic());
+        assert_eq!(merged.foreground_color, Some(Color::Green));
+        assert_eq!(merged.background_color, Some(Color::Grey));
+        assert!(merged.attributes.has(Attribute::Bold));
+        assert!(merged.attributes.has(Attribute::Italic));
+    }
+}
@@ -0,0 +1,220 @@
+//! Tab completion for the prompt, with candidates from a callback
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEventKind},
+    style::{ContentStyle, Stylize},
+};
+
+use crate::{AText, AbletCtx, BufferRef, EventHandler, HandlerResult, TextOffset};
+
+/// A candidate for [TabCompletion], which replaces the text from `start` up to the cursor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    pub start: usize,
+    pub text: String,
+    /// shown next to the text in the popup
+    pub description: Option<String>,
+}
+
+impl Completion {
+    pub fn new(start: usize, text: impl Into<String>) -> Self {
+        Self {
+            start,
+            text: text.into(),
+            description: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+/// Wraps the handler of a prompt, e.g. a [LineEditor](crate::LineEditor), and completes
+/// the input on Tab, with the candidates that the callback returns for the input and the
+/// cursor position. Pressing Tab again cycles through them, and back to what was typed,
+/// BackTab cycles backwards. All other events go to the wrapped handler.
+pub struct TabCompletion<H, F> {
+    handler: H,
+    complete: F,
+    show_popup: bool,
+    state: Option<CompletionState>,
+}
+
+struct CompletionState {
+    /// where the candidates start
+    start: usize,
+    /// what was there before the first completion
+    typed: String,
+    candidates: Vec<Completion>,
+    /// the shown candidate, None for what was typed
+    index: Option<usize>,
+    /// where the cursor is behind the shown candidate, if it didn't move since
+    cursor: usize,
+}
+
+impl<H, F: FnMut(&str, usize) -> Vec<Completion>> TabCompletion<H, F> {
+    pub fn new(handler: H, complete: F) -> Self {
+        Self {
+            handler,
+            complete,
+            show_popup: false,
+            state: None,
+        }
+    }
+
+    /// shows the candidates in a popup above the prompt, see [Ablet::set_popup](crate::Ablet::set_popup)
+    pub fn with_popup(mut self, show_popup: bool) -> Self {
+        self.show_popup = show_popup;
+        self
+    }
+
+    pub fn handler(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    fn cycle(&mut self, buf: &BufferRef, ctx: &mut AbletCtx, forward: bool) {
+        let cursor = buf.cursor().get();
+        let state = match self.state.take() {
+            Some(state) if state.cursor == cursor => state,
+            _ => {
+                let input = buf.get_doc().0.lock().unwrap().content.text.clone();
+                let candidates = (self.complete)(&input, cursor);
+                let Some(start) = candidates.iter().map(|c| c.start).min() else {
+                    self.reset(ctx);
+                    return;
+                };
+                let start = start.min(cursor);
+                CompletionState {
+                    start,
+                    typed: input[start..cursor].to_string(),
+                    candidates,
+                    index: None,
+                    cursor,
+                }
+            }
+        };
+
+        let n = state.candidates.len();
+        let index = match (state.index, forward) {
+            (None, true) => Some(0),
+            (None, false) => Some(n - 1),
+            (Some(i), true) => (i + 1 < n).then_some(i + 1),
+            (Some(i), false) => i.checked_sub(1),
+        };
+        // candidates that start behind `start` keep the text up to their start
+        let text = match index {
+            Some(i) => {
+                let c = &state.candidates[i];
+                let kept = &state.typed[..(c.start - state.start).min(state.typed.len())];
+                format!("{kept}{}", c.text)
+            }
+            None => state.typed.clone(),
+        };
+        buf.delete_range(TextOffset::new(state.start)..buf.cursor());
+        buf.insert_text_at_cursor(text.as_str());
+        let state = CompletionState {
+            index,
+            cursor: buf.cursor().get(),
+            ..state
+        };
+        if self.show_popup {
+            if let Some(ablet) = ctx.ablet() {
+                ablet.set_popup(Some(popup_text(&state)));
+            }
+        }
+        self.state = Some(state);
+    }
+
+    fn reset(&mut self, ctx: &mut AbletCtx) {
+        if self.state.take().is_some() && self.show_popup {
+            if let Some(ablet) = ctx.ablet() {
+                ablet.set_popup(None);
+            }
+        }
+    }
+}
+
+impl<T, H, F> EventHandler<T> for TabCompletion<H, F>
+where
+    H: EventHandler<T>,
+    F: FnMut(&str, usize) -> Vec<Completion>,
+{
+    fn handle(&mut self, ev: &Event, buf: &BufferRef, ctx: &mut AbletCtx) -> HandlerResult<T> {
+        if let Event::Key(ke) = ev {
+            let forward = match ke.code {
+                KeyCode::Tab => true,
+                KeyCode::BackTab => false,
+                _ => {
+                    self.reset(ctx);
+                    return self.handler.handle(ev, buf, ctx);
+                }
+            };
+            if ke.kind != KeyEventKind::Release {
+                self.cycle(buf, ctx, forward);
+            }
+            return HandlerResult::Handled;
+        }
+        self.handler.handle(ev, buf, ctx)
+    }
+}
+
+/// one line per candidate, with the shown one reversed
+fn popup_text(state: &CompletionState) -> AText {
+    let mut res = AText::default();
+    for (i, c) in state.candidates.iter().enumerate() {
+        if i > 0 {
+            res += "\n";
+        }
+        let style = if state.index == Some(i) {
+            ContentStyle::new().reverse()
+        } else {
+            ContentStyle::new()
+        };
+        res += style.apply(c.text.clone());
+        if let Some(description) = &c.description {
+            res += format!("  {description}").dark_grey();
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEvent;
+
+    use super::*;
+    use crate::{Buffer, LineEditor};
+
+    #[test]
+    fn test_cycles_through_candidates() {
+        let buf = Buffer::new().into_ref();
+        buf.insert_text_at_cursor("git ch");
+        let commands = ["checkout", "cherry-pick"];
+        let mut handler = TabCompletion::new(LineEditor::new(), |input: &str, cursor| {
+            let start = input[..cursor].rfind(' ').map_or(0, |i| i + 1);
+            let word = &input[start..cursor];
+            commands
+                .iter()
+                .filter(|c| c.starts_with(word))
+                .map(|c| Completion::new(start, *c))
+                .collect()
+        });
+        let render = || Ok(());
+        let mut ctx = AbletCtx::new(None, &render);
+        let mut press = |code| {
+            handler.handle(&Event::Key(KeyEvent::from(code)), &buf, &mut ctx);
+            buf.get_doc().0.lock().unwrap().content.text.clone()
+        };
+
+        assert_eq!(press(KeyCode::Tab), "git checkout");
+        assert_eq!(press(KeyCode::Tab), "git cherry-pick");
+        assert_eq!(press(KeyCode::Tab), "git ch");
+        assert_eq!(press(KeyCode::BackTab), "git cherry-pick");
+        // other keys end the completion, and go to the line editor
+        assert_eq!(press(KeyCode::Char(' ')), "git cherry-pick ");
+        assert_eq!(press(KeyCode::Char('x')), "git cherry-pick x");
+        assert_eq!(press(KeyCode::Tab), "git cherry-pick x");
+    }
+}
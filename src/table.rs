@@ -0,0 +1,473 @@
+//! Contains the Table, which shows rows of cells in aligned columns, e.g. from a
+//! CSV or TSV file
+
+use std::{
+    cmp::Ordering,
+    io::{self, BufRead},
+};
+
+use crossterm::{
+    event::{Event, KeyCode},
+    style::{ContentStyle, Stylize},
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    shared, AText, AbletCtx, Buffer, BufferRef, EventHandler, Frame, HandlerResult, Rect, Shared,
+    Size, Widget,
+};
+
+/// columns are sized to their widest cell, but not wider than this
+const MAX_COLUMN_WIDTH: usize = 30;
+const SEPARATOR: &str = " \u{2502} ";
+
+/// A table with a header, that is shown in a buffer. One cell is selected, and the
+/// columns are scrolled so it's visible. Clones share the same state, so rows can
+/// be added from a background thread, while the table is shown.
+///
+/// As an [EventHandler], the arrow keys and PageUp/PageDown move the selection,
+/// `s` sorts by the selected column, or reverses the order if it's already sorted
+/// by it, and q or Esc return.
+#[derive(Clone)]
+pub struct Table {
+    buffer: BufferRef,
+    state: Shared<State>,
+}
+
+#[derive(Default)]
+struct State {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+    widths: Vec<usize>,
+    /// row and column of the selected cell
+    selected: (usize, usize),
+    /// the first column that is shown
+    first_column: usize,
+    /// the column the rows are sorted by, and whether it's ascending
+    sorted_by: Option<(usize, bool)>,
+}
+
+impl Table {
+    pub fn new(header: Vec<String>) -> Self {
+        let buffer = Buffer::new().into_ref();
+        buffer.set_cursor_visible(false);
+        // with room for the sort marker
+        let widths = header.iter().map(|h| display_width(h) + 1).collect();
+        let table = Self {
+            buffer,
+            state: shared(State {
+                header,
+                widths,
+                ..State::default()
+            }),
+        };
+        table.redraw();
+        table
+    }
+
+    /// Reads CSV, or TSV with a tab as delimiter. The first record is the header.
+    /// Fields can be quoted with `"`, and contain delimiters, line breaks, and `""`
+    /// for a quote. Rows are added while reading, so large files can be shown
+    /// while they're loaded, if this is done in a background thread via
+    /// [Table::load_csv]
+    pub fn from_csv(reader: impl BufRead, delimiter: char) -> std::io::Result<Self> {
+        let mut records = CsvRecords {
+            lines: reader.lines(),
+            delimiter,
+        };
+        let header = records.next().transpose()?.unwrap_or_default();
+        let table = Self::new(header);
+        table.add_records(records)?;
+        Ok(table)
+    }
+
+    /// adds the records of the CSV to the table, without a header, see [Table::from_csv]
+    pub fn load_csv(&self, reader: impl BufRead, delimiter: char) -> std::io::Result<()> {
+        self.add_records(CsvRecords {
+            lines: reader.lines(),
+            delimiter,
+        })
+    }
+
+    fn add_records(
+        &self,
+        records: impl Iterator<Item = std::io::Result<Vec<String>>>,
+    ) -> std::io::Result<()> {
+        for record in records {
+            self.add_row(record?);
+        }
+        Ok(())
+    }
+
+    /// the buffer that shows the table, to be put into a split tree
+    pub fn buffer(&self) -> &BufferRef {
+        &self.buffer
+    }
+
+    /// Adds a row, behind the others, even if the table is sorted
+    pub fn add_row(&self, row: Vec<String>) {
+        let mut state = self.state.lock().unwrap();
+        let mut widths_changed = false;
+        for (i, cell) in row.iter().enumerate() {
+            let width = display_width(cell).min(MAX_COLUMN_WIDTH);
+            match state.widths.get_mut(i) {
+                Some(w) if *w >= width => {}
+                Some(w) => {
+                    *w = width;
+                    widths_changed = true;
+                }
+                None => {
+                    state.widths.push(width);
+                    widths_changed = true;
+                }
+            }
+        }
+        state.rows.push(row);
+        if widths_changed {
+            drop(state);
+            self.redraw();
+        } else {
+            let line = state.format_row(state.rows.len() - 1);
+            self.buffer.get_doc().add_line(line);
+        }
+    }
+
+    pub fn n_rows(&self) -> usize {
+        self.state.lock().unwrap().rows.len()
+    }
+
+    /// row and column of the selected cell
+    pub fn selected(&self) -> (usize, usize) {
+        self.state.lock().unwrap().selected
+    }
+
+    pub fn selected_cell(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        let (row, col) = state.selected;
+        state.rows.get(row)?.get(col).cloned()
+    }
+
+    /// selects the cell, clamped into the table
+    pub fn select(&self, row: usize, col: usize) {
+        {
+            let mut state = self.state.lock().unwrap();
+            let row = row.min(state.rows.len().saturating_sub(1));
+            let col = col.min(state.widths.len().saturating_sub(1));
+            state.selected = (row, col);
+        }
+        self.redraw();
+    }
+
+    /// Sorts the rows by the column. Numbers are compared as numbers, and come after
+    /// all other cells, which are compared as text
+    pub fn sort_by(&self, col: usize, ascending: bool) {
+        {
+            let mut state = self.state.lock().unwrap();
+            let cell = |row: &Vec<String>| row.get(col).cloned().unwrap_or_default();
+            state.rows.sort_by(|a, b| {
+                let ord = compare_cells(&cell(a), &cell(b));
+                if ascending {
+                    ord
+                } else {
+                    ord.reverse()
+                }
+            });
+            state.sorted_by = Some((col, ascending));
+        }
+        self.redraw();
+    }
+
+    /// rewrites the whole buffer, and moves its cursor to the selected row
+    fn redraw(&self) {
+        let size = self.buffer.rendered_size();
+        let mut state = self.state.lock().unwrap();
+        if let Some(size) = size {
+            state.scroll_to_selection(size.w as usize);
+        }
+
+        let mut text = state.format_header();
+        text.push_char('\n');
+        let (selected_row, selected_col) = state.selected;
+        for i in 0..state.rows.len() {
+            let mut line = state.format_row(i);
+            if i == selected_row {
+                if let Some(range) = state.cell_range(selected_col) {
+                    line.apply_style(range, ContentStyle::new().reverse());
+                }
+            }
+            text += line + "\n";
+        }
+        drop(state);
+
+        self.buffer
+            .get_doc()
+            .update_content(|content| *content = text);
+        // the header is the first line
+        self.buffer.goto_line(selected_row + 1);
+    }
+}
+
+impl State {
+    fn visible_columns(&self) -> std::ops::Range<usize> {
+        self.first_column.min(self.widths.len())..self.widths.len()
+    }
+
+    fn format_header(&self) -> AText {
+        let mut res = AText::default();
+        for i in self.visible_columns() {
+            if i > self.first_column {
+                res += SEPARATOR;
+            }
+            let mut title = self.header.get(i).cloned().unwrap_or_default();
+            if let Some((_, ascending)) = self.sorted_by.filter(|(col, _)| *col == i) {
+                title.push(if ascending { '\u{25b2}' } else { '\u{25bc}' });
+            }
+            res += fit_to_width(&title, self.widths[i]).bold();
+        }
+        res
+    }
+
+    fn format_row(&self, row: usize) -> AText {
+        let mut res = String::new();
+        for i in self.visible_columns() {
+            if i > self.first_column {
+                res += SEPARATOR;
+            }
+            let cell = self.rows[row].get(i).map(String::as_str).unwrap_or("");
+            res += &fit_to_width(cell, self.widths[i]);
+        }
+        AText::from(res)
+    }
+
+    /// the byte range of the column in a formatted row, if it's visible
+    fn cell_range(&self, col: usize) -> Option<std::ops::Range<usize>> {
+        let mut start = 0;
+        for i in self.visible_columns() {
+            let len = self.formatted_len(i);
+            if i == col {
+                return Some(start..start + len);
+            }
+            start += len + SEPARATOR.len();
+        }
+        None
+    }
+
+    /// the length of a formatted cell of the column in bytes, which differs from its
+    /// width, so the selected row is formatted again to get it
+    fn formatted_len(&self, col: usize) -> usize {
+        let cell = self
+            .rows
+            .get(self.selected.0)
+            .and_then(|row| row.get(col))
+            .map(String::as_str)
+            .unwrap_or("");
+        fit_to_width(cell, self.widths[col]).len()
+    }
+
+    /// moves the first shown column, so the selected one fits into the width
+    fn scroll_to_selection(&mut self, width: usize) {
+        let col = self.selected.1;
+        self.first_column = self.first_column.min(col);
+        let sep_width = display_width(SEPARATOR);
+        let used = |first: usize| {
+            self.widths[first..=col].iter().sum::<usize>() + (col - first) * sep_width
+        };
+        while self.first_column < col && used(self.first_column) > width {
+            self.first_column += 1;
+        }
+    }
+}
+
+impl EventHandler<()> for Table {
+    fn handle(&mut self, ev: &Event, _buf: &BufferRef, _ctx: &mut AbletCtx) -> HandlerResult<()> {
+        let Event::Key(ke) = ev else {
+            return HandlerResult::Ignored;
+        };
+        let (row, col) = self.selected();
+        // the header takes one line
+        let page = self
+            .buffer
+            .rendered_size()
+            .map_or(1, |size| size.h.max(2) as usize - 1);
+        match ke.code {
+            KeyCode::Char('q') | KeyCode::Esc => return HandlerResult::Done(()),
+            KeyCode::Up => self.select(row.saturating_sub(1), col),
+            KeyCode::Down => self.select(row + 1, col),
+            KeyCode::Left => self.select(row, col.saturating_sub(1)),
+            KeyCode::Right => self.select(row, col + 1),
+            KeyCode::PageUp => self.select(row.saturating_sub(page), col),
+            KeyCode::PageDown => self.select(row + page, col),
+            KeyCode::Char('s') => {
+                let sorted_by = self.state.lock().unwrap().sorted_by;
+                let ascending = sorted_by != Some((col, true));
+                self.sort_by(col, ascending);
+            }
+            _ => return HandlerResult::Ignored,
+        }
+        HandlerResult::Handled
+    }
+}
+
+/// So a table can be shown in a [ScrollView](crate::ScrollView), which then scrolls the
+/// columns, instead of the table
+impl Widget for Table {
+    fn size(&self) -> Size {
+        let state = self.state.lock().unwrap();
+        let separators = state.widths.len().saturating_sub(1) * display_width(SEPARATOR);
+        let w = state.widths.iter().sum::<usize>() + separators;
+        // the header takes one line
+        let h = state.rows.len() + 1;
+        Size {
+            w: w.min(u16::MAX as usize) as u16,
+            h: h.min(u16::MAX as usize) as u16,
+        }
+    }
+
+    fn render_at(&self, frame: &mut Frame, rect: Rect) -> io::Result<()> {
+        self.buffer.fit_to(rect.size);
+        if std::mem::take(&mut self.state.lock().unwrap().first_column) > 0 {
+            self.redraw();
+        }
+        self.buffer.render_at(frame, rect)
+    }
+}
+
+/// Text lexicographically before numbers numerically. Mixing both in one comparison
+/// wouldn't be a total order, which `sort_by` requires
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    let number = |cell: &str| cell.trim().parse::<f64>().ok();
+    match (number(a), number(b)) {
+        (Some(x), Some(y)) => x.total_cmp(&y).then_with(|| a.cmp(b)),
+        (None, None) => a.cmp(b),
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+    }
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+/// Pads or cuts the text to the given display width. Line breaks and other control
+/// chars are replaced by spaces, so the text stays on one line
+pub(crate) fn fit_to_width(text: &str, width: usize) -> String {
+    let mut res = String::new();
+    let mut w = 0;
+    for c in text.chars() {
+        let c = if c.is_control() { ' ' } else { c };
+        let cw = c.width().unwrap_or(0);
+        if w + cw > width {
+            break;
+        }
+        w += cw;
+        res.push(c);
+    }
+    res.extend(std::iter::repeat_n(' ', width - w));
+    res
+}
+
+/// parses the lines of a CSV into records, reading more lines while inside quotes
+struct CsvRecords<L> {
+    lines: L,
+    delimiter: char,
+}
+
+impl<L: Iterator<Item = std::io::Result<String>>> Iterator for CsvRecords<L> {
+    type Item = std::io::Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = vec![];
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut line = match self.lines.next()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        loop {
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if in_quotes && chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' => in_quotes = !in_quotes,
+                    c if c == self.delimiter && !in_quotes => {
+                        record.push(std::mem::take(&mut field))
+                    }
+                    c => field.push(c),
+                }
+            }
+            if !in_quotes {
+                break;
+            }
+            // the line break is part of a quoted field
+            match self.lines.next() {
+                Some(Ok(next)) => {
+                    field.push('\n');
+                    line = next;
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+        record.push(field.trim_end_matches('\r').to_string());
+        Some(Ok(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_table() {
+        let csv = "name,size\nb,\"1,5\"\n\"a \"\"x\"\"\",20\nc,3\n";
+        let table = Table::from_csv(csv.as_bytes(), ',').unwrap();
+        let text = || {
+            table
+                .buffer()
+                .get_doc()
+                .0
+                .lock()
+                .unwrap()
+                .content
+                .text
+                .clone()
+        };
+        assert_eq!(
+            text(),
+            "name  \u{2502} size \n\
+             b     \u{2502} 1,5  \n\
+             a \"x\" \u{2502} 20   \n\
+             c     \u{2502} 3    \n"
+        );
+
+        table.sort_by(1, true);
+        assert_eq!(
+            text(),
+            "name  \u{2502} size\u{25b2}\n\
+             b     \u{2502} 1,5  \n\
+             c     \u{2502} 3    \n\
+             a \"x\" \u{2502} 20   \n"
+        );
+
+        table.select(2, 5);
+        assert_eq!(table.selected(), (2, 1));
+        assert_eq!(table.selected_cell().unwrap(), "20");
+    }
+
+    #[test]
+    fn test_sort_mixed_column() {
+        let csv = "v\n10\n9a\n9\nb\n2\n10\n";
+        let table = Table::from_csv(csv.as_bytes(), ',').unwrap();
+        table.sort_by(0, true);
+        let column = || {
+            let state = table.state.lock().unwrap();
+            state.rows.iter().map(|r| r[0].clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(column(), ["9a", "b", "2", "9", "10", "10"]);
+        table.sort_by(0, false);
+        assert_eq!(column(), ["10", "10", "9", "2", "b", "9a"]);
+    }
+}
@@ -1,12 +1,23 @@
-use std::io;
+use std::{
+    convert::Infallible,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use crossterm::{
     cursor,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    event::{DisableMouseCapture, EnableMouseCapture},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
     ExecutableCommand as _,
 };
 use log::error;
 
+/// whether mouse capture is on, so it can be restored after a suspend
+static MOUSE_CAPTURE: AtomicBool = AtomicBool::new(false);
+
 #[derive(thiserror::Error, Debug)]
 pub enum SetupError<T> {
     #[error("Error during terminal Setup: {0}")]
@@ -56,3 +67,186 @@ where
         }
     )
 }
+
+/// Sets up the terminal like [with_setup_terminal] does, and undoes it when it's
+/// dropped, e.g. for async code or apps with their own main loop. If a step of the
+/// setup fails, the steps before it are undone right away. Errors during the
+/// cleanup are logged
+pub struct TerminalGuard {
+    alt_screen: bool,
+    raw_mode: bool,
+    cursor_hidden: bool,
+}
+
+impl TerminalGuard {
+    pub fn new() -> Result<Self, SetupError<Infallible>> {
+        let mut guard = Self {
+            alt_screen: false,
+            raw_mode: false,
+            cursor_hidden: false,
+        };
+        io::stdout()
+            .execute(EnterAlternateScreen)
+            .map_err(SetupError::SetupError)?;
+        guard.alt_screen = true;
+        enable_raw_mode().map_err(SetupError::SetupError)?;
+        guard.raw_mode = true;
+        io::stdout()
+            .execute(cursor::Hide)
+            .map_err(SetupError::SetupError)?;
+        guard.cursor_hidden = true;
+        Ok(guard)
+    }
+}
+
+impl TerminalGuard {
+    /// undoes the steps of the setup that were done, and mouse capture, writing to `out`
+    fn restore(&mut self, out: &mut impl Write) {
+        // mouse capture is turned on after the setup, via set_mouse_capture
+        if MOUSE_CAPTURE.swap(false, Ordering::Relaxed) && out.execute(DisableMouseCapture).is_err()
+        {
+            error!("Couldn't disable mouse capture");
+        }
+        if std::mem::take(&mut self.cursor_hidden) && out.execute(cursor::Show).is_err() {
+            error!("Couldn't show cursor");
+        }
+        if std::mem::take(&mut self.raw_mode) && disable_raw_mode().is_err() {
+            error!("Couldn't disable raw mode");
+        }
+        if std::mem::take(&mut self.alt_screen) && out.execute(LeaveAlternateScreen).is_err() {
+            error!("Couldn't leave alt screen");
+        }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        self.restore(&mut io::stdout());
+    }
+}
+
+/// Enables or disables mouse events. They are off by default, since capturing the
+/// mouse prevents selecting text with it in most terminals, see
+/// [MouseFocus::select_and_copy](crate::MouseFocus::select_and_copy) for a replacement
+pub fn set_mouse_capture(enabled: bool) -> io::Result<()> {
+    write_mouse_capture(&mut io::stdout(), enabled)
+}
+
+/// see [set_mouse_capture], writes to `out`
+fn write_mouse_capture(out: &mut impl Write, enabled: bool) -> io::Result<()> {
+    if enabled {
+        out.execute(EnableMouseCapture)?;
+    } else {
+        out.execute(DisableMouseCapture)?;
+    }
+    MOUSE_CAPTURE.store(enabled, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Restores the terminal, stops the process with SIGTSTP, and sets the terminal up
+/// again, once the shell continues it. The screen is cleared, so it must be rendered
+/// again afterwards
+#[cfg(unix)]
+pub(crate) fn suspend_process() -> io::Result<()> {
+    let mouse_capture = MOUSE_CAPTURE.load(Ordering::Relaxed);
+    write_suspend(&mut io::stdout(), mouse_capture)?;
+    disable_raw_mode()?;
+
+    // SAFETY: raise has no preconditions. It returns once the process is continued
+    if unsafe { libc::raise(libc::SIGTSTP) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    enable_raw_mode()?;
+    write_resume(&mut io::stdout(), mouse_capture)
+}
+
+/// undoes the setup of the terminal, but raw mode, before a suspend
+#[cfg(unix)]
+fn write_suspend(out: &mut impl Write, mouse_capture: bool) -> io::Result<()> {
+    if mouse_capture {
+        out.execute(DisableMouseCapture)?;
+    }
+    out.execute(cursor::Show)?;
+    out.execute(LeaveAlternateScreen)?;
+    Ok(())
+}
+
+/// sets the terminal up again, but raw mode, after a suspend, see [write_suspend]
+#[cfg(unix)]
+fn write_resume(out: &mut impl Write, mouse_capture: bool) -> io::Result<()> {
+    out.execute(EnterAlternateScreen)?;
+    out.execute(cursor::Hide)?;
+    out.execute(Clear(ClearType::All))?;
+    if mouse_capture {
+        out.execute(EnableMouseCapture)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// taken by the tests that use [MOUSE_CAPTURE], so they don't run at the same time
+    static MOUSE_CAPTURE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_terminal_guard_restores_what_was_set_up() {
+        let _lock = MOUSE_CAPTURE_LOCK.lock().unwrap();
+        // the setup failed at enabling raw mode
+        let mut guard = TerminalGuard {
+            alt_screen: true,
+            raw_mode: false,
+            cursor_hidden: false,
+        };
+        let mut out = vec![];
+        guard.restore(&mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[?1049l");
+
+        let mut guard = TerminalGuard {
+            alt_screen: true,
+            raw_mode: false,
+            cursor_hidden: true,
+        };
+        let mut out = vec![];
+        write_mouse_capture(&mut out, true).unwrap();
+        let mut out = vec![];
+        guard.restore(&mut out);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[?1006l"));
+        assert!(out.ends_with("\x1b[?25h\x1b[?1049l"));
+        assert!(!MOUSE_CAPTURE.load(Ordering::Relaxed));
+        // nothing is restored twice, e.g. on drop
+        let mut out = vec![];
+        guard.restore(&mut out);
+        assert!(out.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_suspend_restores_mouse_capture() {
+        let _lock = MOUSE_CAPTURE_LOCK.lock().unwrap();
+        let written = |f: &dyn Fn(&mut Vec<u8>) -> io::Result<()>| {
+            let mut out = vec![];
+            f(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        written(&|out| write_mouse_capture(out, true));
+        let mouse_capture = MOUSE_CAPTURE.load(Ordering::Relaxed);
+        let suspend = written(&|out| write_suspend(out, mouse_capture));
+        assert!(suspend.starts_with("\x1b[?1006l"));
+        assert!(suspend.ends_with("\x1b[?25h\x1b[?1049l"));
+        let resume = written(&|out| write_resume(out, mouse_capture));
+        assert!(resume.starts_with("\x1b[?1049h\x1b[?25l\x1b[2J"));
+        assert!(resume.contains("\x1b[?1000h"));
+
+        written(&|out| write_mouse_capture(out, false));
+        let mouse_capture = MOUSE_CAPTURE.load(Ordering::Relaxed);
+        assert_eq!(
+            written(&|out| write_suspend(out, mouse_capture)),
+            "\x1b[?25h\x1b[?1049l"
+        );
+        assert!(!written(&|out| write_resume(out, mouse_capture)).contains("\x1b[?1000h"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Contains the TextOffset, a position in the text of a document
+
+use std::fmt::Display;
+
+use crate::Range;
+
+/// A position in the text of a document, as a byte offset. It's its own type so it
+/// isn't mixed up with char offsets, or with screen columns, like the ones of a
+/// [BufferPosition](crate::BufferPosition). Arithmetic is only possible through
+/// methods that keep it within the text
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct TextOffset(usize);
+
+impl TextOffset {
+    pub const ZERO: TextOffset = TextOffset(0);
+
+    pub const fn new(byte: usize) -> Self {
+        Self(byte)
+    }
+
+    /// the byte offset, e.g. to slice the text
+    pub const fn get(self) -> usize {
+        self.0
+    }
+
+    /// the end of the text
+    pub fn end_of(text: &str) -> Self {
+        Self(text.len())
+    }
+
+    /// moves the offset forward by `n` bytes, but not past the end of the text
+    pub fn forward(self, n: usize, text: &str) -> Self {
+        Self(self.0.saturating_add(n)).clamp_to(text)
+    }
+
+    /// moves the offset back by `n` bytes, but not before the start of the text
+    pub fn back(self, n: usize, text: &str) -> Self {
+        Self(self.0.saturating_sub(n)).clamp_to(text)
+    }
+
+    /// Moves the offset into the text, and back onto a char boundary, e.g. after the
+    /// text changed
+    pub fn clamp_to(self, text: &str) -> Self {
+        Self(text.floor_char_boundary(self.0.min(text.len())))
+    }
+
+    /// the bytes between this offset and an earlier one, or 0 if it's later
+    pub fn distance_from(self, earlier: TextOffset) -> usize {
+        self.0.saturating_sub(earlier.0)
+    }
+}
+
+/// the byte range of the offsets, e.g. to slice the text
+impl From<std::ops::Range<TextOffset>> for Range<usize> {
+    fn from(range: std::ops::Range<TextOffset>) -> Self {
+        Range::new(range.start.get(), range.end.get())
+    }
+}
+
+impl From<TextOffset> for usize {
+    fn from(offset: TextOffset) -> Self {
+        offset.0
+    }
+}
+
+impl Display for TextOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stays_within_the_text() {
+        let text = "aé";
+        let offset = TextOffset::ZERO.forward(2, text);
+        // the é is two bytes long, so the offset moves back to its start
+        assert_eq!(offset, TextOffset::new(1));
+        assert_eq!(offset.forward(10, text), TextOffset::end_of(text));
+        assert_eq!(offset.back(10, text), TextOffset::ZERO);
+        assert_eq!(TextOffset::new(3).distance_from(offset), 2);
+    }
+}
@@ -0,0 +1,263 @@
+//! Contains Theme, which resolves named styles when a frame is rendered, so an app can
+//! switch between themes, e.g. light and dark ones, without rebuilding its documents
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+};
+
+use crossterm::style::{ContentStyle, Stylize};
+
+use crate::ContentStyleExt;
+
+/// the names of the named styles, their index is their id
+static NAMES: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// the theme of frames that aren't drawn by an [Ablet](crate::Ablet) with its own one
+static DEFAULT_THEME: LazyLock<Arc<Theme>> = LazyLock::new(|| Arc::new(Theme::new()));
+
+static NEXT_THEME_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a style that stands for the style of the name in the theme, see
+/// [Ablet::set_theme](crate::Ablet::set_theme). It can be used like any other style,
+/// e.g. in an [AText](crate::AText), and be styled further, like
+/// `named_style("error").underlined()`, which is layered on top of the theme's style
+pub fn named_style(name: &str) -> ThemedStyle {
+    let mut names = NAMES.lock().unwrap();
+    let id = match names.iter().position(|n| n == name) {
+        Some(id) => id,
+        None => {
+            names.push(name.to_string());
+            names.len() - 1
+        }
+    };
+    ThemedStyle {
+        name: Some(id),
+        style: ContentStyle::default(),
+    }
+}
+
+/// A style, that is layered on top of the theme's style of its name, if it has one,
+/// see [named_style]. Plain [ContentStyle]s convert into unnamed ones
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThemedStyle {
+    /// the id of the name in [NAMES]
+    name: Option<usize>,
+    pub style: ContentStyle,
+}
+
+impl ThemedStyle {
+    /// the name of the theme's style this one stands for
+    pub fn name(&self) -> Option<String> {
+        self.name.map(|id| NAMES.lock().unwrap()[id].clone())
+    }
+
+    pub(crate) fn name_id(&self) -> Option<usize> {
+        self.name
+    }
+
+    /// Layers `top` over self, see [ContentStyleExt::merge]. A named `top` replaces
+    /// the name of self, so e.g. selected text is drawn with the selection's style
+    pub fn merge(self, top: impl Into<ThemedStyle>) -> ThemedStyle {
+        let top = top.into();
+        ThemedStyle {
+            name: top.name.or(self.name),
+            style: self.style.merge(top.style),
+        }
+    }
+}
+
+impl From<ContentStyle> for ThemedStyle {
+    fn from(style: ContentStyle) -> Self {
+        Self { name: None, style }
+    }
+}
+
+/// unnamed styles are equal to the ContentStyles they are
+impl PartialEq<ContentStyle> for ThemedStyle {
+    fn eq(&self, other: &ContentStyle) -> bool {
+        self.name.is_none() && self.style == *other
+    }
+}
+
+impl AsRef<ContentStyle> for ThemedStyle {
+    fn as_ref(&self) -> &ContentStyle {
+        &self.style
+    }
+}
+
+impl AsMut<ContentStyle> for ThemedStyle {
+    fn as_mut(&mut self) -> &mut ContentStyle {
+        &mut self.style
+    }
+}
+
+/// so named styles can be styled further, like ContentStyles
+impl Stylize for ThemedStyle {
+    type Styled = Self;
+
+    fn stylize(self) -> Self::Styled {
+        self
+    }
+}
+
+/// unnamed styles are shown like the ContentStyles they are
+impl std::fmt::Debug for ThemedStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => f
+                .debug_struct("ThemedStyle")
+                .field("name", &name)
+                .field("style", &self.style)
+                .finish(),
+            None => self.style.fmt(f),
+        }
+    }
+}
+
+/// The styles of the names that [named_style] stands for. A new theme has the styles
+/// of the names that are used by the library itself, which are "selection", for
+/// selected text, and "border", for the borders between splits. They can be replaced
+/// like any other
+#[derive(Clone, Debug)]
+pub struct Theme {
+    /// differs between themes with different styles, so renders can tell whether the
+    /// theme changed
+    id: u64,
+    styles: HashMap<String, ContentStyle>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            id: NEXT_THEME_ID.fetch_add(1, Ordering::Relaxed),
+            styles: HashMap::from([
+                ("selection".into(), ContentStyle::new().on_grey()),
+                ("border".into(), ContentStyle::new()),
+            ]),
+        }
+    }
+}
+
+impl Theme {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// sets the style of the name
+    pub fn with_style(mut self, name: impl Into<String>, style: ContentStyle) -> Self {
+        self.styles.insert(name.into(), style);
+        self.id = NEXT_THEME_ID.fetch_add(1, Ordering::Relaxed);
+        self
+    }
+
+    /// the style of the name, if the theme has one
+    pub fn get(&self, name: &str) -> Option<ContentStyle> {
+        self.styles.get(name).copied()
+    }
+
+    /// The style to draw the style with: the theme's style of its name, with the
+    /// style layered on top. Names the theme doesn't have are left out
+    pub fn resolve(&self, style: impl Into<ThemedStyle>) -> ContentStyle {
+        let style = style.into();
+        let Some(id) = style.name else {
+            return style.style;
+        };
+        let themed = self.get(&NAMES.lock().unwrap()[id]);
+        match themed {
+            Some(themed) => themed.merge(style.style),
+            None => style.style,
+        }
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+/// the theme frames have, unless another one is given
+pub(crate) fn default_theme() -> Arc<Theme> {
+    DEFAULT_THEME.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Color;
+
+    use super::*;
+    use crate::{split_tree, Ablet, Buffer, BufferPosition, Frame, Size, TextOffset};
+
+    #[test]
+    fn test_named_styles_are_resolved_by_the_theme() {
+        let styles = |theme: Theme| {
+            let mut frame = Frame::new(Size { w: 3, h: 1 }).with_theme(Arc::new(theme));
+            let error = named_style("error");
+            frame.print(BufferPosition::new(0, 0), "a", error, 3);
+            frame.print(BufferPosition::new(0, 1), "b", error.reverse(), 3);
+            frame.print(BufferPosition::new(0, 2), "c", named_style("unknown"), 3);
+            let row = frame.rows().next().unwrap().to_vec();
+            row.iter().map(|c| c.style).collect::<Vec<_>>()
+        };
+        let dark = Theme::new().with_style("error", ContentStyle::new().red());
+        let light = Theme::new().with_style("error", ContentStyle::new().dark_red().bold());
+        assert_eq!(
+            styles(dark),
+            [
+                ContentStyle::new().red(),
+                ContentStyle::new().red().reverse(),
+                ContentStyle::new()
+            ]
+        );
+        assert_eq!(styles(light)[0], ContentStyle::new().dark_red().bold());
+    }
+
+    #[test]
+    fn test_names_are_kept_apart_from_colors() {
+        let theme = Theme::new().with_style("error", ContentStyle::new().red());
+        let error = named_style("error");
+        assert_eq!(
+            theme.resolve(error.underlined()),
+            ContentStyle::new().red().underlined()
+        );
+        assert_eq!(error.underlined().name().as_deref(), Some("error"));
+
+        // an underline color without an underline, like `ESC [58;2;0;0;0m`, is no name
+        let style = ContentStyle {
+            underline_color: Some(Color::Rgb { r: 0, g: 0, b: 0 }),
+            ..ContentStyle::default()
+        };
+        assert_eq!(ThemedStyle::from(style).name(), None);
+        assert_eq!(theme.resolve(style), style);
+    }
+
+    #[test]
+    fn test_built_in_styles_can_be_themed() {
+        let left = Buffer::from_text("ab").into_ref();
+        left.set_selection(TextOffset::ZERO..TextOffset::new(2));
+        let right = Buffer::new().into_ref();
+        let tree = split_tree!(Horizontal: {
+            1: left,
+            1: right,
+        });
+        let ablet = Ablet::new(tree).without_prompt();
+        let theme = Theme::new()
+            .with_style("selection", ContentStyle::new().on_blue())
+            .with_style("border", ContentStyle::new().red());
+        let row = |ablet: &Ablet| {
+            let frame = ablet.render_to_frame(Size { w: 7, h: 1 }).unwrap();
+            let row = frame.rows().next().unwrap().to_vec();
+            row
+        };
+        let default = row(&ablet);
+        assert_eq!(default[1].style.background_color, Some(Color::Grey));
+
+        ablet.set_theme(theme);
+        let themed = row(&ablet);
+        assert_eq!(themed[1].style.background_color, Some(Color::Blue));
+        let border = themed.iter().find(|c| c.symbol == "\u{2502}").unwrap();
+        assert_eq!(border.style.foreground_color, Some(Color::Red));
+    }
+}
@@ -0,0 +1,399 @@
+//! Rate limiting, for code that runs whenever something changes, like rendering in a
+//! background thread that receives messages, and debouncing of work that only the
+//! last of a burst of changes needs, like loading a preview.
+//!
+//! Deferred calls either run on a worker thread, which each instance starts while it
+//! has a deferred call, or, for types created with `polled`, when [Throttle::poll] or
+//! [Debounce::poll] is called, e.g. on every [AppEvent::Tick](crate::AppEvent::Tick),
+//! so they run on the event loop thread.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::error;
+
+type DeferredCall = Box<dyn FnOnce() + Send>;
+
+/// Lets calls through at most once per interval. Calls that come in too early
+/// are coalesced into one, which is run when the interval is over, so the last
+/// one always runs eventually. Clones share the same state.
+#[derive(Clone)]
+pub struct Throttle {
+    interval: Duration,
+    polled: bool,
+    state: Arc<Mutex<ThrottleState>>,
+}
+
+struct ThrottleState {
+    last_run: Option<Instant>,
+    /// the newest call that came in too early
+    pending: Option<DeferredCall>,
+    /// when the pending call may run
+    due: Instant,
+    worker_running: bool,
+}
+
+impl fmt::Debug for Throttle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Throttle")
+            .field("interval", &self.interval)
+            .field("polled", &self.polled)
+            .finish_non_exhaustive()
+    }
+}
+
+/// the state of a throttle or debounce, whose deferred call a worker runs
+trait Deferring: Send + 'static {
+    /// Takes the deferred call, if it's due. Otherwise returns when it will be, or
+    /// None if there is none
+    fn take_due(&mut self, now: Instant) -> Result<DeferredCall, Option<Instant>>;
+
+    fn worker_running(&mut self) -> &mut bool;
+}
+
+impl Deferring for ThrottleState {
+    fn take_due(&mut self, now: Instant) -> Result<DeferredCall, Option<Instant>> {
+        if self.pending.is_none() {
+            return Err(None);
+        }
+        if self.due > now {
+            return Err(Some(self.due));
+        }
+        self.last_run = Some(now);
+        Ok(self.pending.take().unwrap())
+    }
+
+    fn worker_running(&mut self) -> &mut bool {
+        &mut self.worker_running
+    }
+}
+
+/// Starts a thread that runs the deferred calls of the state when they're due, unless
+/// one runs already. It ends once there is no deferred call left, so a burst of calls
+/// shares one thread
+fn start_worker<S: Deferring>(state: &Arc<Mutex<S>>) {
+    if std::mem::replace(state.lock().unwrap().worker_running(), true) {
+        return;
+    }
+    let state = state.clone();
+    thread::spawn(move || loop {
+        let mut guard = state.lock().unwrap();
+        match guard.take_due(Instant::now()) {
+            Ok(f) => {
+                drop(guard);
+                f();
+            }
+            Err(Some(due)) => {
+                drop(guard);
+                thread::sleep(due.saturating_duration_since(Instant::now()));
+            }
+            Err(None) => {
+                *guard.worker_running() = false;
+                return;
+            }
+        }
+    });
+}
+
+impl Throttle {
+    /// creates a throttle that runs deferred calls on its worker thread
+    pub fn new(interval: Duration) -> Self {
+        Self::with_mode(interval, false)
+    }
+
+    /// creates a throttle that runs deferred calls in [Throttle::poll]
+    pub fn polled(interval: Duration) -> Self {
+        Self::with_mode(interval, true)
+    }
+
+    fn with_mode(interval: Duration, polled: bool) -> Self {
+        Self {
+            interval,
+            polled,
+            state: Arc::new(Mutex::new(ThrottleState {
+                last_run: None,
+                pending: None,
+                due: Instant::now(),
+                worker_running: false,
+            })),
+        }
+    }
+
+    /// Runs `f` now, if the last call ran at least the interval ago, and returns true.
+    /// Otherwise defers it, replacing a call that was deferred before
+    pub fn call(&self, f: impl FnOnce() + Send + 'static) -> bool {
+        match self.schedule(self.interval, Box::new(f)) {
+            Some(f) => {
+                f();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the call if it may run now, otherwise defers it. Used for renders,
+    /// which are run directly to return their error
+    pub(crate) fn schedule(&self, interval: Duration, f: DeferredCall) -> Option<DeferredCall> {
+        let res = self.schedule_locked(interval, f);
+        if res.is_none() && !self.polled {
+            start_worker(&self.state);
+        }
+        res
+    }
+
+    fn schedule_locked(&self, interval: Duration, f: DeferredCall) -> Option<DeferredCall> {
+        let mut state = self.state.lock().unwrap();
+        if state.pending.is_some() {
+            state.pending = Some(f);
+            return None;
+        }
+
+        let now = Instant::now();
+        let due = state.last_run.map_or(now, |last| last + interval);
+        if due <= now {
+            state.last_run = Some(now);
+            return Some(f);
+        }
+
+        state.pending = Some(f);
+        state.due = due;
+        None
+    }
+
+    /// runs the deferred call, if there is one and the interval is over. Returns
+    /// whether it ran
+    pub fn poll(&self) -> bool {
+        let f = self.state.lock().unwrap().take_due(Instant::now());
+        match f {
+            Ok(f) => {
+                f();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Counts as a call that ran now, and drops the deferred call, e.g. because what
+    /// it would do was done another way
+    pub(crate) fn mark_run(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.last_run = Some(Instant::now());
+        state.pending = None;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.state.lock().unwrap().pending.is_some()
+    }
+}
+
+/// Runs only the last of a burst of calls, once no new one came in for the delay.
+/// Clones share the same state.
+#[derive(Clone)]
+pub struct Debounce {
+    delay: Duration,
+    polled: bool,
+    state: Arc<Mutex<DebounceState>>,
+}
+
+struct DebounceState {
+    pending: Option<DeferredCall>,
+    due: Instant,
+    worker_running: bool,
+}
+
+impl Deferring for DebounceState {
+    fn take_due(&mut self, now: Instant) -> Result<DeferredCall, Option<Instant>> {
+        if self.pending.is_none() {
+            return Err(None);
+        }
+        if self.due > now {
+            return Err(Some(self.due));
+        }
+        Ok(self.pending.take().unwrap())
+    }
+
+    fn worker_running(&mut self) -> &mut bool {
+        &mut self.worker_running
+    }
+}
+
+impl Debounce {
+    /// creates a debounce that runs the calls on its worker thread
+    pub fn new(delay: Duration) -> Self {
+        Self::with_mode(delay, false)
+    }
+
+    /// creates a debounce that runs the calls in [Debounce::poll]
+    pub fn polled(delay: Duration) -> Self {
+        Self::with_mode(delay, true)
+    }
+
+    fn with_mode(delay: Duration, polled: bool) -> Self {
+        Self {
+            delay,
+            polled,
+            state: Arc::new(Mutex::new(DebounceState {
+                pending: None,
+                due: Instant::now(),
+                worker_running: false,
+            })),
+        }
+    }
+
+    /// replaces the pending call with `f`, which runs after the delay
+    pub fn call(&self, f: impl FnOnce() + Send + 'static) {
+        let mut state = self.state.lock().unwrap();
+        state.pending = Some(Box::new(f));
+        state.due = Instant::now() + self.delay;
+        drop(state);
+        // a running worker sees the new due time when it wakes up
+        if !self.polled {
+            start_worker(&self.state);
+        }
+    }
+
+    /// runs the pending call, if there is one and its delay is over. Returns whether
+    /// it ran
+    pub fn poll(&self) -> bool {
+        self.poll_at(Instant::now())
+    }
+
+    /// like [Debounce::poll], but with an explicit current time, for tests
+    pub(crate) fn poll_at(&self, now: Instant) -> bool {
+        let f = self.state.lock().unwrap().take_due(now);
+        match f {
+            Ok(f) => {
+                f();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// drops the pending call
+    pub fn cancel(&self) {
+        self.state.lock().unwrap().pending = None;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.state.lock().unwrap().pending.is_some()
+    }
+}
+
+/// logs the error of a render that was deferred
+pub(crate) fn log_render_error(res: std::io::Result<()>) {
+    if let Err(e) = res {
+        error!("Deferred render failed: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_renders_are_coalesced() {
+        let interval = Duration::from_millis(50);
+        let throttle = Throttle::new(interval);
+        let count = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(AtomicUsize::new(0));
+
+        for i in 1..=3 {
+            let (count, last) = (count.clone(), last.clone());
+            throttle.call(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+                last.store(i, Ordering::SeqCst);
+            });
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        thread::sleep(interval * 3);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(last.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_debounce_runs_the_last_call() {
+        let debounce = Debounce::new(Duration::from_millis(30));
+        let count = Arc::new(AtomicUsize::new(0));
+        let last = Arc::new(AtomicUsize::new(0));
+        for i in 1..=3 {
+            let (count, last) = (count.clone(), last.clone());
+            debounce.call(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+                last.store(i, Ordering::SeqCst);
+            });
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(last.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_polled() {
+        let delay = Duration::from_millis(20);
+        let debounce = Debounce::polled(delay);
+        let throttle = Throttle::polled(delay);
+        let count = Arc::new(AtomicUsize::new(0));
+        let inc = || {
+            let count = count.clone();
+            move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+        debounce.call(inc());
+        assert!(throttle.call(inc()));
+        assert!(!throttle.call(inc()));
+        assert!(!debounce.poll() && !throttle.poll());
+
+        thread::sleep(delay * 3);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(debounce.poll() && throttle.poll());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert!(!debounce.is_pending() && !throttle.is_pending());
+    }
+
+    #[test]
+    fn test_mark_run_drops_the_deferred_call() {
+        let throttle = Throttle::new(Duration::from_millis(20));
+        let count = Arc::new(AtomicUsize::new(0));
+        for _ in 0..2 {
+            let count = count.clone();
+            throttle.call(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        assert!(throttle.is_pending());
+        throttle.mark_run();
+        assert!(!throttle.is_pending());
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(!throttle.state.lock().unwrap().worker_running);
+    }
+
+    #[test]
+    fn test_debounce_uses_one_worker_for_a_burst() {
+        let debounce = Debounce::new(Duration::from_millis(30));
+        let count = Arc::new(AtomicUsize::new(0));
+        for _ in 0..20 {
+            let count = count.clone();
+            debounce.call(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            });
+            thread::sleep(Duration::from_millis(2));
+        }
+        // the worker that started with the first call is still the only one
+        assert!(debounce.state.lock().unwrap().worker_running);
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(!debounce.state.lock().unwrap().worker_running);
+    }
+}
@@ -0,0 +1,237 @@
+//! Contains the TimerText, a stopwatch or countdown that can be shown in a document
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crossterm::style::Stylize;
+
+use crate::{animation, shared, AText, DocumentRef, Shared};
+
+/// the timers that are shown, with their documents, see [TimerText::show_in]
+static SHOWN: Mutex<Vec<(TimerText, DocumentRef)>> = Mutex::new(vec![]);
+
+type DoneCallback = Box<dyn FnOnce() + Send>;
+
+/// A stopwatch, that shows the elapsed time, or a countdown, that shows the remaining
+/// time, and runs a callback when it reaches zero. Times are shown as `mm:ss`, or
+/// `h:mm:ss` from an hour on. Clones share the same state.
+#[derive(Clone)]
+pub struct TimerText(Shared<TimerState>);
+
+struct TimerState {
+    started: Instant,
+    /// None for a stopwatch
+    duration: Option<Duration>,
+    on_done: Option<DoneCallback>,
+    stopped: bool,
+}
+
+impl TimerText {
+    pub fn stopwatch() -> Self {
+        Self::new(None)
+    }
+
+    pub fn countdown(duration: Duration) -> Self {
+        Self::new(Some(duration))
+    }
+
+    fn new(duration: Option<Duration>) -> Self {
+        Self(shared(TimerState {
+            started: Instant::now(),
+            duration,
+            on_done: None,
+            stopped: false,
+        }))
+    }
+
+    /// Sets the callback that runs when the countdown reaches zero. It's run by
+    /// [TimerText::tick], which is done on every render while the timer is shown
+    pub fn on_done(&self, f: impl FnOnce() + Send + 'static) {
+        self.0.lock().unwrap().on_done = Some(Box::new(f));
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed_at(Instant::now())
+    }
+
+    fn elapsed_at(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.0.lock().unwrap().started)
+    }
+
+    /// the remaining time of a countdown, None for a stopwatch
+    pub fn remaining(&self) -> Option<Duration> {
+        self.remaining_at(Instant::now())
+    }
+
+    fn remaining_at(&self, now: Instant) -> Option<Duration> {
+        let duration = self.0.lock().unwrap().duration?;
+        Some(duration.saturating_sub(self.elapsed_at(now)))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining() == Some(Duration::ZERO)
+    }
+
+    /// the elapsed time of a stopwatch, or the remaining time of a countdown, which
+    /// turns red when it's done
+    pub fn text(&self) -> AText {
+        self.text_at(Instant::now())
+    }
+
+    fn text_at(&self, now: Instant) -> AText {
+        match self.remaining_at(now) {
+            None => format_duration(self.elapsed_at(now)).into(),
+            Some(Duration::ZERO) => format_duration(Duration::ZERO).red().into(),
+            // rounded up, so it shows 00:00 only when it's done
+            Some(remaining) => format_duration(remaining + Duration::from_millis(999)).into(),
+        }
+    }
+
+    /// runs the callback, if the countdown is done, and it didn't run yet
+    pub fn tick(&self) {
+        self.tick_at(Instant::now())
+    }
+
+    fn tick_at(&self, now: Instant) {
+        if self.remaining_at(now) != Some(Duration::ZERO) {
+            return;
+        }
+        let on_done = self.0.lock().unwrap().on_done.take();
+        if let Some(on_done) = on_done {
+            on_done();
+        }
+    }
+
+    /// Shows the timer as the content of the document. It's updated whenever an
+    /// [Ablet](crate::Ablet) or [SplitTree](crate::SplitTree) renders, and a frame is
+    /// requested for each change of the text, so the editing loops render in time.
+    /// That ends when the timer is stopped, or a countdown is done
+    pub fn show_in(&self, doc: DocumentRef) {
+        if let Some(delay) = self.update_at(&doc, Instant::now()) {
+            animation::request_frame_in(delay);
+            SHOWN.lock().unwrap().push((self.clone(), doc));
+        }
+    }
+
+    /// stops updating the document the timer is shown in
+    pub fn stop(&self) {
+        self.0.lock().unwrap().stopped = true;
+    }
+
+    /// Ticks, and shows the text in the document, if it changed. Returns the time
+    /// until it changes again, or None if the timer is stopped or done
+    fn update_at(&self, doc: &DocumentRef, now: Instant) -> Option<Duration> {
+        if self.0.lock().unwrap().stopped {
+            return None;
+        }
+        self.tick_at(now);
+        let text = self.text_at(now);
+        if doc.0.lock().unwrap().content.text != text.text {
+            doc.update_content(|content| *content = text);
+        }
+        let subsec = |d: Duration| Duration::from_nanos(d.subsec_nanos().into());
+        match self.remaining_at(now) {
+            None => Some(Duration::from_secs(1) - subsec(self.elapsed_at(now))),
+            Some(Duration::ZERO) => None,
+            // the remaining time is rounded up, so it changes after a whole second
+            Some(remaining) if subsec(remaining).is_zero() => Some(Duration::from_secs(1)),
+            Some(remaining) => Some(subsec(remaining)),
+        }
+    }
+}
+
+/// updates the shown timers, see [TimerText::show_in]
+pub(crate) fn update_shown_timers() {
+    // taken out, so callbacks can show timers
+    let shown = std::mem::take(&mut *SHOWN.lock().unwrap());
+    let now = Instant::now();
+    let mut still_shown = vec![];
+    for (timer, doc) in shown {
+        if let Some(delay) = timer.update_at(&doc, now) {
+            animation::request_frame_in(delay);
+            still_shown.push((timer, doc));
+        }
+    }
+    SHOWN.lock().unwrap().extend(still_shown);
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m:02}:{s:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::Document;
+
+    #[test]
+    fn test_countdown() {
+        assert_eq!(format_duration(Duration::from_secs(62)), "01:02");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 3600 + 5)),
+            "3:00:05"
+        );
+
+        let timer = TimerText::countdown(Duration::from_millis(30));
+        let started = timer.0.lock().unwrap().started;
+        assert_eq!(timer.text_at(started).as_str(), "00:01");
+        let done = Arc::new(AtomicBool::new(false));
+        timer.on_done({
+            let done = done.clone();
+            move || done.store(true, Ordering::Relaxed)
+        });
+        timer.tick_at(started + Duration::from_millis(20));
+        assert!(!done.load(Ordering::Relaxed));
+
+        let later = started + Duration::from_millis(40);
+        timer.tick_at(later);
+        assert!(done.load(Ordering::Relaxed));
+        assert_eq!(timer.text_at(later).as_str(), "00:00");
+    }
+
+    #[test]
+    fn test_shown_timers_update_their_document() {
+        let doc = Document::new().into_ref();
+        let text = |doc: &DocumentRef| doc.0.lock().unwrap().content.text.clone();
+        let secs = |s: f32| Duration::from_secs_f32(s);
+
+        let stopwatch = TimerText::stopwatch();
+        let started = stopwatch.0.lock().unwrap().started;
+        assert_eq!(
+            stopwatch.update_at(&doc, started + secs(1.25)),
+            Some(secs(0.75))
+        );
+        assert_eq!(text(&doc), "00:01");
+        stopwatch.stop();
+        assert_eq!(stopwatch.update_at(&doc, started + secs(2.5)), None);
+        assert_eq!(text(&doc), "00:01");
+
+        let countdown = TimerText::countdown(secs(2.0));
+        let started = countdown.0.lock().unwrap().started;
+        assert_eq!(
+            countdown.update_at(&doc, started + secs(0.25)),
+            Some(secs(0.75))
+        );
+        assert_eq!(text(&doc), "00:02");
+        assert_eq!(
+            countdown.update_at(&doc, started + secs(1.0)),
+            Some(secs(1.0))
+        );
+        assert_eq!(text(&doc), "00:01");
+        assert_eq!(countdown.update_at(&doc, started + secs(3.0)), None);
+        assert_eq!(text(&doc), "00:00");
+    }
+}
@@ -0,0 +1,118 @@
+//! Contains the BufferLayer, a tracing layer that writes events into a buffer
+
+use std::fmt::{Debug, Write as _};
+
+use crossterm::style::Stylize;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::{logger::styled_level, AText, BufferRef};
+
+/// Writes tracing events into a buffer, one line per event, like
+/// `WARN  app::net: request: connection lost retries=3`, with the level colored,
+/// and the names of the spans the event happened in in front of the message.
+/// The buffer scrolls down as lines are added. Like with the
+/// [BufferLogger](crate::BufferLogger), events must not be recorded while the
+/// buffer is locked.
+pub struct BufferLayer {
+    buffer: BufferRef,
+    show_fields: bool,
+}
+
+impl BufferLayer {
+    pub fn new(buffer: BufferRef) -> Self {
+        Self {
+            buffer,
+            show_fields: true,
+        }
+    }
+
+    /// whether fields other than the message are shown behind it, as `key=value`
+    pub fn with_fields(self, show_fields: bool) -> Self {
+        Self {
+            show_fields,
+            ..self
+        }
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for BufferLayer {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let level = match *meta.level() {
+            Level::ERROR => log::Level::Error,
+            Level::WARN => log::Level::Warn,
+            Level::INFO => log::Level::Info,
+            Level::DEBUG => log::Level::Debug,
+            Level::TRACE => log::Level::Trace,
+        };
+        let mut line = AText::from(styled_level(level, format!("{:<5}", meta.level())))
+            + " "
+            + meta.target().dark_grey()
+            + ": ";
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                line += span.name().italic();
+                line += ": ";
+            }
+        }
+        line += visitor.message;
+        if self.show_fields && !visitor.fields.is_empty() {
+            line += visitor.fields.dark_grey();
+        }
+        self.buffer.add_line(line);
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    /// ` key=value` for each field
+    fields: String,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn test_events_are_formatted() {
+        let buf = Buffer::new().into_ref();
+        let subscriber = tracing_subscriber::registry().with(BufferLayer::new(buf.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("request").entered();
+            tracing::warn!(target: "app::net", retries = 3, "connection lost");
+        });
+
+        let doc = buf.get_doc();
+        let text = doc.0.lock().unwrap().content.text.clone();
+        assert_eq!(text, "WARN  app::net: request: connection lost retries=3\n");
+    }
+}
@@ -0,0 +1,228 @@
+//! Contains the Transcript, which records the lines that are added to documents,
+//! e.g. to save the log of a chat or REPL session
+
+use std::{
+    fmt::Write as _,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use crate::{shared, theme::default_theme, AText, Range, Shared};
+
+/// Records lines that are added to documents, see [DocumentRef::record_to](crate::DocumentRef::record_to).
+/// Clones share the same entries, so one transcript can record several documents.
+#[derive(Clone, Default)]
+pub struct Transcript(Shared<Vec<TranscriptEntry>>);
+
+#[derive(Clone, Debug)]
+pub struct TranscriptEntry {
+    pub time: SystemTime,
+    /// the name the document is recorded under
+    pub source: String,
+    pub text: AText,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self(shared(vec![]))
+    }
+
+    pub fn record(&self, source: &str, text: AText) {
+        self.0.lock().unwrap().push(TranscriptEntry {
+            time: SystemTime::now(),
+            source: source.to_string(),
+            text,
+        });
+    }
+
+    pub fn entries(&self) -> Vec<TranscriptEntry> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear()
+    }
+
+    /// one line per entry, like `[12:04:31] chat: hello`, with times in UTC
+    pub fn to_text(&self) -> String {
+        let mut res = String::new();
+        for entry in self.0.lock().unwrap().iter() {
+            let time = format_time(entry.time);
+            _ = writeln!(res, "[{time}] {}: {}", entry.source, entry.text.as_str());
+        }
+        res
+    }
+
+    /// like [Transcript::to_text], but as a html `pre` element that keeps the styles,
+    /// with the named ones as the default [Theme](crate::Theme) has them
+    pub fn to_html(&self) -> String {
+        let theme = default_theme();
+        let mut res = String::from("<pre class=\"transcript\">\n");
+        for entry in self.0.lock().unwrap().iter() {
+            let time = format_time(entry.time);
+            _ = write!(res, "[{time}] {}: ", escape_html(&entry.source));
+            let text = &entry.text;
+            for run in text.get_range_style_pairs(Range::new(0, text.len())) {
+                let content = escape_html(&text.text[run.range.into_native()]);
+                match css(&theme.resolve(*run.style)) {
+                    Some(css) => _ = write!(res, "<span style=\"{css}\">{content}</span>"),
+                    None => res.push_str(&content),
+                }
+            }
+            res.push('\n');
+        }
+        res.push_str("</pre>\n");
+        res
+    }
+
+    /// a json array of objects with the time in seconds since the unix epoch, the
+    /// source, and the plain text
+    pub fn to_json(&self) -> String {
+        let entries = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                let time = entry
+                    .time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                format!(
+                    "{{\"time\":{time},\"source\":{},\"text\":{}}}",
+                    json_string(&entry.source),
+                    json_string(entry.text.as_str())
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", entries.join(","))
+    }
+}
+
+/// formats the time of day in UTC, as hh:mm:ss
+fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        % 86400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn json_string(text: &str) -> String {
+    let mut res = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if (c as u32) < 0x20 => _ = write!(res, "\\u{:04x}", c as u32),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// the css for the style, or None if it's the default style
+fn css(style: &ContentStyle) -> Option<String> {
+    let mut res = vec![];
+    if let Some(color) = style.foreground_color.and_then(css_color) {
+        res.push(format!("color:{color}"));
+    }
+    if let Some(color) = style.background_color.and_then(css_color) {
+        res.push(format!("background-color:{color}"));
+    }
+    if style.attributes.has(Attribute::Bold) {
+        res.push("font-weight:bold".into());
+    }
+    if style.attributes.has(Attribute::Italic) {
+        res.push("font-style:italic".into());
+    }
+    if style.attributes.has(Attribute::Underlined) {
+        res.push("text-decoration:underline".into());
+    }
+    (!res.is_empty()).then(|| res.join(";"))
+}
+
+fn css_color(color: Color) -> Option<String> {
+    const NAMED: [&str; 16] = [
+        "black", "maroon", "green", "olive", "navy", "purple", "teal", "silver", "gray", "red",
+        "lime", "yellow", "blue", "fuchsia", "aqua", "white",
+    ];
+    let name = |i: usize| Some(NAMED[i].to_string());
+    match color {
+        Color::Reset => None,
+        Color::Black => name(0),
+        Color::DarkRed => name(1),
+        Color::DarkGreen => name(2),
+        Color::DarkYellow => name(3),
+        Color::DarkBlue => name(4),
+        Color::DarkMagenta => name(5),
+        Color::DarkCyan => name(6),
+        Color::Grey => name(7),
+        Color::DarkGrey => name(8),
+        Color::Red => name(9),
+        Color::Green => name(10),
+        Color::Yellow => name(11),
+        Color::Blue => name(12),
+        Color::Magenta => name(13),
+        Color::Cyan => name(14),
+        Color::White => name(15),
+        Color::Rgb { r, g, b } => Some(format!("#{r:02x}{g:02x}{b:02x}")),
+        Color::AnsiValue(v @ 0..16) => name(v as usize),
+        // the 6x6x6 color cube
+        Color::AnsiValue(v @ 16..232) => {
+            let level = |x: u8| if x == 0 { 0 } else { 55 + x * 40 };
+            let v = v - 16;
+            let (r, g, b) = (level(v / 36), level(v / 6 % 6), level(v % 6));
+            Some(format!("#{r:02x}{g:02x}{b:02x}"))
+        }
+        // the gray ramp
+        Color::AnsiValue(v) => {
+            let l = 8 + (v - 232) * 10;
+            Some(format!("#{l:02x}{l:02x}{l:02x}"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crossterm::style::Stylize;
+
+    use super::*;
+
+    #[test]
+    fn test_export() {
+        let transcript = Transcript::new();
+        transcript.0.lock().unwrap().push(TranscriptEntry {
+            time: UNIX_EPOCH + Duration::from_secs(3600 + 62),
+            source: "chat".into(),
+            text: AText::from("< ".green()) + "a \"<b>\"",
+        });
+
+        assert_eq!(transcript.to_text(), "[01:01:02] chat: < a \"<b>\"\n");
+        assert_eq!(
+            transcript.to_html(),
+            "<pre class=\"transcript\">\n[01:01:02] chat: <span style=\"color:lime\">&lt; \
+             </span>a &quot;&lt;b&gt;&quot;\n</pre>\n"
+        );
+        assert_eq!(
+            transcript.to_json(),
+            r#"[{"time":3662,"source":"chat","text":"< a \"<b>\""}]"#
+        );
+    }
+}
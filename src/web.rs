@@ -0,0 +1,380 @@
+//! An experimental backend that renders to [xterm.js](https://xtermjs.org/) in a
+//! browser, via a websocket. Only available with the `web` feature.
+//!
+//! [accept] performs the websocket handshake on a connection and returns a
+//! [WebRenderer], which sends every frame as escape sequences that xterm.js
+//! understands, and [WebEvents], which translates the input of the terminal into
+//! crossterm events. [client_html] returns a minimal page that connects to the app.
+//!
+//! ```no_run
+//! use std::{io, net::TcpListener};
+//! use ablet::{split_tree, web, Buffer};
+//!
+//! fn main() -> io::Result<()> {
+//!     let buf = Buffer::from_text("Hello from the server").into_ref();
+//!     let tree = split_tree!(Vertical: { 1: buf });
+//!
+//!     let (stream, _) = TcpListener::bind("127.0.0.1:7777")?.accept()?;
+//!     let (mut renderer, mut events) = web::accept(stream)?;
+//!     loop {
+//!         tree.render_with(&mut renderer)?;
+//!         events.read()?;
+//!     }
+//! }
+//! ```
+
+use std::{
+    collections::VecDeque,
+    io,
+    iter::Peekable,
+    net::TcpStream,
+    str::Chars,
+    sync::{Arc, Mutex},
+};
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEvent, KeyModifiers},
+    style::ContentStyle,
+};
+use serde::Deserialize;
+use tungstenite::{protocol::Role, Message, WebSocket};
+
+use crate::{BufferPosition, ColorProfile, CrosstermRenderer, Renderer, Size};
+
+/// Messages from the browser, see [client_html]
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    /// what xterm.js reports via `onData`, which is the raw terminal input
+    Data {
+        data: String,
+    },
+    Resize {
+        cols: u16,
+        rows: u16,
+    },
+}
+
+/// Performs the websocket handshake, and waits for the client to announce
+/// its size
+pub fn accept(stream: TcpStream) -> io::Result<(WebRenderer, WebEvents)> {
+    let write_half = stream.try_clone()?;
+    let ws = tungstenite::accept(stream).map_err(io::Error::other)?;
+    let mut events = WebEvents {
+        ws,
+        pending: VecDeque::new(),
+        size: Arc::new(Mutex::new(Size { w: 0, h: 0 })),
+    };
+    while !matches!(events.read()?, Event::Resize(..)) {}
+
+    // The handshake is done, so a second websocket on the same connection can be used for
+    // writing, that way reading and writing don't block each other
+    let renderer = WebRenderer {
+        ws: WebSocket::from_raw_socket(write_half, Role::Server, None),
+        vt: xterm_js_renderer(),
+        size: events.size.clone(),
+    };
+    Ok((renderer, events))
+}
+
+/// A Renderer that sends each frame to xterm.js as escape sequences
+pub struct WebRenderer {
+    ws: WebSocket<TcpStream>,
+    vt: CrosstermRenderer<Vec<u8>>,
+    size: Arc<Mutex<Size>>,
+}
+
+impl Renderer for WebRenderer {
+    fn size(&self) -> io::Result<Size> {
+        Ok(*self.size.lock().unwrap())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.vt.clear()
+    }
+
+    fn move_to(&mut self, pos: BufferPosition) -> io::Result<()> {
+        self.vt.move_to(pos)
+    }
+
+    fn set_style(&mut self, style: &ContentStyle) -> io::Result<()> {
+        self.vt.set_style(style)
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        self.vt.print(text)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let vt = std::mem::replace(&mut self.vt, xterm_js_renderer());
+        let bytes = vt.into_inner();
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let text = String::from_utf8(bytes).map_err(io::Error::other)?;
+        self.ws.send(Message::text(text)).map_err(to_io_error)
+    }
+}
+
+/// xterm.js supports the underline styles and RGB colors, whatever terminal the
+/// server runs in, if any
+fn xterm_js_renderer() -> CrosstermRenderer<Vec<u8>> {
+    CrosstermRenderer::new(vec![])
+        .with_styled_underlines(true)
+        .with_color_profile(ColorProfile::TrueColor)
+}
+
+/// The input of the browser terminal, as crossterm events
+pub struct WebEvents {
+    ws: WebSocket<TcpStream>,
+    pending: VecDeque<Event>,
+    size: Arc<Mutex<Size>>,
+}
+
+impl WebEvents {
+    /// Blocks until the next event arrives. Size changes are turned into Resize events,
+    /// and picked up by the corresponding [WebRenderer]. Fails with
+    /// `ErrorKind::ConnectionAborted` once the browser closed the connection.
+    pub fn read(&mut self) -> io::Result<Event> {
+        loop {
+            if let Some(ev) = self.pending.pop_front() {
+                return Ok(ev);
+            }
+
+            let text = match self.ws.read().map_err(to_io_error)? {
+                Message::Text(text) => text,
+                Message::Close(_) => return Err(io::ErrorKind::ConnectionAborted.into()),
+                _ => continue,
+            };
+            match serde_json::from_str(&text)? {
+                ClientMessage::Data { data } => self.pending.extend(parse_input(&data)),
+                ClientMessage::Resize { cols, rows } => {
+                    *self.size.lock().unwrap() = Size { w: cols, h: rows };
+                    return Ok(Event::Resize(cols, rows));
+                }
+            }
+        }
+    }
+}
+
+fn to_io_error(e: tungstenite::Error) -> io::Error {
+    match e {
+        tungstenite::Error::Io(e) => e,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            io::ErrorKind::ConnectionAborted.into()
+        }
+        e => io::Error::other(e),
+    }
+}
+
+/// Translates raw terminal input, as it is produced by xterm.js, into crossterm events.
+/// Sequences that aren't understood are dropped.
+pub fn parse_input(data: &str) -> Vec<Event> {
+    let mut res = vec![];
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        let ev = match c {
+            '\x1b' => match chars.peek() {
+                None => key(KeyCode::Esc, KeyModifiers::NONE),
+                Some('[') => {
+                    chars.next();
+                    match parse_csi(&mut chars) {
+                        Some(ev) => ev,
+                        None => continue,
+                    }
+                }
+                Some('O') => {
+                    chars.next();
+                    match chars
+                        .next()
+                        .and_then(|c| final_byte_key(c, KeyModifiers::NONE))
+                    {
+                        Some(ev) => ev,
+                        None => continue,
+                    }
+                }
+                Some(&c) => {
+                    chars.next();
+                    let Event::Key(ke) = char_key(c) else {
+                        continue;
+                    };
+                    key(ke.code, ke.modifiers | KeyModifiers::ALT)
+                }
+            },
+            c => char_key(c),
+        };
+        res.push(ev);
+    }
+    res
+}
+
+fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+    Event::Key(KeyEvent::new(code, modifiers))
+}
+
+/// translates a single char that is not part of an escape sequence
+fn char_key(c: char) -> Event {
+    match c {
+        '\r' | '\n' => key(KeyCode::Enter, KeyModifiers::NONE),
+        '\x7f' | '\x08' => key(KeyCode::Backspace, KeyModifiers::NONE),
+        '\t' => key(KeyCode::Tab, KeyModifiers::NONE),
+        '\x00' => key(KeyCode::Char(' '), KeyModifiers::CONTROL),
+        '\x01'..='\x1a' => key(
+            KeyCode::Char((c as u8 - 1 + b'a') as char),
+            KeyModifiers::CONTROL,
+        ),
+        c if c.is_uppercase() => key(KeyCode::Char(c), KeyModifiers::SHIFT),
+        c => key(KeyCode::Char(c), KeyModifiers::NONE),
+    }
+}
+
+/// parses what comes after `ESC [`
+fn parse_csi(chars: &mut Peekable<Chars>) -> Option<Event> {
+    let mut params = String::new();
+    let final_byte = loop {
+        match chars.next()? {
+            c @ ('0'..='9' | ';') => params.push(c),
+            c => break c,
+        }
+    };
+    let mut params = params.split(';').map(|p| p.parse::<u16>().unwrap_or(1));
+    let first = params.next().unwrap_or(1);
+    let modifiers = params
+        .next()
+        .map_or(KeyModifiers::NONE, modifiers_from_param);
+
+    if final_byte != '~' {
+        return final_byte_key(final_byte, modifiers);
+    }
+
+    let code = match first {
+        1 | 7 => KeyCode::Home,
+        2 => KeyCode::Insert,
+        3 => KeyCode::Delete,
+        4 | 8 => KeyCode::End,
+        5 => KeyCode::PageUp,
+        6 => KeyCode::PageDown,
+        200 => return Some(Event::Paste(read_paste(chars))),
+        n @ 11..=15 => KeyCode::F(n as u8 - 10),
+        n @ 17..=21 => KeyCode::F(n as u8 - 11),
+        n @ 23..=24 => KeyCode::F(n as u8 - 12),
+        _ => return None,
+    };
+    Some(key(code, modifiers))
+}
+
+fn final_byte_key(c: char, modifiers: KeyModifiers) -> Option<Event> {
+    let code = match c {
+        'A' => KeyCode::Up,
+        'B' => KeyCode::Down,
+        'C' => KeyCode::Right,
+        'D' => KeyCode::Left,
+        'H' => KeyCode::Home,
+        'F' => KeyCode::End,
+        'P' => KeyCode::F(1),
+        'Q' => KeyCode::F(2),
+        'R' => KeyCode::F(3),
+        'S' => KeyCode::F(4),
+        'Z' => return Some(key(KeyCode::BackTab, modifiers | KeyModifiers::SHIFT)),
+        _ => return None,
+    };
+    Some(key(code, modifiers))
+}
+
+/// the modifier parameter is 1 + a bit mask of shift (1), alt (2) and ctrl (4)
+fn modifiers_from_param(param: u16) -> KeyModifiers {
+    let bits = param.saturating_sub(1);
+    let mut res = KeyModifiers::NONE;
+    if bits & 1 != 0 {
+        res |= KeyModifiers::SHIFT;
+    }
+    if bits & 2 != 0 {
+        res |= KeyModifiers::ALT;
+    }
+    if bits & 4 != 0 {
+        res |= KeyModifiers::CONTROL;
+    }
+    res
+}
+
+/// collects everything up to the end of a bracketed paste
+fn read_paste(chars: &mut Peekable<Chars>) -> String {
+    const END: &str = "\x1b[201~";
+    let mut res = String::new();
+    for c in chars.by_ref() {
+        res.push(c);
+        if res.ends_with(END) {
+            res.truncate(res.len() - END.len());
+            break;
+        }
+    }
+    res
+}
+
+/// Returns a minimal html page that shows xterm.js and connects it to the app
+/// listening at `ws_url`, e.g. `ws://localhost:7777`. xterm.js is loaded from a CDN.
+pub fn client_html(ws_url: &str) -> String {
+    format!(
+        r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/@xterm/xterm/css/xterm.css">
+<script src="https://cdn.jsdelivr.net/npm/@xterm/xterm/lib/xterm.js"></script>
+<script src="https://cdn.jsdelivr.net/npm/@xterm/addon-fit/lib/addon-fit.js"></script>
+</head>
+<body style="margin: 0">
+<div id="terminal" style="height: 100vh"></div>
+<script>
+const term = new Terminal();
+const fit = new FitAddon.FitAddon();
+term.loadAddon(fit);
+term.open(document.getElementById("terminal"));
+fit.fit();
+const ws = new WebSocket("{ws_url}");
+const sendSize = () => ws.send(JSON.stringify({{ type: "resize", cols: term.cols, rows: term.rows }}));
+ws.onopen = sendSize;
+ws.onmessage = (e) => term.write(e.data);
+term.onResize(sendSize);
+term.onData((data) => ws.send(JSON.stringify({{ type: "data", data }})));
+window.addEventListener("resize", () => fit.fit());
+</script>
+</body>
+</html>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::{Color, Stylize};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        let events = parse_input("a\x01\x1b[A\x1b[1;5C\x1bx\x1b[3~\x1b[200~pasted\x1b[201~\r");
+        assert_eq!(
+            events,
+            vec![
+                key(KeyCode::Char('a'), KeyModifiers::NONE),
+                key(KeyCode::Char('a'), KeyModifiers::CONTROL),
+                key(KeyCode::Up, KeyModifiers::NONE),
+                key(KeyCode::Right, KeyModifiers::CONTROL),
+                key(KeyCode::Char('x'), KeyModifiers::ALT),
+                key(KeyCode::Delete, KeyModifiers::NONE),
+                Event::Paste("pasted".into()),
+                key(KeyCode::Enter, KeyModifiers::NONE),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rgb_colors_are_sent_as_they_are() {
+        let mut vt = xterm_js_renderer();
+        let style = ContentStyle::new().with(Color::Rgb { r: 1, g: 2, b: 3 });
+        vt.set_style(&style).unwrap();
+        let out = String::from_utf8(vt.into_inner()).unwrap();
+        assert!(out.contains("38;2;1;2;3"));
+    }
+}
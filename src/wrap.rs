@@ -0,0 +1,196 @@
+//! Wrapping of text into lines that fit a width, for text that is drawn into a
+//! fixed space, like the message of a dialog, optionally justified, e.g. for a
+//! reader-style pane. Buffers don't wrap their lines yet.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{AText, ATextBuilder, Range};
+
+const SOFT_HYPHEN: &str = "\u{ad}";
+
+/// Where [wrap] may break lines
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WrapMode {
+    /// Breaks after spaces, hyphens and soft hyphens, and only cuts words that are
+    /// longer than a line
+    #[default]
+    Word,
+    /// breaks at the last character that fits, spaces count like other characters
+    Char,
+}
+
+/// Splits the text into lines that are at most `width` columns wide. Existing line
+/// breaks are kept, and in [WrapMode::Word], spaces at a break are dropped, like
+/// indentation that doesn't fit. Soft hyphens are only shown as a hyphen where a
+/// line is broken at them
+pub fn wrap(text: &AText, width: usize, mode: WrapMode) -> Vec<AText> {
+    wrap_rows(text, width, mode)
+        .into_iter()
+        .map(|(row, _)| row)
+        .collect()
+}
+
+/// Like [wrap], but the rows of a paragraph are filled up with spaces between the
+/// words, so both margins align. The last row of a paragraph isn't stretched
+pub fn wrap_justified(text: &AText, width: usize, mode: WrapMode) -> Vec<AText> {
+    wrap_rows(text, width, mode)
+        .into_iter()
+        .map(|(row, last)| if last { row } else { justify(row, width) })
+        .collect()
+}
+
+/// the wrapped rows, and whether they are the last row of a line
+fn wrap_rows(text: &AText, width: usize, mode: WrapMode) -> Vec<(AText, bool)> {
+    let mut res = vec![];
+    let mut line_start = 0;
+    for line in text.text.split('\n') {
+        let rows = wrap_line(line, width, mode);
+        let n_rows = rows.len();
+        for (i, (range, hyphen)) in rows.into_iter().enumerate() {
+            let range = Range::new(line_start + range.start, line_start + range.end);
+            let mut row = ATextBuilder::new();
+            let mut hyphen_style = None;
+            for run in text.get_range_style_pairs(range) {
+                let part = &text.text[run.range.into_native()];
+                row.push_styled(&part.replace(SOFT_HYPHEN, ""), *run.style);
+                hyphen_style = Some(*run.style);
+            }
+            if let (true, Some(style)) = (hyphen, hyphen_style) {
+                row.push_styled("-", style);
+            }
+            res.push((row.build(), i + 1 == n_rows));
+        }
+        line_start += line.len() + 1;
+    }
+    res
+}
+
+/// Widens the spaces between words, so the row is `width` wide. Indentation is kept
+fn justify(mut row: AText, width: usize) -> AText {
+    let indent = row.text.len() - row.text.trim_start().len();
+    let gaps: Vec<usize> = row.text[indent..]
+        .match_indices(' ')
+        .map(|(i, _)| indent + i)
+        .collect();
+    let missing = width.saturating_sub(row.text.width());
+    if gaps.is_empty() || missing == 0 {
+        return row;
+    }
+    // from the back, so the indices stay valid
+    for (k, &i) in gaps.iter().enumerate().rev() {
+        let n = missing / gaps.len() + usize::from(k < missing % gaps.len());
+        row.insert(i, " ".repeat(n));
+    }
+    row
+}
+
+/// The ranges of the rows of a line without line breaks, and whether they end at a
+/// soft hyphen. Every row contains at least one grapheme cluster, even if it's too wide
+fn wrap_line(line: &str, width: usize, mode: WrapMode) -> Vec<(Range<usize>, bool)> {
+    let mut rows = vec![];
+    let mut start = 0;
+    let mut row_width = 0;
+    // the end of the row and the start of the next one, if it's broken at the last
+    // opportunity, and whether that is a soft hyphen
+    let mut last_break: Option<(usize, usize, bool)> = None;
+    let mut prev_was_space = false;
+
+    for (i, g) in line.grapheme_indices(true) {
+        let is_space = g.chars().all(char::is_whitespace);
+        if is_space && mode == WrapMode::Word {
+            // spaces may hang over the end, since they are dropped at a break
+            last_break = match last_break {
+                Some((end, _, false)) if prev_was_space => Some((end, i + g.len(), false)),
+                _ => Some((i, i + g.len(), false)),
+            };
+            prev_was_space = true;
+            row_width += g.width();
+            continue;
+        }
+        prev_was_space = false;
+
+        let g_width = grapheme_width(g);
+        if row_width + g_width > width && i > start {
+            match last_break {
+                Some((end, next, hyphen)) if next > start && mode == WrapMode::Word => {
+                    // a row of nothing but indentation is dropped with it
+                    if end > start {
+                        rows.push((Range::new(start, end), hyphen));
+                    }
+                    start = next;
+                }
+                _ => {
+                    rows.push((Range::new(start, i), false));
+                    start = i;
+                }
+            }
+            row_width = line[start..i].graphemes(true).map(grapheme_width).sum();
+            last_break = None;
+        }
+        row_width += g_width;
+
+        if mode == WrapMode::Word {
+            let end = i + g.len();
+            if g == "-" {
+                last_break = Some((end, end, false));
+            } else if g == SOFT_HYPHEN && row_width < width {
+                last_break = Some((end, end, true));
+            }
+        }
+    }
+    rows.push((Range::new(start, line.len()), false));
+    rows
+}
+
+fn grapheme_width(g: &str) -> usize {
+    if g == SOFT_HYPHEN {
+        0
+    } else {
+        g.width()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapped(text: &str, width: usize, mode: WrapMode) -> Vec<String> {
+        wrap(&text.into(), width, mode)
+            .into_iter()
+            .map(|line| line.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap_modes() {
+        let text = "a well-known  para\u{ad}graph\nxy";
+        assert_eq!(
+            wrapped(text, 8, WrapMode::Word),
+            ["a well-", "known", "para-", "graph", "xy"]
+        );
+        assert_eq!(
+            wrapped(text, 8, WrapMode::Char),
+            ["a well-k", "nown  pa", "ragraph", "xy"]
+        );
+        // words that are longer than a line are cut
+        assert_eq!(wrapped("abcdef g", 3, WrapMode::Word), ["abc", "def", "g"]);
+    }
+
+    #[test]
+    fn test_spaces_dont_widen_rows() {
+        assert_eq!(
+            wrapped("ab      cd", 3, WrapMode::Char),
+            ["ab ", "   ", "  c", "d"]
+        );
+        assert_eq!(wrapped("      abc", 3, WrapMode::Word), ["abc"]);
+        assert_eq!(wrapped("  ab cd", 4, WrapMode::Word), ["  ab", "cd"]);
+    }
+
+    #[test]
+    fn test_justified() {
+        let rows = wrap_justified(&"a bc d efg hi\nj k".into(), 8, WrapMode::Word);
+        let rows: Vec<_> = rows.into_iter().map(|row| row.text).collect();
+        assert_eq!(rows, ["a  bc  d", "efg hi", "j k"]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Contains the LineWriter, which lets code that writes to an [io::Write] append to
+//! a document, e.g. the output of a child process
+
+use std::io;
+
+use crate::{AText, BufferRef, DocumentRef};
+
+/// Appends everything that is written to it to a document, line by line, see
+/// [DocumentRef::writer] and [BufferRef::writer]. Bytes are buffered until a line
+/// is complete, and an incomplete last line is added when the writer is dropped.
+/// Invalid UTF-8 is replaced.
+pub struct LineWriter {
+    target: Target,
+    pending: Vec<u8>,
+    #[cfg(feature = "ansi")]
+    ansi: bool,
+}
+
+enum Target {
+    Doc(DocumentRef),
+    /// adding lines to a buffer scrolls it down
+    Buffer(BufferRef),
+}
+
+impl LineWriter {
+    /// parses ANSI escape sequences in the written lines, so colored output
+    /// keeps its colors
+    #[cfg(feature = "ansi")]
+    pub fn with_ansi(mut self) -> Self {
+        self.ansi = true;
+        self
+    }
+
+    fn new(target: Target) -> Self {
+        Self {
+            target,
+            pending: vec![],
+            #[cfg(feature = "ansi")]
+            ansi: false,
+        }
+    }
+
+    fn add_line(&self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        let text = self.to_atext(line);
+        match &self.target {
+            Target::Doc(doc) => doc.add_line(text),
+            Target::Buffer(buf) => buf.add_line(text),
+        }
+    }
+
+    fn to_atext(&self, line: &str) -> AText {
+        #[cfg(feature = "ansi")]
+        if self.ansi {
+            return AText::from_ansi(line);
+        }
+        AText::from(line)
+    }
+}
+
+impl io::Write for LineWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if let Some(last_newline) = self.pending.iter().rposition(|&b| b == b'\n') {
+            let rest = self.pending.split_off(last_newline + 1);
+            let complete = std::mem::replace(&mut self.pending, rest);
+            for line in complete[..last_newline].split(|&b| b == b'\n') {
+                self.add_line(line);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// does nothing, since only complete lines are added
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LineWriter {
+    fn drop(&mut self) {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.add_line(&pending);
+        }
+    }
+}
+
+impl DocumentRef {
+    /// an [io::Write] that appends to the document, see [LineWriter]
+    pub fn writer(&self) -> LineWriter {
+        LineWriter::new(Target::Doc(self.clone()))
+    }
+}
+
+impl BufferRef {
+    /// an [io::Write] that appends to the buffer, and scrolls down, see [LineWriter]
+    pub fn writer(&self) -> LineWriter {
+        LineWriter::new(Target::Buffer(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::Document;
+
+    #[test]
+    fn test_lines_are_buffered() {
+        let doc = Document::new().into_ref();
+        let mut writer = doc.writer();
+        write!(writer, "one\r\ntw").unwrap();
+        let text = || doc.0.lock().unwrap().content.text.clone();
+        assert_eq!(text(), "one\n");
+
+        // a multi byte char split across writes
+        writer.write_all(&[b'o', 0xc3]).unwrap();
+        writer.write_all(&[0xa9, b'\n', b'x']).unwrap();
+        assert_eq!(text(), "one\ntwo\u{e9}\n");
+
+        drop(writer);
+        assert_eq!(text(), "one\ntwo\u{e9}\nx\n");
+    }
+}